@@ -1,6 +1,7 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use minijinja::machinery::parse;
-use minijinja::{context, Environment, State};
+use minijinja::value::Value;
+use minijinja::{context, Environment, RenderOptions, State};
 
 fn do_parse() {
     parse(
@@ -21,9 +22,8 @@ fn do_parse_and_compile() {
     .unwrap();
 }
 
-fn do_render(env: &Environment) {
-    let tmpl = env.get_template("all_elements.html").unwrap();
-    tmpl.render(context! {
+fn render_context() -> Value {
+    context! {
         DEBUG => false,
         site => context! {
             nav => vec![
@@ -33,8 +33,21 @@ fn do_render(env: &Environment) {
             ]
         },
         items => (0..200).skip(3).collect::<Vec<_>>(),
-    })
-    .unwrap();
+    }
+}
+
+fn do_render(env: &Environment) {
+    let tmpl = env.get_template("all_elements.html").unwrap();
+    tmpl.render(render_context()).unwrap();
+}
+
+fn do_render_profiled(env: &Environment) {
+    let tmpl = env.get_template("all_elements.html").unwrap();
+    let options = RenderOptions::new().with_profiling(true);
+    let captured = tmpl
+        .render_captured_with(render_context(), &options)
+        .unwrap();
+    black_box(captured.profile());
 }
 
 fn create_real_env() -> Environment<'static> {
@@ -58,6 +71,10 @@ pub fn criterion_benchmark(c: &mut Criterion) {
         let env = create_real_env();
         b.iter(|| do_render(&env));
     });
+    c.bench_function("render_profiled", |b| {
+        let env = create_real_env();
+        b.iter(|| do_render_profiled(&env));
+    });
 }
 
 criterion_group!(benches, criterion_benchmark);
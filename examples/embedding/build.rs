@@ -1,8 +1,15 @@
 fn main() {
     // we only need to bundle the templates with the
     // feature is enabled.
-    #[cfg(feature = "bundled")]
+    #[cfg(all(feature = "bundled", not(feature = "precompiled")))]
     {
         minijinja_embed::embed_templates!("src/templates");
     }
+
+    // alternatively the templates can be compiled ahead of time.
+    #[cfg(feature = "precompiled")]
+    {
+        let env = minijinja::Environment::new();
+        minijinja_embed::embed_precompiled_templates!(&env, "src/templates");
+    }
 }
@@ -0,0 +1,74 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use minijinja::i18n::{Catalog, Translator};
+use minijinja::{context, Environment, State};
+
+const TEMPLATE: &str = r#"{% trans %}Hello {{ user }}!{% endtrans %}
+{% trans trimmed count=items|length %}
+  You have one item in your cart.
+{% pluralize %}
+  You have {{ count }} items in your cart.
+{% endtrans %}
+{{ _("Thank you for shopping with us.") }}"#;
+
+/// A translator that picks the catalog based on the `LANG` variable.
+///
+/// The default `ngettext` picks the plural form like English does which
+/// also works for German.
+struct Catalogs {
+    languages: HashMap<&'static str, HashMap<&'static str, &'static str>>,
+}
+
+impl Catalogs {
+    fn lookup(&self, state: &State, message: &str) -> Option<&'static str> {
+        let lang = state.lookup("LANG")?;
+        self.languages.get(lang.as_str()?)?.get(message).copied()
+    }
+}
+
+impl Translator for Catalogs {
+    fn gettext<'a>(&'a self, state: &State, message: &'a str) -> Cow<'a, str> {
+        Cow::Borrowed(self.lookup(state, message).unwrap_or(message))
+    }
+}
+
+fn main() {
+    let mut german = HashMap::new();
+    german.insert("Hello %(user)s!", "Hallo %(user)s!");
+    german.insert(
+        "You have one item in your cart.",
+        "Du hast einen Artikel im Warenkorb.",
+    );
+    german.insert(
+        "You have %(count)s items in your cart.",
+        "Du hast %(count)s Artikel im Warenkorb.",
+    );
+    german.insert(
+        "Thank you for shopping with us.",
+        "Danke für deinen Einkauf.",
+    );
+
+    let mut env = Environment::new();
+    env.set_translator(Catalogs {
+        languages: HashMap::from([("de", german)]),
+    });
+    env.add_template("cart.txt", TEMPLATE).unwrap();
+
+    let tmpl = env.get_template("cart.txt").unwrap();
+    for lang in ["en", "de"] {
+        println!("[{lang}]");
+        println!(
+            "{}\n",
+            tmpl.render(context! {
+                LANG => lang,
+                user => "Peter",
+                items => vec!["apple", "banana"],
+            })
+            .unwrap()
+        );
+    }
+
+    println!("[messages.pot]");
+    print!("{}", Catalog::from_environment(&env).unwrap().to_pot());
+}
@@ -8,7 +8,7 @@ Results as they come in:
 
 <ul>
 {%- for item in stream %}
-  <li>Item {{ item }}</li>
+  <li>Item {{ item }}</li>{{ flush() }}
 {%- endfor %}
 </ul>
 
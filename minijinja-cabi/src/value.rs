@@ -266,7 +266,7 @@ impl TryFrom<ValueKind> for mj_value_kind {
             ValueKind::Number => mj_value_kind::MJ_VALUE_KIND_NUMBER,
             ValueKind::String => mj_value_kind::MJ_VALUE_KIND_STRING,
             ValueKind::Bytes => mj_value_kind::MJ_VALUE_KIND_BYTES,
-            ValueKind::Seq => mj_value_kind::MJ_VALUE_KIND_SEQ,
+            ValueKind::Seq | ValueKind::Tuple => mj_value_kind::MJ_VALUE_KIND_SEQ,
             ValueKind::Map => mj_value_kind::MJ_VALUE_KIND_MAP,
             ValueKind::Iterable => mj_value_kind::MJ_VALUE_KIND_ITERABLE,
             ValueKind::Plain => mj_value_kind::MJ_VALUE_KIND_PLAIN,
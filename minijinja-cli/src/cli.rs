@@ -7,6 +7,7 @@ use std::{fs, io};
 
 use anyhow::{bail, Context, Error};
 use clap::ArgMatches;
+use minijinja::lint::{LintRule, Linter};
 use minijinja::machinery::{
     get_compiled_template, parse, tokenize, Instructions, WhitespaceConfig,
 };
@@ -439,6 +440,24 @@ pub fn execute() -> Result<i32, Error> {
         exit_code = print_expr_out(rv, &config, &mut output)?;
     } else if let Some(dump) = matches.get_one::<String>("dump") {
         dump_info(dump, &env, &template_name, &mut output, &config)?;
+    } else if matches.get_flag("lint") {
+        let mut linter = Linter::new(&env);
+        for name in matches
+            .get_many::<String>("lint-disable")
+            .unwrap_or_default()
+        {
+            let Some(rule) = LintRule::from_name(name) else {
+                bail!("unknown lint rule '{}'", name);
+            };
+            linter.set_rule_enabled(rule, false);
+        }
+        let diagnostics = linter.lint_template(&env.get_template(&template_name)?)?;
+        for diagnostic in &diagnostics {
+            writeln!(output, "{diagnostic}")?;
+        }
+        if !diagnostics.is_empty() {
+            exit_code = 1;
+        }
     } else if cfg!(feature = "repl") && matches.get_flag("repl") {
         #[cfg(feature = "repl")]
         {
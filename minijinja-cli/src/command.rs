@@ -102,10 +102,12 @@ pub(super) fn make_command() -> Command {
                     \
                     html means that variables are escaped to HTML5 and XML rules.  json means \
                     that output is safe for both JSON and YAML rules (eg: strings are formatted \
-                    as JSON strings etc.).  none disables escaping entirely.\n\n\
+                    as JSON strings etc.).  shell, css, url and latex quote or escape values \
+                    for POSIX shells, CSS, URL components and LaTeX.  none disables escaping \
+                    entirely.\n\n\
                     \
                     [env var: MINIJINJA_AUTOESCAPE]")
-                .value_parser(["auto", "html", "json", "none"])
+                .value_parser(["auto", "html", "json", "shell", "css", "url", "latex", "none"])
                 .help_heading(BEHAVIOR),
             arg!(-D --define <EXPR> "Defines an input variable (key=value / key:=json_value)")
                 .long_help("\
@@ -285,6 +287,33 @@ pub(super) fn make_command() -> Command {
                     after lexing.")
                 .value_parser(["instructions", "ast", "tokens"])
                 .help_heading(ADVANCED),
+            arg!(--lint "Checks the template for likely mistakes instead of rendering it")
+                .long_help("\
+                    Checks the template for likely mistakes instead of rendering it.\n\n\
+                    \
+                    This reports unknown filters and tests, variables and macros that are never \
+                    used, loop variables shadowing outer loop variables, blocks that do not exist \
+                    in the parent template and output after 'extends' that is never rendered.  \
+                    Every problem is printed as a line to stdout.  If problems were found the exit \
+                    code is 1.")
+                .conflicts_with_all(["expr", "dump"])
+                .help_heading(ADVANCED),
+            arg!(--"lint-disable" <RULE> "Disables a lint rule")
+                .long_help("\
+                    Disables a rule for --lint.  It can be supplied multiple times to disable more \
+                    than one rule.")
+                .value_parser([
+                    "unknown-filter",
+                    "unknown-test",
+                    "unused-variable",
+                    "unused-macro",
+                    "shadowed-loop-variable",
+                    "unknown-block",
+                    "unreachable-code",
+                ])
+                .requires("lint")
+                .action(ArgAction::Append)
+                .help_heading(ADVANCED),
             #[cfg(feature = "repl")]
             arg!(--repl "Starts the repl with the given data")
                 .long_help("\
@@ -246,9 +246,16 @@ impl Config {
             "none" => AutoEscape::None,
             "html" => AutoEscape::Html,
             "json" => AutoEscape::Json,
+            "shell" => AutoEscape::Shell,
+            "css" => AutoEscape::Css,
+            "url" => AutoEscape::Url,
+            "latex" => AutoEscape::Latex,
             "auto" => match name.strip_suffix(".j2").unwrap_or(name).rsplit('.').next() {
                 Some("htm" | "html" | "xml" | "xhtml") => AutoEscape::Html,
                 Some("json" | "json5" | "yml" | "yaml") => AutoEscape::Json,
+                Some("sh") => AutoEscape::Shell,
+                Some("css") => AutoEscape::Css,
+                Some("tex") => AutoEscape::Latex,
                 _ => AutoEscape::None,
             },
             _ => unreachable!(),
@@ -1,8 +1,13 @@
 use std::collections::BTreeMap;
 use std::fmt;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 
 use anyhow::{anyhow, Error};
-use minijinja::{context, Environment, Value};
+use minijinja::instrument::{Instrumentation, Location};
+use minijinja::value::ValueKind;
+use minijinja::{context, Environment, State, Value};
 use rustyline::error::ReadlineError;
 use rustyline::DefaultEditor;
 
@@ -37,6 +42,11 @@ pub fn run(mut env: Environment, ctx: Value) -> Result<(), Error> {
                     Some(Command::Render { template }) => {
                         render(&env, template, &ctx, &locals);
                     }
+                    Some(Command::Step { template }) => {
+                        let mut env = env.clone();
+                        env.set_instrumentation(Stepper::new(template));
+                        render(&env, template, &ctx, &locals);
+                    }
                     Some(Command::Invalid) => {
                         print_error(&anyhow!("invalid command"));
                     }
@@ -47,6 +57,7 @@ pub fn run(mut env: Environment, ctx: Value) -> Result<(), Error> {
                         println!(".set x=expr    set variable x to the evaluated expression");
                         println!(".unset x       unsets variable x");
                         println!(".render tmpl   renders the given template source");
+                        println!(".step tmpl     renders the given template source line by line");
                     }
                     Some(Command::Quit) => break,
                     None => {
@@ -68,6 +79,7 @@ enum Command<'a> {
     Set { var: &'a str, expr: &'a str },
     Unset { var: &'a str },
     Render { template: &'a str },
+    Step { template: &'a str },
     Help,
     Quit,
     Invalid,
@@ -100,6 +112,9 @@ fn parse_command(line: &str) -> Option<Command<'_>> {
             "render" => {
                 return Some(Command::Render { template: rest });
             }
+            "step" => {
+                return Some(Command::Step { template: rest });
+            }
             _ => {}
         }
     }
@@ -141,6 +156,91 @@ fn render(env: &Environment, template: &str, ctx: &Value, locals: &BTreeMap<Stri
     }
 }
 
+/// Pauses the render whenever it reaches a new line.
+struct Stepper {
+    source: String,
+    last: Mutex<Option<(String, usize)>>,
+    running: AtomicBool,
+}
+
+impl Stepper {
+    fn new(source: &str) -> Stepper {
+        Stepper {
+            source: source.to_string(),
+            last: Mutex::new(None),
+            running: AtomicBool::new(false),
+        }
+    }
+
+    fn source_line(&self, state: &State, line: usize) -> String {
+        let source = if state.name() == "<string>" {
+            Some(self.source.clone())
+        } else {
+            state
+                .get_template(state.name())
+                .ok()
+                .map(|tmpl| tmpl.source().to_string())
+        };
+        source
+            .as_deref()
+            .and_then(|source| source.lines().nth(line.saturating_sub(1)))
+            .unwrap_or("")
+            .trim()
+            .to_string()
+    }
+}
+
+impl Instrumentation for Stepper {
+    fn on_instruction(&self, state: &State, location: &Location) {
+        if self.running.load(Ordering::Relaxed) {
+            return;
+        }
+        let Some(line) = location.line() else {
+            return;
+        };
+        let current = Some((state.name().to_string(), line));
+        if *self.last.lock().unwrap() == current {
+            return;
+        }
+        *self.last.lock().unwrap() = current;
+
+        println!(
+            "{}:{}  {}",
+            state.name(),
+            line,
+            self.source_line(state, line)
+        );
+        loop {
+            print!("(step) ");
+            io::stdout().flush().ok();
+            let mut input = String::new();
+            if io::stdin().read_line(&mut input).unwrap_or(0) == 0 {
+                self.running.store(true, Ordering::Relaxed);
+                return;
+            }
+            match input.trim() {
+                "" | "n" => return,
+                "c" => {
+                    self.running.store(true, Ordering::Relaxed);
+                    return;
+                }
+                "v" => {
+                    let mut names = state.known_variables();
+                    names.sort();
+                    for name in names {
+                        if let Some(value) = state.lookup(&name) {
+                            if value.kind() != ValueKind::Plain {
+                                println!("  {name} = {value:?}");
+                            }
+                        }
+                    }
+                }
+                _ => println!("<enter> next line, c continue, v show variables"),
+            }
+        }
+    }
+}
+
 fn print_result(value: &Value) {
     if value.is_undefined() {
         // nothing
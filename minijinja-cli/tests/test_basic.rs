@@ -602,6 +602,60 @@ fn test_dump() {
     "###);
 }
 
+#[test]
+fn test_lint() {
+    let tmpl = file_with_contents("{% set x = 1 %}\n{{ foo|uper }}");
+
+    bind_common_filters!();
+
+    assert_cmd_snapshot!(
+        cli()
+            .arg(tmpl.path())
+            .arg("--lint"),
+        @r###"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    variable `x` is assigned but never used (in [TEMPLATE]:1) [unused-variable]
+    unknown filter `uper` (in [TEMPLATE]:2) [unknown-filter]
+
+    ----- stderr -----
+    "###);
+
+    assert_cmd_snapshot!(
+        cli()
+            .arg(tmpl.path())
+            .arg("--lint")
+            .arg("--lint-disable=unknown-filter")
+            .arg("--lint-disable=unused-variable"),
+        @r###"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+
+    ----- stderr -----
+    "###);
+}
+
+#[test]
+fn test_lint_disable_all_rules() {
+    let tmpl = file_with_contents("{% set x = 1 %}");
+    for rule in minijinja::lint::LintRule::all() {
+        let output = cli()
+            .arg(tmpl.path())
+            .arg("--lint")
+            .arg(format!("--lint-disable={}", rule.name()))
+            .output()
+            .unwrap();
+        assert!(
+            output.stderr.is_empty(),
+            "{}: {}",
+            rule.name(),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
+
 #[test]
 fn test_include() {
     let tmpl = file_with_contents(r#"{% include ENV.OTHER_TEMPLATE %}"#);
@@ -50,7 +50,7 @@ fn value_to_datetime(
                 .map_err(|_| Error::new(ErrorKind::InvalidOperation, "date out of range"))?,
             true,
         )
-    } else if value.kind() == ValueKind::Seq {
+    } else if matches!(value.kind(), ValueKind::Seq | ValueKind::Tuple) {
         let mut items = Vec::new();
         for item in value.try_iter()? {
             items.push(i64::try_from(item)?);
@@ -84,7 +84,10 @@ pub fn pluralize(
 pub fn random(state: &minijinja::State, seq: &Value) -> Result<Value, Error> {
     use minijinja::value::ValueKind;
 
-    if matches!(seq.kind(), ValueKind::Seq | ValueKind::String) {
+    if matches!(
+        seq.kind(),
+        ValueKind::Seq | ValueKind::Tuple | ValueKind::String
+    ) {
         let len = seq.len().unwrap_or(0);
         let idx = crate::rand::XorShiftRng::for_state(state).next_usize(len);
         seq.get_item_by_index(idx)
@@ -142,7 +145,8 @@ pub fn filesizeformat(value: f64, binary: Option<bool>) -> String {
 /// {{ "Hello World"|truncate(length=5) }}
 /// ```
 ///
-/// The filter accepts a few keyword arguments:
+/// The filter accepts a few arguments which can be passed positionally or
+/// as keyword arguments:
 /// * `length`: maximum length of the output string (defaults to 255)
 /// * `killwords`: set to `true` if you want to cut text exactly at length; if `false`,
 ///   the filter will preserve last word (defaults to `false`)
@@ -173,6 +177,21 @@ pub fn filesizeformat(value: f64, binary: Option<bool>) -> String {
 /// ) }}
 /// ```
 pub fn truncate(state: &State, value: &Value, kwargs: Kwargs) -> Result<String, Error> {
+    truncate_with_args(state, value, None, None, None, None, kwargs)
+}
+
+/// The `truncate` filter as registered by [`add_to_environment`](crate::add_to_environment).
+///
+/// Unlike [`truncate`] this also accepts the arguments positionally.
+pub(crate) fn truncate_with_args(
+    state: &State,
+    value: &Value,
+    length: Option<usize>,
+    killwords: Option<bool>,
+    end: Option<&str>,
+    leeway: Option<usize>,
+    kwargs: Kwargs,
+) -> Result<String, Error> {
     if matches!(value.kind(), ValueKind::None | ValueKind::Undefined) {
         return Ok("".into());
     }
@@ -184,10 +203,23 @@ pub fn truncate(state: &State, value: &Value, kwargs: Kwargs) -> Result<String,
         )
     })?;
 
-    let length = kwargs.get::<Option<usize>>("length")?.unwrap_or(255);
-    let killwords = kwargs.get::<Option<bool>>("killwords")?.unwrap_or_default();
-    let end = kwargs.get::<Option<&str>>("end")?.unwrap_or("...");
-    let leeway = kwargs.get::<Option<usize>>("leeway")?.unwrap_or_else(|| {
+    let length = match length {
+        Some(length) => length,
+        None => kwargs.get::<Option<usize>>("length")?.unwrap_or(255),
+    };
+    let killwords = match killwords {
+        Some(killwords) => killwords,
+        None => kwargs.get::<Option<bool>>("killwords")?.unwrap_or_default(),
+    };
+    let end = match end {
+        Some(end) => end,
+        None => kwargs.get::<Option<&str>>("end")?.unwrap_or("..."),
+    };
+    let leeway = match leeway {
+        Some(leeway) => Some(leeway),
+        None => kwargs.get::<Option<usize>>("leeway")?,
+    };
+    let leeway = leeway.unwrap_or_else(|| {
         state
             .lookup("TRUNCATE_LEEWAY")
             .and_then(|x| usize::try_from(x.clone()).ok())
@@ -40,7 +40,7 @@ mod rand;
 pub fn add_to_environment(env: &mut Environment) {
     env.add_filter("pluralize", filters::pluralize);
     env.add_filter("filesizeformat", filters::filesizeformat);
-    env.add_filter("truncate", filters::truncate);
+    env.add_filter("truncate", filters::truncate_with_args);
     env.add_filter("striptags", filters::striptags);
     #[cfg(feature = "wordcount")]
     {
@@ -21,6 +21,7 @@ use minijinja::{format_filter, Error, ErrorKind, FormatStyle, State, Value};
 /// * `dict.keys`
 /// * `dict.values`
 /// * `list.count`
+/// * `tuple.count`
 /// * `str.capitalize`
 /// * `str.count`
 /// * `str.endswith`
@@ -55,7 +56,7 @@ pub fn unknown_method_callback(
     match value.kind() {
         ValueKind::String => string_methods(value, method, args),
         ValueKind::Map => map_methods(value, method, args),
-        ValueKind::Seq => seq_methods(value, method, args),
+        ValueKind::Seq | ValueKind::Tuple => seq_methods(value, method, args),
         _ => Err(Error::from(ErrorKind::UnknownMethod)),
     }
 }
@@ -203,7 +204,10 @@ fn string_methods(value: &Value, method: &str, args: &[Value]) -> Result<Value,
             let (prefix,): (&Value,) = from_args(args)?;
             if let Some(prefix) = prefix.as_str() {
                 Ok(Value::from(s.starts_with(prefix)))
-            } else if matches!(prefix.kind(), ValueKind::Iterable | ValueKind::Seq) {
+            } else if matches!(
+                prefix.kind(),
+                ValueKind::Iterable | ValueKind::Seq | ValueKind::Tuple
+            ) {
                 for prefix in prefix.try_iter()? {
                     if s.starts_with(prefix.as_str().ok_or_else(|| {
                         Error::new(
@@ -232,7 +236,10 @@ fn string_methods(value: &Value, method: &str, args: &[Value]) -> Result<Value,
             let (suffix,): (&Value,) = from_args(args)?;
             if let Some(suffix) = suffix.as_str() {
                 Ok(Value::from(s.ends_with(suffix)))
-            } else if matches!(suffix.kind(), ValueKind::Iterable | ValueKind::Seq) {
+            } else if matches!(
+                suffix.kind(),
+                ValueKind::Iterable | ValueKind::Seq | ValueKind::Tuple
+            ) {
                 for suffix in suffix.try_iter()? {
                     if s.ends_with(suffix.as_str().ok_or_else(|| {
                         Error::new(
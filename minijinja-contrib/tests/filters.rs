@@ -203,6 +203,26 @@ fn test_truncate() {
     );
 }
 
+#[test]
+fn test_truncate_positional() {
+    use minijinja::render;
+
+    const SHORT_TEXT: &str = "Fifteen chars !";
+
+    let mut env = Environment::new();
+    minijinja_contrib::add_to_environment(&mut env);
+
+    insta::assert_snapshot!(
+        render!(in env, r"{{ text|truncate(10, true, '!', 0) }}", text=>SHORT_TEXT),
+        @"Fifteen c!"
+    );
+
+    insta::assert_snapshot!(
+        render!(in env, r"{{ text|truncate(10, end='!', leeway=0) }}", text=>SHORT_TEXT),
+        @"Fifteen!"
+    );
+}
+
 #[test]
 #[cfg(feature = "wordwrap")]
 fn test_wordcount() {
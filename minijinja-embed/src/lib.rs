@@ -34,6 +34,9 @@
 //! ```
 //!
 //! For more information see [`embed_templates`].
+//!
+//! With the `bytecode` feature templates can also be compiled ahead of time
+//! with [`embed_precompiled_templates`] which avoids parsing them at runtime.
 #![cfg_attr(docsrs, feature(doc_cfg))]
 #![deny(missing_docs)]
 #![allow(clippy::needless_doctest_main)]
@@ -97,6 +100,55 @@ macro_rules! embed_templates {
     };
 }
 
+/// Utility macro to store precompiled templates in a `build.rs` file.
+///
+/// This works like [`embed_templates!`] but instead of the template source
+/// the compiled bytecode is embedded.  The first argument is the
+/// [`Environment`](minijinja::Environment) that is used to compile the
+/// templates.  It needs to be configured with the same syntax and whitespace
+/// settings as the environment the templates are later loaded into.
+///
+/// ```rust
+/// # fn foo() {
+/// let env = minijinja::Environment::new();
+/// minijinja_embed::embed_precompiled_templates!(&env, "src/templates");
+/// minijinja_embed::embed_precompiled_templates!(&env, "src/templates", &[".html"]);
+/// minijinja_embed::embed_precompiled_templates!(&env, "src/templates", &[][..], "main");
+/// # }
+/// ```
+///
+/// The templates are loaded with [`load_templates!`] as usual.  This requires
+/// the `bytecode` feature of `minijinja` to be enabled for the runtime
+/// dependency as well.  Bytecode can only be loaded by the exact same version
+/// of MiniJinja that created it with the same set of engine features.  As this
+/// is not necessarily the case for build dependencies, the template source is
+/// embedded as well and used as fallback when the bytecode is rejected.
+///
+/// # Panics
+///
+/// This function panics if the templates are not valid (eg: invalid syntax).
+#[cfg(feature = "bytecode")]
+#[cfg_attr(docsrs, doc(cfg(feature = "bytecode")))]
+#[macro_export]
+macro_rules! embed_precompiled_templates {
+    ($env:expr, $path:expr, $exts:expr, $bundle_name:expr) => {{
+        let out_dir = ::std::env::var_os("OUT_DIR").unwrap();
+        let dst_path = ::std::path::Path::new(&out_dir)
+            .join(format!("minijinja_templates_{}.rs", $bundle_name));
+        let generated = $crate::_embed_precompiled_templates($env, $path, $exts);
+        println!("cargo:rerun-if-changed={}", $path);
+        ::std::fs::write(dst_path, generated).unwrap();
+    }};
+
+    ($env:expr, $path:expr) => {
+        $crate::embed_precompiled_templates!($env, $path, &[][..], "main");
+    };
+
+    ($env:expr, $path:expr, $exts:expr) => {
+        $crate::embed_precompiled_templates!($env, $path, $exts, "main");
+    };
+}
+
 /// Loads embedded templates into the environment.
 ///
 /// This macro takes a MiniJinja environment as argument and optionally
@@ -153,16 +205,12 @@ fn visit_dirs(dir: &Path, cb: &mut dyn FnMut(&DirEntry)) -> io::Result<()> {
     Ok(())
 }
 
-#[doc(hidden)]
-pub fn _embed_templates<P>(path: P, extensions: &[&str]) -> String
-where
-    P: AsRef<Path>,
-{
-    let path = path.as_ref().canonicalize().unwrap();
-    let mut gen = String::new();
-    writeln!(gen, "|env: &mut minijinja::Environment| {{").unwrap();
-
-    visit_dirs(&path, &mut |f| {
+fn visit_templates(
+    path: &Path,
+    extensions: &[&str],
+    cb: &mut dyn FnMut(String, String),
+) -> io::Result<()> {
+    visit_dirs(path, &mut |f| {
         let p = f.path();
         if !extensions.is_empty()
             && !p
@@ -174,13 +222,64 @@ where
         }
 
         let contents = fs::read_to_string(&p).unwrap();
-        let name = p.strip_prefix(&path).unwrap();
+        let name = p.strip_prefix(path).unwrap();
+        cb(name.to_string_lossy().replace('\\', "/"), contents);
+    })
+}
+
+#[doc(hidden)]
+pub fn _embed_templates<P>(path: P, extensions: &[&str]) -> String
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref().canonicalize().unwrap();
+    let mut gen = String::new();
+    writeln!(gen, "|env: &mut minijinja::Environment| {{").unwrap();
 
+    visit_templates(&path, extensions, &mut |name, contents| {
         writeln!(
             gen,
             "env.add_template({:?}, {:?}).expect(\"Embedded an invalid template\");",
-            name.to_string_lossy().replace('\\', "/"),
-            contents
+            name, contents
+        )
+        .unwrap();
+    })
+    .unwrap();
+
+    writeln!(gen, "}}").unwrap();
+
+    gen
+}
+
+#[doc(hidden)]
+#[cfg(feature = "bytecode")]
+pub fn _embed_precompiled_templates<P>(
+    env: &minijinja::Environment<'_>,
+    path: P,
+    extensions: &[&str],
+) -> String
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref().canonicalize().unwrap();
+    let mut env = env.clone();
+    let mut gen = String::new();
+    writeln!(gen, "|env: &mut minijinja::Environment| {{").unwrap();
+
+    visit_templates(&path, extensions, &mut |name, contents| {
+        let bytecode = env
+            .add_template_owned(name.clone(), contents.clone())
+            .and_then(|_| env.get_template(&name)?.to_bytecode())
+            .expect("Embedded an invalid template");
+        let literal = bytecode
+            .iter()
+            .flat_map(|x| std::ascii::escape_default(*x))
+            .map(char::from)
+            .collect::<String>();
+        writeln!(
+            gen,
+            "env.add_template_bytecode({:?}, b\"{}\").or_else(|_| env.add_template({:?}, {:?})).expect(\"Embedded an invalid template\");",
+            name, literal, name, contents
         )
         .unwrap();
     })
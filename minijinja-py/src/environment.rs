@@ -375,6 +375,10 @@ impl Environment {
                         match &value as &str {
                             "html" => AutoEscape::Html,
                             "json" => AutoEscape::Json,
+                            "shell" => AutoEscape::Shell,
+                            "css" => AutoEscape::Css,
+                            "url" => AutoEscape::Url,
+                            "latex" => AutoEscape::Latex,
                             other => get_custom_autoescape(other),
                         }
                     } else if let Ok(value) = rv.extract::<bool>() {
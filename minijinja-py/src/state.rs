@@ -37,6 +37,10 @@ impl StateRef {
                 AutoEscape::None => None,
                 AutoEscape::Html => Some("html"),
                 AutoEscape::Json => Some("json"),
+                AutoEscape::Shell => Some("shell"),
+                AutoEscape::Css => Some("css"),
+                AutoEscape::Url => Some("url"),
+                AutoEscape::Latex => Some("latex"),
                 AutoEscape::Custom(custom) => Some(custom),
                 _ => None,
             })
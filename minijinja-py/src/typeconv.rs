@@ -246,6 +246,15 @@ fn to_python_value_impl(py: Python<'_>, value: Value) -> PyResult<Py<PyAny>> {
                 }
                 return Ok(rv.into());
             }
+            ObjectRepr::Tuple => {
+                let mut items = Vec::new();
+                if let Some(iter) = obj.try_iter() {
+                    for value in iter {
+                        items.push(to_python_value_impl(py, value)?);
+                    }
+                }
+                return PyTuple::new(py, items)?.into_py_any(py);
+            }
             _ => {}
         }
     }
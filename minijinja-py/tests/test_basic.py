@@ -135,6 +135,11 @@ def test_custom_test():
     assert rv == False
 
 
+def test_tuples():
+    assert eval_expr("(1, 2)") == [1, 2]
+    assert render_str("{{ x }}", x=(1, 2)) == "[1, 2]"
+
+
 def test_basic_types():
     env = Environment()
     rv = env.eval_expr("{'a': 42, 'b': 42.5, 'c': 'blah'}")
@@ -1,10 +1,8 @@
-use std::ops::Deref;
-
-#[cfg(feature = "internal_debug")]
 use std::fmt;
+use std::ops::Deref;
 
 use crate::compiler::tokens::Span;
-use crate::value::{ops, value_map_with_capacity, Value};
+use crate::value::{ops, value_map_with_capacity, Value, ValueKind};
 
 /// Container for nodes with location info.
 ///
@@ -39,7 +37,6 @@ impl<T> Deref for Spanned<T> {
     }
 }
 
-#[cfg(feature = "internal_debug")]
 impl<T: fmt::Debug> fmt::Debug for Spanned<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         ok!(fmt::Debug::fmt(&self.inner.0, f));
@@ -48,6 +45,7 @@ impl<T: fmt::Debug> fmt::Debug for Spanned<T> {
 }
 
 /// A statement node.
+#[non_exhaustive]
 #[cfg_attr(
     feature = "unstable_machinery_serde",
     derive(serde::Serialize),
@@ -83,9 +81,10 @@ pub enum Stmt<'a> {
     #[cfg(feature = "loop_controls")]
     Break(Spanned<Break>),
     Do(Spanned<Do<'a>>),
+    #[cfg(feature = "i18n")]
+    Trans(Spanned<Trans<'a>>),
 }
 
-#[cfg(feature = "internal_debug")]
 impl fmt::Debug for Stmt<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -118,11 +117,53 @@ impl fmt::Debug for Stmt<'_> {
             #[cfg(feature = "loop_controls")]
             Stmt::Break(s) => fmt::Debug::fmt(s, f),
             Stmt::Do(s) => fmt::Debug::fmt(s, f),
+            #[cfg(feature = "i18n")]
+            Stmt::Trans(s) => fmt::Debug::fmt(s, f),
+        }
+    }
+}
+
+impl Stmt<'_> {
+    /// Returns the span of the statement.
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::Template(s) => s.span(),
+            Stmt::EmitExpr(s) => s.span(),
+            Stmt::EmitRaw(s) => s.span(),
+            Stmt::ForLoop(s) => s.span(),
+            Stmt::IfCond(s) => s.span(),
+            Stmt::WithBlock(s) => s.span(),
+            Stmt::Set(s) => s.span(),
+            Stmt::SetBlock(s) => s.span(),
+            Stmt::AutoEscape(s) => s.span(),
+            Stmt::FilterBlock(s) => s.span(),
+            #[cfg(feature = "multi_template")]
+            Stmt::Block(s) => s.span(),
+            #[cfg(feature = "multi_template")]
+            Stmt::Extends(s) => s.span(),
+            #[cfg(feature = "multi_template")]
+            Stmt::Include(s) => s.span(),
+            #[cfg(feature = "multi_template")]
+            Stmt::Import(s) => s.span(),
+            #[cfg(feature = "multi_template")]
+            Stmt::FromImport(s) => s.span(),
+            #[cfg(feature = "macros")]
+            Stmt::Macro(s) => s.span(),
+            #[cfg(feature = "macros")]
+            Stmt::CallBlock(s) => s.span(),
+            #[cfg(feature = "loop_controls")]
+            Stmt::Continue(s) => s.span(),
+            #[cfg(feature = "loop_controls")]
+            Stmt::Break(s) => s.span(),
+            Stmt::Do(s) => s.span(),
+            #[cfg(feature = "i18n")]
+            Stmt::Trans(s) => s.span(),
         }
     }
 }
 
 /// An expression node.
+#[non_exhaustive]
 #[allow(clippy::enum_variant_names)]
 #[cfg_attr(
     feature = "unstable_machinery_serde",
@@ -142,10 +183,10 @@ pub enum Expr<'a> {
     GetItem(Spanned<GetItem<'a>>),
     Call(Spanned<Call<'a>>),
     List(Spanned<List<'a>>),
+    Tuple(Spanned<Tuple<'a>>),
     Map(Spanned<Map<'a>>),
 }
 
-#[cfg(feature = "internal_debug")]
 impl fmt::Debug for Expr<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -161,12 +202,14 @@ impl fmt::Debug for Expr<'_> {
             Expr::GetItem(s) => fmt::Debug::fmt(s, f),
             Expr::Call(s) => fmt::Debug::fmt(s, f),
             Expr::List(s) => fmt::Debug::fmt(s, f),
+            Expr::Tuple(s) => fmt::Debug::fmt(s, f),
             Expr::Map(s) => fmt::Debug::fmt(s, f),
         }
     }
 }
 
 impl Expr<'_> {
+    /// Returns a short description of the kind of expression.
     pub fn description(&self) -> &'static str {
         match self {
             Expr::Var(_) => "variable",
@@ -179,12 +222,14 @@ impl Expr<'_> {
             | Expr::GetItem(_) => "expression",
             Expr::Call(_) => "call",
             Expr::List(_) => "list literal",
+            Expr::Tuple(_) => "tuple literal",
             Expr::Map(_) => "map literal",
             Expr::Test(_) => "test expression",
             Expr::Filter(_) => "filter expression",
         }
     }
 
+    /// Returns the span of the expression.
     pub fn span(&self) -> Span {
         match self {
             Expr::Var(s) => s.span(),
@@ -199,14 +244,17 @@ impl Expr<'_> {
             Expr::GetItem(s) => s.span(),
             Expr::Call(s) => s.span(),
             Expr::List(s) => s.span(),
+            Expr::Tuple(s) => s.span(),
             Expr::Map(s) => s.span(),
         }
     }
 
-    pub fn as_const(&self) -> Option<Value> {
+    /// Evaluates the expression if it's made up of constants only.
+    pub(crate) fn as_const(&self) -> Option<Value> {
         match self {
             Expr::Const(c) => Some(c.value.clone()),
             Expr::List(l) => l.as_const(),
+            Expr::Tuple(t) => t.as_const(),
             Expr::Map(m) => m.as_const(),
             Expr::UnaryOp(c) => match c.op {
                 UnaryOpKind::Not => c.expr.as_const().map(|value| Value::from(!value.is_true())),
@@ -217,14 +265,24 @@ impl Expr<'_> {
                     return None;
                 };
                 match c.op {
-                    BinOpKind::Add => ops::add(&left, &right).ok(),
+                    BinOpKind::Add => ops::add(&left, &right, None).ok(),
                     BinOpKind::Sub => ops::sub(&left, &right).ok(),
-                    BinOpKind::Mul => ops::mul(&left, &right).ok(),
+                    // repetitions and string formatting are left to the
+                    // runtime so that they are subject to the size limits and
+                    // the sandbox policy
+                    BinOpKind::Mul
+                        if left.kind() != ValueKind::Number
+                            || right.kind() != ValueKind::Number =>
+                    {
+                        None
+                    }
+                    BinOpKind::Rem if left.kind() == ValueKind::String => None,
+                    BinOpKind::Mul => ops::mul(&left, &right, None).ok(),
                     BinOpKind::Div => ops::div(&left, &right).ok(),
                     BinOpKind::FloorDiv => ops::int_div(&left, &right).ok(),
-                    BinOpKind::Rem => ops::rem(&left, &right).ok(),
+                    BinOpKind::Rem => ops::rem(&left, &right, None).ok(),
                     BinOpKind::Pow => ops::pow(&left, &right).ok(),
-                    BinOpKind::Concat => Some(ops::string_concat(left, &right)),
+                    BinOpKind::Concat => ops::string_concat(left, &right, None).ok(),
                     BinOpKind::Eq => Some(Value::from(left == right)),
                     BinOpKind::Ne => Some(Value::from(left != right)),
                     BinOpKind::Lt => Some(Value::from(left < right)),
@@ -246,14 +304,16 @@ impl Expr<'_> {
 }
 
 /// Root template node.
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct Template<'a> {
     pub children: Vec<Stmt<'a>>,
 }
 
 /// A for loop.
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct ForLoop<'a> {
     pub target: Expr<'a>,
@@ -265,7 +325,8 @@ pub struct ForLoop<'a> {
 }
 
 /// An if/else condition.
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct IfCond<'a> {
     pub expr: Expr<'a>,
@@ -274,7 +335,8 @@ pub struct IfCond<'a> {
 }
 
 /// A with block.
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct WithBlock<'a> {
     pub assignments: Vec<(Expr<'a>, Expr<'a>)>,
@@ -282,7 +344,8 @@ pub struct WithBlock<'a> {
 }
 
 /// A set statement.
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct Set<'a> {
     pub target: Expr<'a>,
@@ -290,7 +353,8 @@ pub struct Set<'a> {
 }
 
 /// A set capture statement.
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct SetBlock<'a> {
     pub target: Expr<'a>,
@@ -299,7 +363,8 @@ pub struct SetBlock<'a> {
 }
 
 /// A block for inheritance elements.
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg(feature = "multi_template")]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct Block<'a> {
@@ -309,7 +374,8 @@ pub struct Block<'a> {
 }
 
 /// An extends block.
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg(feature = "multi_template")]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct Extends<'a> {
@@ -317,7 +383,8 @@ pub struct Extends<'a> {
 }
 
 /// An include block.
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg(feature = "multi_template")]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct Include<'a> {
@@ -326,7 +393,8 @@ pub struct Include<'a> {
 }
 
 /// An auto escape control block.
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct AutoEscape<'a> {
     pub enabled: Expr<'a>,
@@ -334,7 +402,8 @@ pub struct AutoEscape<'a> {
 }
 
 /// Applies filters to a block.
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct FilterBlock<'a> {
     pub filter: Expr<'a>,
@@ -342,18 +411,22 @@ pub struct FilterBlock<'a> {
 }
 
 /// Declares a macro.
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg(feature = "macros")]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct Macro<'a> {
     pub name: &'a str,
     pub args: Vec<Expr<'a>>,
     pub defaults: Vec<Expr<'a>>,
+    pub varargs: Option<Expr<'a>>,
+    pub kwargs: Option<Expr<'a>>,
     pub body: Vec<Stmt<'a>>,
 }
 
 /// A call block
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg(feature = "macros")]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct CallBlock<'a> {
@@ -362,26 +435,46 @@ pub struct CallBlock<'a> {
 }
 
 /// Continue
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg(feature = "loop_controls")]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct Continue;
 
 /// Break
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg(feature = "loop_controls")]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct Break;
 
 /// A call block
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct Do<'a> {
     pub call: Spanned<Call<'a>>,
 }
 
+/// A translatable block.
+///
+/// The messages use `%(name)s` placeholders for the variables which are
+/// evaluated in the order of `variables`.  If the block has a plural form,
+/// `count` names the variable that selects it.
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
+#[cfg(feature = "i18n")]
+#[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
+pub struct Trans<'a> {
+    pub variables: Vec<(&'a str, Expr<'a>)>,
+    pub singular: String,
+    pub plural: Option<String>,
+    pub count: Option<&'a str>,
+}
+
 /// A "from" import
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg(feature = "multi_template")]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct FromImport<'a> {
@@ -390,7 +483,8 @@ pub struct FromImport<'a> {
 }
 
 /// A full module import
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg(feature = "multi_template")]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct Import<'a> {
@@ -399,35 +493,40 @@ pub struct Import<'a> {
 }
 
 /// Outputs the expression.
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct EmitExpr<'a> {
     pub expr: Expr<'a>,
 }
 
 /// Outputs raw template code.
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct EmitRaw<'a> {
     pub raw: &'a str,
 }
 
 /// Looks up a variable.
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct Var<'a> {
     pub id: &'a str,
 }
 
 /// Loads a constant
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct Const {
     pub value: Value,
 }
 
 /// Represents a slice.
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct Slice<'a> {
     pub expr: Expr<'a>,
@@ -437,7 +536,8 @@ pub struct Slice<'a> {
 }
 
 /// A kind of unary operator.
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[non_exhaustive]
+#[derive(Debug)]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub enum UnaryOpKind {
     Not,
@@ -445,7 +545,8 @@ pub enum UnaryOpKind {
 }
 
 /// An unary operator expression.
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct UnaryOp<'a> {
     pub op: UnaryOpKind,
@@ -453,7 +554,8 @@ pub struct UnaryOp<'a> {
 }
 
 /// A kind of binary operator.
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[non_exhaustive]
+#[derive(Debug)]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub enum BinOpKind {
     Eq,
@@ -476,7 +578,8 @@ pub enum BinOpKind {
 }
 
 /// A binary operator expression.
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct BinOp<'a> {
     pub op: BinOpKind,
@@ -485,7 +588,8 @@ pub struct BinOp<'a> {
 }
 
 /// An if expression.
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct IfExpr<'a> {
     pub test_expr: Expr<'a>,
@@ -494,7 +598,8 @@ pub struct IfExpr<'a> {
 }
 
 /// A filter expression.
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct Filter<'a> {
     pub name: &'a str,
@@ -503,7 +608,8 @@ pub struct Filter<'a> {
 }
 
 /// A test expression.
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct Test<'a> {
     pub name: &'a str,
@@ -512,7 +618,8 @@ pub struct Test<'a> {
 }
 
 /// An attribute lookup expression.
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct GetAttr<'a> {
     pub expr: Expr<'a>,
@@ -520,7 +627,8 @@ pub struct GetAttr<'a> {
 }
 
 /// An item lookup expression.
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct GetItem<'a> {
     pub expr: Expr<'a>,
@@ -528,7 +636,8 @@ pub struct GetItem<'a> {
 }
 
 /// Calls something.
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct Call<'a> {
     pub expr: Expr<'a>,
@@ -536,7 +645,8 @@ pub struct Call<'a> {
 }
 
 /// A call argument helper
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[non_exhaustive]
+#[derive(Debug)]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub enum CallArg<'a> {
     Pos(Expr<'a>),
@@ -546,14 +656,15 @@ pub enum CallArg<'a> {
 }
 
 /// Creates a list of values.
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct List<'a> {
     pub items: Vec<Expr<'a>>,
 }
 
 impl List<'_> {
-    pub fn as_const(&self) -> Option<Value> {
+    pub(crate) fn as_const(&self) -> Option<Value> {
         if !self.items.iter().all(|x| matches!(x, Expr::Const(_))) {
             return None;
         }
@@ -568,8 +679,30 @@ impl List<'_> {
     }
 }
 
+/// Creates a tuple of values.
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
+#[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
+pub struct Tuple<'a> {
+    pub items: Vec<Expr<'a>>,
+}
+
+impl Tuple<'_> {
+    pub(crate) fn as_const(&self) -> Option<Value> {
+        let items = self.items.iter();
+        let values = items
+            .map(|expr| match expr {
+                Expr::Const(v) => Some(v.value.clone()),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>();
+        values.map(Value::from_tuple)
+    }
+}
+
 /// Creates a map of values.
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub struct Map<'a> {
     pub keys: Vec<Expr<'a>>,
@@ -577,7 +710,7 @@ pub struct Map<'a> {
 }
 
 impl Map<'_> {
-    pub fn as_const(&self) -> Option<Value> {
+    pub(crate) fn as_const(&self) -> Option<Value> {
         if !self.keys.iter().all(|x| matches!(x, Expr::Const(_)))
             || !self.values.iter().all(|x| matches!(x, Expr::Const(_)))
         {
@@ -596,7 +729,8 @@ impl Map<'_> {
 }
 
 /// Defines the specific type of call.
-#[cfg_attr(feature = "internal_debug", derive(Debug))]
+#[cfg_attr(not(feature = "unstable_machinery"), non_exhaustive)]
+#[derive(Debug)]
 #[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
 pub enum CallType<'ast, 'source> {
     Function(&'source str),
@@ -0,0 +1,881 @@
+//! Serialization of compiled templates into a binary format.
+//!
+//! The format is a private implementation detail of MiniJinja.  It's only
+//! guaranteed to load into the exact same version of MiniJinja compiled with
+//! the same set of features that affect the instruction set.  The header
+//! carries all the information to detect mismatches so that stale caches can
+//! be rejected rather than misinterpreted.
+//!
+//! All strings referenced by instructions are stored in a single string
+//! table which starts with the template source.  Strings that are slices of
+//! the source are encoded as offsets into it, other strings are appended after
+//! the source.  This lets the loaded instructions borrow from a single owned
+//! buffer in the same way as templates compiled from source do.
+use std::collections::{BTreeMap, HashMap};
+
+use crate::compiler::html_context::HtmlContext;
+use crate::compiler::instructions::{Instruction, Instructions, LineInfo};
+use crate::error::{Error, ErrorKind};
+use crate::output::CaptureMode;
+use crate::template::{CompiledTemplate, TemplateConfig};
+use crate::utils::untrusted_size_hint;
+use crate::value::{
+    value_map_with_capacity, Kwargs, StringType, UndefinedType, Value, ValueKind, ValueRepr,
+};
+
+#[cfg(feature = "debug")]
+use crate::compiler::{instructions::SpanInfo, tokens::Span};
+
+const MAGIC: &[u8; 4] = b"MJBC";
+/// Version of the encoding.  This must be bumped whenever the encoding or
+/// the instruction set changes.
+const FORMAT_VERSION: u16 = 2;
+const MAX_VALUE_DEPTH: usize = 256;
+
+/// Bits for the features that change the shape of the compiled instructions.
+fn feature_flags() -> u8 {
+    let mut rv = 0;
+    if cfg!(feature = "debug") {
+        rv |= 1;
+    }
+    if cfg!(feature = "macros") {
+        rv |= 2;
+    }
+    if cfg!(feature = "multi_template") {
+        rv |= 4;
+    }
+    if cfg!(feature = "i18n") {
+        rv |= 8;
+    }
+    rv
+}
+
+/// FNV-1a over the payload to detect truncated or corrupted data.
+fn checksum(data: &[u8]) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    for byte in data {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+fn invalid(detail: &'static str) -> Error {
+    Error::new(ErrorKind::InvalidBytecode, detail)
+}
+
+struct Writer<'source> {
+    out: Vec<u8>,
+    source: &'source str,
+    extra_strings: String,
+    extra_offsets: HashMap<&'source str, u32>,
+}
+
+impl<'source> Writer<'source> {
+    fn u8(&mut self, value: u8) {
+        self.out.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.out.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.out.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.out.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn usize(&mut self, value: usize) {
+        self.u64(value as u64);
+    }
+
+    fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    fn bytes(&mut self, value: &[u8]) {
+        self.usize(value.len());
+        self.out.extend_from_slice(value);
+    }
+
+    fn opt_u16(&mut self, value: Option<u16>) {
+        match value {
+            Some(value) => {
+                self.u8(1);
+                self.u16(value);
+            }
+            None => self.u8(0),
+        }
+    }
+
+    /// Writes a reference into the string table.
+    fn str_ref(&mut self, value: &'source str) {
+        let source_start = self.source.as_ptr() as usize;
+        let start = value.as_ptr() as usize;
+        let offset =
+            if start >= source_start && start + value.len() <= source_start + self.source.len() {
+                (start - source_start) as u32
+            } else if let Some(offset) = self.extra_offsets.get(value) {
+                *offset
+            } else {
+                let offset = (self.source.len() + self.extra_strings.len()) as u32;
+                self.extra_strings.push_str(value);
+                self.extra_offsets.insert(value, offset);
+                offset
+            };
+        self.u32(offset);
+        self.u32(value.len() as u32);
+    }
+
+    #[cfg(feature = "debug")]
+    fn span(&mut self, span: &Span) {
+        self.u16(span.start_line);
+        self.u16(span.start_col);
+        self.u32(span.start_offset);
+        self.u16(span.end_line);
+        self.u16(span.end_col);
+        self.u32(span.end_offset);
+    }
+
+    fn value(&mut self, value: &Value) -> Result<(), Error> {
+        match value.0 {
+            ValueRepr::None => self.u8(0),
+            ValueRepr::Undefined(UndefinedType::Default, _) => self.u8(1),
+            ValueRepr::Undefined(UndefinedType::Silent, _) => self.u8(2),
+            ValueRepr::Bool(val) => {
+                self.u8(3);
+                self.bool(val);
+            }
+            ValueRepr::U64(val) => {
+                self.u8(4);
+                self.u64(val);
+            }
+            ValueRepr::I64(val) => {
+                self.u8(5);
+                self.u64(val as u64);
+            }
+            ValueRepr::F64(val) => {
+                self.u8(6);
+                self.u64(val.to_bits());
+            }
+            ValueRepr::U128(val) => {
+                self.u8(7);
+                self.out.extend_from_slice(&{ val.0 }.to_le_bytes());
+            }
+            ValueRepr::I128(val) => {
+                self.u8(8);
+                self.out.extend_from_slice(&{ val.0 }.to_le_bytes());
+            }
+            ValueRepr::String(ref val, StringType::Safe) => {
+                self.u8(10);
+                self.bytes(val.as_bytes());
+            }
+            ValueRepr::String(..) | ValueRepr::SmallStr(_) => {
+                self.u8(9);
+                self.bytes(value.as_str().unwrap_or_default().as_bytes());
+            }
+            ValueRepr::Bytes(ref val) => {
+                self.u8(11);
+                self.bytes(val);
+            }
+            ValueRepr::Object(ref obj) => {
+                if let Some(kwargs) = Kwargs::extract(value) {
+                    self.u8(14);
+                    self.usize(kwargs.values.len());
+                    for (key, value) in kwargs.values.iter() {
+                        ok!(self.value(key));
+                        ok!(self.value(value));
+                    }
+                } else if matches!(
+                    value.kind(),
+                    ValueKind::Seq | ValueKind::Tuple | ValueKind::Iterable
+                ) {
+                    // constant folding can produce lazy iterables (eg: `[1, 2] * 2`).
+                    let items = obj.try_iter().into_iter().flatten().collect::<Vec<_>>();
+                    self.u8(match value.kind() {
+                        ValueKind::Seq => 12,
+                        ValueKind::Tuple => 16,
+                        _ => 15,
+                    });
+                    self.usize(items.len());
+                    for item in &items {
+                        ok!(self.value(item));
+                    }
+                } else if value.kind() == ValueKind::Map {
+                    let items = obj
+                        .try_iter_pairs()
+                        .into_iter()
+                        .flatten()
+                        .collect::<Vec<_>>();
+                    self.u8(13);
+                    self.usize(items.len());
+                    for (key, value) in &items {
+                        ok!(self.value(key));
+                        ok!(self.value(value));
+                    }
+                } else {
+                    return Err(Error::new(
+                        ErrorKind::InvalidOperation,
+                        "cannot serialize constant object to bytecode",
+                    ));
+                }
+            }
+            ValueRepr::Invalid(_) => {
+                return Err(Error::new(
+                    ErrorKind::InvalidOperation,
+                    "cannot serialize invalid constant to bytecode",
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    fn instruction(&mut self, instr: &Instruction<'source>) -> Result<(), Error> {
+        match *instr {
+            Instruction::EmitRaw(s) => {
+                self.u8(0);
+                self.str_ref(s);
+            }
+            Instruction::EmitRawConst(ref value) => {
+                self.u8(66);
+                ok!(self.value(value));
+            }
+            Instruction::StoreLocal(s) => {
+                self.u8(1);
+                self.str_ref(s);
+            }
+            Instruction::Lookup(s) => {
+                self.u8(2);
+                self.str_ref(s);
+            }
+            Instruction::GetAttr(s) => {
+                self.u8(3);
+                self.str_ref(s);
+            }
+            Instruction::SetAttr(s) => {
+                self.u8(4);
+                self.str_ref(s);
+            }
+            Instruction::GetItem => self.u8(5),
+            Instruction::Slice => self.u8(6),
+            Instruction::LoadConst(ref value) => {
+                self.u8(7);
+                ok!(self.value(value));
+            }
+            Instruction::BuildMap(n) => {
+                self.u8(8);
+                self.usize(n);
+            }
+            Instruction::BuildKwargs(n) => {
+                self.u8(9);
+                self.usize(n);
+            }
+            Instruction::MergeKwargs(n) => {
+                self.u8(10);
+                self.usize(n);
+            }
+            Instruction::BuildList(n) => {
+                self.u8(11);
+                match n {
+                    Some(n) => {
+                        self.u8(1);
+                        self.usize(n);
+                    }
+                    None => self.u8(0),
+                }
+            }
+            Instruction::BuildTuple(n) => {
+                self.u8(67);
+                self.usize(n);
+            }
+            Instruction::UnpackList(n) => {
+                self.u8(12);
+                self.usize(n);
+            }
+            Instruction::UnpackLists(n) => {
+                self.u8(13);
+                self.usize(n);
+            }
+            Instruction::Add => self.u8(14),
+            Instruction::Sub => self.u8(15),
+            Instruction::Mul => self.u8(16),
+            Instruction::Div => self.u8(17),
+            Instruction::IntDiv => self.u8(18),
+            Instruction::Rem => self.u8(19),
+            Instruction::Pow => self.u8(20),
+            Instruction::Neg => self.u8(21),
+            Instruction::Eq => self.u8(22),
+            Instruction::Ne => self.u8(23),
+            Instruction::Gt => self.u8(24),
+            Instruction::Gte => self.u8(25),
+            Instruction::Lt => self.u8(26),
+            Instruction::Lte => self.u8(27),
+            Instruction::Not => self.u8(28),
+            Instruction::StringConcat => self.u8(29),
+            Instruction::In => self.u8(30),
+            Instruction::ApplyFilter(s, args, local_id) => {
+                self.u8(31);
+                self.str_ref(s);
+                self.opt_u16(args);
+                self.u8(local_id);
+            }
+            Instruction::PerformTest(s, args, local_id) => {
+                self.u8(32);
+                self.str_ref(s);
+                self.opt_u16(args);
+                self.u8(local_id);
+            }
+            Instruction::Emit => self.u8(33),
+            Instruction::EmitContextual(ctx) => {
+                self.u8(68);
+                self.u8(ctx.to_u8());
+            }
+            Instruction::PushLoop(flags) => {
+                self.u8(34);
+                self.u8(flags);
+            }
+            Instruction::PushWith => self.u8(35),
+            Instruction::Iterate(target) => {
+                self.u8(36);
+                self.u32(target);
+            }
+            Instruction::PushDidNotIterate => self.u8(37),
+            Instruction::PopFrame => self.u8(38),
+            Instruction::PopLoopFrame => self.u8(39),
+            Instruction::Jump(target) => {
+                self.u8(40);
+                self.u32(target);
+            }
+            Instruction::JumpIfFalse(target) => {
+                self.u8(41);
+                self.u32(target);
+            }
+            Instruction::JumpIfFalseOrPop(target) => {
+                self.u8(42);
+                self.u32(target);
+            }
+            Instruction::JumpIfTrueOrPop(target) => {
+                self.u8(43);
+                self.u32(target);
+            }
+            Instruction::PushAutoEscape => self.u8(44),
+            Instruction::PopAutoEscape => self.u8(45),
+            Instruction::BeginCapture(mode) => {
+                self.u8(46);
+                self.bool(matches!(mode, CaptureMode::Discard));
+            }
+            Instruction::EndCapture => self.u8(47),
+            Instruction::CallFunction(s, args) => {
+                self.u8(48);
+                self.str_ref(s);
+                self.opt_u16(args);
+            }
+            Instruction::CallMethod(s, args) => {
+                self.u8(49);
+                self.str_ref(s);
+                self.opt_u16(args);
+            }
+            Instruction::CallObject(args) => {
+                self.u8(50);
+                self.opt_u16(args);
+            }
+            Instruction::DupTop => self.u8(51),
+            Instruction::DiscardTop => self.u8(52),
+            Instruction::FastSuper => self.u8(53),
+            Instruction::FastRecurse => self.u8(54),
+            Instruction::Swap => self.u8(55),
+            #[cfg(feature = "multi_template")]
+            Instruction::CallBlock(s) => {
+                self.u8(56);
+                self.str_ref(s);
+            }
+            #[cfg(feature = "multi_template")]
+            Instruction::LoadBlocks => self.u8(57),
+            #[cfg(feature = "multi_template")]
+            Instruction::Include(ignore_missing) => {
+                self.u8(58);
+                self.bool(ignore_missing);
+            }
+            #[cfg(feature = "multi_template")]
+            Instruction::ExportLocals => self.u8(59),
+            #[cfg(feature = "macros")]
+            Instruction::BuildMacro(s, offset, flags) => {
+                self.u8(60);
+                self.str_ref(s);
+                self.u32(offset);
+                self.u8(flags);
+            }
+            #[cfg(feature = "macros")]
+            Instruction::Return => self.u8(61),
+            #[cfg(feature = "macros")]
+            Instruction::IsUndefined => self.u8(62),
+            #[cfg(feature = "macros")]
+            Instruction::Enclose(s) => {
+                self.u8(63);
+                self.str_ref(s);
+            }
+            #[cfg(feature = "macros")]
+            Instruction::GetClosure => self.u8(64),
+            #[cfg(feature = "i18n")]
+            Instruction::Translate(has_plural) => {
+                self.u8(65);
+                self.bool(has_plural);
+            }
+        }
+        Ok(())
+    }
+
+    fn instructions(&mut self, instructions: &Instructions<'source>) -> Result<(), Error> {
+        self.usize(instructions.instructions.len());
+        for instr in &instructions.instructions {
+            ok!(self.instruction(instr));
+        }
+        self.usize(instructions.line_infos.len());
+        for info in &instructions.line_infos {
+            self.u32(info.first_instruction);
+            self.u16(info.line);
+        }
+        #[cfg(feature = "debug")]
+        {
+            self.usize(instructions.span_infos.len());
+            for info in &instructions.span_infos {
+                self.u32(info.first_instruction);
+                self.span(&info.span);
+            }
+        }
+        #[cfg(feature = "multi_template")]
+        {
+            self.bool(instructions.required_block);
+        }
+        Ok(())
+    }
+}
+
+/// Serializes a compiled template into bytecode.
+pub fn encode<'source>(compiled: &CompiledTemplate<'source>) -> Result<Vec<u8>, Error> {
+    let source = compiled.instructions.source();
+    if source.len() > u32::MAX as usize / 2 {
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            "template too large to serialize to bytecode",
+        ));
+    }
+
+    let mut body = Writer::<'source> {
+        out: Vec::new(),
+        source,
+        extra_strings: String::new(),
+        extra_offsets: HashMap::new(),
+    };
+    body.usize(compiled.buffer_size_hint);
+    ok!(body.instructions(&compiled.instructions));
+    body.usize(compiled.blocks.len());
+    for (name, instructions) in &compiled.blocks {
+        body.str_ref(name);
+        ok!(body.instructions(instructions));
+    }
+
+    let mut payload = Vec::with_capacity(16 + source.len() + body.out.len());
+    payload.extend_from_slice(&(source.len() as u32).to_le_bytes());
+    let strings_len = source.len() + body.extra_strings.len();
+    payload.extend_from_slice(&(strings_len as u32).to_le_bytes());
+    payload.extend_from_slice(source.as_bytes());
+    payload.extend_from_slice(body.extra_strings.as_bytes());
+    payload.extend_from_slice(&body.out);
+
+    let version = env!("CARGO_PKG_VERSION");
+    let mut rv = Vec::with_capacity(payload.len() + 32);
+    rv.extend_from_slice(MAGIC);
+    rv.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    rv.push(version.len() as u8);
+    rv.extend_from_slice(version.as_bytes());
+    rv.push(feature_flags());
+    rv.extend_from_slice(&checksum(&payload).to_le_bytes());
+    rv.extend_from_slice(&payload);
+    Ok(rv)
+}
+
+struct Reader<'b> {
+    buf: &'b [u8],
+    pos: usize,
+}
+
+impl<'b> Reader<'b> {
+    fn take(&mut self, n: usize) -> Result<&'b [u8], Error> {
+        let rv = some_or_truncated(
+            self.pos
+                .checked_add(n)
+                .and_then(|end| self.buf.get(self.pos..end)),
+        );
+        self.pos += n;
+        rv
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        self.take(1).map(|x| x[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        self.take(2).map(|x| u16::from_le_bytes([x[0], x[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        self.take(4)
+            .map(|x| u32::from_le_bytes(x.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        self.take(8)
+            .map(|x| u64::from_le_bytes(x.try_into().unwrap()))
+    }
+
+    fn u128(&mut self) -> Result<u128, Error> {
+        self.take(16)
+            .map(|x| u128::from_le_bytes(x.try_into().unwrap()))
+    }
+
+    fn usize(&mut self) -> Result<usize, Error> {
+        usize::try_from(ok!(self.u64())).map_err(|_| invalid("length out of range"))
+    }
+
+    fn bool(&mut self) -> Result<bool, Error> {
+        self.u8().map(|x| x != 0)
+    }
+
+    fn bytes(&mut self) -> Result<&'b [u8], Error> {
+        let len = ok!(self.usize());
+        self.take(len)
+    }
+
+    fn opt_u16(&mut self) -> Result<Option<u16>, Error> {
+        Ok(if ok!(self.bool()) {
+            Some(ok!(self.u16()))
+        } else {
+            None
+        })
+    }
+
+    fn str_ref<'s>(&mut self, strings: &'s str) -> Result<&'s str, Error> {
+        let offset = ok!(self.u32()) as usize;
+        let len = ok!(self.u32()) as usize;
+        offset
+            .checked_add(len)
+            .and_then(|end| strings.get(offset..end))
+            .ok_or_else(|| invalid("invalid string reference"))
+    }
+
+    #[cfg(feature = "debug")]
+    fn span(&mut self) -> Result<Span, Error> {
+        Ok(Span {
+            start_line: ok!(self.u16()),
+            start_col: ok!(self.u16()),
+            start_offset: ok!(self.u32()),
+            end_line: ok!(self.u16()),
+            end_col: ok!(self.u16()),
+            end_offset: ok!(self.u32()),
+        })
+    }
+
+    fn string(&mut self) -> Result<&'b str, Error> {
+        std::str::from_utf8(ok!(self.bytes())).map_err(|_| invalid("invalid utf-8 in constant"))
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, Error> {
+        if depth > MAX_VALUE_DEPTH {
+            return Err(invalid("constant nested too deeply"));
+        }
+        Ok(match ok!(self.u8()) {
+            0 => Value::from(()),
+            1 => Value::UNDEFINED,
+            2 => ValueRepr::Undefined(UndefinedType::Silent, None).into(),
+            3 => Value::from(ok!(self.bool())),
+            4 => Value::from(ok!(self.u64())),
+            5 => Value::from(ok!(self.u64()) as i64),
+            6 => Value::from(f64::from_bits(ok!(self.u64()))),
+            7 => Value::from(ok!(self.u128())),
+            8 => Value::from(ok!(self.u128()) as i128),
+            9 => Value::from(ok!(self.string())),
+            10 => Value::from_safe_string(ok!(self.string()).to_string()),
+            11 => Value::from_bytes(ok!(self.bytes()).to_vec()),
+            tag @ (12 | 15 | 16) => {
+                let len = ok!(self.usize());
+                let mut items = Vec::with_capacity(untrusted_size_hint(len));
+                for _ in 0..len {
+                    items.push(ok!(self.value(depth + 1)));
+                }
+                if tag == 12 {
+                    Value::from(items)
+                } else if tag == 16 {
+                    Value::from_tuple(items)
+                } else {
+                    Value::make_iterable(move || items.clone().into_iter())
+                }
+            }
+            tag @ (13 | 14) => {
+                let len = ok!(self.usize());
+                let mut map = value_map_with_capacity(len);
+                for _ in 0..len {
+                    let key = ok!(self.value(depth + 1));
+                    let value = ok!(self.value(depth + 1));
+                    map.insert(key, value);
+                }
+                if tag == 14 {
+                    Kwargs::wrap(map)
+                } else {
+                    Value::from_object(map)
+                }
+            }
+            _ => return Err(invalid("unknown constant type")),
+        })
+    }
+
+    fn instruction<'s>(&mut self, strings: &'s str) -> Result<Instruction<'s>, Error> {
+        Ok(match ok!(self.u8()) {
+            0 => Instruction::EmitRaw(ok!(self.str_ref(strings))),
+            1 => Instruction::StoreLocal(ok!(self.str_ref(strings))),
+            2 => Instruction::Lookup(ok!(self.str_ref(strings))),
+            3 => Instruction::GetAttr(ok!(self.str_ref(strings))),
+            4 => Instruction::SetAttr(ok!(self.str_ref(strings))),
+            5 => Instruction::GetItem,
+            6 => Instruction::Slice,
+            7 => Instruction::LoadConst(ok!(self.value(0))),
+            8 => Instruction::BuildMap(ok!(self.usize())),
+            9 => Instruction::BuildKwargs(ok!(self.usize())),
+            10 => Instruction::MergeKwargs(ok!(self.usize())),
+            11 => Instruction::BuildList(if ok!(self.bool()) {
+                Some(ok!(self.usize()))
+            } else {
+                None
+            }),
+            12 => Instruction::UnpackList(ok!(self.usize())),
+            13 => Instruction::UnpackLists(ok!(self.usize())),
+            14 => Instruction::Add,
+            15 => Instruction::Sub,
+            16 => Instruction::Mul,
+            17 => Instruction::Div,
+            18 => Instruction::IntDiv,
+            19 => Instruction::Rem,
+            20 => Instruction::Pow,
+            21 => Instruction::Neg,
+            22 => Instruction::Eq,
+            23 => Instruction::Ne,
+            24 => Instruction::Gt,
+            25 => Instruction::Gte,
+            26 => Instruction::Lt,
+            27 => Instruction::Lte,
+            28 => Instruction::Not,
+            29 => Instruction::StringConcat,
+            30 => Instruction::In,
+            op @ (31 | 32) => {
+                let name = ok!(self.str_ref(strings));
+                let args = ok!(self.opt_u16());
+                let local_id = ok!(self.u8());
+                if local_id as usize >= crate::compiler::instructions::MAX_LOCALS {
+                    return Err(invalid("local id out of range"));
+                }
+                if op == 31 {
+                    Instruction::ApplyFilter(name, args, local_id)
+                } else {
+                    Instruction::PerformTest(name, args, local_id)
+                }
+            }
+            33 => Instruction::Emit,
+            34 => Instruction::PushLoop(ok!(self.u8())),
+            35 => Instruction::PushWith,
+            36 => Instruction::Iterate(ok!(self.u32())),
+            37 => Instruction::PushDidNotIterate,
+            38 => Instruction::PopFrame,
+            39 => Instruction::PopLoopFrame,
+            40 => Instruction::Jump(ok!(self.u32())),
+            41 => Instruction::JumpIfFalse(ok!(self.u32())),
+            42 => Instruction::JumpIfFalseOrPop(ok!(self.u32())),
+            43 => Instruction::JumpIfTrueOrPop(ok!(self.u32())),
+            44 => Instruction::PushAutoEscape,
+            45 => Instruction::PopAutoEscape,
+            46 => Instruction::BeginCapture(if ok!(self.bool()) {
+                CaptureMode::Discard
+            } else {
+                CaptureMode::Capture
+            }),
+            47 => Instruction::EndCapture,
+            48 => Instruction::CallFunction(ok!(self.str_ref(strings)), ok!(self.opt_u16())),
+            49 => Instruction::CallMethod(ok!(self.str_ref(strings)), ok!(self.opt_u16())),
+            50 => Instruction::CallObject(ok!(self.opt_u16())),
+            51 => Instruction::DupTop,
+            52 => Instruction::DiscardTop,
+            53 => Instruction::FastSuper,
+            54 => Instruction::FastRecurse,
+            55 => Instruction::Swap,
+            #[cfg(feature = "multi_template")]
+            56 => Instruction::CallBlock(ok!(self.str_ref(strings))),
+            #[cfg(feature = "multi_template")]
+            57 => Instruction::LoadBlocks,
+            #[cfg(feature = "multi_template")]
+            58 => Instruction::Include(ok!(self.bool())),
+            #[cfg(feature = "multi_template")]
+            59 => Instruction::ExportLocals,
+            #[cfg(feature = "macros")]
+            60 => {
+                Instruction::BuildMacro(ok!(self.str_ref(strings)), ok!(self.u32()), ok!(self.u8()))
+            }
+            #[cfg(feature = "macros")]
+            61 => Instruction::Return,
+            #[cfg(feature = "macros")]
+            62 => Instruction::IsUndefined,
+            #[cfg(feature = "macros")]
+            63 => Instruction::Enclose(ok!(self.str_ref(strings))),
+            #[cfg(feature = "macros")]
+            64 => Instruction::GetClosure,
+            #[cfg(feature = "i18n")]
+            65 => Instruction::Translate(ok!(self.bool())),
+            66 => Instruction::EmitRawConst(ok!(self.value(0))),
+            67 => Instruction::BuildTuple(ok!(self.usize())),
+            68 => {
+                Instruction::EmitContextual(ok!(HtmlContext::from_u8(ok!(self.u8()))
+                    .ok_or_else(|| invalid("unknown html context"))))
+            }
+            _ => return Err(invalid("unknown instruction")),
+        })
+    }
+
+    fn instructions<'s>(
+        &mut self,
+        name: &'s str,
+        strings: &'s str,
+        source: &'s str,
+    ) -> Result<Instructions<'s>, Error> {
+        let mut rv = Instructions::new(name, source);
+        let count = ok!(self.usize());
+        rv.instructions = Vec::with_capacity(untrusted_size_hint(count));
+        for _ in 0..count {
+            rv.instructions.push(ok!(self.instruction(strings)));
+        }
+        let count = ok!(self.usize());
+        rv.line_infos = Vec::with_capacity(untrusted_size_hint(count));
+        for _ in 0..count {
+            rv.line_infos.push(LineInfo {
+                first_instruction: ok!(self.u32()),
+                line: ok!(self.u16()),
+            });
+        }
+        #[cfg(feature = "debug")]
+        {
+            let count = ok!(self.usize());
+            rv.span_infos = Vec::with_capacity(untrusted_size_hint(count));
+            for _ in 0..count {
+                rv.span_infos.push(SpanInfo {
+                    first_instruction: ok!(self.u32()),
+                    span: ok!(self.span()),
+                });
+            }
+        }
+        #[cfg(feature = "multi_template")]
+        {
+            rv.required_block = ok!(self.bool());
+        }
+        Ok(rv)
+    }
+}
+
+fn some_or_truncated<T>(value: Option<T>) -> Result<T, Error> {
+    value.ok_or_else(|| invalid("unexpected end of bytecode"))
+}
+
+/// The string table and the still undecoded instructions of a bytecode blob.
+pub struct BytecodeParts<'b> {
+    /// Template source followed by extra strings.
+    pub strings: Box<str>,
+    /// Length of the template source at the start of the string table.
+    pub source_len: usize,
+    body: &'b [u8],
+}
+
+/// Validates the header and extracts the string table.
+///
+/// The string table needs to be moved into the final owner of the template
+/// before the instructions can be decoded with [`decode`].
+pub fn read_parts(bytecode: &[u8]) -> Result<BytecodeParts<'_>, Error> {
+    let mut reader = Reader {
+        buf: bytecode,
+        pos: 0,
+    };
+    if bytecode.get(..4) != Some(&MAGIC[..]) {
+        return Err(invalid("not minijinja bytecode"));
+    }
+    reader.pos = 4;
+    let format_version = ok!(reader.u16());
+    let version_len = ok!(reader.u8()) as usize;
+    let version = ok!(reader.take(version_len));
+    let flags = ok!(reader.u8());
+    if format_version != FORMAT_VERSION
+        || version != env!("CARGO_PKG_VERSION").as_bytes()
+        || flags != feature_flags()
+    {
+        return Err(Error::new(
+            ErrorKind::InvalidBytecode,
+            format!(
+                "bytecode was created by an incompatible version of minijinja ({})",
+                String::from_utf8_lossy(version)
+            ),
+        ));
+    }
+    let expected_checksum = ok!(reader.u64());
+    let payload = &bytecode[reader.pos..];
+    if checksum(payload) != expected_checksum {
+        return Err(invalid("bytecode checksum mismatch"));
+    }
+
+    let mut reader = Reader {
+        buf: payload,
+        pos: 0,
+    };
+    let source_len = ok!(reader.u32()) as usize;
+    let strings_len = ok!(reader.u32()) as usize;
+    let strings = ok!(std::str::from_utf8(ok!(reader.take(strings_len)))
+        .map_err(|_| invalid("invalid utf-8 in string table")));
+    if !strings.is_char_boundary(source_len) {
+        return Err(invalid("invalid source length"));
+    }
+    Ok(BytecodeParts {
+        strings: strings.into(),
+        source_len,
+        body: &payload[reader.pos..],
+    })
+}
+
+/// Decodes the instructions of a template from previously read parts.
+///
+/// `strings` must be the string table taken from the parts.
+pub fn decode<'s>(
+    name: &'s str,
+    strings: &'s str,
+    parts: &BytecodeParts<'_>,
+    config: &TemplateConfig,
+) -> Result<CompiledTemplate<'s>, Error> {
+    let source = &strings[..parts.source_len];
+    let mut reader = Reader {
+        buf: parts.body,
+        pos: 0,
+    };
+    let buffer_size_hint = ok!(reader.usize());
+    let instructions = ok!(reader.instructions(name, strings, source));
+    let block_count = ok!(reader.usize());
+    let mut blocks = BTreeMap::new();
+    for _ in 0..block_count {
+        let block_name = ok!(reader.str_ref(strings));
+        blocks.insert(block_name, ok!(reader.instructions(name, strings, source)));
+    }
+    if reader.pos != parts.body.len() {
+        return Err(invalid("trailing data in bytecode"));
+    }
+    Ok(CompiledTemplate {
+        instructions,
+        blocks,
+        buffer_size_hint,
+        syntax_config: config.syntax_config.clone(),
+        initial_auto_escape: (config.default_auto_escape)(name),
+    })
+}
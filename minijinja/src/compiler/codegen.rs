@@ -3,10 +3,12 @@ use std::collections::BTreeMap;
 use std::mem;
 
 use crate::compiler::ast;
+use crate::compiler::html_context::{Context as HtmlContext, Tracker as HtmlTracker};
 use crate::compiler::instructions::{
     Instruction, Instructions, LocalId, LOOP_FLAG_RECURSIVE, LOOP_FLAG_WITH_LOOP_VAR, MAX_LOCALS,
 };
 use crate::compiler::tokens::Span;
+use crate::error::{Error, ErrorKind};
 use crate::output::CaptureMode;
 use crate::value::ops::neg;
 use crate::value::{Kwargs, UndefinedType, Value, ValueMap, ValueRepr};
@@ -110,6 +112,8 @@ pub struct CodeGenerator<'source> {
     filter_local_ids: BTreeMap<&'source str, LocalId>,
     test_local_ids: BTreeMap<&'source str, LocalId>,
     raw_template_bytes: usize,
+    html_tracker: Option<HtmlTracker>,
+    tuples: bool,
 }
 
 impl<'source> CodeGenerator<'source> {
@@ -124,9 +128,37 @@ impl<'source> CodeGenerator<'source> {
             filter_local_ids: BTreeMap::new(),
             test_local_ids: BTreeMap::new(),
             raw_template_bytes: 0,
+            html_tracker: None,
+            tuples: false,
         }
     }
 
+    /// Makes the tuple syntax create tuples.
+    ///
+    /// By default tuple literals are compiled into lists.
+    pub fn enable_tuples(&mut self) {
+        self.tuples = true;
+    }
+
+    /// Enables tracking of the HTML context for contextual auto escaping.
+    ///
+    /// Values emitted in contexts where HTML escaping is not sufficient are
+    /// then compiled into [`Instruction::EmitContextual`].
+    pub fn enable_html_context_tracking(&mut self) {
+        self.html_tracker = Some(HtmlTracker::default());
+    }
+
+    /// Finishes tracking the HTML context and returns the first error
+    /// encountered while tracking it.
+    pub fn take_html_context_error(&mut self) -> Option<Error> {
+        let tracker = self.html_tracker.as_mut()?;
+        tracker.finish();
+        let (msg, span) = tracker.take_error()?;
+        let mut err = Error::new(ErrorKind::SyntaxError, msg);
+        err.set_filename_and_span(self.instructions.name(), span);
+        Some(err)
+    }
+
     /// Sets the current location's line.
     pub fn set_line(&mut self, lineno: u16) {
         self.current_line = lineno;
@@ -170,22 +202,67 @@ impl<'source> CodeGenerator<'source> {
 
     /// Creates a sub generator.
     #[cfg(feature = "multi_template")]
-    fn new_subgenerator(&self) -> CodeGenerator<'source> {
+    fn new_subgenerator(&mut self) -> CodeGenerator<'source> {
         let mut sub = CodeGenerator::new(self.instructions.name(), self.instructions.source());
         sub.current_line = self.current_line;
         sub.span_stack = self.span_stack.last().copied().into_iter().collect();
+        sub.html_tracker = self.html_tracker.take();
+        sub.tuples = self.tuples;
         sub
     }
 
     /// Finishes a sub generator and syncs it back.
     #[cfg(feature = "multi_template")]
-    fn finish_subgenerator(&mut self, sub: CodeGenerator<'source>) -> Instructions<'source> {
+    fn finish_subgenerator(&mut self, mut sub: CodeGenerator<'source>) -> Instructions<'source> {
         self.current_line = sub.current_line;
+        self.html_tracker = sub.html_tracker.take();
         let (instructions, blocks) = sub.finish();
         self.blocks.extend(blocks);
         instructions
     }
 
+    /// Adds an emit instruction escaping for the current HTML context.
+    fn add_emit(&mut self, span: Span) {
+        match self.html_tracker.as_mut().and_then(|x| x.emit(span)) {
+            Some(ctx) => self.add(Instruction::EmitContextual(ctx)),
+            None => self.add(Instruction::Emit),
+        };
+    }
+
+    /// Returns the tracked HTML context.
+    fn html_context(&self) -> Option<HtmlContext> {
+        self.html_tracker.as_ref().map(|x| x.context())
+    }
+
+    /// Continues tracking the HTML from a previously returned context.
+    fn set_html_context(&mut self, ctx: Option<HtmlContext>) {
+        if let (Some(tracker), Some(ctx)) = (self.html_tracker.as_mut(), ctx) {
+            tracker.set_context(ctx);
+        }
+    }
+
+    /// Starts tracking a separate fragment of HTML such as a macro body.
+    ///
+    /// Returns the context to restore after the fragment.
+    fn enter_html_fragment(&mut self) -> Option<HtmlContext> {
+        self.html_tracker.as_mut().map(|x| x.enter_fragment())
+    }
+
+    /// Fails unless the HTML context is HTML text.
+    #[cfg(feature = "multi_template")]
+    fn expect_html_text(&mut self, span: Span, what: &str) {
+        if let Some(ref mut tracker) = self.html_tracker {
+            tracker.expect_text(span, what);
+        }
+    }
+
+    /// Joins the HTML context with the end of another code path.
+    fn join_html_context(&mut self, other: Option<HtmlContext>, span: Span, what: &str) {
+        if let (Some(tracker), Some(other)) = (self.html_tracker.as_mut(), other) {
+            tracker.join(other, span, what);
+        }
+    }
+
     /// Starts a for loop
     pub fn start_for_loop(&mut self, with_loop_var: bool, recursive: bool) {
         let mut flags = 0;
@@ -318,6 +395,9 @@ impl<'source> CodeGenerator<'source> {
                 self.set_line_from_span(raw.span());
                 self.add(Instruction::EmitRaw(raw.raw));
                 self.raw_template_bytes += raw.raw.len();
+                if let Some(ref mut tracker) = self.html_tracker {
+                    tracker.feed(raw.raw, raw.span());
+                }
             }
             ast::Stmt::ForLoop(for_loop) => {
                 self.compile_for_loop(for_loop);
@@ -345,9 +425,11 @@ impl<'source> CodeGenerator<'source> {
             ast::Stmt::SetBlock(set_block) => {
                 self.set_line_from_span(set_block.span());
                 self.add(Instruction::BeginCapture(CaptureMode::Capture));
+                let html_context = self.enter_html_fragment();
                 for node in &set_block.body {
                     self.compile_stmt(node);
                 }
+                self.set_html_context(html_context);
                 self.add(Instruction::EndCapture);
                 if let Some(ref filter) = set_block.filter {
                     self.compile_expr(filter);
@@ -366,12 +448,14 @@ impl<'source> CodeGenerator<'source> {
             ast::Stmt::FilterBlock(filter_block) => {
                 self.set_line_from_span(filter_block.span());
                 self.add(Instruction::BeginCapture(CaptureMode::Capture));
+                let html_context = self.enter_html_fragment();
                 for node in &filter_block.body {
                     self.compile_stmt(node);
                 }
+                self.set_html_context(html_context);
                 self.add(Instruction::EndCapture);
                 self.compile_expr(&filter_block.filter);
-                self.add(Instruction::Emit);
+                self.add_emit(filter_block.span());
             }
             #[cfg(feature = "multi_template")]
             ast::Stmt::Block(block) => {
@@ -412,6 +496,7 @@ impl<'source> CodeGenerator<'source> {
             #[cfg(feature = "multi_template")]
             ast::Stmt::Include(include) => {
                 self.set_line_from_span(include.span());
+                self.expect_html_text(include.span(), "include a template");
                 self.compile_expr(&include.name);
                 self.add_with_span(Instruction::Include(include.ignore_missing), include.span());
             }
@@ -451,27 +536,58 @@ impl<'source> CodeGenerator<'source> {
             ast::Stmt::Do(do_tag) => {
                 self.compile_do(do_tag);
             }
+            #[cfg(feature = "i18n")]
+            ast::Stmt::Trans(trans) => {
+                self.compile_trans(trans);
+            }
         }
     }
 
     #[cfg(feature = "multi_template")]
     fn compile_block(&mut self, block: &ast::Spanned<ast::Block<'source>>) {
         self.set_line_from_span(block.span());
+        // blocks can be overridden by templates that track their HTML
+        // separately, so they have to start and end in HTML text.
+        self.expect_html_text(block.span(), "use a block");
+        let html_context = self.html_context();
         let mut sub = self.new_subgenerator();
         for node in &block.body {
             sub.compile_stmt(node);
         }
         sub.instructions.mark_required_block(block.required);
         let instructions = self.finish_subgenerator(sub);
+        self.join_html_context(html_context, block.span(), "blocks start and end");
         self.blocks.insert(block.name, instructions);
         self.add(Instruction::CallBlock(block.name));
     }
 
     #[cfg(feature = "macros")]
     fn compile_macro_expression(&mut self, macro_decl: &ast::Spanned<ast::Macro<'source>>) {
-        use crate::compiler::instructions::MACRO_CALLER;
+        use crate::compiler::instructions::{MACRO_CALLER, MACRO_KWARGS, MACRO_VARARGS};
         self.set_line_from_span(macro_decl.span());
+        let mut undeclared = crate::compiler::meta::find_macro_closure(macro_decl);
+        let caller_reference = undeclared.remove("caller");
+        // like in Jinja2 a macro that references `varargs` or `kwargs` without
+        // declaring them catches extra arguments implicitly.
+        let catch_varargs = macro_decl.varargs.is_some() || undeclared.remove("varargs");
+        let catch_kwargs = macro_decl.kwargs.is_some() || undeclared.remove("kwargs");
         let instr = self.add(Instruction::Jump(!0));
+        if catch_kwargs {
+            match macro_decl.kwargs {
+                Some(ref target) => self.compile_assignment(target),
+                None => {
+                    self.add(Instruction::StoreLocal("kwargs"));
+                }
+            }
+        }
+        if catch_varargs {
+            match macro_decl.varargs {
+                Some(ref target) => self.compile_assignment(target),
+                None => {
+                    self.add(Instruction::StoreLocal("varargs"));
+                }
+            }
+        }
         let mut defaults_iter = macro_decl.defaults.iter().rev();
         for arg in macro_decl.args.iter().rev() {
             if let Some(default) = defaults_iter.next() {
@@ -484,12 +600,12 @@ impl<'source> CodeGenerator<'source> {
             }
             self.compile_assignment(arg);
         }
+        let html_context = self.enter_html_fragment();
         for node in &macro_decl.body {
             self.compile_stmt(node);
         }
+        self.set_html_context(html_context);
         self.add(Instruction::Return);
-        let mut undeclared = crate::compiler::meta::find_macro_closure(macro_decl);
-        let caller_reference = undeclared.remove("caller");
         let macro_instr = self.next_instruction();
         for name in &undeclared {
             self.add(Instruction::Enclose(name));
@@ -509,6 +625,12 @@ impl<'source> CodeGenerator<'source> {
         if caller_reference {
             flags |= MACRO_CALLER;
         }
+        if catch_varargs {
+            flags |= MACRO_VARARGS;
+        }
+        if catch_kwargs {
+            flags |= MACRO_KWARGS;
+        }
         self.add(Instruction::BuildMacro(macro_decl.name, instr + 1, flags));
         if let Some(&mut Instruction::Jump(ref mut target)) = self.instructions.get_mut(instr) {
             *target = macro_instr;
@@ -526,27 +648,60 @@ impl<'source> CodeGenerator<'source> {
     #[cfg(feature = "macros")]
     fn compile_call_block(&mut self, call_block: &ast::Spanned<ast::CallBlock<'source>>) {
         self.compile_call(&call_block.call, Some(&call_block.macro_decl));
-        self.add(Instruction::Emit);
+        self.add_emit(call_block.span());
     }
 
     fn compile_do(&mut self, do_tag: &ast::Spanned<ast::Do<'source>>) {
         self.compile_call(&do_tag.call, None);
     }
 
+    #[cfg(feature = "i18n")]
+    fn compile_trans(&mut self, trans: &ast::Spanned<ast::Trans<'source>>) {
+        self.push_span(trans.span());
+        self.add(Instruction::LoadConst(Value::from(trans.singular.as_str())));
+        if let Some(ref plural) = trans.plural {
+            self.add(Instruction::LoadConst(Value::from(plural.as_str())));
+            match trans
+                .variables
+                .iter()
+                .find(|(name, _)| Some(*name) == trans.count)
+            {
+                Some((_, expr)) => self.compile_expr(expr),
+                None => {
+                    self.add(Instruction::LoadConst(Value::UNDEFINED));
+                }
+            }
+        }
+        for (name, expr) in &trans.variables {
+            self.add(Instruction::LoadConst(Value::from(*name)));
+            self.compile_expr(expr);
+        }
+        self.add(Instruction::BuildMap(trans.variables.len()));
+        self.add(Instruction::Translate(trans.plural.is_some()));
+        self.add_emit(trans.span());
+        self.pop_span();
+    }
+
     fn compile_if_stmt(&mut self, if_cond: &ast::Spanned<ast::IfCond<'source>>) {
         self.set_line_from_span(if_cond.span());
         self.push_span(if_cond.expr.span());
         self.compile_expr(&if_cond.expr);
         self.start_if();
         self.pop_span();
+        let html_context = self.html_context();
         for node in &if_cond.true_body {
             self.compile_stmt(node);
         }
         if !if_cond.false_body.is_empty() {
             self.start_else();
+            let true_html_context = self.html_context();
+            self.set_html_context(html_context);
             for node in &if_cond.false_body {
                 self.compile_stmt(node);
             }
+            self.join_html_context(true_html_context, if_cond.span(), "if branches end");
+        } else {
+            self.join_html_context(html_context, if_cond.span(), "if branches end");
         }
         self.end_if();
     }
@@ -557,6 +712,8 @@ impl<'source> CodeGenerator<'source> {
             match call.identify_call() {
                 ast::CallType::Function(name) => {
                     if name == "super" && call.args.is_empty() {
+                        #[cfg(feature = "multi_template")]
+                        self.expect_html_text(call.span(), "render a block");
                         self.add_with_span(Instruction::FastSuper, call.span());
                         return;
                     } else if name == "loop" && call.args.len() == 1 {
@@ -567,6 +724,7 @@ impl<'source> CodeGenerator<'source> {
                 }
                 #[cfg(feature = "multi_template")]
                 ast::CallType::Block(name) => {
+                    self.expect_html_text(call.span(), "render a block");
                     self.add(Instruction::CallBlock(name));
                     return;
                 }
@@ -575,7 +733,7 @@ impl<'source> CodeGenerator<'source> {
         }
         self.push_span(expr.expr.span());
         self.compile_expr(&expr.expr);
-        self.add(Instruction::Emit);
+        self.add_emit(expr.expr.span());
         self.pop_span();
     }
 
@@ -613,15 +771,20 @@ impl<'source> CodeGenerator<'source> {
         }
 
         self.compile_assignment(&for_loop.target);
+        let html_context = self.html_context();
         for node in &for_loop.body {
             self.compile_stmt(node);
         }
+        self.join_html_context(html_context, for_loop.span(), "loop iterations end");
         self.end_for_loop(!for_loop.else_body.is_empty());
         if !for_loop.else_body.is_empty() {
             self.start_if();
+            let loop_html_context = self.html_context();
+            self.set_html_context(html_context);
             for node in &for_loop.else_body {
                 self.compile_stmt(node);
             }
+            self.join_html_context(loop_html_context, for_loop.span(), "loop and else body end");
             self.end_if();
         };
     }
@@ -651,11 +814,14 @@ impl<'source> CodeGenerator<'source> {
 
     /// Compiles an expression.
     pub fn compile_expr(&mut self, expr: &ast::Expr<'source>) {
-        // try to do constant folding
-        if let Some(v) = expr.as_const() {
-            self.set_line_from_span(expr.span());
-            self.add(Instruction::LoadConst(v.clone()));
-            return;
+        // try to do constant folding.  Constant tuples are only folded if
+        // tuples are enabled, otherwise they are compiled into lists below.
+        if self.tuples || !contains_tuple(expr) {
+            if let Some(v) = expr.as_const() {
+                self.set_line_from_span(expr.span());
+                self.add(Instruction::LoadConst(v.clone()));
+                return;
+            }
         }
 
         match expr {
@@ -723,7 +889,7 @@ impl<'source> CodeGenerator<'source> {
                     // to permit special casing.  This is for compatibility also with
                     // what Jinja2 does.
                     self.add(Instruction::LoadConst(
-                        ValueRepr::Undefined(UndefinedType::Silent).into(),
+                        ValueRepr::Undefined(UndefinedType::Silent, None).into(),
                     ));
                 }
                 self.end_if();
@@ -769,6 +935,7 @@ impl<'source> CodeGenerator<'source> {
                 }
                 self.add(Instruction::BuildList(Some(l.items.len())));
             }
+            ast::Expr::Tuple(t) => self.compile_tuple(t, self.tuples),
             ast::Expr::Map(m) => {
                 self.set_line_from_span(m.span());
                 assert_eq!(m.keys.len(), m.values.len());
@@ -952,11 +1119,28 @@ impl<'source> CodeGenerator<'source> {
             ast::BinOpKind::In => Instruction::In,
         };
         self.compile_expr(&c.left);
-        self.compile_expr(&c.right);
+        match (&c.op, &c.right) {
+            // string formatting only takes the positional arguments from
+            // tuples, so `'%s, %s' % (a, b)` always builds one.
+            (ast::BinOpKind::Rem, ast::Expr::Tuple(t)) => self.compile_tuple(t, true),
+            _ => self.compile_expr(&c.right),
+        }
         self.add(instr);
         self.pop_span();
     }
 
+    fn compile_tuple(&mut self, t: &ast::Spanned<ast::Tuple<'source>>, as_tuple: bool) {
+        self.set_line_from_span(t.span());
+        for item in &t.items {
+            self.compile_expr(item);
+        }
+        if as_tuple {
+            self.add(Instruction::BuildTuple(t.items.len()));
+        } else {
+            self.add(Instruction::BuildList(Some(t.items.len())));
+        }
+    }
+
     /// Returns the size hint for buffers.
     ///
     /// This is a proposal for the initial buffer size when rendering directly to a string.
@@ -981,3 +1165,15 @@ impl<'source> CodeGenerator<'source> {
         (self.instructions, self.blocks)
     }
 }
+
+/// Checks if a constant expression contains a tuple literal.
+fn contains_tuple(expr: &ast::Expr<'_>) -> bool {
+    match expr {
+        ast::Expr::Tuple(_) => true,
+        ast::Expr::List(l) => l.items.iter().any(contains_tuple),
+        ast::Expr::Map(m) => m.keys.iter().chain(m.values.iter()).any(contains_tuple),
+        ast::Expr::UnaryOp(u) => contains_tuple(&u.expr),
+        ast::Expr::BinOp(b) => contains_tuple(&b.left) || contains_tuple(&b.right),
+        _ => false,
+    }
+}
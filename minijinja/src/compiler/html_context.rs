@@ -0,0 +1,1167 @@
+//! Tracking of the HTML context for contextual auto escaping.
+//!
+//! When enabled, the code generator feeds the raw template source into a
+//! [`Tracker`] which follows the lexical state of the HTML across the raw
+//! chunks of a template.  Values emitted in a context where plain HTML
+//! escaping is not sufficient (such as in a `<script>` tag, an event handler
+//! or a URL attribute) are compiled into an `EmitContextual` instruction
+//! which carries the escaping to apply.  This is modelled after Go's
+//! `html/template` but only distinguishes the contexts that change how a
+//! value has to be escaped.
+use std::fmt::{self, Write};
+
+use crate::compiler::tokens::Span;
+use crate::error::{Error, ErrorKind};
+use crate::output::Output;
+use crate::utils::{CssEscape, HtmlEscape, UrlEscape};
+use crate::value::{StringType, Value, ValueKind, ValueRepr};
+
+/// Emitted in place of values that cannot be safely emitted in a context.
+const FAILSAFE: &str = "ZgotmplZ";
+
+/// The escaping that is applied to an emitted value.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
+pub enum Escaper {
+    /// An attribute value that only needs the escaping of its delimiter.
+    Attr,
+    /// The name of an attribute.
+    AttrName,
+    /// The start of a URL, unsafe schemes are rejected.
+    UrlStart,
+    /// A URL after its start but before the query string.
+    UrlPath,
+    /// The query string or fragment of a URL.
+    UrlQuery,
+    /// A JavaScript expression.
+    JsValue,
+    /// The inside of a JavaScript string literal.
+    JsString,
+    /// The inside of a JavaScript regular expression literal.
+    JsRegexp,
+    /// A CSS value.
+    CssValue,
+    /// The inside of a CSS string.
+    CssString,
+    /// A comment, values are dropped.
+    Comment,
+}
+
+impl Escaper {
+    fn describe(self) -> &'static str {
+        match self {
+            Escaper::Attr => "an unquoted attribute value",
+            Escaper::AttrName => "an attribute name",
+            Escaper::UrlStart | Escaper::UrlPath | Escaper::UrlQuery => "a URL",
+            Escaper::JsValue => "JavaScript",
+            Escaper::JsString => "a JavaScript string",
+            Escaper::JsRegexp => "a JavaScript regular expression",
+            Escaper::CssValue => "CSS",
+            Escaper::CssString => "a CSS string",
+            Escaper::Comment => "a comment",
+        }
+    }
+}
+
+#[cfg(feature = "bytecode")]
+const ESCAPERS: [Escaper; 11] = [
+    Escaper::Attr,
+    Escaper::AttrName,
+    Escaper::UrlStart,
+    Escaper::UrlPath,
+    Escaper::UrlQuery,
+    Escaper::JsValue,
+    Escaper::JsString,
+    Escaper::JsRegexp,
+    Escaper::CssValue,
+    Escaper::CssString,
+    Escaper::Comment,
+];
+
+/// The delimiter of the attribute value a value is emitted into.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
+pub enum Delim {
+    /// Not in an attribute value.
+    None,
+    /// In a double quoted attribute value.
+    DoubleQuote,
+    /// In a single quoted attribute value.
+    SingleQuote,
+    /// In an unquoted attribute value.
+    Space,
+}
+
+#[cfg(feature = "bytecode")]
+const DELIMS: [Delim; 4] = [
+    Delim::None,
+    Delim::DoubleQuote,
+    Delim::SingleQuote,
+    Delim::Space,
+];
+
+/// The HTML context a value is emitted in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "unstable_machinery_serde", derive(serde::Serialize))]
+pub struct HtmlContext {
+    pub escaper: Escaper,
+    pub delim: Delim,
+}
+
+#[cfg(feature = "bytecode")]
+impl HtmlContext {
+    /// Encodes the context into a single byte.
+    pub fn to_u8(self) -> u8 {
+        ((self.escaper as u8) << 2) | self.delim as u8
+    }
+
+    /// Decodes a context encoded with [`to_u8`](Self::to_u8).
+    pub fn from_u8(value: u8) -> Option<HtmlContext> {
+        Some(HtmlContext {
+            escaper: *ESCAPERS.get((value >> 2) as usize)?,
+            delim: DELIMS[(value & 3) as usize],
+        })
+    }
+}
+
+/// Writes a value escaped for the given HTML context.
+///
+/// Safe strings are HTML that was only escaped for HTML text (for instance
+/// the output of macros) so they are rejected.
+pub fn write_contextual(out: &mut Output, ctx: HtmlContext, value: &Value) -> Result<(), Error> {
+    if let ValueRepr::String(_, StringType::Safe) = value.0 {
+        if ctx.escaper == Escaper::Comment {
+            return Ok(());
+        }
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!(
+                "cannot emit a safe string in {}, only plain values are escaped for it",
+                ctx.escaper.describe()
+            ),
+        ));
+    }
+
+    let mut rv = String::new();
+    match ctx.escaper {
+        Escaper::Attr => rv = value.to_string(),
+        Escaper::AttrName => {
+            let name = value.to_string();
+            if !name.is_empty()
+                && name
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+                && AttrKind::from_name(&name) == AttrKind::Plain
+            {
+                rv = name;
+            } else {
+                rv.push_str(FAILSAFE);
+            }
+        }
+        Escaper::UrlStart => {
+            let url = value.to_string();
+            if is_safe_url(&url) {
+                write_normalized_url(&mut rv, &url);
+            } else {
+                rv.push('#');
+                rv.push_str(FAILSAFE);
+            }
+        }
+        Escaper::UrlPath => write_normalized_url(&mut rv, &value.to_string()),
+        Escaper::UrlQuery => rv = UrlEscape(&value.to_string()).to_string(),
+        Escaper::JsValue => {
+            write_js_value(&mut rv, value);
+            // keep numbers and keywords from running into surrounding tokens
+            if rv.starts_with(is_js_ident_char) || rv.ends_with(is_js_ident_char) {
+                rv = format!(" {rv} ");
+            }
+        }
+        Escaper::JsString => write_js_string(&mut rv, &value.to_string(), false),
+        Escaper::JsRegexp => {
+            let s = value.to_string();
+            if s.is_empty() {
+                // an empty regular expression would turn into a comment
+                rv.push_str("(?:)");
+            } else {
+                write_js_string(&mut rv, &s, true);
+            }
+        }
+        Escaper::CssValue => {
+            let s = value.to_string();
+            rv.push_str(if is_safe_css_value(&s) { &s } else { FAILSAFE });
+        }
+        Escaper::CssString => rv = CssEscape(&value.to_string()).to_string(),
+        Escaper::Comment => return Ok(()),
+    }
+
+    match ctx.delim {
+        Delim::None => out.write_str(&rv),
+        Delim::DoubleQuote | Delim::SingleQuote => write!(out, "{}", HtmlEscape(&rv)),
+        Delim::Space => write_unquoted_attr(out, &rv),
+    }
+    .map_err(Error::from)
+}
+
+fn is_js_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+/// Checks that a URL does not use a scheme other than http, https or mailto.
+fn is_safe_url(url: &str) -> bool {
+    match url.find([':', '/', '?', '#']) {
+        Some(idx) if url.as_bytes()[idx] == b':' => {
+            matches!(
+                url[..idx].to_ascii_lowercase().as_str(),
+                "http" | "https" | "mailto"
+            )
+        }
+        _ => true,
+    }
+}
+
+/// Percent encodes everything that is not valid in a URL.
+fn write_normalized_url(rv: &mut String, url: &str) {
+    for b in url.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~!#$&*+,/:;=?@[]%".contains(&b) {
+            rv.push(b as char);
+        } else {
+            write!(rv, "%{b:02X}").ok();
+        }
+    }
+}
+
+fn write_js_value(rv: &mut String, value: &Value) {
+    match value.kind() {
+        ValueKind::Undefined | ValueKind::None => rv.push_str("null"),
+        ValueKind::Bool | ValueKind::Number => match value.0 {
+            ValueRepr::F64(f) if f.is_nan() => rv.push_str("NaN"),
+            ValueRepr::F64(f) if f.is_infinite() => {
+                rv.push_str(if f > 0.0 { "Infinity" } else { "-Infinity" })
+            }
+            _ => {
+                write!(rv, "{value}").ok();
+            }
+        },
+        ValueKind::Seq | ValueKind::Tuple | ValueKind::Iterable => {
+            rv.push('[');
+            if let Ok(iter) = value.try_iter() {
+                for (idx, item) in iter.enumerate() {
+                    if idx > 0 {
+                        rv.push(',');
+                    }
+                    write_js_value(rv, &item);
+                }
+            }
+            rv.push(']');
+        }
+        ValueKind::Map => {
+            rv.push('{');
+            if let Ok(iter) = value.try_iter() {
+                for (idx, key) in iter.enumerate() {
+                    if idx > 0 {
+                        rv.push(',');
+                    }
+                    rv.push('"');
+                    write_js_string(rv, &key.to_string(), false);
+                    rv.push_str("\":");
+                    write_js_value(rv, &value.get_item(&key).unwrap_or_default());
+                }
+            }
+            rv.push('}');
+        }
+        _ => {
+            rv.push('"');
+            write_js_string(rv, &value.to_string(), false);
+            rv.push('"');
+        }
+    }
+}
+
+/// Escapes a string for the inside of a JavaScript string or regular expression.
+///
+/// Quotes and characters significant to HTML are escaped so the result can
+/// neither terminate the literal nor the surrounding script tag.
+fn write_js_string(rv: &mut String, s: &str, regexp: bool) {
+    for c in s.chars() {
+        match c {
+            '\\' => rv.push_str("\\\\"),
+            '\n' => rv.push_str("\\n"),
+            '\r' => rv.push_str("\\r"),
+            '\t' => rv.push_str("\\t"),
+            '/' => rv.push_str("\\/"),
+            '"' | '\'' | '`' | '<' | '>' | '&' | '+' | '$' | '=' | '\u{7f}' | '\u{2028}'
+            | '\u{2029}' => {
+                write!(rv, "\\u{:04x}", c as u32).ok();
+            }
+            c if c < ' ' => {
+                write!(rv, "\\u{:04x}", c as u32).ok();
+            }
+            '.' | '*' | '?' | '^' | '|' | '(' | ')' | '[' | ']' | '{' | '}' if regexp => {
+                rv.push('\\');
+                rv.push(c);
+            }
+            // keeps a `$` before the value from starting an interpolation
+            '{' | '}' => {
+                write!(rv, "\\u{:04x}", c as u32).ok();
+            }
+            c => rv.push(c),
+        }
+    }
+}
+
+/// Checks that a value cannot escape from a CSS property value.
+fn is_safe_css_value(s: &str) -> bool {
+    let lower = s.to_ascii_lowercase();
+    !s.bytes().any(|b| {
+        matches!(
+            b,
+            0 | b'"'
+                | b'\''
+                | b'('
+                | b')'
+                | b'/'
+                | b';'
+                | b'@'
+                | b'['
+                | b'\\'
+                | b']'
+                | b'`'
+                | b'{'
+                | b'}'
+                | b'<'
+                | b'>'
+        )
+    }) && !s.contains("--")
+        && !lower.contains("expression")
+        && !lower.contains("mozbinding")
+}
+
+/// Escapes an unquoted attribute value so that it cannot end early.
+fn write_unquoted_attr(out: &mut Output, s: &str) -> fmt::Result {
+    if s.is_empty() {
+        // an empty value would make the next attribute the value
+        return out.write_str(FAILSAFE);
+    }
+    for c in s.chars() {
+        ok!(match c {
+            '&' => out.write_str("&amp;"),
+            '<' => out.write_str("&lt;"),
+            '>' => out.write_str("&gt;"),
+            '"' => out.write_str("&quot;"),
+            '\'' => out.write_str("&#x27;"),
+            '/' => out.write_str("&#x2f;"),
+            '\0' | '\t' | '\n' | '\x0b' | '\x0c' | '\r' | ' ' | '=' | '`' => {
+                write!(out, "&#{};", c as u32)
+            }
+            c => out.write_char(c),
+        });
+    }
+    Ok(())
+}
+
+/// The lexical state of the HTML.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum State {
+    Text,
+    Tag,
+    AttrName,
+    AfterName,
+    BeforeValue,
+    HtmlComment,
+    Attr,
+    Url,
+    Js,
+    JsDqStr,
+    JsSqStr,
+    JsTmplLit,
+    JsRegexp,
+    JsLineComment,
+    JsBlockComment,
+    Css,
+    CssDqStr,
+    CssSqStr,
+    CssComment,
+}
+
+impl State {
+    fn describe(self) -> &'static str {
+        match self {
+            State::Text => "HTML text",
+            State::Tag | State::AfterName => "HTML tag",
+            State::AttrName => "attribute name",
+            State::BeforeValue | State::Attr => "attribute value",
+            State::HtmlComment => "HTML comment",
+            State::Url => "URL",
+            State::Js => "JavaScript",
+            State::JsDqStr | State::JsSqStr | State::JsTmplLit => "JavaScript string",
+            State::JsRegexp => "JavaScript regular expression",
+            State::JsLineComment | State::JsBlockComment => "JavaScript comment",
+            State::Css => "CSS",
+            State::CssDqStr | State::CssSqStr => "CSS string",
+            State::CssComment => "CSS comment",
+        }
+    }
+}
+
+/// The element whose content is not parsed as HTML.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Element {
+    None,
+    Script,
+    Style,
+    Textarea,
+    Title,
+}
+
+impl Element {
+    fn from_tag(name: &str) -> Element {
+        match name.to_ascii_lowercase().as_str() {
+            "script" => Element::Script,
+            "style" => Element::Style,
+            "textarea" => Element::Textarea,
+            "title" => Element::Title,
+            _ => Element::None,
+        }
+    }
+
+    fn end_tag(self) -> Option<&'static str> {
+        match self {
+            Element::None => None,
+            Element::Script => Some("</script"),
+            Element::Style => Some("</style"),
+            Element::Textarea => Some("</textarea"),
+            Element::Title => Some("</title"),
+        }
+    }
+}
+
+/// The kind of content of an attribute value.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum AttrKind {
+    Plain,
+    /// The name was emitted by a value, it was filtered to be plain.
+    Dynamic,
+    Script,
+    Style,
+    Url,
+}
+
+impl AttrKind {
+    fn from_name(name: &str) -> AttrKind {
+        let name = name.to_ascii_lowercase();
+        let name = name.strip_prefix("data-").unwrap_or(&name);
+        let name = match name.split_once(':') {
+            Some(("xmlns", _)) => return AttrKind::Url,
+            Some((_, local)) => local,
+            None => name,
+        };
+        if name.starts_with("on") {
+            AttrKind::Script
+        } else if name == "style" {
+            AttrKind::Style
+        } else if matches!(
+            name,
+            "action"
+                | "archive"
+                | "background"
+                | "cite"
+                | "classid"
+                | "codebase"
+                | "data"
+                | "formaction"
+                | "href"
+                | "icon"
+                | "longdesc"
+                | "manifest"
+                | "poster"
+                | "profile"
+                | "usemap"
+                | "xmlns"
+        ) || name.contains("src")
+            || name.contains("uri")
+            || name.contains("url")
+        {
+            AttrKind::Url
+        } else {
+            AttrKind::Plain
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum UrlPart {
+    Start,
+    Path,
+    Query,
+}
+
+/// Whether a `/` in JavaScript starts a regular expression.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum JsCtx {
+    Regexp,
+    DivOp,
+    Unknown,
+}
+
+/// A position in the HTML as tracked by the [`Tracker`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Context {
+    state: State,
+    element: Element,
+    attr: AttrKind,
+    delim: Delim,
+    url_part: UrlPart,
+    js_ctx: JsCtx,
+    /// The number of open `${` interpolations of template literals.
+    tmpl_depth: u8,
+    /// The number of open braces in each of these interpolations.
+    tmpl_braces: [u8; MAX_TMPL_DEPTH],
+}
+
+/// How deep template literal interpolations can be nested.
+const MAX_TMPL_DEPTH: usize = 8;
+
+impl Context {
+    const TEXT: Context = Context {
+        state: State::Text,
+        element: Element::None,
+        attr: AttrKind::Plain,
+        delim: Delim::None,
+        url_part: UrlPart::Start,
+        js_ctx: JsCtx::Regexp,
+        tmpl_depth: 0,
+        tmpl_braces: [0; MAX_TMPL_DEPTH],
+    };
+
+    /// The state after the end of a start tag.
+    fn after_tag(element: Element) -> Context {
+        match element {
+            Element::Script => Context {
+                state: State::Js,
+                element,
+                ..Context::TEXT
+            },
+            Element::Style => Context {
+                state: State::Css,
+                element,
+                ..Context::TEXT
+            },
+            Element::Textarea | Element::Title => Context {
+                element,
+                ..Context::TEXT
+            },
+            Element::None => Context::TEXT,
+        }
+    }
+
+    /// The state at the start of an attribute value.
+    fn value(self, delim: Delim) -> Context {
+        Context {
+            state: match self.attr {
+                AttrKind::Plain | AttrKind::Dynamic => State::Attr,
+                AttrKind::Script => State::Js,
+                AttrKind::Style => State::Css,
+                AttrKind::Url => State::Url,
+            },
+            delim,
+            url_part: UrlPart::Start,
+            js_ctx: JsCtx::Regexp,
+            ..self
+        }
+    }
+
+    /// Moves states that end differently depending on what follows into
+    /// the state that also covers the other possibility.
+    fn nudge(self) -> Context {
+        match self.state {
+            State::Tag => Context {
+                state: State::AttrName,
+                attr: AttrKind::Plain,
+                ..self
+            },
+            State::AfterName => Context {
+                state: State::AttrName,
+                ..self
+            },
+            State::BeforeValue => self.value(Delim::Space),
+            _ => self,
+        }
+    }
+
+    fn join(self, other: Context) -> Option<Context> {
+        if self == other {
+            return Some(self);
+        }
+        let (a, b) = (self.nudge(), other.nudge());
+        if a == b {
+            return Some(a);
+        }
+        if a.state == State::AttrName
+            && (Context { attr: b.attr, ..a }) == b
+            && matches!(a.attr, AttrKind::Plain | AttrKind::Dynamic)
+            && matches!(b.attr, AttrKind::Plain | AttrKind::Dynamic)
+        {
+            return Some(Context {
+                attr: AttrKind::Dynamic,
+                ..a
+            });
+        }
+        if (Context {
+            js_ctx: b.js_ctx,
+            ..a
+        }) == b
+        {
+            return Some(Context {
+                js_ctx: JsCtx::Unknown,
+                ..a
+            });
+        }
+        None
+    }
+}
+
+/// Follows the HTML context through the raw chunks of a template.
+pub struct Tracker {
+    ctx: Context,
+    span: Span,
+    error: Option<(String, Span)>,
+}
+
+impl Default for Tracker {
+    fn default() -> Tracker {
+        Tracker {
+            ctx: Context::TEXT,
+            span: Span::default(),
+            error: None,
+        }
+    }
+}
+
+impl Tracker {
+    /// Returns the current context.
+    pub fn context(&self) -> Context {
+        self.ctx
+    }
+
+    /// Continues tracking from the given context.
+    pub fn set_context(&mut self, ctx: Context) {
+        self.ctx = ctx;
+    }
+
+    /// Starts a separate fragment of HTML and returns the previous context.
+    pub fn enter_fragment(&mut self) -> Context {
+        std::mem::replace(&mut self.ctx, Context::TEXT)
+    }
+
+    /// Joins the current context with the context at the end of another path.
+    pub fn join(&mut self, other: Context, span: Span, what: &str) {
+        match self.ctx.join(other) {
+            Some(ctx) => self.ctx = ctx,
+            None => self.fail(
+                span,
+                format!(
+                    "{what} in different HTML contexts ({} and {})",
+                    self.ctx.state.describe(),
+                    other.state.describe()
+                ),
+            ),
+        }
+    }
+
+    /// Fails unless the current position is in HTML text.
+    ///
+    /// This is used for constructs that emit HTML which was tracked
+    /// separately, such as blocks and included templates.
+    #[cfg(feature = "multi_template")]
+    pub fn expect_text(&mut self, span: Span, what: &str) {
+        if self.ctx.state != State::Text {
+            self.fail(
+                span,
+                format!("cannot {what} in {}", self.ctx.state.describe()),
+            );
+        }
+    }
+
+    /// Fails unless the tracked HTML ends in HTML text.
+    pub fn finish(&mut self) {
+        if self.ctx.state != State::Text {
+            self.fail(
+                self.span,
+                format!("template ends in {}", self.ctx.state.describe()),
+            );
+        }
+    }
+
+    /// Returns the first error and the span it happened at.
+    pub fn take_error(&mut self) -> Option<(String, Span)> {
+        self.error.take()
+    }
+
+    fn fail(&mut self, span: Span, msg: String) {
+        if self.error.is_none() {
+            self.error = Some((msg, span));
+        }
+    }
+
+    /// Enters the `${` interpolation of a template literal.
+    fn enter_interpolation(&mut self) {
+        let depth = self.ctx.tmpl_depth as usize;
+        if depth == MAX_TMPL_DEPTH {
+            self.fail(
+                self.span,
+                "template literal interpolations are nested too deeply".into(),
+            );
+            return;
+        }
+        self.ctx.tmpl_braces[depth] = 0;
+        self.ctx.tmpl_depth += 1;
+        self.ctx.state = State::Js;
+        self.ctx.js_ctx = JsCtx::Regexp;
+    }
+
+    /// Returns the context for a value emitted at the current position.
+    ///
+    /// Returns `None` if regular HTML escaping is sufficient.
+    pub fn emit(&mut self, span: Span) -> Option<HtmlContext> {
+        let ctx = &mut self.ctx;
+        let escaper = match ctx.state {
+            State::Text => return None,
+            State::Tag | State::AfterName => {
+                ctx.state = State::AttrName;
+                ctx.attr = AttrKind::Dynamic;
+                Escaper::AttrName
+            }
+            State::AttrName => {
+                self.fail(
+                    span,
+                    "cannot emit a value inside an HTML attribute name".into(),
+                );
+                return None;
+            }
+            State::BeforeValue => {
+                *ctx = ctx.value(Delim::Space);
+                return self.emit(span);
+            }
+            State::Attr if ctx.delim != Delim::Space => return None,
+            State::Attr => Escaper::Attr,
+            State::Url => match std::mem::replace(&mut ctx.url_part, UrlPart::Path) {
+                UrlPart::Start => Escaper::UrlStart,
+                UrlPart::Path => Escaper::UrlPath,
+                UrlPart::Query => {
+                    ctx.url_part = UrlPart::Query;
+                    Escaper::UrlQuery
+                }
+            },
+            State::Js => {
+                ctx.js_ctx = JsCtx::DivOp;
+                Escaper::JsValue
+            }
+            State::JsDqStr | State::JsSqStr | State::JsTmplLit => Escaper::JsString,
+            State::JsRegexp => Escaper::JsRegexp,
+            State::Css => Escaper::CssValue,
+            State::CssDqStr | State::CssSqStr => Escaper::CssString,
+            State::HtmlComment
+            | State::JsLineComment
+            | State::JsBlockComment
+            | State::CssComment => Escaper::Comment,
+        };
+        Some(HtmlContext {
+            escaper,
+            delim: ctx.delim,
+        })
+    }
+
+    /// Advances the context over raw template source.
+    pub fn feed(&mut self, text: &str, span: Span) {
+        self.span = span;
+        let mut rest = text;
+        while !rest.is_empty() {
+            let consumed = self.step(rest);
+            rest = &rest[consumed..];
+        }
+    }
+
+    fn step(&mut self, s: &str) -> usize {
+        if self.ctx.delim != Delim::None {
+            return self.step_attr_value(s);
+        }
+        match self.ctx.state {
+            State::Text if self.ctx.element == Element::None => self.step_text(s),
+            State::Tag => self.step_tag(s),
+            State::AttrName => self.step_attr_name(s),
+            State::AfterName => {
+                let trimmed = s.trim_start_matches(|c: char| c.is_ascii_whitespace());
+                if let Some(rest) = trimmed.strip_prefix('=') {
+                    self.ctx.state = State::BeforeValue;
+                    s.len() - rest.len()
+                } else {
+                    if !trimmed.is_empty() {
+                        self.ctx.state = State::Tag;
+                    }
+                    s.len() - trimmed.len()
+                }
+            }
+            State::BeforeValue => {
+                let trimmed = s.trim_start_matches(|c: char| c.is_ascii_whitespace());
+                let skipped = s.len() - trimmed.len();
+                match trimmed.as_bytes().first() {
+                    None => skipped,
+                    Some(b'"') => {
+                        self.ctx = self.ctx.value(Delim::DoubleQuote);
+                        skipped + 1
+                    }
+                    Some(b'\'') => {
+                        self.ctx = self.ctx.value(Delim::SingleQuote);
+                        skipped + 1
+                    }
+                    Some(b'>') => {
+                        self.ctx.state = State::Tag;
+                        skipped
+                    }
+                    Some(_) => {
+                        self.ctx = self.ctx.value(Delim::Space);
+                        skipped
+                    }
+                }
+            }
+            State::HtmlComment => match s.find("-->") {
+                Some(idx) => {
+                    self.ctx = Context::TEXT;
+                    idx + 3
+                }
+                None => s.len(),
+            },
+            _ => self.step_element_content(s),
+        }
+    }
+
+    fn step_text(&mut self, s: &str) -> usize {
+        let Some(idx) = s.find('<') else {
+            return s.len();
+        };
+        let after = &s[idx + 1..];
+        if after.starts_with("!--") {
+            self.ctx.state = State::HtmlComment;
+            return idx + 4;
+        }
+        let closing = after.starts_with('/');
+        let name_start = idx + 1 + closing as usize;
+        let name = &s[name_start..];
+        let name = &name[..name
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '-' && c != ':')
+            .unwrap_or(name.len())];
+        if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            return idx + 1;
+        }
+        self.ctx = Context {
+            state: State::Tag,
+            element: if closing {
+                Element::None
+            } else {
+                Element::from_tag(name)
+            },
+            ..Context::TEXT
+        };
+        name_start + name.len()
+    }
+
+    fn step_tag(&mut self, s: &str) -> usize {
+        let trimmed = s.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == '/');
+        let skipped = s.len() - trimmed.len();
+        if trimmed.is_empty() {
+            return skipped;
+        }
+        if trimmed.starts_with('>') {
+            self.ctx = Context::after_tag(self.ctx.element);
+            return skipped + 1;
+        }
+        // a leading equals sign is part of the attribute name
+        let name_len = trimmed
+            .char_indices()
+            .skip(1)
+            .find(|(_, c)| is_attr_name_end(*c))
+            .map_or(trimmed.len(), |(idx, _)| idx);
+        self.ctx.attr = AttrKind::from_name(&trimmed[..name_len]);
+        self.ctx.state = if name_len == trimmed.len() {
+            State::AttrName
+        } else {
+            State::AfterName
+        };
+        skipped + name_len
+    }
+
+    fn step_attr_name(&mut self, s: &str) -> usize {
+        let name_len = s.find(is_attr_name_end).unwrap_or(s.len());
+        if name_len > 0 {
+            if self.ctx.attr == AttrKind::Dynamic {
+                self.fail(
+                    self.span,
+                    "cannot emit a value inside an HTML attribute name".into(),
+                );
+            }
+            // the start of the name might have been plain on its own
+            let attr = AttrKind::from_name(&s[..name_len]);
+            if attr != AttrKind::Plain {
+                self.ctx.attr = attr;
+            }
+        }
+        if name_len < s.len() {
+            if self.ctx.attr == AttrKind::Dynamic {
+                self.ctx.attr = AttrKind::Plain;
+            }
+            self.ctx.state = State::AfterName;
+        }
+        name_len
+    }
+
+    fn step_attr_value(&mut self, s: &str) -> usize {
+        let delim = self.ctx.delim;
+        let end = match delim {
+            Delim::DoubleQuote => s.find('"'),
+            Delim::SingleQuote => s.find('\''),
+            _ => s.find(|c: char| c.is_ascii_whitespace() || c == '>'),
+        };
+        let Some(end) = end else {
+            self.feed_content(s);
+            return s.len();
+        };
+        self.feed_content(&s[..end]);
+        self.ctx = Context {
+            state: State::Tag,
+            element: self.ctx.element,
+            ..Context::TEXT
+        };
+        if delim == Delim::Space {
+            end
+        } else {
+            end + 1
+        }
+    }
+
+    /// Steps over the content of script, style, textarea and title tags.
+    fn step_element_content(&mut self, s: &str) -> usize {
+        let end_tag = self.ctx.element.end_tag();
+        let end = end_tag.and_then(|tag| s.to_ascii_lowercase().find(tag));
+        let content = &s[..end.unwrap_or(s.len())];
+        if self.ctx.state != State::Text {
+            self.feed_content(content);
+        }
+        match (end, end_tag) {
+            (Some(idx), Some(tag)) => {
+                self.ctx = Context {
+                    state: State::Tag,
+                    ..Context::TEXT
+                };
+                idx + tag.len()
+            }
+            _ => s.len(),
+        }
+    }
+
+    /// Advances over the content of an attribute value or element.
+    fn feed_content(&mut self, mut s: &str) {
+        while !s.is_empty() {
+            let consumed = self.step_content(s);
+            s = &s[consumed..];
+        }
+    }
+
+    fn step_content(&mut self, s: &str) -> usize {
+        let bytes = s.as_bytes();
+        match self.ctx.state {
+            State::Url => {
+                if s.contains(['?', '#']) {
+                    self.ctx.url_part = UrlPart::Query;
+                } else if self.ctx.url_part == UrlPart::Start {
+                    self.ctx.url_part = UrlPart::Path;
+                }
+                s.len()
+            }
+            State::Js => self.step_js(s),
+            State::JsDqStr
+            | State::JsSqStr
+            | State::JsTmplLit
+            | State::CssDqStr
+            | State::CssSqStr => {
+                let quote = match self.ctx.state {
+                    State::JsDqStr | State::CssDqStr => b'"',
+                    State::JsSqStr | State::CssSqStr => b'\'',
+                    _ => b'`',
+                };
+                let mut idx = 0;
+                while idx < bytes.len() {
+                    if bytes[idx] == b'\\' {
+                        idx += 2;
+                    } else if quote == b'`' && bytes[idx..].starts_with(b"${") {
+                        self.enter_interpolation();
+                        return idx + 2;
+                    } else if bytes[idx] == quote {
+                        if matches!(self.ctx.state, State::CssDqStr | State::CssSqStr) {
+                            self.ctx.state = State::Css;
+                        } else {
+                            self.ctx.state = State::Js;
+                            self.ctx.js_ctx = JsCtx::DivOp;
+                        }
+                        return idx + 1;
+                    } else {
+                        idx += 1;
+                    }
+                }
+                s.len()
+            }
+            State::JsRegexp => {
+                let mut in_class = false;
+                let mut idx = 0;
+                while idx < bytes.len() {
+                    match bytes[idx] {
+                        b'\\' => idx += 1,
+                        b'[' => in_class = true,
+                        b']' => in_class = false,
+                        b'/' if !in_class => {
+                            self.ctx.state = State::Js;
+                            self.ctx.js_ctx = JsCtx::DivOp;
+                            return idx + 1;
+                        }
+                        _ => {}
+                    }
+                    idx += 1;
+                }
+                s.len()
+            }
+            State::JsLineComment => match s.find(['\n', '\r', '\u{2028}', '\u{2029}']) {
+                Some(idx) => {
+                    self.ctx.state = State::Js;
+                    idx
+                }
+                None => s.len(),
+            },
+            State::JsBlockComment | State::CssComment => match s.find("*/") {
+                Some(idx) => {
+                    self.ctx.state = if self.ctx.state == State::CssComment {
+                        State::Css
+                    } else {
+                        State::Js
+                    };
+                    idx + 2
+                }
+                None => s.len(),
+            },
+            State::Css => {
+                for (idx, b) in bytes.iter().enumerate() {
+                    match b {
+                        b'"' => self.ctx.state = State::CssDqStr,
+                        b'\'' => self.ctx.state = State::CssSqStr,
+                        b'/' if bytes.get(idx + 1) == Some(&b'*') => {
+                            self.ctx.state = State::CssComment;
+                            return idx + 2;
+                        }
+                        _ => continue,
+                    }
+                    return idx + 1;
+                }
+                s.len()
+            }
+            _ => s.len(),
+        }
+    }
+
+    fn step_js(&mut self, s: &str) -> usize {
+        let bytes = s.as_bytes();
+        for (idx, c) in s.char_indices() {
+            self.ctx.state = match c {
+                '"' => State::JsDqStr,
+                '\'' => State::JsSqStr,
+                '`' => State::JsTmplLit,
+                '/' if bytes.get(idx + 1) == Some(&b'/') => {
+                    self.ctx.state = State::JsLineComment;
+                    return idx + 2;
+                }
+                '/' if bytes.get(idx + 1) == Some(&b'*') => {
+                    self.ctx.state = State::JsBlockComment;
+                    return idx + 2;
+                }
+                '/' => {
+                    let regexp = match self.ctx.js_ctx {
+                        JsCtx::Regexp => true,
+                        JsCtx::DivOp => ends_with_js_keyword(&s[..idx]),
+                        JsCtx::Unknown => {
+                            self.fail(
+                                self.span,
+                                "'/' could start a division or regular expression".into(),
+                            );
+                            false
+                        }
+                    };
+                    if regexp {
+                        State::JsRegexp
+                    } else {
+                        self.ctx.js_ctx = JsCtx::Regexp;
+                        continue;
+                    }
+                }
+                '{' if self.ctx.tmpl_depth > 0 => {
+                    let braces = &mut self.ctx.tmpl_braces[self.ctx.tmpl_depth as usize - 1];
+                    match braces.checked_add(1) {
+                        Some(n) => *braces = n,
+                        None => self.fail(self.span, "too many nested braces".into()),
+                    }
+                    self.ctx.js_ctx = JsCtx::Regexp;
+                    continue;
+                }
+                '}' if self.ctx.tmpl_depth > 0 => {
+                    let depth = self.ctx.tmpl_depth as usize;
+                    if self.ctx.tmpl_braces[depth - 1] == 0 {
+                        // end of the interpolation, back in the template literal
+                        self.ctx.tmpl_depth -= 1;
+                        State::JsTmplLit
+                    } else {
+                        self.ctx.tmpl_braces[depth - 1] -= 1;
+                        self.ctx.js_ctx = JsCtx::Regexp;
+                        continue;
+                    }
+                }
+                c if c.is_whitespace() => continue,
+                c => {
+                    self.ctx.js_ctx = if is_js_ident_char(c) || c == ')' || c == ']' {
+                        JsCtx::DivOp
+                    } else {
+                        JsCtx::Regexp
+                    };
+                    continue;
+                }
+            };
+            return idx + c.len_utf8();
+        }
+        s.len()
+    }
+}
+
+fn is_attr_name_end(c: char) -> bool {
+    c.is_ascii_whitespace() || matches!(c, '=' | '>' | '/')
+}
+
+/// Checks if JavaScript source ends in a keyword after which a `/` starts
+/// a regular expression.
+fn ends_with_js_keyword(s: &str) -> bool {
+    let s = s.trim_end();
+    let word = match s.char_indices().rfind(|(_, c)| !is_js_ident_char(*c)) {
+        Some((idx, c)) => &s[idx + c.len_utf8()..],
+        None => s,
+    };
+    matches!(
+        word,
+        "await"
+            | "case"
+            | "delete"
+            | "do"
+            | "else"
+            | "in"
+            | "instanceof"
+            | "new"
+            | "return"
+            | "throw"
+            | "typeof"
+            | "void"
+            | "yield"
+    )
+}
@@ -1,6 +1,8 @@
 #[cfg(feature = "internal_debug")]
 use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
+use crate::compiler::html_context::HtmlContext;
 use crate::compiler::tokens::Span;
 use crate::output::CaptureMode;
 use crate::value::Value;
@@ -15,6 +17,14 @@ pub const LOOP_FLAG_RECURSIVE: u8 = 2;
 #[cfg(feature = "macros")]
 pub const MACRO_CALLER: u8 = 2;
 
+/// This macro collects extra positional arguments.
+#[cfg(feature = "macros")]
+pub const MACRO_VARARGS: u8 = 4;
+
+/// This macro collects extra keyword arguments.
+#[cfg(feature = "macros")]
+pub const MACRO_KWARGS: u8 = 8;
+
 /// Rust type to represent locals.
 pub type LocalId = u8;
 
@@ -33,6 +43,9 @@ pub enum Instruction<'source> {
     /// Emits raw source
     EmitRaw(&'source str),
 
+    /// Emits a constant string as is (merged raw source)
+    EmitRawConst(Value),
+
     /// Stores a variable (only possible in for loops)
     StoreLocal(&'source str),
 
@@ -66,6 +79,9 @@ pub enum Instruction<'source> {
     /// Builds a list of the last n pairs on the stack.
     BuildList(Option<usize>),
 
+    /// Builds a tuple of the last n values on the stack.
+    BuildTuple(usize),
+
     /// Unpacks a list into N stack items.
     UnpackList(usize),
 
@@ -136,6 +152,9 @@ pub enum Instruction<'source> {
     /// Emit the stack top as output
     Emit,
 
+    /// Emit the stack top as output escaped for an HTML context
+    EmitContextual(HtmlContext),
+
     /// Starts a loop
     ///
     /// The argument are loop flags.
@@ -242,31 +261,37 @@ pub enum Instruction<'source> {
     /// Returns the closure of this context level.
     #[cfg(feature = "macros")]
     GetClosure,
+
+    /// Translates a message (true if it has a plural form) and formats it
+    /// with the variables on the stack.
+    #[cfg(feature = "i18n")]
+    Translate(bool),
 }
 
 #[derive(Copy, Clone)]
-struct LineInfo {
-    first_instruction: u32,
-    line: u16,
+pub(crate) struct LineInfo {
+    pub(crate) first_instruction: u32,
+    pub(crate) line: u16,
 }
 
 #[cfg(feature = "debug")]
 #[derive(Copy, Clone)]
-struct SpanInfo {
-    first_instruction: u32,
-    span: Span,
+pub(crate) struct SpanInfo {
+    pub(crate) first_instruction: u32,
+    pub(crate) span: Span,
 }
 
 /// Wrapper around instructions to help with location management.
 pub struct Instructions<'source> {
     pub(crate) instructions: Vec<Instruction<'source>>,
-    line_infos: Vec<LineInfo>,
+    pub(crate) line_infos: Vec<LineInfo>,
     #[cfg(feature = "debug")]
-    span_infos: Vec<SpanInfo>,
-    name: &'source str,
-    source: &'source str,
+    pub(crate) span_infos: Vec<SpanInfo>,
+    pub(crate) name: &'source str,
+    pub(crate) source: &'source str,
     #[cfg(feature = "multi_template")]
-    required_block: bool,
+    pub(crate) required_block: bool,
+    pub(crate) id: usize,
 }
 
 pub(crate) static EMPTY_INSTRUCTIONS: Instructions<'static> = Instructions {
@@ -278,11 +303,13 @@ pub(crate) static EMPTY_INSTRUCTIONS: Instructions<'static> = Instructions {
     source: "",
     #[cfg(feature = "multi_template")]
     required_block: false,
+    id: 0,
 };
 
 impl<'source> Instructions<'source> {
     /// Creates a new instructions object.
     pub fn new(name: &'source str, source: &'source str) -> Instructions<'source> {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
         Instructions {
             instructions: Vec::with_capacity(256),
             line_infos: Vec::with_capacity(128),
@@ -292,9 +319,18 @@ impl<'source> Instructions<'source> {
             source,
             #[cfg(feature = "multi_template")]
             required_block: false,
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
         }
     }
 
+    /// Returns an id that is unique to these instructions.
+    ///
+    /// This is used to refer back to instructions from values that outlive
+    /// them (eg: the origin of undefined values).
+    pub(crate) fn id(&self) -> usize {
+        self.id
+    }
+
     /// Returns the name of the template.
     pub fn name(&self) -> &'source str {
         self.name
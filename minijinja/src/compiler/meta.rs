@@ -2,6 +2,10 @@ use std::collections::HashSet;
 use std::fmt::Write;
 
 use crate::compiler::ast;
+#[cfg(feature = "multi_template")]
+use crate::compiler::visitor::{walk_stmt, Visitor};
+#[cfg(feature = "multi_template")]
+use crate::template_graph::{TemplateReference, TemplateReferenceKind};
 
 struct AssignmentTracker<'a> {
     out: HashSet<&'a str>,
@@ -82,10 +86,15 @@ fn tracker_visit_macro<'a>(
         // this is not completely correct as caller is actually only defined
         // if the macro was used in the context of a call block.  However it
         // is impossible to determine this at compile time so we err on the
-        // side of assuming caller is there.
+        // side of assuming caller is there.  The same applies to the
+        // implicit varargs and kwargs arguments.
         state.assign("caller");
+        state.assign("varargs");
+        state.assign("kwargs");
     }
     m.args.iter().for_each(|arg| track_assign(arg, state));
+    m.varargs.iter().for_each(|arg| track_assign(arg, state));
+    m.kwargs.iter().for_each(|arg| track_assign(arg, state));
     m.defaults
         .iter()
         .for_each(|expr| tracker_visit_expr(expr, state));
@@ -186,6 +195,7 @@ fn tracker_visit_expr<'a>(expr: &ast::Expr<'a>, state: &mut AssignmentTracker<'a
                 .for_each(|x| tracker_visit_callarg(x, state));
         }
         ast::Expr::List(expr) => expr.items.iter().for_each(|x| tracker_visit_expr(x, state)),
+        ast::Expr::Tuple(expr) => expr.items.iter().for_each(|x| tracker_visit_expr(x, state)),
         ast::Expr::Map(expr) => expr.keys.iter().zip(expr.values.iter()).for_each(|(k, v)| {
             tracker_visit_expr(k, state);
             tracker_visit_expr(v, state);
@@ -303,5 +313,69 @@ fn track_walk<'a>(node: &ast::Stmt<'a>, state: &mut AssignmentTracker<'a>) {
                 .iter()
                 .for_each(|x| tracker_visit_callarg(x, state));
         }
+        #[cfg(feature = "i18n")]
+        ast::Stmt::Trans(stmt) => stmt
+            .variables
+            .iter()
+            .for_each(|(_, expr)| tracker_visit_expr(expr, state)),
     }
 }
+
+/// Finds all templates referenced by `extends`, `include`, `import` and
+/// `from ... import` in a template.
+#[cfg(feature = "multi_template")]
+pub fn find_template_references(t: &ast::Stmt<'_>) -> Vec<TemplateReference> {
+    let mut finder = ReferenceFinder { out: Vec::new() };
+    finder.visit_stmt(t);
+    finder.out
+}
+
+#[cfg(feature = "multi_template")]
+struct ReferenceFinder {
+    out: Vec<TemplateReference>,
+}
+
+#[cfg(feature = "multi_template")]
+impl ReferenceFinder {
+    fn add(&mut self, kind: TemplateReferenceKind, expr: &ast::Expr<'_>, ignore_missing: bool) {
+        match expr {
+            // includes can take a list of templates to try in order
+            ast::Expr::List(l) if kind == TemplateReferenceKind::Include => {
+                for item in &l.items {
+                    self.add(kind, item, ignore_missing);
+                }
+            }
+            ast::Expr::Tuple(t) if kind == TemplateReferenceKind::Include => {
+                for item in &t.items {
+                    self.add(kind, item, ignore_missing);
+                }
+            }
+            _ => self.out.push(TemplateReference {
+                kind,
+                name: expr
+                    .as_const()
+                    .and_then(|value| value.as_str().map(|x| x.to_string())),
+                ignore_missing,
+                line: expr.span().start_line as usize,
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "multi_template")]
+impl<'a> Visitor<'a> for ReferenceFinder {
+    fn visit_stmt(&mut self, stmt: &ast::Stmt<'a>) {
+        match stmt {
+            ast::Stmt::Extends(e) => self.add(TemplateReferenceKind::Extends, &e.name, false),
+            ast::Stmt::Include(i) => {
+                self.add(TemplateReferenceKind::Include, &i.name, i.ignore_missing)
+            }
+            ast::Stmt::Import(i) => self.add(TemplateReferenceKind::Import, &i.expr, false),
+            ast::Stmt::FromImport(i) => self.add(TemplateReferenceKind::FromImport, &i.expr, false),
+            _ => walk_stmt(self, stmt),
+        }
+    }
+
+    // template references are only found in statements
+    fn visit_expr(&mut self, _expr: &ast::Expr<'a>) {}
+}
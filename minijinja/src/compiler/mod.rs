@@ -1,9 +1,14 @@
 #![allow(missing_docs)]
 /// This module contains the internals of the compiler.
 pub mod ast;
+#[cfg(feature = "bytecode")]
+pub mod bytecode;
 pub mod codegen;
+pub mod html_context;
 pub mod instructions;
 pub mod lexer;
 pub mod meta;
+pub mod optimizer;
 pub mod parser;
 pub mod tokens;
+pub mod visitor;
@@ -0,0 +1,288 @@
+use std::collections::BTreeSet;
+
+#[cfg(feature = "debug")]
+use crate::compiler::instructions::SpanInfo;
+use crate::compiler::instructions::{Instruction, Instructions, LineInfo};
+use crate::environment::Environment;
+use crate::value::{Value, ValueKind};
+use crate::vm::normalize_filter_test_name;
+
+/// Builtin filters that only depend on their arguments.
+///
+/// If filter folding is enabled, applications of these filters to
+/// constants are evaluated at compile time unless the filter was replaced
+/// on the environment.
+pub const PURE_FILTERS: &[&str] = &[
+    "abs",
+    "bool",
+    "capitalize",
+    "count",
+    "float",
+    "int",
+    "join",
+    "length",
+    "lower",
+    "replace",
+    "round",
+    "safe",
+    "string",
+    "title",
+    "trim",
+    "upper",
+];
+
+/// Upper bound for following chains of jumps.
+const MAX_JUMP_CHAIN: usize = 16;
+
+/// Runs a peephole optimizer over the instructions.
+///
+/// The optimizer merges consecutive raw output and shortcuts jumps to
+/// unconditional jumps.  If an environment is passed that has constant
+/// folding enabled, pure filters on constant arguments are evaluated with
+/// it.  Removed instructions are compacted away and the line and span
+/// information is moved to the instructions replacing them.
+pub fn optimize(instructions: &mut Instructions<'_>, env: Option<&Environment<'_>>) {
+    let targets = find_jump_targets(&instructions.instructions);
+    let mut keep = vec![true; instructions.instructions.len()];
+    if let Some(env) = env.filter(|env| env.filter_folding()) {
+        fold_filters(instructions, &targets, &mut keep, env);
+    }
+    merge_raw_output(instructions, &targets, &mut keep);
+    thread_jumps(instructions, &mut keep);
+    if keep.contains(&false) {
+        compact(instructions, &keep);
+    }
+}
+
+/// Returns the jump target of an instruction.
+fn jump_target(instr: &Instruction<'_>) -> Option<u32> {
+    match *instr {
+        Instruction::Jump(target)
+        | Instruction::JumpIfFalse(target)
+        | Instruction::JumpIfFalseOrPop(target)
+        | Instruction::JumpIfTrueOrPop(target)
+        | Instruction::Iterate(target) => Some(target),
+        #[cfg(feature = "macros")]
+        Instruction::BuildMacro(_, target, _) => Some(target),
+        _ => None,
+    }
+}
+
+fn jump_target_mut<'a>(instr: &'a mut Instruction<'_>) -> Option<&'a mut u32> {
+    match instr {
+        Instruction::Jump(target)
+        | Instruction::JumpIfFalse(target)
+        | Instruction::JumpIfFalseOrPop(target)
+        | Instruction::JumpIfTrueOrPop(target)
+        | Instruction::Iterate(target) => Some(target),
+        #[cfg(feature = "macros")]
+        Instruction::BuildMacro(_, target, _) => Some(target),
+        _ => None,
+    }
+}
+
+fn find_jump_targets(instructions: &[Instruction<'_>]) -> BTreeSet<u32> {
+    instructions.iter().filter_map(jump_target).collect()
+}
+
+/// Replaces `LoadConst`s followed by a pure filter with the result.
+fn fold_filters(
+    instructions: &mut Instructions<'_>,
+    targets: &BTreeSet<u32>,
+    keep: &mut [bool],
+    env: &Environment<'_>,
+) {
+    for idx in 0..instructions.instructions.len() {
+        let (name, arg_count) = match instructions.instructions[idx] {
+            Instruction::ApplyFilter(name, Some(arg_count), _) => (name, arg_count as usize),
+            _ => continue,
+        };
+        let name = normalize_filter_test_name(name);
+        if !env.is_foldable_filter(&name) {
+            continue;
+        }
+
+        // collect the arguments backwards skipping over already folded
+        // instructions.  Nothing after the first argument may be the target
+        // of a jump as otherwise the stack would not be known.
+        let mut arg_indexes = Vec::with_capacity(arg_count);
+        let mut pos = idx;
+        while arg_indexes.len() < arg_count && pos > 0 {
+            pos -= 1;
+            if keep[pos] {
+                match instructions.instructions[pos] {
+                    Instruction::LoadConst(ref value) if !value.is_undefined() => {
+                        arg_indexes.push(pos)
+                    }
+                    _ => break,
+                }
+            }
+        }
+        if arg_indexes.len() != arg_count
+            || targets.range(pos as u32 + 1..=idx as u32).next().is_some()
+        {
+            continue;
+        }
+
+        let args = arg_indexes
+            .iter()
+            .rev()
+            .map(|&pos| match instructions.instructions[pos] {
+                Instruction::LoadConst(ref value) => value.clone(),
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        if let Some(value) = eval_filter(env, &name, &args) {
+            for pos in arg_indexes {
+                keep[pos] = false;
+            }
+            instructions.instructions[idx] = Instruction::LoadConst(value);
+        }
+    }
+}
+
+/// Invokes a builtin filter at compile time.
+///
+/// Errors are not folded so that they are reported at runtime with the
+/// correct location.  Only results that can be represented as constants
+/// are used.
+fn eval_filter(env: &Environment<'_>, name: &str, args: &[Value]) -> Option<Value> {
+    let filter = env.get_filter(name)?;
+    let rv = filter.call(&env.empty_state(), args).ok()?;
+    matches!(
+        rv.kind(),
+        ValueKind::None | ValueKind::Bool | ValueKind::Number | ValueKind::String
+    )
+    .then_some(rv)
+}
+
+/// Merges consecutive raw output into a single instruction.
+fn merge_raw_output(
+    instructions: &mut Instructions<'_>,
+    targets: &BTreeSet<u32>,
+    keep: &mut [bool],
+) {
+    let raw_at = |instructions: &Instructions<'_>, idx: usize| -> bool {
+        matches!(
+            instructions.instructions.get(idx),
+            Some(Instruction::EmitRaw(_) | Instruction::EmitRawConst(_))
+        )
+    };
+    let mut idx = 0;
+    while idx < instructions.instructions.len() {
+        if !raw_at(instructions, idx) {
+            idx += 1;
+            continue;
+        }
+        let start = idx;
+        idx += 1;
+        while raw_at(instructions, idx) && !targets.contains(&(idx as u32)) {
+            idx += 1;
+        }
+        if idx - start < 2 {
+            continue;
+        }
+
+        // the merged output is placed into the last instruction of the run
+        let mut merged = String::new();
+        for instr in &instructions.instructions[start..idx] {
+            merged.push_str(match *instr {
+                Instruction::EmitRaw(raw) => raw,
+                Instruction::EmitRawConst(ref value) => value.as_str().unwrap_or_default(),
+                _ => unreachable!(),
+            });
+        }
+        keep[start..idx - 1].fill(false);
+        instructions.instructions[idx - 1] = Instruction::EmitRawConst(Value::from(merged));
+    }
+}
+
+/// Points jumps that land on unconditional jumps to the final target and
+/// removes jumps to the next instruction.
+fn thread_jumps(instructions: &mut Instructions<'_>, keep: &mut [bool]) {
+    for idx in 0..instructions.instructions.len() {
+        let mut target = match instructions.instructions[idx] {
+            Instruction::Jump(target) | Instruction::JumpIfFalse(target) => target,
+            _ => continue,
+        };
+        for _ in 0..MAX_JUMP_CHAIN {
+            match instructions.instructions.get(target as usize) {
+                Some(Instruction::Jump(next)) if *next != target => target = *next,
+                _ => break,
+            }
+        }
+        if let Some(old_target) = jump_target_mut(&mut instructions.instructions[idx]) {
+            *old_target = target;
+        }
+
+        if let Instruction::Jump(target) = instructions.instructions[idx] {
+            let target = target as usize;
+            if target > idx
+                && keep
+                    .get(idx + 1..target)
+                    .is_some_and(|x| !x.contains(&true))
+            {
+                keep[idx] = false;
+            }
+        }
+    }
+}
+
+/// Removes all instructions not flagged to be kept and fixes up jump
+/// targets as well as the location information.
+fn compact(instructions: &mut Instructions<'_>, keep: &[bool]) {
+    // removed instructions map to the next instruction that is kept
+    let mut new_indexes = Vec::with_capacity(keep.len() + 1);
+    let mut kept = 0;
+    for &flag in keep {
+        new_indexes.push(kept);
+        if flag {
+            kept += 1;
+        }
+    }
+    new_indexes.push(kept);
+
+    let mut idx = 0;
+    instructions.instructions.retain(|_| {
+        idx += 1;
+        keep[idx - 1]
+    });
+    for instr in &mut instructions.instructions {
+        if let Some(target) = jump_target_mut(instr) {
+            *target = new_indexes[*target as usize];
+        }
+    }
+
+    let mut line_infos: Vec<LineInfo> = Vec::with_capacity(instructions.line_infos.len());
+    for mut info in instructions.line_infos.drain(..) {
+        info.first_instruction = new_indexes[info.first_instruction as usize];
+        if let Some(last) = line_infos.last_mut() {
+            if last.first_instruction == info.first_instruction {
+                *last = info;
+                continue;
+            } else if last.line == info.line {
+                continue;
+            }
+        }
+        line_infos.push(info);
+    }
+    instructions.line_infos = line_infos;
+
+    #[cfg(feature = "debug")]
+    {
+        let mut span_infos: Vec<SpanInfo> = Vec::with_capacity(instructions.span_infos.len());
+        for mut info in instructions.span_infos.drain(..) {
+            info.first_instruction = new_indexes[info.first_instruction as usize];
+            if let Some(last) = span_infos.last_mut() {
+                if last.first_instruction == info.first_instruction {
+                    *last = info;
+                    continue;
+                } else if last.span == info.span {
+                    continue;
+                }
+            }
+            span_infos.push(info);
+        }
+        instructions.span_infos = span_infos;
+    }
+}
@@ -771,11 +771,9 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_tuple_or_expression(&mut self, span: Span) -> Result<ast::Expr<'a>, Error> {
-        // MiniJinja does not really have tuples, but it treats the tuple
-        // syntax the same as lists.
         if skip_token!(self, Token::ParenClose) {
-            return Ok(ast::Expr::List(Spanned::new(
-                ast::List { items: vec![] },
+            return Ok(ast::Expr::Tuple(Spanned::new(
+                ast::Tuple { items: vec![] },
                 self.stream.expand_span(span),
             )));
         }
@@ -792,8 +790,8 @@ impl<'a> Parser<'a> {
                 }
                 items.push(ok!(self.parse_expr()));
             }
-            expr = ast::Expr::List(Spanned::new(
-                ast::List { items },
+            expr = ast::Expr::Tuple(Spanned::new(
+                ast::Tuple { items },
                 self.stream.expand_span(span),
             ));
         } else {
@@ -867,6 +865,8 @@ impl<'a> Parser<'a> {
                 ast::Stmt::Break(respan!(ast::Break))
             }
             "do" => ast::Stmt::Do(respan!(ok!(self.parse_do()))),
+            #[cfg(feature = "i18n")]
+            "trans" => ast::Stmt::Trans(respan!(ok!(self.parse_trans()))),
             name => syntax_error!("unknown statement {}", name),
         })
     }
@@ -1047,8 +1047,8 @@ impl<'a> Parser<'a> {
                         break;
                     }
                 }
-                ast::Expr::List(Spanned::new(
-                    ast::List { items },
+                ast::Expr::Tuple(Spanned::new(
+                    ast::Tuple { items },
                     self.stream.expand_span(span),
                 ))
             } else {
@@ -1226,26 +1226,32 @@ impl<'a> Parser<'a> {
     }
 
     #[cfg(feature = "macros")]
-    fn parse_macro_args_and_defaults(
-        &mut self,
-        args: &mut Vec<ast::Expr<'a>>,
-        defaults: &mut Vec<ast::Expr<'a>>,
-    ) -> Result<(), Error> {
+    fn parse_macro_args_and_defaults(&mut self, decl: &mut ast::Macro<'a>) -> Result<(), Error> {
         loop {
             if skip_token!(self, Token::ParenClose) {
                 break;
             }
-            if !args.is_empty() {
+            if !decl.args.is_empty() || decl.varargs.is_some() || decl.kwargs.is_some() {
                 expect_token!(self, Token::Comma, "`,`");
                 if skip_token!(self, Token::ParenClose) {
                     break;
                 }
             }
-            args.push(ok!(self.parse_assign_name(false)));
-            if skip_token!(self, Token::Assign) {
-                defaults.push(ok!(self.parse_expr()));
-            } else if !defaults.is_empty() {
-                expect_token!(self, Token::Assign, "`=`");
+            if decl.kwargs.is_some() {
+                syntax_error!("unexpected argument after `**` argument");
+            } else if skip_token!(self, Token::Pow) {
+                decl.kwargs = Some(ok!(self.parse_assign_name(false)));
+            } else if decl.varargs.is_some() {
+                syntax_error!("unexpected argument after `*` argument");
+            } else if skip_token!(self, Token::Mul) {
+                decl.varargs = Some(ok!(self.parse_assign_name(false)));
+            } else {
+                decl.args.push(ok!(self.parse_assign_name(false)));
+                if skip_token!(self, Token::Assign) {
+                    decl.defaults.push(ok!(self.parse_expr()));
+                } else if !decl.defaults.is_empty() {
+                    expect_token!(self, Token::Assign, "`=`");
+                }
             }
         }
         Ok(())
@@ -1254,46 +1260,50 @@ impl<'a> Parser<'a> {
     #[cfg(feature = "macros")]
     fn parse_macro_or_call_block_body(
         &mut self,
-        args: Vec<ast::Expr<'a>>,
-        defaults: Vec<ast::Expr<'a>>,
-        name: Option<&'a str>,
+        mut decl: ast::Macro<'a>,
+        is_call_block: bool,
     ) -> Result<ast::Macro<'a>, Error> {
         expect_token!(self, Token::BlockEnd, "end of block");
         let old_in_loop = std::mem::replace(&mut self.in_loop, false);
         let old_in_macro = std::mem::replace(&mut self.in_macro, true);
-        let body = ok!(self.subparse(&|tok| match tok {
-            Token::Ident("endmacro") if name.is_some() => true,
-            Token::Ident("endcall") if name.is_none() => true,
+        decl.body = ok!(self.subparse(&|tok| match tok {
+            Token::Ident("endmacro") if !is_call_block => true,
+            Token::Ident("endcall") if is_call_block => true,
             _ => false,
         }));
         self.in_macro = old_in_macro;
         self.in_loop = old_in_loop;
         ok!(self.stream.next());
-        Ok(ast::Macro {
-            name: name.unwrap_or("caller"),
-            args,
-            defaults,
-            body,
-        })
+        Ok(decl)
+    }
+
+    #[cfg(feature = "macros")]
+    fn new_macro_decl(name: &'a str) -> ast::Macro<'a> {
+        ast::Macro {
+            name,
+            args: Vec::with_capacity(4),
+            defaults: Vec::with_capacity(4),
+            varargs: None,
+            kwargs: None,
+            body: Vec::new(),
+        }
     }
 
     #[cfg(feature = "macros")]
     fn parse_macro(&mut self) -> Result<ast::Macro<'a>, Error> {
         let (name, _) = expect_token!(self, Token::Ident(name) => name, "identifier");
         expect_token!(self, Token::ParenOpen, "`(`");
-        let mut args = Vec::with_capacity(4);
-        let mut defaults = Vec::with_capacity(4);
-        ok!(self.parse_macro_args_and_defaults(&mut args, &mut defaults));
-        self.parse_macro_or_call_block_body(args, defaults, Some(name))
+        let mut decl = Self::new_macro_decl(name);
+        ok!(self.parse_macro_args_and_defaults(&mut decl));
+        self.parse_macro_or_call_block_body(decl, false)
     }
 
     #[cfg(feature = "macros")]
     fn parse_call_block(&mut self) -> Result<ast::CallBlock<'a>, Error> {
         let span = self.stream.last_span();
-        let mut args = Vec::with_capacity(4);
-        let mut defaults = Vec::with_capacity(4);
+        let mut decl = Self::new_macro_decl("caller");
         if skip_token!(self, Token::ParenOpen) {
-            ok!(self.parse_macro_args_and_defaults(&mut args, &mut defaults));
+            ok!(self.parse_macro_args_and_defaults(&mut decl));
         }
         let call = match ok!(self.parse_expr()) {
             ast::Expr::Call(call) => call,
@@ -1302,7 +1312,7 @@ impl<'a> Parser<'a> {
                 expr.description()
             ),
         };
-        let macro_decl = ok!(self.parse_macro_or_call_block_body(args, defaults, None));
+        let macro_decl = ok!(self.parse_macro_or_call_block_body(decl, true));
         Ok(ast::CallBlock {
             call,
             macro_decl: Spanned::new(macro_decl, self.stream.expand_span(span)),
@@ -1320,6 +1330,133 @@ impl<'a> Parser<'a> {
         Ok(ast::Do { call })
     }
 
+    #[cfg(feature = "i18n")]
+    fn parse_trans(&mut self) -> Result<ast::Trans<'a>, Error> {
+        let mut variables = Vec::<(&'a str, ast::Expr<'a>)>::new();
+        let mut trimmed = None;
+
+        while !matches_token!(self, Token::BlockEnd) {
+            if !variables.is_empty() {
+                expect_token!(self, Token::Comma, "comma");
+            }
+            let (name, span) = expect_token!(self, Token::Ident(name) => name, "identifier");
+            if variables.iter().any(|x| x.0 == name) {
+                syntax_error!("translatable variable '{}' defined twice", name);
+            }
+            if skip_token!(self, Token::Assign) {
+                variables.push((name, ok!(self.parse_expr())));
+            } else if trimmed.is_none() && (name == "trimmed" || name == "notrimmed") {
+                trimmed = Some(name == "trimmed");
+            } else {
+                variables.push((
+                    name,
+                    ast::Expr::Var(Spanned::new(ast::Var { id: name }, span)),
+                ));
+            }
+        }
+        expect_token!(self, Token::BlockEnd, "end of block");
+
+        let mut referenced = Vec::new();
+        let mut singular = ok!(self.parse_trans_body(&mut referenced));
+        let mut count = variables
+            .first()
+            .map(|x| x.0)
+            .or_else(|| referenced.first().map(|x| x.0));
+        let mut plural = None;
+        if matches!(
+            ok!(self.stream.next()),
+            Some((Token::Ident("pluralize"), _))
+        ) {
+            let name = match ok!(self.stream.current()) {
+                Some((Token::Ident(name), _)) => Some(*name),
+                _ => None,
+            };
+            if let Some(name) = name {
+                ok!(self.stream.next());
+                if !variables.iter().any(|x| x.0 == name) {
+                    syntax_error!("unknown variable '{}' for pluralization", name);
+                }
+                count = Some(name);
+            }
+            expect_token!(self, Token::BlockEnd, "end of block");
+            plural = Some(ok!(self.parse_trans_body(&mut referenced)));
+            if !matches!(ok!(self.stream.next()), Some((Token::Ident("endtrans"), _))) {
+                syntax_error!("a translatable section can have only one pluralize section");
+            }
+            if count.is_none() {
+                syntax_error!("pluralize without variables");
+            }
+        }
+
+        for (name, span) in referenced.iter().copied() {
+            if !variables.iter().any(|x| x.0 == name) {
+                variables.push((
+                    name,
+                    ast::Expr::Var(Spanned::new(ast::Var { id: name }, span)),
+                ));
+            }
+        }
+
+        // percent signs only need escaping if there is something to format
+        if referenced.is_empty() {
+            singular = singular.replace("%%", "%");
+            plural = plural.map(|x| x.replace("%%", "%"));
+        }
+        if trimmed == Some(true) {
+            singular = trim_trans_whitespace(&singular);
+            plural = plural.map(|x| trim_trans_whitespace(&x));
+        }
+
+        Ok(ast::Trans {
+            variables,
+            singular,
+            count: if plural.is_some() { count } else { None },
+            plural,
+        })
+    }
+
+    /// Parses the body of a trans block up to `pluralize` or `endtrans`
+    /// which is left on the stream.
+    #[cfg(feature = "i18n")]
+    fn parse_trans_body(&mut self, referenced: &mut Vec<(&'a str, Span)>) -> Result<String, Error> {
+        let mut rv = String::new();
+        loop {
+            match ok!(self.stream.next()) {
+                Some((Token::TemplateData(raw), _)) => rv.push_str(&raw.replace('%', "%%")),
+                Some((Token::VariableStart, _)) => {
+                    let (name, span) = expect_token!(
+                        self,
+                        Token::Ident(name) => name,
+                        "variable name (expressions must be bound in the trans tag)"
+                    );
+                    expect_token!(self, Token::VariableEnd, "end of variable block");
+                    if !referenced.iter().any(|x| x.0 == name) {
+                        referenced.push((name, span));
+                    }
+                    rv.push_str("%(");
+                    rv.push_str(name);
+                    rv.push_str(")s");
+                }
+                Some((Token::BlockStart, _)) => match ok!(self.stream.current()) {
+                    Some((Token::Ident("endtrans" | "pluralize"), _)) => return Ok(rv),
+                    Some((Token::Ident("trans"), _)) => {
+                        syntax_error!("trans blocks can't be nested; did you mean `endtrans`?")
+                    }
+                    Some((Token::Ident(name), _)) => syntax_error!(
+                        "control structures in translatable sections are not allowed; saw `{}`",
+                        name
+                    ),
+                    Some((token, _)) => syntax_error!("unexpected {}, expected keyword", token),
+                    None => syntax_error!("unclosed translation block"),
+                },
+                Some((token, _)) => {
+                    syntax_error!("unexpected {} in translation block", token)
+                }
+                None => syntax_error!("unclosed translation block"),
+            }
+        }
+    }
+
     fn subparse(
         &mut self,
         end_check: &dyn Fn(&Token) -> bool,
@@ -1364,6 +1501,22 @@ impl<'a> Parser<'a> {
     }
 }
 
+/// Collapses whitespace around line breaks for `trimmed` trans blocks.
+#[cfg(feature = "i18n")]
+fn trim_trans_whitespace(s: &str) -> String {
+    let mut rv = String::with_capacity(s.len());
+    for (idx, line) in s.trim().split('\n').enumerate() {
+        if idx > 0 {
+            rv.truncate(rv.trim_end().len());
+            rv.push(' ');
+            rv.push_str(line.trim_start());
+        } else {
+            rv.push_str(line);
+        }
+    }
+    rv
+}
+
 /// Parses a template.
 pub fn parse<'source>(
     source: &'source str,
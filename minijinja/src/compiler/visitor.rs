@@ -0,0 +1,245 @@
+use crate::compiler::ast;
+
+/// A trait to walk the syntax tree of a template.
+///
+/// All methods have default implementations that recurse into the children
+/// of the node by calling the matching `walk_*` function.  When overriding a
+/// method, call the walk function to continue the traversal into the node's
+/// children or leave it out to skip them.
+///
+/// ```
+/// # use minijinja::Environment;
+/// use minijinja::ast::{walk_expr, Expr, Visitor};
+///
+/// #[derive(Default)]
+/// struct VarCollector<'a> {
+///     names: Vec<&'a str>,
+/// }
+///
+/// impl<'a> Visitor<'a> for VarCollector<'a> {
+///     fn visit_expr(&mut self, expr: &Expr<'a>) {
+///         if let Expr::Var(var) = expr {
+///             self.names.push(var.id);
+///         }
+///         walk_expr(self, expr);
+///     }
+/// }
+///
+/// let env = Environment::new();
+/// let ast = env.parse("hello.txt", "{{ greeting }}, {{ user.name }}!").unwrap();
+/// let mut collector = VarCollector::default();
+/// collector.visit_stmt(&ast);
+/// assert_eq!(collector.names, ["greeting", "user"]);
+/// ```
+pub trait Visitor<'a> {
+    /// Visits a statement.
+    fn visit_stmt(&mut self, stmt: &ast::Stmt<'a>) {
+        walk_stmt(self, stmt);
+    }
+
+    /// Visits an expression.
+    fn visit_expr(&mut self, expr: &ast::Expr<'a>) {
+        walk_expr(self, expr);
+    }
+
+    /// Visits an argument passed to a call, filter or test.
+    fn visit_call_arg(&mut self, arg: &ast::CallArg<'a>) {
+        walk_call_arg(self, arg);
+    }
+
+    /// Visits a macro declaration.
+    ///
+    /// This is also invoked for the implicit `caller` macro of a call block.
+    #[cfg(feature = "macros")]
+    fn visit_macro(&mut self, m: &ast::Macro<'a>) {
+        walk_macro(self, m);
+    }
+}
+
+fn walk_stmts<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, stmts: &[ast::Stmt<'a>]) {
+    for stmt in stmts {
+        visitor.visit_stmt(stmt);
+    }
+}
+
+fn walk_call<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, call: &ast::Call<'a>) {
+    visitor.visit_expr(&call.expr);
+    for arg in &call.args {
+        visitor.visit_call_arg(arg);
+    }
+}
+
+/// Visits the children of a statement.
+pub fn walk_stmt<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, stmt: &ast::Stmt<'a>) {
+    match stmt {
+        ast::Stmt::Template(t) => walk_stmts(visitor, &t.children),
+        ast::Stmt::EmitExpr(e) => visitor.visit_expr(&e.expr),
+        ast::Stmt::EmitRaw(_) => {}
+        ast::Stmt::ForLoop(f) => {
+            visitor.visit_expr(&f.target);
+            visitor.visit_expr(&f.iter);
+            if let Some(ref filter_expr) = f.filter_expr {
+                visitor.visit_expr(filter_expr);
+            }
+            walk_stmts(visitor, &f.body);
+            walk_stmts(visitor, &f.else_body);
+        }
+        ast::Stmt::IfCond(i) => {
+            visitor.visit_expr(&i.expr);
+            walk_stmts(visitor, &i.true_body);
+            walk_stmts(visitor, &i.false_body);
+        }
+        ast::Stmt::WithBlock(w) => {
+            for (target, expr) in &w.assignments {
+                visitor.visit_expr(target);
+                visitor.visit_expr(expr);
+            }
+            walk_stmts(visitor, &w.body);
+        }
+        ast::Stmt::Set(s) => {
+            visitor.visit_expr(&s.target);
+            visitor.visit_expr(&s.expr);
+        }
+        ast::Stmt::SetBlock(s) => {
+            visitor.visit_expr(&s.target);
+            if let Some(ref filter) = s.filter {
+                visitor.visit_expr(filter);
+            }
+            walk_stmts(visitor, &s.body);
+        }
+        ast::Stmt::AutoEscape(a) => {
+            visitor.visit_expr(&a.enabled);
+            walk_stmts(visitor, &a.body);
+        }
+        ast::Stmt::FilterBlock(f) => {
+            visitor.visit_expr(&f.filter);
+            walk_stmts(visitor, &f.body);
+        }
+        #[cfg(feature = "multi_template")]
+        ast::Stmt::Block(b) => walk_stmts(visitor, &b.body),
+        #[cfg(feature = "multi_template")]
+        ast::Stmt::Import(i) => {
+            visitor.visit_expr(&i.expr);
+            visitor.visit_expr(&i.name);
+        }
+        #[cfg(feature = "multi_template")]
+        ast::Stmt::FromImport(i) => {
+            visitor.visit_expr(&i.expr);
+            for (name, alias) in &i.names {
+                visitor.visit_expr(name);
+                if let Some(alias) = alias {
+                    visitor.visit_expr(alias);
+                }
+            }
+        }
+        #[cfg(feature = "multi_template")]
+        ast::Stmt::Extends(e) => visitor.visit_expr(&e.name),
+        #[cfg(feature = "multi_template")]
+        ast::Stmt::Include(i) => visitor.visit_expr(&i.name),
+        #[cfg(feature = "macros")]
+        ast::Stmt::Macro(m) => visitor.visit_macro(m),
+        #[cfg(feature = "macros")]
+        ast::Stmt::CallBlock(c) => {
+            walk_call(visitor, &c.call);
+            visitor.visit_macro(&c.macro_decl);
+        }
+        #[cfg(feature = "loop_controls")]
+        ast::Stmt::Continue(_) | ast::Stmt::Break(_) => {}
+        ast::Stmt::Do(d) => walk_call(visitor, &d.call),
+        #[cfg(feature = "i18n")]
+        ast::Stmt::Trans(t) => {
+            for (_, expr) in &t.variables {
+                visitor.visit_expr(expr);
+            }
+        }
+    }
+}
+
+/// Visits the children of an expression.
+pub fn walk_expr<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, expr: &ast::Expr<'a>) {
+    match expr {
+        ast::Expr::Var(_) | ast::Expr::Const(_) => {}
+        ast::Expr::Slice(s) => {
+            visitor.visit_expr(&s.expr);
+            for expr in [&s.start, &s.stop, &s.step].into_iter().flatten() {
+                visitor.visit_expr(expr);
+            }
+        }
+        ast::Expr::UnaryOp(u) => visitor.visit_expr(&u.expr),
+        ast::Expr::BinOp(b) => {
+            visitor.visit_expr(&b.left);
+            visitor.visit_expr(&b.right);
+        }
+        ast::Expr::IfExpr(i) => {
+            visitor.visit_expr(&i.test_expr);
+            visitor.visit_expr(&i.true_expr);
+            if let Some(ref false_expr) = i.false_expr {
+                visitor.visit_expr(false_expr);
+            }
+        }
+        ast::Expr::Filter(f) => {
+            if let Some(ref expr) = f.expr {
+                visitor.visit_expr(expr);
+            }
+            for arg in &f.args {
+                visitor.visit_call_arg(arg);
+            }
+        }
+        ast::Expr::Test(t) => {
+            visitor.visit_expr(&t.expr);
+            for arg in &t.args {
+                visitor.visit_call_arg(arg);
+            }
+        }
+        ast::Expr::GetAttr(g) => visitor.visit_expr(&g.expr),
+        ast::Expr::GetItem(g) => {
+            visitor.visit_expr(&g.expr);
+            visitor.visit_expr(&g.subscript_expr);
+        }
+        ast::Expr::Call(c) => walk_call(visitor, c),
+        ast::Expr::List(l) => {
+            for item in &l.items {
+                visitor.visit_expr(item);
+            }
+        }
+        ast::Expr::Tuple(t) => {
+            for item in &t.items {
+                visitor.visit_expr(item);
+            }
+        }
+        ast::Expr::Map(m) => {
+            for (key, value) in m.keys.iter().zip(m.values.iter()) {
+                visitor.visit_expr(key);
+                visitor.visit_expr(value);
+            }
+        }
+    }
+}
+
+/// Visits the expression of a call argument.
+pub fn walk_call_arg<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, arg: &ast::CallArg<'a>) {
+    match arg {
+        ast::CallArg::Pos(expr)
+        | ast::CallArg::Kwarg(_, expr)
+        | ast::CallArg::PosSplat(expr)
+        | ast::CallArg::KwargSplat(expr) => visitor.visit_expr(expr),
+    }
+}
+
+/// Visits the arguments, defaults and body of a macro declaration.
+#[cfg(feature = "macros")]
+pub fn walk_macro<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, m: &ast::Macro<'a>) {
+    for arg in &m.args {
+        visitor.visit_expr(arg);
+    }
+    for default in &m.defaults {
+        visitor.visit_expr(default);
+    }
+    if let Some(ref varargs) = m.varargs {
+        visitor.visit_expr(varargs);
+    }
+    if let Some(ref kwargs) = m.kwargs {
+        visitor.visit_expr(kwargs);
+    }
+    walk_stmts(visitor, &m.body);
+}
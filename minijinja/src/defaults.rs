@@ -6,7 +6,7 @@ use crate::error::Error;
 use crate::filters;
 use crate::output::Output;
 use crate::tests;
-use crate::utils::{write_escaped, AutoEscape};
+use crate::utils::AutoEscape;
 use crate::value::Value;
 use crate::vm::State;
 
@@ -24,6 +24,9 @@ const IGNORED_EXTENSIONS: [&str; 3] = [".j2", ".jinja2", ".jinja"];
     feature = "json",
     doc = r" * [`Json`](AutoEscape::Json): `.json`, `.json5`, `.js`, `.yaml`, `.yml`"
 )]
+/// * [`Shell`](AutoEscape::Shell): `.sh`
+/// * [`Css`](AutoEscape::Css): `.css`
+/// * [`Latex`](AutoEscape::Latex): `.tex`
 /// * [`None`](AutoEscape::None): _all others_
 ///
 /// Additionally `.j2`, `.jinja` and `.jinja2` as final extension is ignored. So
@@ -40,6 +43,9 @@ pub fn default_auto_escape_callback(mut name: &str) -> AutoEscape {
         Some("html" | "htm" | "xml") => AutoEscape::Html,
         #[cfg(feature = "json")]
         Some("json" | "json5" | "js" | "yaml" | "yml") => AutoEscape::Json,
+        Some("sh") => AutoEscape::Shell,
+        Some("css") => AutoEscape::Css,
+        Some("tex") => AutoEscape::Latex,
         _ => AutoEscape::None,
     }
 }
@@ -56,10 +62,15 @@ pub fn default_auto_escape_callback(mut name: &str) -> AutoEscape {
     feature = "json",
     doc = r" * [`Json`](AutoEscape::Json): serializes values to JSON"
 )]
+/// * [`Shell`](AutoEscape::Shell): quotes values for POSIX shells
+/// * [`Css`](AutoEscape::Css): escapes values for CSS
+/// * [`Url`](AutoEscape::Url): percent encodes values as URL components
+/// * [`Latex`](AutoEscape::Latex): escapes LaTeX special characters
 /// * [`None`](AutoEscape::None): no escaping
-/// * [`Custom(..)`](AutoEscape::Custom): results in an error
+/// * [`Custom(..)`](AutoEscape::Custom): uses the format registered with
+///   [`add_auto_escape`](crate::Environment::add_auto_escape), errors otherwise
 pub fn escape_formatter(out: &mut Output, state: &State, value: &Value) -> Result<(), Error> {
-    write_escaped(out, state.auto_escape(), value)
+    state.env().write_escaped(out, state.auto_escape(), value)
 }
 
 fn build_builtin_filters() -> BTreeMap<Cow<'static, str>, Value> {
@@ -70,6 +81,8 @@ fn build_builtin_filters() -> BTreeMap<Cow<'static, str>, Value> {
     rv.insert("e".into(), escape);
     #[cfg(feature = "builtins")]
     {
+        use crate::functions::BoxedFunction;
+
         rv.insert("lower".into(), Value::from_function(filters::lower));
         rv.insert("upper".into(), Value::from_function(filters::upper));
         rv.insert("title".into(), Value::from_function(filters::title));
@@ -77,36 +90,86 @@ fn build_builtin_filters() -> BTreeMap<Cow<'static, str>, Value> {
             "capitalize".into(),
             Value::from_function(filters::capitalize),
         );
-        rv.insert("replace".into(), Value::from_function(filters::replace));
+        rv.insert(
+            "replace".into(),
+            BoxedFunction::new(filters::limited_replace)
+                .with_debug_name_of(filters::replace)
+                .to_value(),
+        );
         let length = Value::from_function(filters::length);
         rv.insert("length".into(), length.clone());
         rv.insert("count".into(), length);
-        rv.insert("dictsort".into(), Value::from_function(filters::dictsort));
+        rv.insert(
+            "dictsort".into(),
+            BoxedFunction::new(filters::dictsort_with_args)
+                .with_debug_name_of(filters::dictsort)
+                .to_value(),
+        );
         rv.insert("items".into(), Value::from_function(filters::items));
         rv.insert("reverse".into(), Value::from_function(filters::reverse));
         rv.insert("trim".into(), Value::from_function(filters::trim));
-        rv.insert("join".into(), Value::from_function(filters::join));
+        rv.insert(
+            "join".into(),
+            BoxedFunction::new(filters::limited_join)
+                .with_debug_name_of(filters::join)
+                .to_value(),
+        );
         rv.insert("split".into(), Value::from_function(filters::split));
         rv.insert("lines".into(), Value::from_function(filters::lines));
         rv.insert("default".into(), Value::from_function(filters::default));
         rv.insert("d".into(), Value::from_function(filters::default));
-        rv.insert("round".into(), Value::from_function(filters::round));
+        rv.insert(
+            "round".into(),
+            BoxedFunction::new(filters::round_with_args)
+                .with_debug_name_of(filters::round)
+                .to_value(),
+        );
         rv.insert("abs".into(), Value::from_function(filters::abs));
         rv.insert("int".into(), Value::from_function(filters::int));
         rv.insert("float".into(), Value::from_function(filters::float));
         rv.insert("attr".into(), Value::from_function(filters::attr));
         rv.insert("first".into(), Value::from_function(filters::first));
         rv.insert("last".into(), Value::from_function(filters::last));
-        rv.insert("min".into(), Value::from_function(filters::min));
-        rv.insert("max".into(), Value::from_function(filters::max));
+        rv.insert(
+            "min".into(),
+            BoxedFunction::new(filters::min_with_args)
+                .with_debug_name_of(filters::min)
+                .to_value(),
+        );
+        rv.insert(
+            "max".into(),
+            BoxedFunction::new(filters::max_with_args)
+                .with_debug_name_of(filters::max)
+                .to_value(),
+        );
         rv.insert("sort".into(), Value::from_function(filters::sort));
         rv.insert("list".into(), Value::from_function(filters::list));
         rv.insert("string".into(), Value::from_function(filters::string));
         rv.insert("bool".into(), Value::from_function(filters::bool));
-        rv.insert("batch".into(), Value::from_function(filters::batch));
-        rv.insert("slice".into(), Value::from_function(filters::slice));
-        rv.insert("sum".into(), Value::from_function(filters::sum));
-        rv.insert("indent".into(), Value::from_function(filters::indent));
+        rv.insert(
+            "batch".into(),
+            BoxedFunction::new(filters::batch_with_args)
+                .with_debug_name_of(filters::batch)
+                .to_value(),
+        );
+        rv.insert(
+            "slice".into(),
+            BoxedFunction::new(filters::slice_with_args)
+                .with_debug_name_of(filters::slice)
+                .to_value(),
+        );
+        rv.insert(
+            "sum".into(),
+            BoxedFunction::new(filters::sum_with_args)
+                .with_debug_name_of(filters::sum)
+                .to_value(),
+        );
+        rv.insert(
+            "indent".into(),
+            BoxedFunction::new(filters::limited_indent)
+                .with_debug_name_of(filters::indent)
+                .to_value(),
+        );
         rv.insert("select".into(), Value::from_function(filters::select));
         rv.insert("reject".into(), Value::from_function(filters::reject));
         rv.insert(
@@ -119,11 +182,28 @@ fn build_builtin_filters() -> BTreeMap<Cow<'static, str>, Value> {
         );
         rv.insert("map".into(), Value::from_function(filters::map));
         rv.insert("groupby".into(), Value::from_function(filters::groupby));
-        rv.insert("unique".into(), Value::from_function(filters::unique));
+        rv.insert(
+            "unique".into(),
+            BoxedFunction::new(filters::unique_with_args)
+                .with_debug_name_of(filters::unique)
+                .to_value(),
+        );
         rv.insert("chain".into(), Value::from_function(filters::chain));
         rv.insert("zip".into(), Value::from_function(filters::zip));
         rv.insert("pprint".into(), Value::from_function(filters::pprint));
-        rv.insert("format".into(), Value::from_function(filters::format));
+        rv.insert(
+            "format".into(),
+            BoxedFunction::new(filters::limited_format)
+                .with_debug_name_of(filters::format)
+                .to_value(),
+        );
+        rv.insert("center".into(), Value::from_function(filters::center));
+        rv.insert(
+            "forceescape".into(),
+            Value::from_function(filters::forceescape),
+        );
+        rv.insert("xmlattr".into(), Value::from_function(filters::xmlattr));
+        rv.insert("urlize".into(), Value::from_function(filters::urlize));
 
         #[cfg(feature = "json")]
         {
@@ -231,7 +311,9 @@ fn build_globals() -> BTreeMap<Cow<'static, str>, Value> {
         use crate::functions::{self, BoxedFunction};
         rv.insert(
             "range".into(),
-            BoxedFunction::new(functions::range).to_value(),
+            BoxedFunction::new(functions::sandboxed_range)
+                .with_debug_name_of(functions::range)
+                .to_value(),
         );
         rv.insert(
             "dict".into(),
@@ -241,6 +323,10 @@ fn build_globals() -> BTreeMap<Cow<'static, str>, Value> {
             "debug".into(),
             BoxedFunction::new(functions::debug).to_value(),
         );
+        rv.insert(
+            "flush".into(),
+            BoxedFunction::new(functions::flush).to_value(),
+        );
         rv.insert(
             "namespace".into(),
             BoxedFunction::new(functions::namespace).to_value(),
@@ -299,6 +385,10 @@ mod unit_tests {
             AutoEscape::None
         );
 
+        assert_eq!(default_auto_escape_callback("foo.sh"), AutoEscape::Shell);
+        assert_eq!(default_auto_escape_callback("foo.css.j2"), AutoEscape::Css);
+        assert_eq!(default_auto_escape_callback("foo.tex"), AutoEscape::Latex);
+
         // only one is removed
         assert_eq!(
             default_auto_escape_callback("foo.html.j2.jinja"),
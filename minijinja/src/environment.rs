@@ -1,25 +1,29 @@
 use std::borrow::Cow;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
 use std::sync::{Arc, OnceLock};
 
 use serde::Serialize;
 
+use crate::compiler::ast;
 use crate::compiler::codegen::CodeGenerator;
 use crate::compiler::instructions::Instructions;
-use crate::compiler::parser::parse_expr;
-use crate::error::{attach_basic_debug_info, Error, ErrorKind};
+use crate::compiler::optimizer::{optimize, PURE_FILTERS};
+use crate::compiler::parser::{parse, parse_expr};
+use crate::error::{attach_basic_debug_info, Error};
 use crate::expression::Expression;
+use crate::loader::LoaderStore;
 use crate::output::Output;
 use crate::template::{
     AutoEscapeFunc, CompiledTemplate, CompiledTemplateRef, Template, TemplateConfig,
 };
 use crate::utils::{write_escaped, AutoEscape, BTreeMapKeysDebug, UndefinedBehavior};
-use crate::value::{FunctionArgs, FunctionResult, UndefinedType, Value, ValueRepr};
+use crate::value::{DynObject, FunctionArgs, FunctionResult, UndefinedType, Value, ValueRepr};
 use crate::vm::State;
 use crate::{defaults, functions};
 
 type FormatterFunc = dyn Fn(&mut Output, &State, &Value) -> Result<(), Error> + Sync + Send;
+type AutoEscapeWriterFunc = dyn Fn(&mut Output, &Value) -> Result<(), Error> + Sync + Send;
 type PathJoinFunc = dyn for<'s> Fn(&'s str, &'s str) -> Cow<'s, str> + Sync + Send;
 type UnknownMethodFunc =
     dyn Fn(&State, &Value, &str, &[Value]) -> Result<Value, Error> + Sync + Send;
@@ -50,7 +54,7 @@ fn default_formatter() -> Arc<FormatterFunc> {
 /// However in some situations more depth is pushed if the cost
 /// of the stack frame is higher.  Raising this limit
 /// requires enabling the `stacker` feature.
-const MAX_RECURSION: usize = 500;
+pub(crate) const MAX_RECURSION: usize = 500;
 
 /// An abstraction that holds the engine configuration.
 ///
@@ -78,11 +82,23 @@ pub struct Environment<'source> {
     undefined_behavior: UndefinedBehavior,
     formatter: Arc<FormatterFunc>,
     formatter_is_default: bool,
+    custom_auto_escapes: Arc<BTreeMap<&'static str, Arc<AutoEscapeWriterFunc>>>,
     #[cfg(feature = "debug")]
     debug: bool,
     #[cfg(feature = "fuel")]
     fuel: Option<u64>,
+    #[cfg(feature = "i18n")]
+    translator: Option<Arc<dyn crate::i18n::Translator>>,
+    sandbox_policy: Option<Arc<dyn crate::sandbox::SandboxPolicy>>,
+    // the names of the global objects by object address.  This is only
+    // maintained while a sandbox policy is set.
+    sandbox_global_names: Arc<BTreeMap<usize, Vec<Cow<'source, str>>>>,
+    instrumentation: Option<Arc<dyn crate::instrument::Instrumentation>>,
+    output_limit: Option<usize>,
+    size_limit: Option<usize>,
     recursion_limit: usize,
+    filter_folding: bool,
+    foldable_filters: BTreeSet<&'static str>,
 }
 
 impl Default for Environment<'_> {
@@ -129,11 +145,21 @@ impl<'source> Environment<'source> {
             undefined_behavior: UndefinedBehavior::default(),
             formatter: default_formatter(),
             formatter_is_default: true,
+            custom_auto_escapes: Default::default(),
             #[cfg(feature = "debug")]
             debug: cfg!(debug_assertions),
             #[cfg(feature = "fuel")]
             fuel: None,
+            #[cfg(feature = "i18n")]
+            translator: None,
+            sandbox_policy: None,
+            sandbox_global_names: Default::default(),
+            instrumentation: None,
+            output_limit: None,
+            size_limit: None,
             recursion_limit: MAX_RECURSION,
+            filter_folding: false,
+            foldable_filters: PURE_FILTERS.iter().copied().collect(),
         }
     }
 
@@ -152,11 +178,21 @@ impl<'source> Environment<'source> {
             undefined_behavior: UndefinedBehavior::default(),
             formatter: default_formatter(),
             formatter_is_default: true,
+            custom_auto_escapes: Default::default(),
             #[cfg(feature = "debug")]
             debug: cfg!(debug_assertions),
             #[cfg(feature = "fuel")]
             fuel: None,
+            #[cfg(feature = "i18n")]
+            translator: None,
+            sandbox_policy: None,
+            sandbox_global_names: Default::default(),
+            instrumentation: None,
+            output_limit: None,
+            size_limit: None,
             recursion_limit: MAX_RECURSION,
+            filter_folding: false,
+            foldable_filters: BTreeSet::new(),
         }
     }
 
@@ -178,7 +214,9 @@ impl<'source> Environment<'source> {
     /// of the environment. To avoid this restriction use
     /// [`add_template_owned`](Self::add_template_owned).
     pub fn add_template(&mut self, name: &'source str, source: &'source str) -> Result<(), Error> {
-        self.templates.insert(name, source)
+        let template = ok!(CompiledTemplate::new_for_env(name, source, self));
+        self.templates.insert(name, template);
+        Ok(())
     }
 
     /// Adds a template without borrowing.
@@ -200,7 +238,54 @@ impl<'source> Environment<'source> {
         N: Into<Cow<'source, str>>,
         S: Into<Cow<'source, str>>,
     {
-        self.templates.insert_cow(name.into(), source.into())
+        match (source.into(), name.into()) {
+            (Cow::Borrowed(source), Cow::Borrowed(name)) => self.add_template(name, source),
+            (source, name) => {
+                let name: Arc<str> = name.into();
+                let template = ok!(LoaderStore::compile_owned(
+                    name.clone(),
+                    source.into_owned(),
+                    self
+                ));
+                self.templates.insert_owned(name, template);
+                Ok(())
+            }
+        }
+    }
+
+    /// Loads a template from bytecode into the environment.
+    ///
+    /// The bytecode is created with [`Template::to_bytecode`](crate::Template::to_bytecode)
+    /// and lets you skip parsing and compiling templates on startup.  The
+    /// bytecode can only be loaded by the exact same version of MiniJinja with
+    /// the same set of engine features enabled.  If that's not the case, or the
+    /// bytecode is corrupted, an error of kind
+    /// [`ErrorKind::InvalidBytecode`](crate::ErrorKind::InvalidBytecode) is
+    /// returned and the template should be compiled from source instead.
+    ///
+    /// ```
+    /// # use minijinja::Environment;
+    /// let mut env = Environment::new();
+    /// let bytecode = env
+    ///     .template_from_named_str("index.html", "Hello {{ name }}!")
+    ///     .unwrap()
+    ///     .to_bytecode()
+    ///     .unwrap();
+    ///
+    /// let mut other_env = Environment::new();
+    /// other_env.add_template_bytecode("index.html", &bytecode).unwrap();
+    /// ```
+    ///
+    /// Note that bytecode is expected to come from a trusted source.  The
+    /// version check and checksum guard against stale and damaged caches but
+    /// not against maliciously crafted input.
+    #[cfg(feature = "bytecode")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "bytecode")))]
+    pub fn add_template_bytecode<N>(&mut self, name: N, bytecode: &[u8]) -> Result<(), Error>
+    where
+        N: Into<Cow<'source, str>>,
+    {
+        self.templates.insert_bytecode(name.into(), bytecode)
     }
 
     /// Register a template loader as source of templates.
@@ -213,7 +298,8 @@ impl<'source> Environment<'source> {
     /// once per template name.
     ///
     /// For loading templates from the file system, you can use the
-    /// [`path_loader`](crate::path_loader) function.
+    /// [`path_loader`](crate::path_loader) function or the
+    /// [`FileSystemLoader`](crate::FileSystemLoader).
     ///
     /// # Example
     ///
@@ -387,6 +473,24 @@ impl<'source> Environment<'source> {
         })
     }
 
+    /// Returns the dependency graph of all loaded templates.
+    ///
+    /// The graph contains the [referenced
+    /// templates](crate::Template::referenced_templates) of every template
+    /// returned by [`templates`](Self::templates).  As templates from a loader
+    /// are only loaded on demand, they only show up once they have been
+    /// requested.  If one of the templates cannot be parsed, its syntax error
+    /// is returned.
+    #[cfg(feature = "multi_template")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "multi_template")))]
+    pub fn template_graph(&self) -> Result<crate::TemplateGraph, Error> {
+        let mut references = BTreeMap::new();
+        for (name, tmpl) in self.templates() {
+            references.insert(name.to_string(), ok!(tmpl.referenced_templates()));
+        }
+        Ok(crate::TemplateGraph { references })
+    }
+
     /// Fetches a template by name.
     ///
     /// This requires that the template has been loaded with
@@ -402,7 +506,7 @@ impl<'source> Environment<'source> {
     /// println!("{}", tmpl.render(context!{ name => "World" }).unwrap());
     /// ```
     pub fn get_template(&self, name: &str) -> Result<Template<'_, '_>, Error> {
-        let compiled = ok!(self.templates.get(name));
+        let compiled = ok!(self.templates.get(name, self));
         Ok(Template::new(self, CompiledTemplateRef::Borrowed(compiled)))
     }
 
@@ -424,10 +528,8 @@ impl<'source> Environment<'source> {
     ) -> Result<Template<'_, 'source>, Error> {
         Ok(Template::new(
             self,
-            CompiledTemplateRef::Owned(Arc::new(ok!(CompiledTemplate::new(
-                name,
-                source,
-                &self.templates.template_config,
+            CompiledTemplateRef::Owned(Arc::new(ok!(CompiledTemplate::new_for_env(
+                name, source, self,
             )))),
         ))
     }
@@ -440,6 +542,28 @@ impl<'source> Environment<'source> {
         self.template_from_named_str("<string>", source)
     }
 
+    /// Parses a template into a syntax tree without compiling it.
+    ///
+    /// The returned root node is always a [`Stmt::Template`](crate::ast::Stmt::Template).
+    /// The syntax and whitespace settings of the environment are taken into
+    /// account.  For more information see the [`ast`](crate::ast) module.
+    ///
+    /// ```
+    /// # use minijinja::Environment;
+    /// use minijinja::ast::Stmt;
+    ///
+    /// let env = Environment::new();
+    /// let ast = env.parse("hello.txt", "Hello {{ name }}!").unwrap();
+    /// assert!(matches!(ast, Stmt::Template(_)));
+    /// ```
+    pub fn parse<'a>(&self, name: &'a str, source: &'a str) -> Result<ast::Stmt<'a>, Error> {
+        let config = &self.templates.template_config;
+        attach_basic_debug_info(
+            parse(source, name, config.syntax_config.clone(), config.ws_config),
+            source,
+        )
+    }
+
     /// Parses and renders a template from a string in one go with name.
     ///
     /// Like [`render_str`](Self::render_str), but provide a name for the
@@ -512,6 +636,172 @@ impl<'source> Environment<'source> {
         self.templates.template_config.default_auto_escape = Arc::new(f);
     }
 
+    /// Registers a custom auto escape format.
+    ///
+    /// Values emitted while [`AutoEscape::Custom`] with the given name is
+    /// active are passed to the function which writes them escaped into the
+    /// [`Output`].  Values marked as safe are written unchanged.  The format
+    /// is honored by the default formatter, the `escape` filter and can be
+    /// enabled with `{% autoescape "name" %}` blocks.  The builtin formats
+    /// such as `"html"` take precedence over custom formats with the same name.
+    ///
+    /// To enable the format for templates by file extension, return it from
+    /// the [auto escape callback](Self::set_auto_escape_callback):
+    ///
+    /// ```
+    /// # use minijinja::{Environment, AutoEscape, Error, context};
+    /// use minijinja::default_auto_escape_callback;
+    ///
+    /// let mut env = Environment::new();
+    /// env.add_auto_escape("csv", |out, value| {
+    ///     let value = value.to_string();
+    ///     if value.contains([',', '"', '\n']) {
+    ///         write!(out, "\"{}\"", value.replace('"', "\"\""))?;
+    ///     } else {
+    ///         out.write_str(&value)?;
+    ///     }
+    ///     Ok(())
+    /// });
+    /// env.set_auto_escape_callback(|name| {
+    ///     if name.ends_with(".csv") {
+    ///         AutoEscape::Custom("csv")
+    ///     } else {
+    ///         default_auto_escape_callback(name)
+    ///     }
+    /// });
+    /// env.add_template("users.csv", "{{ name }},{{ city }}").unwrap();
+    /// let tmpl = env.get_template("users.csv").unwrap();
+    /// let rv = tmpl.render(context! { name => "Doe, John", city => "Vienna" }).unwrap();
+    /// assert_eq!(rv, "\"Doe, John\",Vienna");
+    /// ```
+    pub fn add_auto_escape<F>(&mut self, name: &'static str, f: F)
+    where
+        F: Fn(&mut Output, &Value) -> Result<(), Error> + Sync + Send + 'static,
+    {
+        Arc::make_mut(&mut self.custom_auto_escapes).insert(name, Arc::new(f));
+    }
+
+    /// Enables context-aware escaping for HTML templates.
+    ///
+    /// By default HTML auto escaping applies the same rules to every value.
+    /// That is only safe for values in HTML text and quoted attributes.  With
+    /// contextual escaping enabled, the HTML of templates that start out with
+    /// [`AutoEscape::Html`] is tracked at compile time and values are escaped
+    /// for where they appear, similar to Go's `html/template`:
+    ///
+    /// * in `<script>` tags and event handler attributes values are emitted
+    ///   as JavaScript literals, or escaped for JavaScript strings.
+    /// * in URL attributes such as `href` values are percent encoded and
+    ///   URLs with schemes other than `http`, `https` and `mailto` are
+    ///   replaced with `#ZgotmplZ`.
+    /// * in `<style>` tags and `style` attributes values are escaped for CSS
+    ///   strings, or replaced with `ZgotmplZ` if they could escape from a
+    ///   property value.
+    /// * unquoted attribute values are escaped so that they cannot end early.
+    /// * values in comments are dropped.
+    ///
+    /// ```
+    /// # use minijinja::{Environment, context};
+    /// let mut env = Environment::new();
+    /// env.set_contextual_html_escaping(true);
+    /// env.add_template("hello.html", "<a href=\"{{ url }}\" onclick=\"greet({{ name }})\">").unwrap();
+    /// let tmpl = env.get_template("hello.html").unwrap();
+    /// let rv = tmpl.render(context! { url => "javascript:alert(1)", name => "Peter" }).unwrap();
+    /// assert_eq!(rv, "<a href=\"#ZgotmplZ\" onclick=\"greet(&quot;Peter&quot;)\">");
+    /// ```
+    ///
+    /// Since the tracking happens per template, included templates as well as
+    /// the bodies of blocks, macros, call blocks and `{% set %}` blocks are
+    /// tracked as HTML text.  To keep their output from ending up in another
+    /// context, templates fail to compile if they use blocks or includes
+    /// anywhere but in HTML text, if a block does not end in the context it
+    /// starts in or if a template does not end in HTML text.  Likewise, the
+    /// branches of an `{% if %}` and the body of a `{% for %}` loop must end
+    /// in the same context.
+    ///
+    /// Values marked as safe (which includes the output of macros and
+    /// `{% set %}` blocks) are only emitted unchanged in HTML text and quoted
+    /// attribute values.  Emitting them anywhere else fails the render; use an
+    /// `{% autoescape false %}` block to emit trusted code in these contexts.
+    /// Custom [formatters](Self::set_formatter) bypass contextual escaping.
+    ///
+    /// This setting is used whenever a template is loaded into the environment.
+    /// Changing it at a later point only affects future templates loaded.
+    pub fn set_contextual_html_escaping(&mut self, yes: bool) {
+        self.templates.template_config.contextual_html_escaping = yes;
+    }
+
+    /// Returns the value of the contextual HTML escaping flag.
+    pub fn contextual_html_escaping(&self) -> bool {
+        self.templates.template_config.contextual_html_escaping
+    }
+
+    /// Makes the tuple syntax create tuples.
+    ///
+    /// By default `(1, 2)` in a template creates a list just like `[1, 2]`.
+    /// If this is enabled it instead creates a tuple like
+    /// [`Value::from_tuple`] which reports [`ValueKind::Tuple`](crate::value::ValueKind::Tuple)
+    /// as kind and renders with parentheses like in Python:
+    ///
+    /// ```
+    /// # use minijinja::Environment;
+    /// let mut env = Environment::new();
+    /// assert_eq!(env.render_str("{{ (1, 2) }}", ()).unwrap(), "[1, 2]");
+    /// env.set_python_tuples(true);
+    /// assert_eq!(env.render_str("{{ (1, 2) }}|{{ (1,) }}", ()).unwrap(), "(1, 2)|(1,)");
+    /// ```
+    ///
+    /// This setting is used whenever a template is loaded into the environment.
+    /// Changing it at a later point only affects future templates loaded.
+    pub fn set_python_tuples(&mut self, yes: bool) {
+        self.templates.template_config.python_tuples = yes;
+    }
+
+    /// Returns the value of the python tuples flag.
+    pub fn python_tuples(&self) -> bool {
+        self.templates.template_config.python_tuples
+    }
+
+    /// Enables evaluating builtin filters when templates are loaded.
+    ///
+    /// If enabled, builtin filters that only depend on their arguments (like
+    /// `upper` or `round`) are evaluated when a template is loaded if they
+    /// are applied to constants.  Filters that were replaced or removed are
+    /// never evaluated early.
+    ///
+    /// ```
+    /// # use minijinja::Environment;
+    /// let mut env = Environment::new();
+    /// env.set_filter_folding(true);
+    /// assert_eq!(env.render_str("{{ 'hello'|upper }}", ()).unwrap(), "HELLO");
+    /// ```
+    ///
+    /// This is disabled by default because the results are baked into the
+    /// loaded templates: replacing a builtin filter with
+    /// [`add_filter`](Self::add_filter) or setting a
+    /// [sandbox policy](Self::set_sandbox_policy) only affects templates
+    /// loaded afterwards.  Only enable it once the environment is fully
+    /// configured.  Changing this setting also only affects future templates
+    /// loaded.
+    pub fn set_filter_folding(&mut self, yes: bool) {
+        self.filter_folding = yes;
+    }
+
+    /// Returns the value of the filter folding flag.
+    pub fn filter_folding(&self) -> bool {
+        self.filter_folding
+    }
+
+    /// Checks if a filter may be evaluated while compiling templates.
+    pub(crate) fn is_foldable_filter(&self, name: &str) -> bool {
+        self.sandbox_policy.is_none() && self.foldable_filters.contains(name)
+    }
+
+    /// Returns the settings used for loading templates.
+    pub(crate) fn template_config(&self) -> &TemplateConfig {
+        &self.templates.template_config
+    }
+
     /// Changes the undefined behavior.
     ///
     /// This changes the runtime behavior of [`undefined`](Value::UNDEFINED) values in
@@ -572,6 +862,101 @@ impl<'source> Environment<'source> {
         self.formatter_is_default = false;
     }
 
+    /// Sets the translator used by `{% trans %}` blocks and the translation
+    /// functions.
+    ///
+    /// This also registers the `_`, `gettext` and `ngettext` global functions.
+    /// Without a translator `{% trans %}` blocks are rendered untranslated.  To
+    /// get the functions without translating use
+    /// [`NullTranslator`](crate::i18n::NullTranslator).  For more information
+    /// see the [`i18n`](crate::i18n) module.
+    #[cfg(feature = "i18n")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "i18n")))]
+    pub fn set_translator<T: crate::i18n::Translator>(&mut self, translator: T) {
+        self.translator = Some(Arc::new(translator));
+        self.add_global("_", Value::from_function(crate::i18n::gettext));
+        self.add_global("gettext", Value::from_function(crate::i18n::gettext));
+        self.add_global("ngettext", Value::from_function(crate::i18n::ngettext));
+    }
+
+    /// Returns the current translator.
+    #[cfg(feature = "i18n")]
+    pub(crate) fn translator(&self) -> Option<&dyn crate::i18n::Translator> {
+        self.translator.as_deref()
+    }
+
+    /// Sets the sandbox policy.
+    ///
+    /// The policy is consulted by the engine before attribute and item
+    /// lookups, calls and operations that grow values.  If it denies an
+    /// operation, rendering fails with
+    /// [`ErrorKind::SecurityError`](crate::ErrorKind::SecurityError).  For
+    /// more information see the [`sandbox`](crate::sandbox) module.
+    ///
+    /// While a policy is set, filters are no longer evaluated when templates
+    /// are compiled as that would bypass the policy.  As this only affects
+    /// templates loaded afterwards, the policy should be set before any
+    /// template is loaded.
+    pub fn set_sandbox_policy<P: crate::sandbox::SandboxPolicy>(&mut self, policy: P) {
+        self.sandbox_policy = Some(Arc::new(policy));
+        let mut names = BTreeMap::<usize, Vec<Cow<'source, str>>>::new();
+        for (name, value) in self.globals.iter() {
+            if let Some(obj) = value.as_object() {
+                names.entry(obj.addr()).or_default().push(name.clone());
+            }
+        }
+        self.sandbox_global_names = Arc::new(names);
+    }
+
+    /// Returns the current sandbox policy.
+    pub(crate) fn sandbox_policy(&self) -> Option<&dyn crate::sandbox::SandboxPolicy> {
+        self.sandbox_policy.as_deref()
+    }
+
+    /// Returns the names a global object is registered under.
+    ///
+    /// This only knows about globals while a sandbox policy is set.
+    pub(crate) fn sandbox_global_names(&self, obj: &DynObject) -> &[Cow<'source, str>] {
+        self.sandbox_global_names
+            .get(&obj.addr())
+            .map_or(&[], |names| names.as_slice())
+    }
+
+    /// Removes a global from the names of the sandbox.
+    fn forget_sandbox_global_name(&mut self, name: &str) {
+        let Some(addr) = self
+            .globals
+            .get(name)
+            .and_then(|value| value.as_object())
+            .map(|obj| obj.addr())
+        else {
+            return;
+        };
+        let names = Arc::make_mut(&mut self.sandbox_global_names);
+        if let Some(list) = names.get_mut(&addr) {
+            list.retain(|x| x != name);
+            if list.is_empty() {
+                names.remove(&addr);
+            }
+        }
+    }
+
+    /// Sets the instrumentation.
+    ///
+    /// The instrumentation is notified while templates are evaluated.  For
+    /// more information see the [`instrument`](crate::instrument) module.
+    pub fn set_instrumentation<I: crate::instrument::Instrumentation>(
+        &mut self,
+        instrumentation: I,
+    ) {
+        self.instrumentation = Some(Arc::new(instrumentation));
+    }
+
+    /// Returns the current instrumentation.
+    pub(crate) fn instrumentation(&self) -> Option<&dyn crate::instrument::Instrumentation> {
+        self.instrumentation.as_deref()
+    }
+
     /// Enable or disable the debug mode.
     ///
     /// When the debug mode is enabled the engine will dump out some of the
@@ -621,6 +1006,55 @@ impl<'source> Environment<'source> {
         self.fuel
     }
 
+    /// Sets the maximum number of bytes a template can render.
+    ///
+    /// By default the output is not limited (`None`).  When the limit is
+    /// exceeded rendering fails with
+    /// [`ErrorKind::SizeLimitExceeded`](crate::ErrorKind::SizeLimitExceeded).
+    /// The limit also applies to captured output (for instance of
+    /// `{% set %}` and `{% filter %}` blocks and macro calls).
+    ///
+    /// ```
+    /// # use minijinja::{Environment, ErrorKind};
+    /// let mut env = Environment::new();
+    /// env.set_output_limit(Some(100));
+    /// let err = env.render_str("{% for x in range(100) %}{{ x }}{% endfor %}", ()).unwrap_err();
+    /// assert_eq!(err.kind(), ErrorKind::SizeLimitExceeded);
+    /// ```
+    pub fn set_output_limit(&mut self, limit: Option<usize>) {
+        self.output_limit = limit;
+    }
+
+    /// Returns the configured output limit.
+    pub fn output_limit(&self) -> Option<usize> {
+        self.output_limit
+    }
+
+    /// Sets the maximum size of values created by the engine.
+    ///
+    /// This limits the size of strings (in bytes) and sequences (in items)
+    /// created by operators such as `*`, `+` and `~` and by builtin filters
+    /// like `join`, `replace` or `indent`.  Values passed to the engine are
+    /// not limited.  By default there is no limit (`None`).  When the limit
+    /// is exceeded rendering fails with
+    /// [`ErrorKind::SizeLimitExceeded`](crate::ErrorKind::SizeLimitExceeded).
+    ///
+    /// ```
+    /// # use minijinja::{Environment, ErrorKind};
+    /// let mut env = Environment::new();
+    /// env.set_size_limit(Some(1000));
+    /// let err = env.render_str("{{ 'x' * 100000000 }}", ()).unwrap_err();
+    /// assert_eq!(err.kind(), ErrorKind::SizeLimitExceeded);
+    /// ```
+    pub fn set_size_limit(&mut self, limit: Option<usize>) {
+        self.size_limit = limit;
+    }
+
+    /// Returns the configured size limit.
+    pub fn size_limit(&self) -> Option<usize> {
+        self.size_limit
+    }
+
     /// Sets the syntax for the environment.
     ///
     /// This setting is used whenever a template is loaded into the environment.
@@ -702,8 +1136,13 @@ impl<'source> Environment<'source> {
         attach_basic_debug_info(
             parse_expr(expr).map(|ast| {
                 let mut g = CodeGenerator::new("<expression>", expr);
+                if self.templates.template_config.python_tuples {
+                    g.enable_tuples();
+                }
                 g.compile_expr(&ast);
-                g.finish().0
+                let mut instructions = g.finish().0;
+                optimize(&mut instructions, Some(self));
+                instructions
             }),
             expr,
         )
@@ -721,11 +1160,14 @@ impl<'source> Environment<'source> {
         Rv: FunctionResult,
         Args: for<'a> FunctionArgs<'a>,
     {
-        Arc::make_mut(&mut self.filters).insert(name.into(), Value::from_function(f));
+        let name = name.into();
+        self.foldable_filters.remove(&*name);
+        Arc::make_mut(&mut self.filters).insert(name, Value::from_function(f));
     }
 
     /// Removes a filter by name.
     pub fn remove_filter(&mut self, name: &str) {
+        self.foldable_filters.remove(name);
         Arc::make_mut(&mut self.filters).remove(name);
     }
 
@@ -768,17 +1210,77 @@ impl<'source> Environment<'source> {
         self.add_global(name.into(), Value::from_function(f))
     }
 
+    /// Adds a new global function that returns a future.
+    ///
+    /// The function receives all arguments as a vector and returns a future
+    /// resolving to the return value.  Async functions can only be called from
+    /// templates rendered with [`Template::render_async`].  For more
+    /// information see [`Value::from_async_function`].
+    ///
+    /// ```
+    /// # use minijinja::Environment;
+    /// # let mut env = Environment::new();
+    /// use minijinja::value::{from_args, Value};
+    ///
+    /// env.add_async_function("get_config", |args: Vec<Value>| async move {
+    ///     let (key,): (String,) = from_args(&args)?;
+    ///     Ok(Value::from(format!("config value for {key}")))
+    /// });
+    /// ```
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn add_async_function<N, F, Fut>(&mut self, name: N, f: F)
+    where
+        N: Into<Cow<'source, str>>,
+        F: Fn(Vec<Value>) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = Result<Value, Error>> + Send + 'static,
+    {
+        self.add_global(name.into(), Value::from_async_function(f))
+    }
+
+    /// Adds a new filter function that returns a future.
+    ///
+    /// This works like [`add_async_function`](Self::add_async_function) but
+    /// registers a filter.  The value the filter is applied to is passed as
+    /// first argument.
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn add_async_filter<N, F, Fut>(&mut self, name: N, f: F)
+    where
+        N: Into<Cow<'source, str>>,
+        F: Fn(Vec<Value>) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = Result<Value, Error>> + Send + 'static,
+    {
+        let name = name.into();
+        self.foldable_filters.remove(&*name);
+        Arc::make_mut(&mut self.filters).insert(name, Value::from_async_function(f));
+    }
+
     /// Adds a global variable.
     pub fn add_global<N, V>(&mut self, name: N, value: V)
     where
         N: Into<Cow<'source, str>>,
         V: Into<Value>,
     {
-        Arc::make_mut(&mut self.globals).insert(name.into(), value.into());
+        let name = name.into();
+        let value = value.into();
+        if self.sandbox_policy.is_some() {
+            self.forget_sandbox_global_name(&name);
+            if let Some(obj) = value.as_object() {
+                Arc::make_mut(&mut self.sandbox_global_names)
+                    .entry(obj.addr())
+                    .or_default()
+                    .push(name.clone());
+            }
+        }
+        Arc::make_mut(&mut self.globals).insert(name, value);
     }
 
     /// Removes a global function or variable by name.
     pub fn remove_global(&mut self, name: &str) {
+        if self.sandbox_policy.is_some() {
+            self.forget_sandbox_global_name(name);
+        }
         Arc::make_mut(&mut self.globals).remove(name);
     }
 
@@ -829,17 +1331,17 @@ impl<'source> Environment<'source> {
         state: &State,
         out: &mut Output,
     ) -> Result<(), Error> {
-        match (self.undefined_behavior, &value.0) {
+        match (state.undefined_behavior(), &value.0) {
             // this intentionally does not check for SilentUndefined.  SilentUndefined is
             // exclusively used in the missing else condition of an if expression to match
             // Jinja2 behavior.  Those go straight to the formatter.
             (
                 UndefinedBehavior::Strict | UndefinedBehavior::SemiStrict,
-                &ValueRepr::Undefined(UndefinedType::Default),
-            ) => Err(Error::from(ErrorKind::UndefinedError)),
+                &ValueRepr::Undefined(UndefinedType::Default, _),
+            ) => Err(value.undefined_error()),
             _ => {
                 if self.formatter_is_default {
-                    write_escaped(out, state.auto_escape(), value)
+                    self.write_escaped(out, state.auto_escape(), value)
                 } else {
                     (self.formatter)(out, state, value)
                 }
@@ -847,6 +1349,33 @@ impl<'source> Environment<'source> {
         }
     }
 
+    /// Writes a value with the given auto escaping.
+    ///
+    /// Unlike [`write_escaped`] this supports registered custom formats.
+    pub(crate) fn write_escaped(
+        &self,
+        out: &mut Output,
+        auto_escape: AutoEscape,
+        value: &Value,
+    ) -> Result<(), Error> {
+        if let AutoEscape::Custom(name) = auto_escape {
+            if let Some(f) = self.custom_auto_escapes.get(name) {
+                if value.is_safe() {
+                    return write!(out, "{value}").map_err(Error::from);
+                }
+                return f(out, value);
+            }
+        }
+        write_escaped(out, auto_escape, value)
+    }
+
+    /// Looks up the name of a registered custom auto escape format.
+    pub(crate) fn custom_auto_escape_name(&self, name: &str) -> Option<&'static str> {
+        self.custom_auto_escapes
+            .get_key_value(name)
+            .map(|(name, _)| *name)
+    }
+
     /// Performs a template path join.
     pub(crate) fn join_template_path<'s>(&self, name: &'s str, parent: &'s str) -> Cow<'s, str> {
         match self.path_join_callback {
@@ -3,6 +3,7 @@ use std::fmt;
 use std::sync::Arc;
 
 use crate::compiler::tokens::Span;
+use crate::value::UndefinedOrigin;
 
 /// Represents template errors.
 ///
@@ -52,6 +53,7 @@ struct ErrorRepr {
     source: Option<Arc<dyn std::error::Error + Send + Sync>>,
     #[cfg(feature = "debug")]
     debug_info: Option<Arc<crate::debug::DebugInfo>>,
+    undefined_origin: Option<UndefinedOrigin>,
 }
 
 impl fmt::Debug for Error {
@@ -130,6 +132,12 @@ pub enum ErrorKind {
     CannotUnpack,
     /// Failed writing output.
     WriteFailure,
+    /// The sandbox policy denied an operation.
+    SecurityError,
+    /// The output or a value exceeded the configured size limits.
+    SizeLimitExceeded,
+    /// The render was cancelled or ran past its deadline.
+    Cancelled,
     /// Engine ran out of fuel
     #[cfg(feature = "fuel")]
     OutOfFuel,
@@ -139,6 +147,9 @@ pub enum ErrorKind {
     /// An unknown block was called
     #[cfg(feature = "multi_template")]
     UnknownBlock,
+    /// Bytecode could not be loaded because it's corrupted or incompatible.
+    #[cfg(feature = "bytecode")]
+    InvalidBytecode,
 }
 
 impl ErrorKind {
@@ -162,6 +173,9 @@ impl ErrorKind {
             ErrorKind::EvalBlock => "could not render block",
             ErrorKind::CannotUnpack => "cannot unpack",
             ErrorKind::WriteFailure => "failed to write output",
+            ErrorKind::SecurityError => "security error",
+            ErrorKind::SizeLimitExceeded => "size limit exceeded",
+            ErrorKind::Cancelled => "cancelled",
             #[cfg(feature = "deserialization")]
             ErrorKind::CannotDeserialize => "cannot deserialize",
             #[cfg(feature = "fuel")]
@@ -170,6 +184,8 @@ impl ErrorKind {
             ErrorKind::InvalidDelimiter => "invalid custom delimiters",
             #[cfg(feature = "multi_template")]
             ErrorKind::UnknownBlock => "unknown block",
+            #[cfg(feature = "bytecode")]
+            ErrorKind::InvalidBytecode => "invalid bytecode",
         }
     }
 }
@@ -213,6 +229,7 @@ impl Error {
                 source: None,
                 #[cfg(feature = "debug")]
                 debug_info: None,
+                undefined_origin: None,
             }),
         }
     }
@@ -265,6 +282,14 @@ impl Error {
         self.repr.detail = Some(d.into());
     }
 
+    pub(crate) fn undefined_origin(&self) -> Option<UndefinedOrigin> {
+        self.repr.undefined_origin
+    }
+
+    pub(crate) fn set_undefined_origin(&mut self, origin: Option<UndefinedOrigin>) {
+        self.repr.undefined_origin = origin;
+    }
+
     /// Returns the filename of the template that caused the error.
     pub fn name(&self) -> Option<&str> {
         self.repr.name.as_deref()
@@ -386,6 +411,7 @@ impl From<ErrorKind> for Error {
                 source: None,
                 #[cfg(feature = "debug")]
                 debug_info: None,
+                undefined_origin: None,
             }),
         }
     }
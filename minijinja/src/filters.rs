@@ -137,6 +137,9 @@ pub fn safe(v: String) -> Value {
 /// this filter escapes with the format that is native to the format or HTML
 /// otherwise.  This means that if the auto escape setting is set to
 /// `Json` for instance then this filter will serialize to JSON instead.
+/// Likewise it quotes for the shell in `Shell` mode, escapes for CSS in
+/// `Css` mode, percent encodes in `Url` mode and escapes LaTeX in `Latex`
+/// mode.
 pub fn escape(state: &State, v: &Value) -> Result<Value, Error> {
     if v.is_safe() {
         return Ok(v.clone());
@@ -160,9 +163,9 @@ pub fn escape(state: &State, v: &Value) -> Result<Value, Error> {
     if matches!(auto_escape, AutoEscape::Custom(_)) {
         // The formatter reads the escape mode from state, so temporarily
         // override it to ensure |e honors the computed escape mode even when
-        // auto-escape is disabled in the current scope. The default formatter
-        // also errors on custom auto-escape formats, so we must route through
-        // the environment formatter here.
+        // auto-escape is disabled in the current scope. Custom auto-escape
+        // formats are either registered on the environment or handled by a
+        // custom formatter, so we must route through the environment here.
         ok!(state.with_auto_escape(auto_escape, |state| {
             state.env().format(v, state, &mut out)
         }));
@@ -177,13 +180,17 @@ mod builtins {
     use super::*;
 
     use crate::error::ErrorKind;
-    use crate::format_utils::{format_filter, FormatStyle};
-    use crate::utils::{safe_sort, splitn_whitespace};
+    use crate::format_utils::{format_filter, format_with_limit, FormatStyle};
+    use crate::urlize::{is_valid_scheme, UrlizeOptions};
+    use crate::utils::{safe_sort, splitn_whitespace, HtmlEscape};
     use crate::value::merge_object::{MergeDict, MergeSeq};
     use crate::value::ops::{self, as_f64, LenIterWrap};
-    use crate::value::{Enumerator, Kwargs, Object, ObjectRepr, Rest, ValueKind, ValueRepr};
+    use crate::value::{
+        ArgType, Enumerator, Kwargs, Object, ObjectRepr, Rest, ValueKind, ValueRepr,
+    };
     use std::borrow::Cow;
     use std::cmp::Ordering;
+    use std::collections::BTreeSet;
     use std::fmt::Write;
     use std::mem;
     use std::sync::Arc;
@@ -249,6 +256,35 @@ mod builtins {
         }
     }
 
+    /// Centers the value in a field of a given width.
+    ///
+    /// The width defaults to 80 characters.  If the value is already wider
+    /// it's returned unchanged.
+    ///
+    /// ```jinja
+    /// <pre>{{ "Title"|center(20) }}</pre>
+    /// ```
+    #[cfg_attr(docsrs, doc(cfg(feature = "builtins")))]
+    pub fn center(state: &State, value: &str, width: Option<usize>) -> Result<String, Error> {
+        let width = width.unwrap_or(80);
+        let len = value.chars().count();
+        if width <= len {
+            return Ok(value.to_string());
+        }
+        let margin = width - len;
+        ok!(ops::check_string_size(
+            value.len() + margin,
+            state.env().size_limit()
+        ));
+        // same rounding as Python's str.center
+        let left = margin / 2 + (margin & width & 1);
+        let mut rv = String::with_capacity(value.len() + margin);
+        rv.extend(std::iter::repeat(' ').take(left));
+        rv.push_str(value);
+        rv.extend(std::iter::repeat(' ').take(margin - left));
+        Ok(rv)
+    }
+
     /// Does a string replace.
     ///
     /// It replaces all occurrences of the first parameter with the second.
@@ -278,6 +314,32 @@ mod builtins {
         v.replace(from, to)
     }
 
+    /// The `replace` filter as registered on the environment.
+    ///
+    /// Unlike [`replace`] this also enforces the size limit of the environment.
+    pub(crate) fn limited_replace(
+        state: &State,
+        v: Cow<'_, str>,
+        from: Cow<'_, str>,
+        to: Cow<'_, str>,
+    ) -> Result<String, Error> {
+        if let Some(size_limit) = state.env().size_limit() {
+            if to.len() > from.len() {
+                let count = if from.is_empty() {
+                    v.chars().count() + 1
+                } else {
+                    v.matches(from.as_ref()).count()
+                };
+                ok!(ops::check_string_size(
+                    v.len()
+                        .saturating_add(count.saturating_mul(to.len() - from.len())),
+                    Some(size_limit)
+                ));
+            }
+        }
+        Ok(replace(state, v, from, to))
+    }
+
     /// Returns the "length" of the value
     ///
     /// By default this filter is also registered under the alias `count`.
@@ -320,17 +382,62 @@ mod builtins {
         }
     }
 
+    /// Returns a positional argument or the keyword argument of the same name.
+    fn arg_or_kwarg<'a, T>(
+        arg: Option<T>,
+        kwargs: &'a Kwargs,
+        name: &'a str,
+    ) -> Result<Option<T>, Error>
+    where
+        Option<T>: ArgType<'a, Output = Option<T>>,
+    {
+        match arg {
+            Some(arg) => Ok(Some(arg)),
+            None => kwargs.get(name),
+        }
+    }
+
+    /// Looks up the value of an `attribute` argument on an item.
+    ///
+    /// Strings are treated as dotted paths, other values as keys.
+    fn lookup_attribute(item: &Value, attr: &Value) -> Value {
+        match attr.as_str() {
+            Some(path) => item.get_path_or_default(path, &Value::UNDEFINED),
+            None => item.get_item(attr).unwrap_or(Value::UNDEFINED),
+        }
+    }
+
     /// Dict sorting functionality.
     ///
     /// This filter works like `|items` but sorts the pairs by key first.
     ///
-    /// The filter accepts a few keyword arguments:
+    /// The filter accepts a few arguments which can be passed positionally
+    /// or as keyword arguments:
     ///
     /// * `case_sensitive`: set to `true` to make the sorting of strings case sensitive.
     /// * `by`: set to `"value"` to sort by value. Defaults to `"key"`.
     /// * `reverse`: set to `true` to sort in reverse.
+    ///
+    /// ```jinja
+    /// {% for key, value in mydict|dictsort(false, "value") %}
+    ///   {{ key }}: {{ value }}
+    /// {% endfor %}
+    /// ```
     #[cfg_attr(docsrs, doc(cfg(feature = "builtins")))]
     pub fn dictsort(v: &Value, kwargs: Kwargs) -> Result<Value, Error> {
+        dictsort_with_args(v, None, None, None, kwargs)
+    }
+
+    /// The `dictsort` filter as registered on the environment.
+    ///
+    /// Unlike [`dictsort`] this also accepts the arguments positionally.
+    pub(crate) fn dictsort_with_args(
+        v: &Value,
+        case_sensitive: Option<bool>,
+        by: Option<&str>,
+        reverse: Option<bool>,
+        kwargs: Kwargs,
+    ) -> Result<Value, Error> {
         if v.kind() != ValueKind::Map {
             return Err(Error::new(
                 ErrorKind::InvalidOperation,
@@ -338,9 +445,19 @@ mod builtins {
             ));
         }
 
-        let by_value = matches!(ok!(kwargs.get("by")), Some("value"));
-        let case_sensitive = ok!(kwargs.get::<Option<bool>>("case_sensitive")).unwrap_or(false);
-        let reverse = ok!(kwargs.get::<Option<bool>>("reverse")).unwrap_or(false);
+        let by_value = match ok!(arg_or_kwarg(by, &kwargs, "by")) {
+            None | Some("key") => false,
+            Some("value") => true,
+            Some(_) => {
+                return Err(Error::new(
+                    ErrorKind::InvalidOperation,
+                    "can only sort by either 'key' or 'value'",
+                ))
+            }
+        };
+        let case_sensitive =
+            ok!(arg_or_kwarg(case_sensitive, &kwargs, "case_sensitive")).unwrap_or(false);
+        let reverse = ok!(arg_or_kwarg(reverse, &kwargs, "reverse")).unwrap_or(false);
         let mut rv: Vec<_> = ok!(v.try_iter())
             .map(|key| (key.clone(), v.get_item(&key).unwrap_or(Value::UNDEFINED)))
             .collect();
@@ -439,8 +556,42 @@ mod builtins {
     /// ```jinja
     /// {{ "Foo Bar Baz" | join(", ") }} -> foo, bar, baz
     /// ```
+    ///
+    /// The separator can also be passed as keyword argument `d`.  With the
+    /// `attribute` argument an attribute (or dotted path) of each item is
+    /// joined instead:
+    ///
+    /// ```jinja
+    /// {{ users|join(", ", attribute="username") }}
+    /// ```
     #[cfg_attr(docsrs, doc(cfg(feature = "builtins")))]
     pub fn join(val: &Value, joiner: Option<Cow<'_, str>>) -> Result<String, Error> {
+        join_impl(None, val, joiner, None)
+    }
+
+    /// The `join` filter as registered on the environment.
+    ///
+    /// Unlike [`join`] this also enforces the size limit of the environment
+    /// and accepts the `d` and `attribute` arguments.
+    pub(crate) fn limited_join(
+        state: &State,
+        val: &Value,
+        joiner: Option<Cow<'_, str>>,
+        attribute: Option<Value>,
+        kwargs: Kwargs,
+    ) -> Result<String, Error> {
+        let joiner = ok!(arg_or_kwarg(joiner, &kwargs, "d"));
+        let attribute = ok!(arg_or_kwarg(attribute, &kwargs, "attribute"));
+        ok!(kwargs.assert_all_used());
+        join_impl(state.env().size_limit(), val, joiner, attribute)
+    }
+
+    fn join_impl(
+        size_limit: Option<usize>,
+        val: &Value,
+        joiner: Option<Cow<'_, str>>,
+        attribute: Option<Value>,
+    ) -> Result<String, Error> {
         if val.is_undefined() || val.is_none() {
             return Ok(String::new());
         }
@@ -459,11 +610,16 @@ mod builtins {
             if idx > 0 {
                 rv.push_str(joiner);
             }
+            let item = match attribute {
+                Some(ref attr) => lookup_attribute(&item, attr),
+                None => item,
+            };
             if let Some(s) = item.as_str() {
                 rv.push_str(s);
             } else {
                 write!(rv, "{item}").ok();
             }
+            ok!(ops::check_string_size(rv.len(), size_limit));
         }
         Ok(rv)
     }
@@ -579,7 +735,7 @@ mod builtins {
     #[cfg_attr(docsrs, doc(cfg(feature = "builtins")))]
     pub fn int(state: &State, value: &Value) -> Result<Value, Error> {
         match &value.0 {
-            ValueRepr::Undefined(_) | ValueRepr::None => {
+            ValueRepr::Undefined(..) | ValueRepr::None => {
                 ok!(state.undefined_behavior().assert_value_not_undefined(value));
                 Ok(Value::from(0))
             }
@@ -615,7 +771,7 @@ mod builtins {
     #[cfg_attr(docsrs, doc(cfg(feature = "builtins")))]
     pub fn float(state: &State, value: &Value) -> Result<Value, Error> {
         match &value.0 {
-            ValueRepr::Undefined(_) | ValueRepr::None => {
+            ValueRepr::Undefined(..) | ValueRepr::None => {
                 ok!(state.undefined_behavior().assert_value_not_undefined(value));
                 Ok(Value::from(0.0))
             }
@@ -641,13 +797,48 @@ mod builtins {
     /// ```jinja
     /// {{ range(10)|sum }} -> 45
     /// ```
+    ///
+    /// The `attribute` argument sums up an attribute (or dotted path) of
+    /// each item and `start` is the value to start with (defaults to `0`):
+    ///
+    /// ```jinja
+    /// Total: {{ items|sum(attribute="price", start=10) }}
+    /// ```
     #[cfg_attr(docsrs, doc(cfg(feature = "builtins")))]
     pub fn sum(state: &State, values: Value) -> Result<Value, Error> {
-        let mut rv = Value::from(0);
+        sum_impl(state, values, None, None)
+    }
+
+    /// The `sum` filter as registered on the environment.
+    ///
+    /// Unlike [`sum`] this also accepts the `attribute` and `start` arguments.
+    pub(crate) fn sum_with_args(
+        state: &State,
+        values: Value,
+        attribute: Option<Value>,
+        start: Option<Value>,
+        kwargs: Kwargs,
+    ) -> Result<Value, Error> {
+        let attribute = ok!(arg_or_kwarg(attribute, &kwargs, "attribute"));
+        let start = ok!(arg_or_kwarg(start, &kwargs, "start"));
+        ok!(kwargs.assert_all_used());
+        sum_impl(state, values, attribute, start)
+    }
+
+    fn sum_impl(
+        state: &State,
+        values: Value,
+        attribute: Option<Value>,
+        start: Option<Value>,
+    ) -> Result<Value, Error> {
+        let mut rv = start.unwrap_or(Value::from(0));
         let iter = ok!(state.undefined_behavior().try_iter(values));
         for value in iter {
+            let value = match attribute {
+                Some(ref attr) => lookup_attribute(&value, attr),
+                None => value,
+            };
             if value.is_undefined() {
-                ok!(state.undefined_behavior().handle_undefined(false));
                 continue;
             } else if !value.is_number() {
                 return Err(Error::new(
@@ -655,7 +846,7 @@ mod builtins {
                     format!("can only sum numbers, got {}", value.kind()),
                 ));
             }
-            rv = ok!(ops::add(&rv, &value));
+            rv = ok!(ops::add(&rv, &value, None));
         }
 
         Ok(rv)
@@ -678,21 +869,62 @@ mod builtins {
     /// Round the number to a given precision.
     ///
     /// Round the number to a given precision. The first parameter specifies the
-    /// precision (default is 0).
+    /// precision (default is 0), the second the rounding method:
+    ///
+    /// * `"common"`: rounds either up or down (default)
+    /// * `"ceil"`: always rounds up
+    /// * `"floor"`: always rounds down
+    ///
+    /// Both can also be passed as keyword arguments `precision` and `method`.
     ///
     /// ```jinja
     /// {{ 42.55|round }}
     ///   -> 43.0
+    /// {{ 42.55|round(1, "floor") }}
+    ///   -> 42.5
     /// ```
     #[cfg_attr(docsrs, doc(cfg(feature = "builtins")))]
     pub fn round(value: Value, precision: Option<i32>) -> Result<Value, Error> {
+        round_impl(value, precision, f64::round)
+    }
+
+    /// The `round` filter as registered on the environment.
+    ///
+    /// Unlike [`round`] this also accepts the `method` argument.
+    pub(crate) fn round_with_args(
+        value: Value,
+        precision: Option<i32>,
+        method: Option<&str>,
+        kwargs: Kwargs,
+    ) -> Result<Value, Error> {
+        let precision = ok!(arg_or_kwarg(precision, &kwargs, "precision"));
+        let method: fn(f64) -> f64 = match ok!(arg_or_kwarg(method, &kwargs, "method")) {
+            None | Some("common") => f64::round,
+            Some("ceil") => f64::ceil,
+            Some("floor") => f64::floor,
+            Some(_) => {
+                return Err(Error::new(
+                    ErrorKind::InvalidOperation,
+                    "method must be 'common', 'ceil' or 'floor'",
+                ))
+            }
+        };
+        ok!(kwargs.assert_all_used());
+        round_impl(value, precision, method)
+    }
+
+    fn round_impl(
+        value: Value,
+        precision: Option<i32>,
+        method: fn(f64) -> f64,
+    ) -> Result<Value, Error> {
         match value.0 {
             ValueRepr::I64(_) | ValueRepr::I128(_) | ValueRepr::U64(_) | ValueRepr::U128(_) => {
                 Ok(value)
             }
             ValueRepr::F64(val) => {
                 let x = 10f64.powi(precision.unwrap_or(0));
-                Ok(Value::from((x * val).round() / x))
+                Ok(Value::from(method(x * val) / x))
             }
             _ => Err(Error::new(
                 ErrorKind::InvalidOperation,
@@ -744,7 +976,10 @@ mod builtins {
     pub fn last(value: Value) -> Result<Value, Error> {
         if let Some(s) = value.as_str() {
             Ok(s.chars().next_back().map_or(Value::UNDEFINED, Value::from))
-        } else if matches!(value.kind(), ValueKind::Seq | ValueKind::Iterable) {
+        } else if matches!(
+            value.kind(),
+            ValueKind::Seq | ValueKind::Tuple | ValueKind::Iterable
+        ) {
             let rev = ok!(value.reverse());
             let mut iter = ok!(rev.try_iter());
             Ok(iter.next().unwrap_or_default())
@@ -756,17 +991,90 @@ mod builtins {
         }
     }
 
+    fn min_or_max_with_args(
+        state: &State,
+        value: Value,
+        case_sensitive: Option<bool>,
+        attribute: Option<Value>,
+        kwargs: Kwargs,
+        wanted: Ordering,
+    ) -> Result<Value, Error> {
+        let case_sensitive =
+            ok!(arg_or_kwarg(case_sensitive, &kwargs, "case_sensitive")).unwrap_or(true);
+        let attribute = ok!(arg_or_kwarg(attribute, &kwargs, "attribute"));
+        ok!(kwargs.assert_all_used());
+        min_or_max(state, value, case_sensitive, attribute, wanted)
+    }
+
+    fn min_or_max(
+        state: &State,
+        value: Value,
+        case_sensitive: bool,
+        attribute: Option<Value>,
+        wanted: Ordering,
+    ) -> Result<Value, Error> {
+        let iter = ok!(state.undefined_behavior().try_iter(value).map_err(|err| {
+            Error::new(ErrorKind::InvalidOperation, "cannot convert value to list").with_source(err)
+        }));
+
+        let mut rv: Option<(Value, Value)> = None;
+        for item in iter {
+            let key = match attribute {
+                Some(ref attr) => lookup_attribute(&item, attr),
+                None => item.clone(),
+            };
+            let replace = match rv {
+                Some((_, ref best)) => match cmp_helper(&key, best, case_sensitive, false) {
+                    // like `Iterator::max` the last of equal items wins
+                    Ordering::Equal => case_sensitive && wanted == Ordering::Greater,
+                    ordering => ordering == wanted,
+                },
+                None => true,
+            };
+            if replace {
+                rv = Some((item, key));
+            }
+        }
+        Ok(rv.map_or(Value::UNDEFINED, |(item, _)| item))
+    }
+
     /// Returns the smallest item from an iterable.
     ///
     /// ```jinja
     /// {{ [1, 2, 3, 4]|min }} -> 1
     /// ```
+    ///
+    /// Strings are compared case-sensitive unless `case_sensitive` is set to
+    /// `false`.  With the `attribute` argument the item with the smallest
+    /// attribute (or dotted path) is returned:
+    ///
+    /// ```jinja
+    /// {{ users|min(attribute="age") }}
+    /// ```
     #[cfg_attr(docsrs, doc(cfg(feature = "builtins")))]
     pub fn min(state: &State, value: Value) -> Result<Value, Error> {
-        let iter = ok!(state.undefined_behavior().try_iter(value).map_err(|err| {
-            Error::new(ErrorKind::InvalidOperation, "cannot convert value to list").with_source(err)
-        }));
-        Ok(iter.min().unwrap_or(Value::UNDEFINED))
+        min_or_max(state, value, true, None, Ordering::Less)
+    }
+
+    /// The `min` filter as registered on the environment.
+    ///
+    /// Unlike [`min`] this also accepts the `case_sensitive` and
+    /// `attribute` arguments.
+    pub(crate) fn min_with_args(
+        state: &State,
+        value: Value,
+        case_sensitive: Option<bool>,
+        attribute: Option<Value>,
+        kwargs: Kwargs,
+    ) -> Result<Value, Error> {
+        min_or_max_with_args(
+            state,
+            value,
+            case_sensitive,
+            attribute,
+            kwargs,
+            Ordering::Less,
+        )
     }
 
     /// Returns the largest item from an iterable.
@@ -774,12 +1082,32 @@ mod builtins {
     /// ```jinja
     /// {{ [1, 2, 3, 4]|max }} -> 4
     /// ```
+    ///
+    /// This accepts the same arguments as [`min`].
     #[cfg_attr(docsrs, doc(cfg(feature = "builtins")))]
     pub fn max(state: &State, value: Value) -> Result<Value, Error> {
-        let iter = ok!(state.undefined_behavior().try_iter(value).map_err(|err| {
-            Error::new(ErrorKind::InvalidOperation, "cannot convert value to list").with_source(err)
-        }));
-        Ok(iter.max().unwrap_or(Value::UNDEFINED))
+        min_or_max(state, value, true, None, Ordering::Greater)
+    }
+
+    /// The `max` filter as registered on the environment.
+    ///
+    /// Unlike [`max`] this also accepts the `case_sensitive` and
+    /// `attribute` arguments.
+    pub(crate) fn max_with_args(
+        state: &State,
+        value: Value,
+        case_sensitive: Option<bool>,
+        attribute: Option<Value>,
+        kwargs: Kwargs,
+    ) -> Result<Value, Error> {
+        min_or_max_with_args(
+            state,
+            value,
+            case_sensitive,
+            attribute,
+            kwargs,
+            Ordering::Greater,
+        )
     }
 
     /// Returns the sorted version of the given list.
@@ -913,7 +1241,8 @@ mod builtins {
     /// ```
     ///
     /// If you pass it a second argument it’s used to fill missing values on the
-    /// last iteration.
+    /// last iteration.  The arguments can also be passed as keyword arguments
+    /// `slices` and `fill_with`.
     #[cfg_attr(docsrs, doc(cfg(feature = "builtins")))]
     pub fn slice(
         state: &State,
@@ -954,11 +1283,32 @@ mod builtins {
         Ok(Value::from(rv))
     }
 
+    /// The `slice` filter as registered on the environment.
+    ///
+    /// Unlike [`slice`] this also accepts the `slices` and `fill_with`
+    /// arguments as keyword arguments.
+    pub(crate) fn slice_with_args(
+        state: &State,
+        value: Value,
+        count: Option<usize>,
+        fill_with: Option<Value>,
+        kwargs: Kwargs,
+    ) -> Result<Value, Error> {
+        let count = match count {
+            Some(count) => count,
+            None => ok!(kwargs.get::<usize>("slices")),
+        };
+        let fill_with = ok!(arg_or_kwarg(fill_with, &kwargs, "fill_with"));
+        ok!(kwargs.assert_all_used());
+        slice(state, value, count, fill_with)
+    }
+
     /// Batch items.
     ///
     /// This filter works pretty much like `slice` just the other way round. It
     /// returns a list of lists with the given number of items. If you provide a
-    /// second parameter this is used to fill up missing items.
+    /// second parameter this is used to fill up missing items.  The arguments
+    /// can also be passed as keyword arguments `linecount` and `fill_with`.
     ///
     /// ```jinja
     /// <table>
@@ -1006,6 +1356,26 @@ mod builtins {
         Ok(Value::from(rv))
     }
 
+    /// The `batch` filter as registered on the environment.
+    ///
+    /// Unlike [`batch`] this also accepts the `linecount` and `fill_with`
+    /// arguments as keyword arguments.
+    pub(crate) fn batch_with_args(
+        state: &State,
+        value: Value,
+        count: Option<usize>,
+        fill_with: Option<Value>,
+        kwargs: Kwargs,
+    ) -> Result<Value, Error> {
+        let count = match count {
+            Some(count) => count,
+            None => ok!(kwargs.get::<usize>("linecount")),
+        };
+        let fill_with = ok!(arg_or_kwarg(fill_with, &kwargs, "fill_with"));
+        ok!(kwargs.assert_all_used());
+        batch(state, value, count, fill_with)
+    }
+
     /// Dumps a value to JSON.
     ///
     /// This filter is only available if the `json` feature is enabled.  The resulting
@@ -1101,6 +1471,45 @@ mod builtins {
     /// ```
     #[cfg_attr(docsrs, doc(cfg(all(feature = "builtins"))))]
     pub fn indent(
+        value: String,
+        width: Option<usize>,
+        indent_first_line: Option<bool>,
+        indent_blank_lines: Option<bool>,
+        kwargs: Kwargs,
+    ) -> Result<String, Error> {
+        indent_impl(
+            None,
+            value,
+            width,
+            indent_first_line,
+            indent_blank_lines,
+            kwargs,
+        )
+    }
+
+    /// The `indent` filter as registered on the environment.
+    ///
+    /// Unlike [`indent`] this also enforces the size limit of the environment.
+    pub(crate) fn limited_indent(
+        state: &State,
+        value: String,
+        width: Option<usize>,
+        indent_first_line: Option<bool>,
+        indent_blank_lines: Option<bool>,
+        kwargs: Kwargs,
+    ) -> Result<String, Error> {
+        indent_impl(
+            state.env().size_limit(),
+            value,
+            width,
+            indent_first_line,
+            indent_blank_lines,
+            kwargs,
+        )
+    }
+
+    fn indent_impl(
+        size_limit: Option<usize>,
         mut value: String,
         width: Option<usize>,
         indent_first_line: Option<bool>,
@@ -1131,6 +1540,11 @@ mod builtins {
         ok!(kwargs.assert_all_used());
 
         strip_trailing_newline(&mut value);
+        let lines = value.bytes().filter(|&c| c == b'\n').count() + 1;
+        ok!(ops::check_string_size(
+            value.len().saturating_add(lines.saturating_mul(width)),
+            size_limit
+        ));
         let indent_with = " ".repeat(width);
         let mut output = String::new();
         let mut iterator = value.split('\n');
@@ -1192,7 +1606,7 @@ mod builtins {
             Ok(rv)
         } else {
             match &value.0 {
-                ValueRepr::None | ValueRepr::Undefined(_) => Ok("".into()),
+                ValueRepr::None | ValueRepr::Undefined(..) => Ok("".into()),
                 ValueRepr::Bytes(b) => Ok(percent_encoding::percent_encode(b, SET).to_string()),
                 ValueRepr::String(..) | ValueRepr::SmallStr(_) => Ok(
                     percent_encoding::utf8_percent_encode(value.as_str().unwrap(), SET).to_string(),
@@ -1212,6 +1626,9 @@ mod builtins {
     ) -> Result<Vec<Value>, Error> {
         let mut rv = vec![];
         let test = if let Some(test_name) = test_name {
+            if let Some(policy) = state.env().sandbox_policy() {
+                ok!(crate::sandbox::check_test(policy, &test_name));
+            }
             Some(ok!(state
                 .env()
                 .get_test(&test_name)
@@ -1341,6 +1758,12 @@ mod builtins {
     /// ```jinja
     /// Users on this page: {{ titles|map('lower')|join(', ') }}
     /// ```
+    ///
+    /// Keyword arguments are passed on to the filter as well:
+    ///
+    /// ```jinja
+    /// {{ prices|map('round', method='ceil')|list }}
+    /// ```
     #[cfg_attr(docsrs, doc(cfg(feature = "builtins")))]
     pub fn map(
         state: &State,
@@ -1350,10 +1773,10 @@ mod builtins {
         let mut rv = Vec::with_capacity(value.len().unwrap_or(0));
 
         // attribute mapping
-        let (args, kwargs): (&[Value], Kwargs) = crate::value::from_args(&args)?;
+        let (pos_args, kwargs): (&[Value], Kwargs) = crate::value::from_args(&args)?;
 
         if let Some(attr) = ok!(kwargs.get::<Option<Value>>("attribute")) {
-            if !args.is_empty() {
+            if !pos_args.is_empty() {
                 return Err(Error::from(ErrorKind::TooManyArguments));
             }
             let default = if kwargs.has("default") {
@@ -1382,14 +1805,17 @@ mod builtins {
             return Ok(rv);
         }
 
-        // filter mapping
-        let filter_name = ok!(args
+        // filter mapping, keyword arguments are passed on to the filter
+        let filter_name = ok!(pos_args
             .first()
             .ok_or_else(|| Error::new(ErrorKind::InvalidOperation, "filter name is required")));
         let filter_name = ok!(filter_name.as_str().ok_or_else(|| {
             Error::new(ErrorKind::InvalidOperation, "filter name must be a string")
         }));
 
+        if let Some(policy) = state.env().sandbox_policy() {
+            ok!(crate::sandbox::check_filter(policy, filter_name));
+        }
         let filter = ok!(state
             .env()
             .get_filter(filter_name)
@@ -1547,13 +1973,26 @@ mod builtins {
     /// Like the [`sort`] filter this operates case-insensitive by default.
     /// For example, if a list has the US state codes `["CA", "NY", "ca"]``,
     /// the resulting list will have `["CA", "NY"]`.  This can be disabled by
-    /// passing `case_sensitive=True`.
+    /// passing `case_sensitive=True`.  Both arguments can also be passed
+    /// positionally as `unique(case_sensitive, attribute)`.
     #[cfg_attr(docsrs, doc(cfg(feature = "builtins")))]
     pub fn unique(state: &State, values: Value, kwargs: Kwargs) -> Result<Value, Error> {
-        use std::collections::BTreeSet;
+        unique_with_args(state, values, None, None, kwargs)
+    }
 
-        let attr = ok!(kwargs.get::<Option<&str>>("attribute"));
-        let case_sensitive = ok!(kwargs.get::<Option<bool>>("case_sensitive")).unwrap_or(false);
+    /// The `unique` filter as registered on the environment.
+    ///
+    /// Unlike [`unique`] this also accepts the arguments positionally.
+    pub(crate) fn unique_with_args(
+        state: &State,
+        values: Value,
+        case_sensitive: Option<bool>,
+        attribute: Option<Value>,
+        kwargs: Kwargs,
+    ) -> Result<Value, Error> {
+        let case_sensitive =
+            ok!(arg_or_kwarg(case_sensitive, &kwargs, "case_sensitive")).unwrap_or(false);
+        let attr = ok!(arg_or_kwarg(attribute, &kwargs, "attribute"));
         ok!(kwargs.assert_all_used());
 
         let mut rv = Vec::new();
@@ -1561,8 +2000,8 @@ mod builtins {
 
         let iter = ok!(state.undefined_behavior().try_iter(values));
         for item in iter {
-            let value_to_compare = if let Some(attr) = attr {
-                item.get_path_or_default(attr, &Value::UNDEFINED)
+            let value_to_compare = if let Some(ref attr) = attr {
+                lookup_attribute(&item, attr)
             } else {
                 item.clone()
             };
@@ -1619,7 +2058,7 @@ mod builtins {
             Ok(Value::from_object(MergeDict::new(all_values)))
         } else if all_values
             .iter()
-            .all(|v| matches!(v.kind(), ValueKind::Seq))
+            .all(|v| matches!(v.kind(), ValueKind::Seq | ValueKind::Tuple))
         {
             Ok(Value::from_object(MergeSeq::new(all_values)))
         } else {
@@ -1741,6 +2180,173 @@ mod builtins {
     pub fn format(format_str: &str, format_args: Rest<Value>) -> Result<String, Error> {
         format_filter(FormatStyle::Printf, format_str, &format_args)
     }
+
+    /// The `format` filter as registered on the environment.
+    ///
+    /// Unlike [`format`] this also enforces the size limit of the environment.
+    pub(crate) fn limited_format(
+        state: &State,
+        format_str: &str,
+        format_args: Rest<Value>,
+    ) -> Result<String, Error> {
+        format_with_limit(
+            FormatStyle::Printf,
+            format_str,
+            &format_args,
+            state.env().size_limit(),
+        )
+    }
+
+    /// Enforces HTML escaping.
+    ///
+    /// Unlike [`escape`] this also escapes values that are already marked
+    /// as safe.  Like [`escape`] it uses the escaping format of the current
+    /// auto escape mode.
+    ///
+    /// ```jinja
+    /// {{ "<b>"|safe|forceescape }}
+    ///   -> &lt;b&gt;
+    /// ```
+    #[cfg_attr(docsrs, doc(cfg(feature = "builtins")))]
+    pub fn forceescape(state: &State, v: &Value) -> Result<Value, Error> {
+        escape(state, &Value::from(v.to_string()))
+    }
+
+    /// Creates an SGML/XML attribute string based on the items in a map.
+    ///
+    /// All values that are neither `none` nor undefined are automatically
+    /// escaped.  Keys that contain whitespace, `/`, `>` or `=` are rejected.
+    /// If `autospace` is enabled (the default) a space is placed in front of
+    /// the result if it's not empty.
+    ///
+    /// The result is marked as safe if HTML auto escaping is enabled.
+    ///
+    /// ```jinja
+    /// <ul{{ {"class": "my_list", "missing": none, "id": "list-" ~ 42}|xmlattr }}>
+    /// ...
+    /// </ul>
+    /// ```
+    #[cfg_attr(docsrs, doc(cfg(feature = "builtins")))]
+    pub fn xmlattr(state: &State, d: &Value, autospace: Option<bool>) -> Result<Value, Error> {
+        let iter = ok!(d
+            .as_object()
+            .filter(|_| d.kind() == ValueKind::Map)
+            .and_then(|obj| obj.try_iter_pairs())
+            .ok_or_else(|| Error::new(
+                ErrorKind::InvalidOperation,
+                "xmlattr filter expects a map"
+            )));
+        let mut rv = String::new();
+        for (key, value) in iter {
+            if value.is_none() || value.is_undefined() {
+                continue;
+            }
+            let key = key.to_string();
+            if key
+                .chars()
+                .any(|c| c.is_ascii_whitespace() || matches!(c, '\x0b' | '/' | '>' | '='))
+            {
+                return Err(Error::new(
+                    ErrorKind::InvalidOperation,
+                    format!("invalid character in attribute name: {key:?}"),
+                ));
+            }
+            if !rv.is_empty() || autospace.unwrap_or(true) {
+                rv.push(' ');
+            }
+            write!(rv, "{}=\"", HtmlEscape(&key)).ok();
+            if value.is_safe() {
+                write!(rv, "{value}").ok();
+            } else {
+                write!(rv, "{}", HtmlEscape(&value.to_string())).ok();
+            }
+            rv.push('"');
+        }
+        ok!(ops::check_string_size(rv.len(), state.env().size_limit()));
+        Ok(html_result(state, rv))
+    }
+
+    /// Converts URLs in plain text into clickable links.
+    ///
+    /// The text is HTML escaped first unless it's already marked as safe.
+    /// Links to URLs get a `rel` attribute which defaults to `noopener`.
+    /// The following keyword arguments can be provided:
+    ///
+    /// * `trim_url_limit`: shortens the displayed URLs to this many
+    ///   characters.  Can also be passed as positional argument.
+    /// * `nofollow`: adds `nofollow` to the `rel` attribute.
+    /// * `target`: adds a `target` attribute to the links.
+    /// * `rel`: additional values for the `rel` attribute.
+    /// * `extra_schemes`: a list of additional URI scheme prefixes (such as
+    ///   `ftp://`) that should be linked.
+    ///
+    /// The result is marked as safe if HTML auto escaping is enabled.
+    ///
+    /// ```jinja
+    /// {{ "Visit https://example.com/"|urlize(target="_blank") }}
+    ///   -> Visit <a href="https://example.com/" rel="noopener" target="_blank">https://example.com/</a>
+    /// ```
+    #[cfg_attr(docsrs, doc(cfg(feature = "builtins")))]
+    pub fn urlize(
+        state: &State,
+        value: &Value,
+        trim_url_limit: Option<usize>,
+        kwargs: Kwargs,
+    ) -> Result<Value, Error> {
+        let trim_url_limit = match trim_url_limit {
+            Some(limit) => Some(limit),
+            None => ok!(kwargs.get::<Option<usize>>("trim_url_limit")),
+        };
+        let nofollow = ok!(kwargs.get::<Option<bool>>("nofollow")).unwrap_or(false);
+        let target = ok!(kwargs.get::<Option<&str>>("target"));
+        let rel = ok!(kwargs.get::<Option<&str>>("rel"));
+        let extra_schemes = ok!(kwargs.get::<Option<Vec<String>>>("extra_schemes"));
+        ok!(kwargs.assert_all_used());
+
+        let extra_schemes = extra_schemes.unwrap_or_default();
+        if let Some(scheme) = extra_schemes.iter().find(|x| !is_valid_scheme(x)) {
+            return Err(Error::new(
+                ErrorKind::InvalidOperation,
+                format!("{scheme:?} is not a valid URI scheme prefix"),
+            ));
+        }
+        let mut rel_values = rel
+            .unwrap_or("")
+            .split_whitespace()
+            .collect::<BTreeSet<_>>();
+        if nofollow {
+            rel_values.insert("nofollow");
+        }
+        rel_values.insert("noopener");
+        let rel = rel_values.into_iter().collect::<Vec<_>>().join(" ");
+
+        let text = value.to_string();
+        let text = if value.is_safe() {
+            text
+        } else {
+            crate::urlize::escape(&text)
+        };
+        let rv = crate::urlize::urlize(
+            &text,
+            &UrlizeOptions {
+                trim_url_limit,
+                rel: Some(&rel),
+                target,
+                extra_schemes: &extra_schemes,
+            },
+        );
+        ok!(ops::check_string_size(rv.len(), state.env().size_limit()));
+        Ok(html_result(state, rv))
+    }
+
+    /// Marks generated markup as safe if HTML auto escaping is enabled.
+    fn html_result(state: &State, rv: String) -> Value {
+        if matches!(state.auto_escape(), AutoEscape::Html) {
+            Value::from_safe_string(rv)
+        } else {
+            Value::from(rv)
+        }
+    }
 }
 
 #[cfg(feature = "builtins")]
@@ -1,6 +1,7 @@
 use std::fmt::{Display, LowerExp};
 use std::num::FpCategory;
 
+use crate::value::ops::check_string_size;
 use crate::value::ValueKind;
 use crate::{Error, ErrorKind, Value};
 
@@ -32,13 +33,40 @@ pub fn format_filter(
     style: FormatStyle,
     format_str: &str,
     args: &[Value],
+) -> Result<String, Error> {
+    format_with_limit(style, format_str, args, None)
+}
+
+/// Like [`format_filter`] but fails if the result exceeds the size limit.
+///
+/// Widths and precisions are checked against the limit before they are
+/// applied so that no oversized strings are allocated.
+pub(crate) fn format_with_limit(
+    style: FormatStyle,
+    format_str: &str,
+    args: &[Value],
+    size_limit: Option<usize>,
 ) -> Result<String, Error> {
     match style {
-        FormatStyle::Printf => printf_style::format(format_str, args),
-        FormatStyle::StrFormat => str_format_style::format(format_str, args),
+        FormatStyle::Printf => printf_style::format(format_str, args, size_limit, false),
+        FormatStyle::StrFormat => str_format_style::format(format_str, args, size_limit),
     }
 }
 
+/// Implements the `%` operator.
+///
+/// This works like printf-style [`format_with_limit`] but like in Python it
+/// fails if not all positional arguments are converted.  A single mapping
+/// does not have to be used.
+#[cfg(feature = "builtins")]
+pub(crate) fn format_operator(
+    format_str: &str,
+    args: &[Value],
+    size_limit: Option<usize>,
+) -> Result<String, Error> {
+    printf_style::format(format_str, args, size_limit, true)
+}
+
 // Token produced by the format string parser
 #[derive(Debug)]
 enum Token<'src> {
@@ -144,6 +172,12 @@ struct FormatSpec {
 }
 
 impl FormatSpec {
+    // Fails if the width or precision of this spec exceed the size limit
+    fn check_size_limit(&self, size_limit: Option<usize>) -> Result<(), Error> {
+        let requested = self.width.max(self.precision).unwrap_or(0);
+        check_string_size(requested, size_limit)
+    }
+
     // Format the given value according to this spec
     fn format(&self, val: &Value) -> Result<String, Error> {
         if let Ok(boolean) = bool::try_from(val.clone()) {
@@ -1124,7 +1158,12 @@ mod printf_style {
     // Do printf-style formatting. Parse the format string and apply values from args
     // to the fields found in the string, by formatting the value according to the
     // spec found in the field.
-    pub(super) fn format(format_str: &str, args: &[Value]) -> Result<String, Error> {
+    pub(super) fn format(
+        format_str: &str,
+        args: &[Value],
+        size_limit: Option<usize>,
+        convert_all: bool,
+    ) -> Result<String, Error> {
         let mut input = Tokenizer::new(format_str, FormatStyle::Printf);
         let mut result = String::new();
         let mut arg_index = 0;
@@ -1173,10 +1212,21 @@ mod printf_style {
                             return Err(missing_arg_err(format_spec.location));
                         }
                     };
+                    ok!(format_spec.check_size_limit(size_limit));
                     result.push_str(&ok!(format_spec.format(&arg)));
+                    ok!(check_string_size(result.len(), size_limit));
                 }
             }
         }
+        if convert_all
+            && arg_index < args.len()
+            && !(args.len() == 1 && args[0].kind() == ValueKind::Map)
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidOperation,
+                "not all arguments converted during string formatting",
+            ));
+        }
         Ok(result)
     }
 }
@@ -1472,7 +1522,7 @@ mod str_format_style {
             };
         }
         if curr.is_undefined() {
-            Err(Error::from(ErrorKind::UndefinedError))
+            Err(curr.undefined_error())
         } else {
             Ok(curr)
         }
@@ -1481,7 +1531,11 @@ mod str_format_style {
     // Do str.format style formatting. Parse the format string and apply values from
     // args to the fields found in the string, by formatting the value according to
     // the spec found in the field.
-    pub(super) fn format(format_str: &str, args: &[Value]) -> Result<String, Error> {
+    pub(super) fn format(
+        format_str: &str,
+        args: &[Value],
+        size_limit: Option<usize>,
+    ) -> Result<String, Error> {
         let mut input = Tokenizer::new(format_str, FormatStyle::StrFormat);
         let mut result = String::new();
 
@@ -1562,7 +1616,9 @@ mod str_format_style {
 
                     // apply the spec to the replacement value, and insert the
                     // formatted result into final string
+                    ok!(format_spec.check_size_limit(size_limit));
                     result.push_str(&ok!(format_spec.format(&arg)));
+                    ok!(check_string_size(result.len(), size_limit));
                 }
             }
         }
@@ -116,7 +116,7 @@ pub(crate) struct BoxedFunction(Arc<FuncFunc>, #[cfg(feature = "debug")] &'stati
 /// method to abstract over different types of functions.
 ///
 /// Functions which at the very least accept the [`State`] by reference as first
-/// parameter and additionally up to 4 further parameters.  They share much of
+/// parameter and additionally up to 6 further parameters.  They share much of
 /// their interface with [`filters`](crate::filters).
 ///
 /// A function can return any of the following types:
@@ -245,6 +245,8 @@ tuple_impls! { A B }
 tuple_impls! { A B C }
 tuple_impls! { A B C D }
 tuple_impls! { A B C D E }
+tuple_impls! { A B C D E F }
+tuple_impls! { A B C D E F G }
 
 impl BoxedFunction {
     /// Creates a new boxed filter.
@@ -264,6 +266,17 @@ impl BoxedFunction {
         )
     }
 
+    /// Makes the function show up like `f` in debug output.
+    #[cfg(feature = "builtins")]
+    pub(crate) fn with_debug_name_of<G>(self, f: G) -> BoxedFunction {
+        let _ = f;
+        BoxedFunction(
+            self.0,
+            #[cfg(feature = "debug")]
+            std::any::type_name::<G>(),
+        )
+    }
+
     /// Invokes the function.
     pub fn invoke(&self, state: &State, args: &[Value]) -> Result<Value, Error> {
         (self.0)(state, args)
@@ -321,12 +334,39 @@ mod builtins {
     /// </ul>
     /// ```
     ///
-    /// This function will refuse to create ranges over 10.000 items.
+    /// This function will refuse to create ranges over 100.000 items.  A
+    /// [sandbox policy](crate::sandbox::SandboxPolicy::max_range) can lower
+    /// this limit further.
     #[cfg_attr(docsrs, doc(cfg(feature = "builtins")))]
     pub fn range(lower: isize, upper: Option<isize>, step: Option<isize>) -> Result<Value, Error> {
+        range_impl(None, lower, upper, step)
+    }
+
+    /// The `range` function as registered on the environment.
+    ///
+    /// Unlike [`range`] this also enforces the limit of the sandbox policy.
+    pub(crate) fn sandboxed_range(
+        state: &State,
+        lower: isize,
+        upper: Option<isize>,
+        step: Option<isize>,
+    ) -> Result<Value, Error> {
+        range_impl(state.env().sandbox_policy(), lower, upper, step)
+    }
+
+    fn range_impl(
+        policy: Option<&dyn crate::sandbox::SandboxPolicy>,
+        lower: isize,
+        upper: Option<isize>,
+        step: Option<isize>,
+    ) -> Result<Value, Error> {
         fn to_result<I: ExactSizeIterator<Item = isize> + Send + Sync + Clone + 'static>(
+            policy: Option<&dyn crate::sandbox::SandboxPolicy>,
             i: I,
         ) -> Result<Value, Error> {
+            if let Some(policy) = policy {
+                ok!(crate::sandbox::check_range(policy, i.len()));
+            }
             if i.len() > 100000 {
                 Err(Error::new(
                     ErrorKind::InvalidOperation,
@@ -343,7 +383,7 @@ mod builtins {
         };
 
         let Some(step) = step else {
-            return to_result(rng);
+            return to_result(policy, rng);
         };
 
         match step.cmp(&0) {
@@ -351,7 +391,7 @@ mod builtins {
                 ErrorKind::InvalidOperation,
                 "cannot create range with step of 0",
             )),
-            Ordering::Greater => to_result(rng.step_by(step as usize)),
+            Ordering::Greater => to_result(policy, rng.step_by(step as usize)),
             Ordering::Less => {
                 // handle negative steps
                 debug_assert!(step < 0);
@@ -367,7 +407,7 @@ mod builtins {
                 };
 
                 let iter = (0..len).map(move |i| start + (i as isize) * step);
-                to_result(iter)
+                to_result(policy, iter)
             }
         }
     }
@@ -395,7 +435,7 @@ mod builtins {
         let mut rv = match value {
             None => ValueMap::default(),
             Some(value) => match value.0 {
-                ValueRepr::Undefined(_) => ValueMap::default(),
+                ValueRepr::Undefined(..) => ValueMap::default(),
                 ValueRepr::Object(obj) if obj.repr() == ObjectRepr::Map => {
                     obj.try_iter_pairs().into_iter().flatten().collect()
                 }
@@ -438,6 +478,26 @@ mod builtins {
         }
     }
 
+    /// Flushes the output written so far.
+    ///
+    /// When the template is rendered into a writer with
+    /// [`render_captured_to`](crate::Template::render_captured_to) the writer
+    /// is flushed, with [`render_iter`](crate::Template::render_iter)
+    /// the output so far is yielded as chunk.  Otherwise this does nothing.
+    /// Flushing also has no effect while output is captured, for instance
+    /// within macros, `{% set %}` or `{% filter %}` blocks.
+    ///
+    /// ```jinja
+    /// <head>...</head>
+    /// {{ flush() }}
+    /// <body>{{ expensive_content() }}</body>
+    /// ```
+    #[cfg_attr(docsrs, doc(cfg(feature = "builtins")))]
+    pub fn flush(state: &State) -> Value {
+        state.flush_requested.set(true);
+        Value::from_safe_string(String::new())
+    }
+
     /// Creates a new container that allows attribute assignment using the `{% set %}` tag.
     ///
     /// ```jinja
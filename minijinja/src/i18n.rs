@@ -0,0 +1,426 @@
+//! Support for translating templates.
+//!
+//! When the `i18n` feature is enabled the engine understands Jinja2's
+//! `{% trans %}` tag.  The actual translations are provided by a
+//! [`Translator`] which is registered on the environment with
+//! [`Environment::set_translator`](crate::Environment::set_translator).  This
+//! also installs the `_`, `gettext` and `ngettext` functions.
+//!
+//! ```jinja
+//! {% trans %}Hello {{ user }}!{% endtrans %}
+//! {% trans count=items|length %}
+//!   One item in your cart.
+//! {% pluralize %}
+//!   {{ count }} items in your cart.
+//! {% endtrans %}
+//! {{ _("Hello %(user)s!", user=user) }}
+//! {{ ngettext("%(num)s apple", "%(num)s apples", apples|length) }}
+//! ```
+//!
+//! Only plain variable references are allowed within a translatable block.
+//! Expressions need to be bound in the tag (`{% trans name=user.name %}`).
+//! The message ids use Python style `%(name)s` placeholders which get
+//! replaced with the auto escaped values of the variables after
+//! translation.  The `trimmed` modifier (`{% trans trimmed %}`) collapses
+//! the whitespace around line breaks in the message.
+//!
+//! Without a translator registered, `{% trans %}` blocks are rendered as
+//! written.  The [`NullTranslator`] does the same but also installs the
+//! functions.
+//!
+//! # Translating
+//!
+//! ```
+//! use std::borrow::Cow;
+//! use minijinja::{context, Environment, State};
+//! use minijinja::i18n::Translator;
+//!
+//! struct German;
+//!
+//! impl Translator for German {
+//!     fn gettext<'a>(&'a self, _state: &State, message: &'a str) -> Cow<'a, str> {
+//!         match message {
+//!             "Hello %(user)s!" => Cow::Borrowed("Hallo %(user)s!"),
+//!             _ => Cow::Borrowed(message),
+//!         }
+//!     }
+//! }
+//!
+//! let mut env = Environment::new();
+//! env.set_translator(German);
+//! let rv = env.render_str(
+//!     "{% trans %}Hello {{ user }}!{% endtrans %}",
+//!     context! { user => "Peter" },
+//! ).unwrap();
+//! assert_eq!(rv, "Hallo Peter!");
+//! ```
+//!
+//! The [`State`] is passed to the translator so it can for instance pick the
+//! language based on a variable in the context.
+//!
+//! # Extracting Messages
+//!
+//! A [`Catalog`] collects the translatable messages of templates together
+//! with their source locations and can write them out as PO template:
+//!
+//! ```
+//! # use minijinja::Environment;
+//! use minijinja::i18n::Catalog;
+//!
+//! let mut env = Environment::new();
+//! env.add_template("hello.html", "{% trans %}Hello {{ user }}!{% endtrans %}").unwrap();
+//! let catalog = Catalog::from_environment(&env).unwrap();
+//! assert_eq!(catalog.messages()[0].id(), "Hello %(user)s!");
+//! println!("{}", catalog.to_pot());
+//! ```
+use std::borrow::Cow;
+use std::fmt::Write;
+
+use crate::compiler::ast;
+use crate::compiler::visitor::{walk_expr, walk_stmt, Visitor};
+use crate::error::{Error, ErrorKind};
+use crate::output::Output;
+use crate::value::{Kwargs, Value};
+use crate::vm::State;
+use crate::{Environment, Template};
+
+/// Provides translations for messages.
+///
+/// Only [`gettext`](Self::gettext) needs to be implemented.  The default
+/// implementation of [`ngettext`](Self::ngettext) picks the singular form for
+/// a count of one and the plural form otherwise and translates it with
+/// `gettext`.
+pub trait Translator: Send + Sync + 'static {
+    /// Translates a message.
+    fn gettext<'a>(&'a self, state: &State, message: &'a str) -> Cow<'a, str>;
+
+    /// Translates a message with a plural form.
+    fn ngettext<'a>(
+        &'a self,
+        state: &State,
+        singular: &'a str,
+        plural: &'a str,
+        n: i64,
+    ) -> Cow<'a, str> {
+        self.gettext(state, if n == 1 { singular } else { plural })
+    }
+}
+
+/// A translator that leaves all messages untranslated.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullTranslator;
+
+impl Translator for NullTranslator {
+    fn gettext<'a>(&'a self, _state: &State, message: &'a str) -> Cow<'a, str> {
+        Cow::Borrowed(message)
+    }
+}
+
+fn gettext_impl<'a>(state: &'a State, message: &'a str) -> Cow<'a, str> {
+    match state.env().translator() {
+        Some(translator) => translator.gettext(state, message),
+        None => Cow::Borrowed(message),
+    }
+}
+
+fn ngettext_impl<'a>(state: &'a State, singular: &'a str, plural: &'a str, n: i64) -> Cow<'a, str> {
+    match state.env().translator() {
+        Some(translator) => translator.ngettext(state, singular, plural, n),
+        None if n == 1 => Cow::Borrowed(singular),
+        None => Cow::Borrowed(plural),
+    }
+}
+
+/// Replaces the `%(name)s` placeholders in a translated message.
+///
+/// The values are formatted like regular output which means they are auto
+/// escaped whereas the message itself is considered safe.
+fn format_message(
+    state: &State,
+    message: &str,
+    lookup: &dyn Fn(&str) -> Value,
+) -> Result<Value, Error> {
+    let mut rv = String::with_capacity(message.len());
+    let mut out = Output::new(&mut rv);
+    let mut rest = message;
+    while let Some(idx) = rest.find('%') {
+        out.write_str(&rest[..idx]).ok();
+        rest = &rest[idx + 1..];
+        if let Some(after) = rest.strip_prefix('%') {
+            out.write_str("%").ok();
+            rest = after;
+        } else if let Some((name, after)) = rest
+            .strip_prefix('(')
+            .and_then(|x| x.split_once(")s"))
+            .filter(|(name, _)| !name.contains(')'))
+        {
+            ok!(state.env().format(&lookup(name), state, &mut out));
+            rest = after;
+        } else {
+            // a lone percent sign is kept as is
+            out.write_str("%").ok();
+        }
+    }
+    out.write_str(rest).ok();
+    Ok(Value::from_safe_string(rv))
+}
+
+fn plural_count(value: &Value) -> Result<i64, Error> {
+    i64::try_from(value.clone()).map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidOperation,
+            format!("plural count must be an integer, got {}", value.kind()),
+        )
+    })
+}
+
+/// Translates and formats the message of a `{% trans %}` block.
+pub(crate) fn translate_block(
+    state: &State,
+    singular: &Value,
+    plural: Option<(&Value, &Value)>,
+    variables: &Value,
+) -> Result<Value, Error> {
+    let singular = singular.as_str().unwrap_or_default();
+    let message = match plural {
+        Some((plural, count)) => ngettext_impl(
+            state,
+            singular,
+            plural.as_str().unwrap_or_default(),
+            ok!(plural_count(count)),
+        ),
+        None => gettext_impl(state, singular),
+    };
+    if variables.len() == Some(0) {
+        return Ok(Value::from_safe_string(message.into_owned()));
+    }
+    format_message(state, &message, &|name| {
+        variables.get_attr(name).unwrap_or_default()
+    })
+}
+
+/// Translates a message.
+///
+/// Keyword arguments are used to fill in `%(name)s` placeholders.
+pub(crate) fn gettext(state: &State, message: &str, kwargs: Kwargs) -> Result<Value, Error> {
+    let message = gettext_impl(state, message);
+    format_message(state, &message, &|name| {
+        kwargs
+            .peek::<Option<Value>>(name)
+            .ok()
+            .flatten()
+            .unwrap_or_default()
+    })
+}
+
+/// Translates a message with a plural form.
+///
+/// Keyword arguments are used to fill in `%(name)s` placeholders, the count
+/// is also available as `num`.
+pub(crate) fn ngettext(
+    state: &State,
+    singular: &str,
+    plural: &str,
+    n: Value,
+    kwargs: Kwargs,
+) -> Result<Value, Error> {
+    let message = ngettext_impl(state, singular, plural, ok!(plural_count(&n)));
+    format_message(
+        state,
+        &message,
+        &|name| match kwargs.peek::<Option<Value>>(name).ok().flatten() {
+            Some(value) => value,
+            None if name == "num" => n.clone(),
+            None => Value::UNDEFINED,
+        },
+    )
+}
+
+/// A translatable message found in templates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    id: String,
+    plural: Option<String>,
+    locations: Vec<(String, usize)>,
+}
+
+impl Message {
+    /// Returns the message id (the singular form).
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns the plural form of the message if it has one.
+    pub fn plural(&self) -> Option<&str> {
+        self.plural.as_deref()
+    }
+
+    /// Returns the template names and lines where the message is used.
+    pub fn locations(&self) -> &[(String, usize)] {
+        &self.locations
+    }
+}
+
+/// A collection of translatable messages extracted from templates.
+///
+/// Messages are found in `{% trans %}` blocks and in calls to `_`, `gettext`
+/// and `ngettext` with constant string arguments.  Messages with the same
+/// id and plural form are merged and keep all their locations.
+#[derive(Debug, Clone, Default)]
+pub struct Catalog {
+    messages: Vec<Message>,
+}
+
+impl Catalog {
+    /// Creates an empty catalog.
+    pub fn new() -> Catalog {
+        Catalog::default()
+    }
+
+    /// Creates a catalog from all templates loaded into an environment.
+    ///
+    /// Fails with the syntax error of the first template that cannot be
+    /// parsed.
+    pub fn from_environment(env: &Environment<'_>) -> Result<Catalog, Error> {
+        let mut templates = env.templates().collect::<Vec<_>>();
+        templates.sort_by(|a, b| a.0.cmp(b.0));
+        let mut rv = Catalog::new();
+        for (_, tmpl) in templates {
+            ok!(rv.add_template(&tmpl));
+        }
+        Ok(rv)
+    }
+
+    /// Adds the messages of a template.
+    ///
+    /// If the template cannot be parsed, the syntax error is returned and
+    /// the catalog is left unchanged.
+    pub fn add_template(&mut self, tmpl: &Template<'_, '_>) -> Result<(), Error> {
+        let ast = ok!(tmpl.parse_ast());
+        self.add_ast(tmpl.name(), &ast);
+        Ok(())
+    }
+
+    /// Adds the messages of a parsed template.
+    ///
+    /// This is useful for templates that are not loaded into an environment
+    /// and were parsed with [`Environment::parse`].
+    pub fn add_ast(&mut self, name: &str, ast: &ast::Stmt<'_>) {
+        let mut extractor = Extractor {
+            catalog: self,
+            name,
+        };
+        extractor.visit_stmt(ast);
+    }
+
+    /// Returns the messages in the order they were found.
+    pub fn messages(&self) -> &[Message] {
+        &self.messages
+    }
+
+    fn add_message(&mut self, name: &str, line: usize, id: &str, plural: Option<&str>) {
+        let location = (name.to_string(), line);
+        if let Some(msg) = self
+            .messages
+            .iter_mut()
+            .find(|msg| msg.id == id && msg.plural.as_deref() == plural)
+        {
+            if !msg.locations.contains(&location) {
+                msg.locations.push(location);
+            }
+        } else {
+            self.messages.push(Message {
+                id: id.to_string(),
+                plural: plural.map(|x| x.to_string()),
+                locations: vec![location],
+            });
+        }
+    }
+
+    /// Writes the catalog as PO template (`.pot`) file.
+    pub fn to_pot(&self) -> String {
+        let mut rv = String::new();
+        for (idx, msg) in self.messages.iter().enumerate() {
+            if idx > 0 {
+                rv.push('\n');
+            }
+            rv.push_str("#:");
+            for (name, line) in &msg.locations {
+                write!(rv, " {name}:{line}").ok();
+            }
+            rv.push('\n');
+            if msg.id.contains("%(") || msg.plural.as_ref().is_some_and(|x| x.contains("%(")) {
+                rv.push_str("#, python-format\n");
+            }
+            write_po_string(&mut rv, "msgid", &msg.id);
+            match msg.plural {
+                Some(ref plural) => {
+                    write_po_string(&mut rv, "msgid_plural", plural);
+                    rv.push_str("msgstr[0] \"\"\nmsgstr[1] \"\"\n");
+                }
+                None => rv.push_str("msgstr \"\"\n"),
+            }
+        }
+        rv
+    }
+}
+
+fn write_po_string(out: &mut String, keyword: &str, value: &str) {
+    let escape = |s: &str| {
+        s.replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\t', "\\t")
+            .replace('\n', "\\n")
+    };
+    if value.trim_end_matches('\n').contains('\n') {
+        writeln!(out, "{keyword} \"\"").ok();
+        for line in value.split_inclusive('\n') {
+            writeln!(out, "\"{}\"", escape(line)).ok();
+        }
+    } else {
+        writeln!(out, "{keyword} \"{}\"", escape(value)).ok();
+    }
+}
+
+struct Extractor<'c, 'n> {
+    catalog: &'c mut Catalog,
+    name: &'n str,
+}
+
+impl<'a> Visitor<'a> for Extractor<'_, '_> {
+    fn visit_stmt(&mut self, stmt: &ast::Stmt<'a>) {
+        if let ast::Stmt::Trans(trans) = stmt {
+            self.catalog.add_message(
+                self.name,
+                trans.span().start_line as usize,
+                &trans.singular,
+                trans.plural.as_deref(),
+            );
+        }
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &ast::Expr<'a>) {
+        if let ast::Expr::Call(call) = expr {
+            let strings = call
+                .args
+                .iter()
+                .map_while(|arg| match arg {
+                    ast::CallArg::Pos(ast::Expr::Const(c)) => c.value.as_str(),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            let line = expr.span().start_line as usize;
+            match (&call.expr, &strings[..]) {
+                (ast::Expr::Var(var), [id, ..]) if var.id == "_" || var.id == "gettext" => {
+                    self.catalog.add_message(self.name, line, id, None)
+                }
+                (ast::Expr::Var(var), [id, plural, ..]) if var.id == "ngettext" => {
+                    self.catalog.add_message(self.name, line, id, Some(plural))
+                }
+                _ => {}
+            }
+        }
+        walk_expr(self, expr);
+    }
+}
@@ -0,0 +1,138 @@
+//! Hooks for observing template execution.
+//!
+//! An [`Instrumentation`] registered with
+//! [`Environment::set_instrumentation`](crate::Environment::set_instrumentation)
+//! is notified by the engine while templates are evaluated: before every
+//! instruction, when variable scopes are pushed and popped and when macros,
+//! includes and blocks are entered and left.  Every callback receives the
+//! current [`State`] which makes it possible to inspect variables and the
+//! position in the template.  This can be used to build execution tracers or
+//! debuggers that pause at breakpoints.
+//!
+//! ```
+//! use std::sync::{Arc, Mutex};
+//! use minijinja::{Environment, State};
+//! use minijinja::instrument::{Instrumentation, Location};
+//!
+//! #[derive(Default)]
+//! struct LineTracer {
+//!     lines: Arc<Mutex<Vec<usize>>>,
+//! }
+//!
+//! impl Instrumentation for LineTracer {
+//!     fn on_instruction(&self, _state: &State, location: &Location) {
+//!         let mut lines = self.lines.lock().unwrap();
+//!         if let Some(line) = location.line() {
+//!             if lines.last() != Some(&line) {
+//!                 lines.push(line);
+//!             }
+//!         }
+//!     }
+//! }
+//!
+//! let tracer = LineTracer::default();
+//! let lines = tracer.lines.clone();
+//! let mut env = Environment::new();
+//! env.set_instrumentation(tracer);
+//! env.render_str("{% set x = 1 %}\n{{ x }}", ()).unwrap();
+//! assert_eq!(*lines.lock().unwrap(), vec![1, 2]);
+//! ```
+//!
+//! Callbacks are invoked synchronously from the evaluation loop, so blocking
+//! in a callback pauses the render.  Instrumentation slows down rendering
+//! considerably and is meant for development only.
+use crate::compiler::tokens::Span;
+use crate::vm::State;
+
+/// The position of an instruction that is about to be executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub(crate) pc: u32,
+    pub(crate) line: Option<usize>,
+    pub(crate) span: Option<Span>,
+}
+
+impl Location {
+    /// Returns the index of the instruction.
+    ///
+    /// The index is only meaningful together with the template that is
+    /// executing (see [`State::name`]).
+    pub fn pc(&self) -> u32 {
+        self.pc
+    }
+
+    /// Returns the line of the instruction.
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+
+    /// Returns the span of the instruction.
+    ///
+    /// Spans are only recorded if the `debug` feature is enabled.
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+}
+
+/// Receives callbacks while templates are evaluated.
+///
+/// All methods have default implementations that do nothing, so
+/// implementations only need to override the events they care about.  Enter
+/// and exit callbacks are always invoked in pairs, even if the evaluation in
+/// between failed.
+pub trait Instrumentation: Send + Sync + 'static {
+    /// Invoked before an instruction is executed.
+    fn on_instruction(&self, state: &State<'_, '_>, location: &Location) {
+        let _ = (state, location);
+    }
+
+    /// Invoked after a variable scope was pushed.
+    ///
+    /// Scopes are created by loops, `{% with %}` blocks and similar
+    /// constructs as well as when entering blocks and macros.
+    fn on_frame_push(&self, state: &State<'_, '_>) {
+        let _ = state;
+    }
+
+    /// Invoked before a variable scope is popped.
+    ///
+    /// If the evaluation fails, this is also invoked for the scopes that
+    /// are left behind by the failure.
+    fn on_frame_pop(&self, state: &State<'_, '_>) {
+        let _ = state;
+    }
+
+    /// Invoked when the macro `name` is entered.
+    ///
+    /// The state is the state of the macro invocation.
+    fn on_macro_enter(&self, state: &State<'_, '_>, name: &str) {
+        let _ = (state, name);
+    }
+
+    /// Invoked when the macro `name` is left.
+    fn on_macro_exit(&self, state: &State<'_, '_>, name: &str) {
+        let _ = (state, name);
+    }
+
+    /// Invoked when the template `name` is included.
+    fn on_include_enter(&self, state: &State<'_, '_>, name: &str) {
+        let _ = (state, name);
+    }
+
+    /// Invoked when the included template `name` finished.
+    fn on_include_exit(&self, state: &State<'_, '_>, name: &str) {
+        let _ = (state, name);
+    }
+
+    /// Invoked when the block `name` is entered.
+    ///
+    /// This is also invoked for `super()` calls.
+    fn on_block_enter(&self, state: &State<'_, '_>, name: &str) {
+        let _ = (state, name);
+    }
+
+    /// Invoked when the block `name` is left.
+    fn on_block_exit(&self, state: &State<'_, '_>, name: &str) {
+        let _ = (state, name);
+    }
+}
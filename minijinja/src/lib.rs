@@ -179,6 +179,16 @@
 //! - `urlencode`: When enabled the `urlencode` filter is added as builtin filter.
 //! - `loop_controls`: enables the `{% break %}` and `{% continue %}` loop control flow
 //!   tags.
+//! - `async`: enables [`Template::render_async`] and the ability to register functions
+//!   and filters that return futures.
+//! - `bytecode`: enables [`Template::to_bytecode`] and
+//!   [`Environment::add_template_bytecode`] to cache compiled templates.
+//! - `i18n`: enables the `{% trans %}` tag, the `_`, `gettext` and `ngettext`
+//!   functions and the [`i18n`] module for translations and message extraction.
+//! - `tracing`: emits [`tracing`](https://docs.rs/tracing) spans for template
+//!   compilation, loader invocations, renders, includes and macro calls.  The
+//!   spans carry the template name and the consumed fuel, failures are recorded
+//!   on the span with the error kind.
 //!
 //! Performance and memory related features:
 //!
@@ -216,19 +226,32 @@ mod expression;
 mod format_utils;
 mod output;
 mod template;
+#[cfg(feature = "multi_template")]
+mod template_graph;
+#[cfg(feature = "tracing")]
+mod trace;
+#[cfg(feature = "builtins")]
+mod urlize;
 mod utils;
 mod vendor;
 mod vm;
 
 pub mod filters;
 pub mod functions;
+#[cfg(feature = "i18n")]
+#[cfg_attr(docsrs, doc(cfg(feature = "i18n")))]
+pub mod i18n;
+pub mod instrument;
+pub mod lint;
+pub mod profile;
+pub mod sandbox;
 pub mod syntax;
 pub mod tests;
 pub mod value;
 
 mod loader;
 
-pub use loader::path_loader;
+pub use loader::{path_loader, FileSystemLoader};
 
 #[cfg(feature = "debug")]
 mod debug;
@@ -238,7 +261,9 @@ pub use self::environment::Environment;
 pub use self::error::{Error, ErrorKind};
 pub use self::expression::Expression;
 pub use self::output::Output;
-pub use self::template::{Captured, Template};
+pub use self::template::{Captured, RenderIter, RenderOptions, Template};
+#[cfg(feature = "multi_template")]
+pub use self::template_graph::{TemplateGraph, TemplateReference, TemplateReferenceKind};
 pub use self::utils::{AutoEscape, HtmlEscape, UndefinedBehavior};
 
 #[cfg(feature = "builtins")]
@@ -250,6 +275,23 @@ pub use self::value::Value;
 pub use self::macros::__context;
 pub use self::vm::State;
 
+/// Read-only access to the syntax tree of templates.
+///
+/// Templates can be parsed into a syntax tree with
+/// [`Environment::parse`].  The tree borrows from the template source and
+/// every node carries a [`Span`] with its location in the source which makes
+/// it a good foundation for linters, migration and analysis tools.  To walk
+/// the tree implement the [`Visitor`] trait.
+///
+/// The syntax tree is meant to be inspected, not constructed.  Node types
+/// might gain new variants and fields in future versions which is why they
+/// are marked as non exhaustive.
+pub mod ast {
+    pub use crate::compiler::ast::*;
+    pub use crate::compiler::tokens::Span;
+    pub use crate::compiler::visitor::*;
+}
+
 /// This module gives access to the low level machinery.
 ///
 /// This module is only provided by the `unstable_machinery` feature and does not
@@ -261,15 +303,30 @@ pub mod machinery {
     #![allow(missing_docs)]
     pub use crate::compiler::ast;
     pub use crate::compiler::codegen::CodeGenerator;
+    pub use crate::compiler::html_context::{Delim, Escaper, HtmlContext};
     pub use crate::compiler::instructions::{Instruction, Instructions};
     pub use crate::compiler::lexer::{tokenize, Tokenizer, WhitespaceConfig};
+    pub use crate::compiler::optimizer::optimize;
     pub use crate::compiler::parser::{parse, parse_expr};
     pub use crate::compiler::tokens::{Span, Token};
     pub use crate::template::{CompiledTemplate, TemplateConfig};
     pub use crate::vm::Vm;
 
+    use crate::value::Value;
     use crate::Output;
 
+    /// Gives access to the constant evaluation of expressions.
+    pub trait ConstExpr {
+        /// Evaluates the expression if it's made up of constants only.
+        fn as_const(&self) -> Option<Value>;
+    }
+
+    impl ConstExpr for ast::Expr<'_> {
+        fn as_const(&self) -> Option<Value> {
+            ast::Expr::as_const(self)
+        }
+    }
+
     /// Returns a reference to a [`CompiledTemplate`] from a [`Template`](crate::Template).
     pub fn get_compiled_template<'x, 'env>(
         tmpl: &'x crate::Template<'env, 'env>,
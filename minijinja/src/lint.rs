@@ -0,0 +1,625 @@
+//! Static checks for templates.
+//!
+//! The [`Linter`] inspects the syntax tree of templates and reports likely
+//! mistakes that would otherwise only show up at render time (or not at all).
+//! Every finding is returned as a [`Diagnostic`] which carries the
+//! [`LintRule`] that produced it together with the location in the template.
+//!
+//! ```
+//! use minijinja::Environment;
+//! use minijinja::lint::{LintRule, Linter};
+//!
+//! let mut env = Environment::new();
+//! env.add_template("hello.txt", "{% set unused = 42 %}{{ name|titel }}").unwrap();
+//!
+//! let linter = Linter::new(&env);
+//! let diagnostics = linter.lint_templates().unwrap();
+//! assert_eq!(diagnostics.len(), 2);
+//! assert_eq!(diagnostics[0].rule(), LintRule::UnusedVariable);
+//! assert_eq!(diagnostics[1].rule(), LintRule::UnknownFilter);
+//! assert_eq!(diagnostics[1].message(), "unknown filter `titel`");
+//! ```
+//!
+//! The following rules exist:
+//!
+//! * `unknown-filter`: a filter is used that is not registered on the
+//!   environment.
+//! * `unknown-test`: a test is used that is not registered on the environment.
+//! * `unused-variable`: a variable is assigned with `{% set %}` but never read
+//!   within the template.
+//! * `unused-macro`: a macro is defined but never referenced within the
+//!   template.
+//! * `shadowed-loop-variable`: the target of a `for` loop hides the target of
+//!   an outer loop.
+//! * `unknown-block`: a template overrides a block that does not exist in the
+//!   template it extends.
+//! * `unreachable-code`: a template that extends another template emits output
+//!   outside of blocks which is never rendered.
+//!
+//! As templates can pass variables and macros to each other (via `include`,
+//! `import` or `extends`) the unused checks are conservative: assignments and
+//! macros on the top level of templates that extend, or are referenced by,
+//! other templates are not reported.  Names starting with an underscore are
+//! never reported as unused.
+use std::collections::{BTreeSet, HashSet};
+use std::fmt;
+use std::ops::Range;
+
+use crate::compiler::ast;
+use crate::compiler::tokens::Span;
+use crate::compiler::visitor::{walk_expr, walk_stmt, Visitor};
+use crate::environment::Environment;
+use crate::error::Error;
+use crate::template::Template;
+use crate::vm::normalize_filter_test_name;
+
+/// The rules the [`Linter`] can check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum LintRule {
+    /// A filter that is not registered on the environment.
+    UnknownFilter,
+    /// A test that is not registered on the environment.
+    UnknownTest,
+    /// A variable that is set but never used.
+    UnusedVariable,
+    /// A macro that is defined but never used.
+    UnusedMacro,
+    /// A loop variable that shadows the variable of an outer loop.
+    ShadowedLoopVariable,
+    /// A block that is not defined in the parent template.
+    UnknownBlock,
+    /// Output after `extends` that is never rendered.
+    UnreachableCode,
+}
+
+impl LintRule {
+    /// Returns all known rules.
+    pub fn all() -> &'static [LintRule] {
+        &[
+            LintRule::UnknownFilter,
+            LintRule::UnknownTest,
+            LintRule::UnusedVariable,
+            LintRule::UnusedMacro,
+            LintRule::ShadowedLoopVariable,
+            LintRule::UnknownBlock,
+            LintRule::UnreachableCode,
+        ]
+    }
+
+    /// Returns the name of the rule (eg: `unknown-filter`).
+    pub fn name(self) -> &'static str {
+        match self {
+            LintRule::UnknownFilter => "unknown-filter",
+            LintRule::UnknownTest => "unknown-test",
+            LintRule::UnusedVariable => "unused-variable",
+            LintRule::UnusedMacro => "unused-macro",
+            LintRule::ShadowedLoopVariable => "shadowed-loop-variable",
+            LintRule::UnknownBlock => "unknown-block",
+            LintRule::UnreachableCode => "unreachable-code",
+        }
+    }
+
+    /// Looks up a rule by its name.
+    pub fn from_name(name: &str) -> Option<LintRule> {
+        LintRule::all().iter().copied().find(|x| x.name() == name)
+    }
+}
+
+impl fmt::Display for LintRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A problem found by the [`Linter`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    rule: LintRule,
+    message: String,
+    name: String,
+    span: Span,
+}
+
+impl Diagnostic {
+    /// Returns the rule that produced the diagnostic.
+    pub fn rule(&self) -> LintRule {
+        self.rule
+    }
+
+    /// Returns the human readable description of the problem.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the name of the template the problem was found in.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the line of the problem.
+    pub fn line(&self) -> usize {
+        self.span.start_line as usize
+    }
+
+    /// Returns the byte range of the problem in the template source.
+    pub fn range(&self) -> Range<usize> {
+        self.span.start_offset as usize..self.span.end_offset as usize
+    }
+
+    /// Returns the span of the problem.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (in {}:{}) [{}]",
+            self.message,
+            self.name,
+            self.line(),
+            self.rule
+        )
+    }
+}
+
+/// Checks templates for likely mistakes.
+///
+/// By default all [rules](LintRule) are enabled.  The linter looks up filters,
+/// tests and parent templates on the environment it was created for.
+pub struct Linter<'env, 'source> {
+    env: &'env Environment<'source>,
+    disabled: BTreeSet<LintRule>,
+}
+
+impl<'env, 'source> Linter<'env, 'source> {
+    /// Creates a linter for the given environment.
+    pub fn new(env: &'env Environment<'source>) -> Linter<'env, 'source> {
+        Linter {
+            env,
+            disabled: BTreeSet::new(),
+        }
+    }
+
+    /// Enables or disables a rule.
+    pub fn set_rule_enabled(&mut self, rule: LintRule, enabled: bool) {
+        if enabled {
+            self.disabled.remove(&rule);
+        } else {
+            self.disabled.insert(rule);
+        }
+    }
+
+    /// Returns `true` if the rule is enabled.
+    pub fn is_rule_enabled(&self, rule: LintRule) -> bool {
+        !self.disabled.contains(&rule)
+    }
+
+    /// Lints all templates loaded into the environment.
+    ///
+    /// As templates from a loader are only loaded on demand, only the
+    /// templates returned by [`Environment::templates`] are checked.
+    pub fn lint_templates(&self) -> Result<Vec<Diagnostic>, Error> {
+        // the template store must not be borrowed while linting as looking
+        // up parent templates needs to access it again.
+        let names = self
+            .env
+            .templates()
+            .map(|(name, _)| name.to_string())
+            .collect::<Vec<_>>();
+        // the graph is shared by all templates.  If it cannot be built, all
+        // templates are considered to be referenced by other templates.
+        #[cfg(feature = "multi_template")]
+        let graph = self.env.template_graph().ok();
+        let mut rv = Vec::new();
+        for name in names {
+            rv.extend(ok!(self.lint(
+                &ok!(self.env.get_template(&name)),
+                #[cfg(feature = "multi_template")]
+                graph.as_ref(),
+            )));
+        }
+        Ok(rv)
+    }
+
+    /// Lints a single template.
+    ///
+    /// The diagnostics are returned in the order in which they appear in the
+    /// template.  Fails if the template cannot be parsed.
+    pub fn lint_template(&self, tmpl: &Template<'_, '_>) -> Result<Vec<Diagnostic>, Error> {
+        self.lint(
+            tmpl,
+            #[cfg(feature = "multi_template")]
+            self.env.template_graph().ok().as_ref(),
+        )
+    }
+
+    fn lint(
+        &self,
+        tmpl: &Template<'_, '_>,
+        #[cfg(feature = "multi_template")] graph: Option<&crate::TemplateGraph>,
+    ) -> Result<Vec<Diagnostic>, Error> {
+        let ast = ok!(tmpl.parse_ast());
+        let mut collector = Collector::default();
+        collector.visit_stmt(&ast);
+
+        let mut rv = Vec::new();
+        let mut report = |rule: LintRule, span: Span, message: String| {
+            if self.is_rule_enabled(rule) {
+                rv.push(Diagnostic {
+                    rule,
+                    message,
+                    name: tmpl.name().to_string(),
+                    span,
+                });
+            }
+        };
+
+        for (name, span) in &collector.filters {
+            if self
+                .env
+                .get_filter(&normalize_filter_test_name(name))
+                .is_none()
+            {
+                report(
+                    LintRule::UnknownFilter,
+                    *span,
+                    format!("unknown filter `{}`", name),
+                );
+            }
+        }
+        for (name, span) in &collector.tests {
+            if self
+                .env
+                .get_test(&normalize_filter_test_name(name))
+                .is_none()
+            {
+                report(
+                    LintRule::UnknownTest,
+                    *span,
+                    format!("unknown test `{}`", name),
+                );
+            }
+        }
+
+        for (name, span) in &collector.shadowed {
+            let message = format!(
+                "loop variable `{}` shadows the variable of an outer loop",
+                name
+            );
+            report(LintRule::ShadowedLoopVariable, *span, message);
+        }
+
+        // included templates see all variables of the including template so
+        // we cannot tell if something is unused.
+        if !collector.has_include {
+            let exported = is_exported(
+                tmpl,
+                &ast,
+                #[cfg(feature = "multi_template")]
+                graph,
+            );
+            let is_unused = |name: &str, top_level: bool| {
+                !(name.starts_with('_')
+                    || (top_level && exported)
+                    || collector.reads.contains(name))
+            };
+            for (name, span, top_level) in &collector.assignments {
+                if is_unused(name, *top_level) {
+                    report(
+                        LintRule::UnusedVariable,
+                        *span,
+                        format!("variable `{}` is assigned but never used", name),
+                    );
+                }
+            }
+            for (name, span, top_level) in &collector.macros {
+                if is_unused(name, *top_level) {
+                    report(
+                        LintRule::UnusedMacro,
+                        *span,
+                        format!("macro `{}` is defined but never used", name),
+                    );
+                }
+            }
+        }
+
+        #[cfg(feature = "multi_template")]
+        {
+            if let ast::Stmt::Template(t) = &ast {
+                if let Some((parent, blocks)) = self.parent_blocks(tmpl, &t.children) {
+                    for (name, span) in &collector.blocks {
+                        if !blocks.contains(*name) {
+                            report(
+                                LintRule::UnknownBlock,
+                                *span,
+                                format!(
+                                    "block `{}` is not defined in parent template `{}`",
+                                    name, parent
+                                ),
+                            );
+                        }
+                    }
+                }
+
+                // only the first statement of a run of output is reported
+                let mut after_extends = false;
+                let mut in_output = false;
+                for stmt in &t.children {
+                    if let ast::Stmt::Extends(_) = stmt {
+                        after_extends = true;
+                    } else if !after_extends {
+                        continue;
+                    } else if emits_output(stmt) {
+                        if !in_output {
+                            report(
+                                LintRule::UnreachableCode,
+                                stmt.span(),
+                                "output after `extends` is never rendered".to_string(),
+                            );
+                        }
+                        in_output = true;
+                    } else if !matches!(stmt, ast::Stmt::EmitRaw(_)) {
+                        in_output = false;
+                    }
+                }
+            }
+        }
+
+        rv.sort_by_key(|x| (x.span.start_offset, x.rule));
+        Ok(rv)
+    }
+
+    /// Finds the parent of a template and the blocks defined along the
+    /// chain of parent templates.
+    ///
+    /// Returns `None` if the template does not extend another template or
+    /// if one of the parents cannot be resolved statically.
+    #[cfg(feature = "multi_template")]
+    fn parent_blocks(
+        &self,
+        tmpl: &Template<'_, '_>,
+        children: &[ast::Stmt<'_>],
+    ) -> Option<(String, HashSet<String>)> {
+        let first_parent = some!(find_extends(children));
+        let first_parent = self
+            .env
+            .join_template_path(first_parent, tmpl.name())
+            .into_owned();
+        let mut blocks = HashSet::new();
+        let mut seen = HashSet::new();
+        let mut current = Some(first_parent.clone());
+        while let Some(name) = current.take() {
+            if !seen.insert(name.clone()) {
+                break;
+            }
+            let parent = self.env.get_template(&name).ok()?;
+            let parent_ast = parent.parse_ast().ok()?;
+            let mut collector = Collector::default();
+            collector.visit_stmt(&parent_ast);
+            blocks.extend(collector.all_blocks.iter().map(|x| x.to_string()));
+            if let ast::Stmt::Template(t) = &parent_ast {
+                if t.children
+                    .iter()
+                    .any(|x| matches!(x, ast::Stmt::Extends(_)))
+                {
+                    let next = some!(find_extends(&t.children));
+                    current = Some(self.env.join_template_path(next, &name).into_owned());
+                }
+            }
+        }
+        Some((first_parent, blocks))
+    }
+}
+
+/// Returns the constant name of the extended template.
+#[cfg(feature = "multi_template")]
+fn find_extends<'a>(children: &'a [ast::Stmt<'_>]) -> Option<&'a str> {
+    children.iter().find_map(|stmt| match stmt {
+        ast::Stmt::Extends(e) => match e.name {
+            ast::Expr::Const(ref c) => c.value.as_str(),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+/// Checks if the top level of a template can be seen by other templates.
+#[allow(unused_variables)]
+fn is_exported(
+    tmpl: &Template<'_, '_>,
+    ast: &ast::Stmt<'_>,
+    #[cfg(feature = "multi_template")] graph: Option<&crate::TemplateGraph>,
+) -> bool {
+    #[cfg(feature = "multi_template")]
+    {
+        if let ast::Stmt::Template(t) = ast {
+            if t.children
+                .iter()
+                .any(|x| matches!(x, ast::Stmt::Extends(_)))
+            {
+                return true;
+            }
+        }
+        // if the graph cannot be built, assume the worst
+        graph.map_or(true, |graph| !graph.dependents(tmpl.name()).is_empty())
+    }
+    #[cfg(not(feature = "multi_template"))]
+    {
+        false
+    }
+}
+
+/// Checks if a top level statement produces output.
+#[cfg(feature = "multi_template")]
+fn emits_output(stmt: &ast::Stmt<'_>) -> bool {
+    match stmt {
+        ast::Stmt::EmitRaw(raw) => !raw.raw.trim().is_empty(),
+        ast::Stmt::EmitExpr(_)
+        | ast::Stmt::ForLoop(_)
+        | ast::Stmt::IfCond(_)
+        | ast::Stmt::WithBlock(_)
+        | ast::Stmt::AutoEscape(_)
+        | ast::Stmt::FilterBlock(_)
+        | ast::Stmt::Include(_) => true,
+        #[cfg(feature = "macros")]
+        ast::Stmt::CallBlock(_) => true,
+        #[cfg(feature = "i18n")]
+        ast::Stmt::Trans(_) => true,
+        _ => false,
+    }
+}
+
+/// Collects the facts the rules are checked against in a single pass.
+#[derive(Default)]
+struct Collector<'a> {
+    filters: Vec<(&'a str, Span)>,
+    tests: Vec<(&'a str, Span)>,
+    targets: HashSet<u32>,
+    reads: HashSet<&'a str>,
+    assignments: Vec<(&'a str, Span, bool)>,
+    macros: Vec<(&'a str, Span, bool)>,
+    loop_vars: Vec<Vec<&'a str>>,
+    shadowed: Vec<(&'a str, Span)>,
+    #[cfg(feature = "multi_template")]
+    blocks: Vec<(&'a str, Span)>,
+    #[cfg(feature = "multi_template")]
+    all_blocks: Vec<&'a str>,
+    has_include: bool,
+    depth: usize,
+    #[cfg(feature = "multi_template")]
+    block_depth: usize,
+}
+
+impl<'a> Collector<'a> {
+    /// Remembers the variables an expression assigns to so that they are
+    /// not counted as reads.
+    fn mark_targets(&mut self, expr: &ast::Expr<'a>) -> Vec<(&'a str, Span)> {
+        let mut rv = Vec::new();
+        self.mark_targets_into(expr, &mut rv);
+        rv
+    }
+
+    fn mark_targets_into(&mut self, expr: &ast::Expr<'a>, rv: &mut Vec<(&'a str, Span)>) {
+        match expr {
+            ast::Expr::Var(var) => {
+                self.targets.insert(var.span().start_offset);
+                rv.push((var.id, var.span()));
+            }
+            ast::Expr::List(list) => {
+                for item in &list.items {
+                    self.mark_targets_into(item, rv);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<'a> Visitor<'a> for Collector<'a> {
+    fn visit_stmt(&mut self, stmt: &ast::Stmt<'a>) {
+        let top_level = self.depth == 1;
+        match stmt {
+            ast::Stmt::ForLoop(f) => {
+                let names = self.mark_targets(&f.target);
+                for (name, span) in &names {
+                    if self.loop_vars.iter().flatten().any(|x| x == name) {
+                        self.shadowed.push((name, *span));
+                    }
+                }
+                self.loop_vars
+                    .push(names.into_iter().map(|x| x.0).collect());
+                self.depth += 1;
+                walk_stmt(self, stmt);
+                self.depth -= 1;
+                self.loop_vars.pop();
+                return;
+            }
+            ast::Stmt::WithBlock(w) => {
+                for (target, _) in &w.assignments {
+                    self.mark_targets(target);
+                }
+            }
+            ast::Stmt::Set(s) => {
+                let names = self.mark_targets(&s.target);
+                self.assignments.extend(
+                    names
+                        .into_iter()
+                        .map(|(name, span)| (name, span, top_level)),
+                );
+            }
+            ast::Stmt::SetBlock(s) => {
+                let names = self.mark_targets(&s.target);
+                self.assignments.extend(
+                    names
+                        .into_iter()
+                        .map(|(name, span)| (name, span, top_level)),
+                );
+            }
+            #[cfg(feature = "multi_template")]
+            ast::Stmt::Import(i) => {
+                self.mark_targets(&i.name);
+            }
+            #[cfg(feature = "multi_template")]
+            ast::Stmt::FromImport(i) => {
+                for (name, alias) in &i.names {
+                    self.mark_targets(name);
+                    if let Some(alias) = alias {
+                        self.mark_targets(alias);
+                    }
+                }
+            }
+            #[cfg(feature = "multi_template")]
+            ast::Stmt::Include(_) => {
+                self.has_include = true;
+            }
+            #[cfg(feature = "multi_template")]
+            ast::Stmt::Block(b) => {
+                if self.block_depth == 0 {
+                    self.blocks.push((b.name, stmt.span()));
+                }
+                self.all_blocks.push(b.name);
+                self.block_depth += 1;
+                self.depth += 1;
+                walk_stmt(self, stmt);
+                self.depth -= 1;
+                self.block_depth -= 1;
+                return;
+            }
+            #[cfg(feature = "macros")]
+            ast::Stmt::Macro(m) => {
+                self.macros.push((m.name, stmt.span(), top_level));
+            }
+            _ => {}
+        }
+        self.depth += 1;
+        walk_stmt(self, stmt);
+        self.depth -= 1;
+    }
+
+    fn visit_expr(&mut self, expr: &ast::Expr<'a>) {
+        match expr {
+            ast::Expr::Var(var) if !self.targets.contains(&var.span().start_offset) => {
+                self.reads.insert(var.id);
+            }
+            ast::Expr::Filter(f) => self.filters.push((f.name, f.span())),
+            ast::Expr::Test(t) => self.tests.push((t.name, t.span())),
+            _ => {}
+        }
+        walk_expr(self, expr);
+    }
+
+    #[cfg(feature = "macros")]
+    fn visit_macro(&mut self, m: &ast::Macro<'a>) {
+        for arg in m.args.iter().chain(&m.varargs).chain(&m.kwargs) {
+            self.mark_targets(arg);
+        }
+        // loops outside of the macro are not visible within it
+        let loop_vars = std::mem::take(&mut self.loop_vars);
+        crate::compiler::visitor::walk_macro(self, m);
+        self.loop_vars = loop_vars;
+    }
+}
@@ -1,3 +1,4 @@
+#[cfg(feature = "bytecode")]
 use std::borrow::Cow;
 use std::collections::BTreeMap;
 use std::fmt;
@@ -5,12 +6,14 @@ use std::fs;
 use std::io;
 use std::path::Path;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 
 use crate::vendor::self_cell::self_cell;
 use memo_map::MemoMap;
 
 use crate::compiler::instructions::Instructions;
+use crate::environment::Environment;
 use crate::error::{Error, ErrorKind};
 use crate::template::CompiledTemplate;
 use crate::template::TemplateConfig;
@@ -47,7 +50,7 @@ impl fmt::Debug for LoaderStore<'_> {
 }
 
 self_cell! {
-    struct LoadedTemplate {
+    pub(crate) struct LoadedTemplate {
         owner: (Arc<str>, Box<str>),
         #[covariant]
         dependent: CompiledTemplate,
@@ -78,37 +81,35 @@ impl<'source> LoaderStore<'source> {
         }
     }
 
-    pub fn insert(&mut self, name: &'source str, source: &'source str) -> Result<(), Error> {
-        self.insert_cow(Cow::Borrowed(name), Cow::Borrowed(source))
+    pub fn insert(&mut self, name: &'source str, template: CompiledTemplate<'source>) {
+        self.owned_templates.remove(name);
+        self.borrowed_templates.insert(name, Arc::new(template));
+    }
+
+    pub fn insert_owned(&mut self, name: Arc<str>, template: Arc<LoadedTemplate>) {
+        self.borrowed_templates.remove(&name as &str);
+        self.owned_templates.replace(name, template);
     }
 
-    pub fn insert_cow(
+    #[cfg(feature = "bytecode")]
+    pub fn insert_bytecode(
         &mut self,
         name: Cow<'source, str>,
-        source: Cow<'source, str>,
+        bytecode: &[u8],
     ) -> Result<(), Error> {
-        match (source, name) {
-            (Cow::Borrowed(source), Cow::Borrowed(name)) => {
-                self.owned_templates.remove(name);
-                self.borrowed_templates.insert(
-                    name,
-                    Arc::new(ok!(CompiledTemplate::new(
-                        name,
-                        source,
-                        &self.template_config
-                    ))),
-                );
-            }
-            (source, name) => {
-                self.borrowed_templates.remove(&name as &str);
-                let name: Arc<str> = name.into();
-                self.owned_templates.replace(
-                    name.clone(),
-                    ok!(self.make_owned_template(name, source.to_string())),
-                );
-            }
-        }
+        use crate::compiler::bytecode;
 
+        let mut parts = ok!(bytecode::read_parts(bytecode));
+        let strings = std::mem::take(&mut parts.strings);
+        self.borrowed_templates.remove(&name as &str);
+        let name: Arc<str> = name.into();
+        let template = ok!(LoadedTemplate::try_new(
+            (name.clone(), strings),
+            |(name, strings)| -> Result<_, Error> {
+                bytecode::decode(name, strings, &parts, &self.template_config)
+            },
+        ));
+        self.owned_templates.replace(name, Arc::new(template));
         Ok(())
     }
 
@@ -122,19 +123,29 @@ impl<'source> LoaderStore<'source> {
         self.owned_templates.clear();
     }
 
-    pub fn get(&self, name: &str) -> Result<&CompiledTemplate<'_>, Error> {
+    /// Looks up a template, loading it with the loader if needed.
+    ///
+    /// `env` is the environment that owns this store.
+    pub fn get(&self, name: &str, env: &Environment<'_>) -> Result<&CompiledTemplate<'_>, Error> {
         if let Some(rv) = self.borrowed_templates.get(name) {
             Ok(&**rv)
         } else {
             let name: Arc<str> = name.into();
             self.owned_templates
                 .get_or_try_insert(&name.clone(), || -> Result<_, Error> {
-                    let loader_result = match self.loader {
-                        Some(ref loader) => ok!(loader(&name)),
-                        None => None,
-                    }
-                    .ok_or_else(|| Error::new_not_found(&name));
-                    self.make_owned_template(name, ok!(loader_result))
+                    #[cfg(feature = "tracing")]
+                    let span = crate::trace::load(&name);
+                    let rv = (|| {
+                        let loader_result = match self.loader {
+                            Some(ref loader) => ok!(loader(&name)),
+                            None => None,
+                        }
+                        .ok_or_else(|| Error::new_not_found(&name));
+                        LoaderStore::compile_owned(name.clone(), ok!(loader_result), env)
+                    })();
+                    #[cfg(feature = "tracing")]
+                    crate::trace::record_result(&span, &rv);
+                    rv
                 })
                 .map(|x| x.borrow_dependent())
         }
@@ -147,15 +158,16 @@ impl<'source> LoaderStore<'source> {
         self.loader = Some(Arc::new(f));
     }
 
-    fn make_owned_template(
-        &self,
+    /// Compiles a template that owns its name and source for `env`.
+    pub fn compile_owned(
         name: Arc<str>,
         source: String,
+        env: &Environment<'_>,
     ) -> Result<Arc<LoadedTemplate>, Error> {
         LoadedTemplate::try_new(
             (name, source.into_boxed_str()),
             |(name, source)| -> Result<_, Error> {
-                CompiledTemplate::new(name, source, &self.template_config)
+                CompiledTemplate::new_for_env(name, source, env)
             },
         )
         .map(Arc::new)
@@ -222,6 +234,219 @@ pub fn path_loader<'x, P: AsRef<Path> + 'x>(
     }
 }
 
+/// Loads templates from an ordered list of directories.
+///
+/// This is a more capable alternative to [`path_loader`].  A template is
+/// looked up in every root directory in the order they were given and the
+/// first root that has the file wins.  This makes it possible to layer a
+/// theme over a set of default templates.  Additionally namespaces can be
+/// registered with [`with_namespace`](Self::with_namespace) which map a
+/// prefix such as `admin/` to a separate list of roots.
+///
+/// The loader remembers the modification time of every file it loaded.  Files
+/// that did not change are not read again, and
+/// [`changed_templates`](Self::changed_templates) reports the templates which
+/// have to be reloaded.  As the [`Environment`](crate::Environment) holds on to
+/// loaded templates, it's up to the caller to clear them, for instance with
+/// [`clear_templates`](crate::Environment::clear_templates).  With the
+/// `minijinja-autoreload` crate, [`has_changes`](Self::has_changes) can be
+/// used as the reload callback.
+///
+/// As with [`path_loader`], templates that start with a dot (`.`) or are
+/// contained in a folder starting with a dot cannot be loaded.  Clones of the
+/// loader share the cache.
+///
+/// # Example
+///
+/// ```rust
+/// # use minijinja::{Environment, FileSystemLoader};
+/// fn create_env(loader: &FileSystemLoader) -> Environment<'static> {
+///     let mut env = Environment::new();
+///     let loader = loader.clone();
+///     env.set_loader(move |name| loader.load(name));
+///     env
+/// }
+///
+/// let loader = FileSystemLoader::new(["themes/dark", "templates"])
+///     .with_namespace("admin", "admin/templates");
+/// let mut env = create_env(&loader);
+///
+/// // later, for instance before handling a request:
+/// if loader.has_changes() {
+///     env.clear_templates();
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct FileSystemLoader {
+    roots: Vec<PathBuf>,
+    namespaces: BTreeMap<String, Vec<PathBuf>>,
+    cache: Arc<Mutex<BTreeMap<String, LoadedFile>>>,
+}
+
+#[derive(Debug)]
+struct LoadedFile {
+    root: PathBuf,
+    path: PathBuf,
+    stamp: Option<(SystemTime, u64)>,
+    source: String,
+}
+
+/// Returns the modification time and size used to detect changes.
+fn file_stamp(metadata: &fs::Metadata) -> Option<(SystemTime, u64)> {
+    metadata
+        .modified()
+        .ok()
+        .map(|mtime| (mtime, metadata.len()))
+}
+
+impl FileSystemLoader {
+    /// Creates a loader that searches the given roots in order.
+    pub fn new<I, P>(roots: I) -> FileSystemLoader
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        FileSystemLoader {
+            roots: roots
+                .into_iter()
+                .map(|x| x.as_ref().to_path_buf())
+                .collect(),
+            namespaces: BTreeMap::new(),
+            cache: Default::default(),
+        }
+    }
+
+    /// Adds a root for templates with the given prefix.
+    ///
+    /// A template named `admin/index.html` is then looked up as `index.html`
+    /// in the roots registered for the `admin` namespace, and only there.
+    /// Invoking this multiple times for the same namespace adds further roots
+    /// with lower priority.
+    pub fn with_namespace<N, P>(mut self, namespace: N, root: P) -> FileSystemLoader
+    where
+        N: Into<String>,
+        P: AsRef<Path>,
+    {
+        self.namespaces
+            .entry(namespace.into())
+            .or_default()
+            .push(root.as_ref().to_path_buf());
+        self
+    }
+
+    /// Loads a template by name.
+    ///
+    /// This has the signature expected by
+    /// [`Environment::set_loader`](crate::Environment::set_loader).  If the
+    /// file was loaded before and its modification time and size did not
+    /// change, the cached source is returned without reading the file.
+    pub fn load(&self, name: &str) -> Result<Option<String>, Error> {
+        let Some((root, path, metadata)) = ok!(self.find(name)) else {
+            self.cache.lock().unwrap().remove(name);
+            return Ok(None);
+        };
+        let stamp = file_stamp(&metadata);
+        if let Some(cached) = self.cache.lock().unwrap().get(name) {
+            if cached.path == path && stamp.is_some() && cached.stamp == stamp {
+                return Ok(Some(cached.source.clone()));
+            }
+        }
+        let source = match fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(
+                    Error::new(ErrorKind::InvalidOperation, "could not read template")
+                        .with_source(err),
+                )
+            }
+        };
+        self.cache.lock().unwrap().insert(
+            name.to_string(),
+            LoadedFile {
+                root: root.to_path_buf(),
+                path,
+                stamp,
+                source: source.clone(),
+            },
+        );
+        Ok(Some(source))
+    }
+
+    /// Returns the root directory that served the given template.
+    ///
+    /// This is `None` if the template was not loaded by this loader.
+    pub fn source_root(&self, name: &str) -> Option<PathBuf> {
+        self.cache.lock().unwrap().get(name).map(|x| x.root.clone())
+    }
+
+    /// Returns the path of the file that served the given template.
+    pub fn source_path(&self, name: &str) -> Option<PathBuf> {
+        self.cache.lock().unwrap().get(name).map(|x| x.path.clone())
+    }
+
+    /// Returns the names of all loaded templates that are out of date.
+    ///
+    /// A template is out of date if its file was modified or removed, or if
+    /// a root with a higher priority now provides the template.
+    pub fn changed_templates(&self) -> Vec<String> {
+        let cache = self.cache.lock().unwrap();
+        cache
+            .iter()
+            .filter(|(name, cached)| self.is_stale(name, cached))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Returns `true` if any loaded template is out of date.
+    ///
+    /// For more information see [`changed_templates`](Self::changed_templates).
+    pub fn has_changes(&self) -> bool {
+        let cache = self.cache.lock().unwrap();
+        cache
+            .iter()
+            .any(|(name, cached)| self.is_stale(name, cached))
+    }
+
+    fn is_stale(&self, name: &str, cached: &LoadedFile) -> bool {
+        match self.find(name) {
+            Ok(Some((_, path, metadata))) => {
+                path != cached.path
+                    || cached.stamp.is_none()
+                    || file_stamp(&metadata) != cached.stamp
+            }
+            _ => true,
+        }
+    }
+
+    /// Finds the first root that has a file for the template.
+    fn find(&self, name: &str) -> Result<Option<(&Path, PathBuf, fs::Metadata)>, Error> {
+        let (roots, name) = match name.split_once('/') {
+            Some((prefix, rest)) if self.namespaces.contains_key(prefix) => {
+                (&self.namespaces[prefix], rest)
+            }
+            _ => (&self.roots, name),
+        };
+        for root in roots {
+            let Some(path) = safe_join(root, name) else {
+                return Ok(None);
+            };
+            match fs::metadata(&path) {
+                Ok(metadata) if metadata.is_file() => return Ok(Some((root, path, metadata))),
+                Ok(_) => {}
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => {
+                    return Err(
+                        Error::new(ErrorKind::InvalidOperation, "could not read template")
+                            .with_source(err),
+                    )
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
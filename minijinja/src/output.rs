@@ -22,7 +22,11 @@ pub enum CaptureMode {
 pub struct Output<'a> {
     w: *mut (dyn fmt::Write + 'a),
     target: *mut (dyn fmt::Write + 'a),
+    flush: Option<*mut (dyn Flush + 'a)>,
     capture_stack: Vec<Option<String>>,
+    limit: Option<usize>,
+    written: usize,
+    limit_exceeded: bool,
 }
 
 impl<'a> Output<'a> {
@@ -31,7 +35,25 @@ impl<'a> Output<'a> {
         Self {
             w,
             target: w,
+            flush: None,
             capture_stack: Vec::new(),
+            limit: None,
+            written: 0,
+            limit_exceeded: false,
+        }
+    }
+
+    /// Creates a new output that forwards flush points to the writer.
+    pub(crate) fn new_flushable<W: Flush + 'a>(w: &'a mut W) -> Self {
+        let w: *mut W = w;
+        Self {
+            w,
+            target: w,
+            flush: Some(w),
+            capture_stack: Vec::new(),
+            limit: None,
+            written: 0,
+            limit_exceeded: false,
         }
     }
 
@@ -43,8 +65,72 @@ impl<'a> Output<'a> {
         Self {
             w: NullWriter::get_mut(),
             target: NullWriter::get_mut(),
+            flush: None,
             capture_stack: vec![None],
+            limit: None,
+            written: 0,
+            limit_exceeded: false,
+        }
+    }
+
+    /// Limits the number of bytes that can be written.
+    ///
+    /// Captured output is limited separately, so that every capture buffer
+    /// cannot grow over the limit either.  The limit can only be set once.
+    pub(crate) fn init_limit(&mut self, limit: Option<usize>) {
+        if self.limit.is_none() {
+            self.limit = limit;
+        }
+    }
+
+    /// Returns the error for exceeding the output limit if it was exceeded.
+    pub(crate) fn limit_error(&self) -> Option<Error> {
+        match self.limit {
+            Some(limit) if self.limit_exceeded => Some(Error::new(
+                ErrorKind::SizeLimitExceeded,
+                format!("output exceeds the limit of {limit} bytes"),
+            )),
+            _ => None,
+        }
+    }
+
+    /// Accounts for `len` bytes written to the current target.
+    #[inline(always)]
+    fn track(&mut self, len: usize) -> fmt::Result {
+        if let Some(limit) = self.limit {
+            let new_len = match self.capture_stack.last() {
+                None => {
+                    self.written += len;
+                    self.written
+                }
+                Some(Some(buf)) => buf.len() + len,
+                Some(None) => 0,
+            };
+            if new_len > limit {
+                self.limit_exceeded = true;
+                return Err(fmt::Error);
+            }
         }
+        Ok(())
+    }
+
+    /// Takes the captures of an evaluation that is being suspended.
+    #[cfg(feature = "async")]
+    pub(crate) fn suspend(&mut self) -> SuspendedOutput {
+        let rv = SuspendedOutput {
+            capture_stack: std::mem::take(&mut self.capture_stack),
+            written: self.written,
+        };
+        self.retarget();
+        rv
+    }
+
+    /// Restores the captures of an evaluation that is resumed.
+    #[cfg(feature = "async")]
+    pub(crate) fn resume(&mut self, suspended: SuspendedOutput) {
+        self.capture_stack = suspended.capture_stack;
+        self.written = suspended.written;
+        self.retarget();
     }
 
     /// Begins capturing into a string or discard.
@@ -93,36 +179,70 @@ impl<'a> Output<'a> {
         matches!(self.capture_stack.last(), Some(None))
     }
 
+    /// Flushes the underlying writer.
+    ///
+    /// This does nothing while output is captured or if the writer does
+    /// not support flushing.
+    pub(crate) fn flush(&mut self) -> fmt::Result {
+        match self.flush {
+            // SAFETY: the flush pointer refers to the same writer as `w`
+            // which is not in use while we are not capturing.
+            Some(flush) if self.capture_stack.is_empty() => unsafe { (*flush).flush() },
+            _ => Ok(()),
+        }
+    }
+
     /// Writes some data to the underlying buffer contained within this output.
     #[inline]
     pub fn write_str(&mut self, s: &str) -> fmt::Result {
+        ok!(self.track(s.len()));
         self.target().write_str(s)
     }
 
     /// Writes some formatted information into this instance.
     #[inline]
     pub fn write_fmt(&mut self, a: fmt::Arguments<'_>) -> fmt::Result {
-        self.target().write_fmt(a)
+        if self.limit.is_some() {
+            // go through `write_str` so that the writes are tracked
+            fmt::write(self, a)
+        } else {
+            self.target().write_fmt(a)
+        }
     }
 }
 
 impl fmt::Write for Output<'_> {
     #[inline]
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        fmt::Write::write_str(self.target(), s)
+        Output::write_str(self, s)
     }
 
     #[inline]
     fn write_char(&mut self, c: char) -> fmt::Result {
+        ok!(self.track(c.len_utf8()));
         fmt::Write::write_char(self.target(), c)
     }
 
     #[inline]
     fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> fmt::Result {
-        fmt::Write::write_fmt(self.target(), args)
+        Output::write_fmt(self, args)
     }
 }
 
+/// The captures of an [`Output`] while the evaluation is suspended.
+#[cfg(feature = "async")]
+#[derive(Default)]
+pub(crate) struct SuspendedOutput {
+    capture_stack: Vec<Option<String>>,
+    written: usize,
+}
+
+/// A [`fmt::Write`] that can be told to flush what was written so far.
+pub trait Flush: fmt::Write {
+    /// Invoked at flush points of the template.
+    fn flush(&mut self) -> fmt::Result;
+}
+
 pub struct NullWriter;
 
 impl NullWriter {
@@ -183,3 +303,42 @@ impl<W: io::Write> fmt::Write for WriteWrapper<W> {
             })
     }
 }
+
+impl<W: io::Write> Flush for WriteWrapper<W> {
+    fn flush(&mut self) -> fmt::Result {
+        self.w.flush().map_err(|e| {
+            self.err = Some(e);
+            fmt::Error
+        })
+    }
+}
+
+/// Buffers output and hands it to a callback at every flush point.
+///
+/// The callback can fail the flush to abort the render.
+pub struct ChunkWriter<F> {
+    pub buf: String,
+    pub f: F,
+}
+
+impl<F: FnMut(String) -> fmt::Result> fmt::Write for ChunkWriter<F> {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.buf.write_str(s)
+    }
+
+    #[inline]
+    fn write_char(&mut self, c: char) -> fmt::Result {
+        self.buf.write_char(c)
+    }
+}
+
+impl<F: FnMut(String) -> fmt::Result> Flush for ChunkWriter<F> {
+    fn flush(&mut self) -> fmt::Result {
+        if self.buf.is_empty() {
+            Ok(())
+        } else {
+            (self.f)(std::mem::take(&mut self.buf))
+        }
+    }
+}
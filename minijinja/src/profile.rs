@@ -0,0 +1,149 @@
+//! Support for profiling template renders.
+//!
+//! When a template is rendered with profiling enabled (see
+//! [`RenderOptions::with_profiling`](crate::RenderOptions::with_profiling))
+//! the engine records how many instructions were executed and how much time
+//! was spent on every line of the involved templates.  The measurements are
+//! grouped by the stack of blocks, includes and macro calls they happened in
+//! and are returned as a [`Profile`] together with the
+//! [`Captured`](crate::Captured) render.
+//!
+//! ```
+//! use minijinja::{Environment, RenderOptions};
+//! use minijinja::profile::ProfileMetric;
+//!
+//! let mut env = Environment::new();
+//! env.add_template("layout.html", "{% block body %}{% endblock %}").unwrap();
+//! env.add_template(
+//!     "index.html",
+//!     "{% extends 'layout.html' %}{% block body %}{{ 1 + 1 }}{% endblock %}",
+//! ).unwrap();
+//! let tmpl = env.get_template("index.html").unwrap();
+//! let options = RenderOptions::new().with_profiling(true);
+//! let captured = tmpl.render_captured_with((), &options).unwrap();
+//! let profile = captured.profile().unwrap();
+//! assert!(profile.entries().iter().any(|x| x.scope() == "block body"));
+//! println!("{}", profile.to_folded(ProfileMetric::Time));
+//! ```
+//!
+//! Profiling adds overhead to every executed instruction, so the absolute
+//! timings are only useful to compare different parts of a template with
+//! each other.
+use std::fmt::Write;
+use std::time::Duration;
+
+/// The measurements of a single line within a stack of frames.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileEntry {
+    pub(crate) stack: Vec<String>,
+    pub(crate) template: String,
+    pub(crate) line: usize,
+    pub(crate) instructions: u64,
+    pub(crate) time: Duration,
+}
+
+impl ProfileEntry {
+    /// Returns the frames the line was executed in.
+    ///
+    /// The first frame is the name of the rendered template, the following
+    /// frames are blocks (`block name`), includes (`include name`) and macro
+    /// calls (`macro name`).
+    pub fn stack(&self) -> &[String] {
+        &self.stack
+    }
+
+    /// Returns the innermost frame the line was executed in.
+    pub fn scope(&self) -> &str {
+        self.stack.last().map_or("", |x| x.as_str())
+    }
+
+    /// Returns the name of the template the line belongs to.
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    /// Returns the line number.
+    ///
+    /// This is `0` for instructions that do not belong to a line.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the number of instructions executed on the line.
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    /// Returns the time spent executing instructions on the line.
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+}
+
+/// Selects the value that is reported by [`Profile::to_folded`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProfileMetric {
+    /// The number of executed instructions.
+    Instructions,
+    /// The elapsed time in nanoseconds.
+    Time,
+}
+
+/// The result of a profiled render.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    pub(crate) entries: Vec<ProfileEntry>,
+}
+
+impl Profile {
+    /// Returns all entries in the order they were first executed.
+    pub fn entries(&self) -> &[ProfileEntry] {
+        &self.entries
+    }
+
+    /// Returns the total number of executed instructions.
+    pub fn total_instructions(&self) -> u64 {
+        self.entries.iter().map(|x| x.instructions).sum()
+    }
+
+    /// Returns the total time spent executing instructions.
+    pub fn total_time(&self) -> Duration {
+        self.entries.iter().map(|x| x.time).sum()
+    }
+
+    /// Exports the profile in the folded stack format.
+    ///
+    /// This is the format used by `flamegraph.pl` and
+    /// [inferno](https://github.com/jonhoo/inferno): every line holds the
+    /// semicolon separated frames followed by the measured value.  The
+    /// innermost frame is the line in the form `template:line`.  Entries
+    /// that measured nothing are left out.
+    pub fn to_folded(&self, metric: ProfileMetric) -> String {
+        let mut rv = String::new();
+        for entry in &self.entries {
+            let value = match metric {
+                ProfileMetric::Instructions => entry.instructions as u128,
+                ProfileMetric::Time => entry.time.as_nanos(),
+            };
+            if value == 0 {
+                continue;
+            }
+            for frame in &entry.stack {
+                write_frame(&mut rv, frame);
+                rv.push(';');
+            }
+            write_frame(&mut rv, &entry.template);
+            writeln!(rv, ":{} {}", entry.line, value).ok();
+        }
+        rv
+    }
+}
+
+fn write_frame(rv: &mut String, frame: &str) {
+    // semicolons separate frames and newlines separate stacks
+    rv.extend(frame.chars().map(|c| match c {
+        ';' | '\n' | '\r' => '_',
+        c => c,
+    }));
+}
@@ -0,0 +1,316 @@
+//! Support for sandboxing untrusted templates.
+//!
+//! MiniJinja templates cannot perform I/O or call into code that was not
+//! registered on the environment, but a template can still reach everything
+//! that is passed to it and allocate large amounts of memory.  When rendering
+//! templates from untrusted sources a [`SandboxPolicy`] can be registered with
+//! [`Environment::set_sandbox_policy`](crate::Environment::set_sandbox_policy)
+//! to restrict this further.
+//!
+//! The engine consults the policy before attribute and item lookups, before
+//! calling functions, methods, filters and tests and before operations that can
+//! grow values such as `range()`, string repetition and sequence
+//! concatenation.  Violations fail the render with an error of kind
+//! [`ErrorKind::SecurityError`].
+//!
+//! ```
+//! use minijinja::{context, Environment, ErrorKind, Value};
+//! use minijinja::sandbox::SandboxPolicy;
+//!
+//! struct Policy;
+//!
+//! impl SandboxPolicy for Policy {
+//!     fn is_safe_attribute(&self, _obj: &Value, attr: &str) -> bool {
+//!         !attr.starts_with('_')
+//!     }
+//!
+//!     fn max_range(&self) -> Option<usize> {
+//!         Some(100)
+//!     }
+//! }
+//!
+//! let mut env = Environment::new();
+//! env.set_sandbox_policy(Policy);
+//! let ctx = context! { user => context! { name => "Peter", _password => "secret" } };
+//! assert_eq!(env.render_str("{{ user.name }}", &ctx).unwrap(), "Peter");
+//! let err = env.render_str("{{ user._password }}", &ctx).unwrap_err();
+//! assert_eq!(err.kind(), ErrorKind::SecurityError);
+//! let err = env.render_str("{{ range(1000)|length }}", &ctx).unwrap_err();
+//! assert_eq!(err.kind(), ErrorKind::SecurityError);
+//! ```
+//!
+//! The sandbox policy is not a replacement for the other limits of the
+//! engine.  To limit the runtime of templates use fuel (see
+//! [`Environment::set_fuel`](crate::Environment::set_fuel)) and the
+//! recursion limit.
+use crate::compiler::instructions::Instruction;
+use crate::error::{Error, ErrorKind};
+use crate::value::{Value, ValueKind};
+use crate::Environment;
+
+/// A policy that restricts what templates can do.
+///
+/// All methods have default implementations that permit everything, so
+/// implementations only need to override the checks they care about.  The
+/// checks are invoked with the values the template operates on, which means
+/// that a policy can for instance deny access to specific objects by
+/// downcasting them.
+pub trait SandboxPolicy: Send + Sync + 'static {
+    /// Checks if the attribute `attr` can be looked up on `obj`.
+    ///
+    /// This is invoked for `obj.attr`.
+    fn is_safe_attribute(&self, obj: &Value, attr: &str) -> bool {
+        let _ = (obj, attr);
+        true
+    }
+
+    /// Checks if the item `key` can be looked up on `obj`.
+    ///
+    /// This is invoked for `obj[key]`.
+    fn is_safe_item(&self, obj: &Value, key: &Value) -> bool {
+        let _ = (obj, key);
+        true
+    }
+
+    /// Checks if the method `method` can be called on `obj`.
+    ///
+    /// This is invoked for `obj.method(...)`.
+    fn is_safe_method(&self, obj: &Value, method: &str) -> bool {
+        let _ = (obj, method);
+        true
+    }
+
+    /// Checks if the function `func` can be called under the name `name`.
+    ///
+    /// This is invoked for every call of a value that is not a method call,
+    /// for instance `name(...)` or `items[0](...)`.  As functions can be
+    /// assigned to other variables, the function is additionally checked
+    /// under every name it's registered with as global on the environment.
+    /// If a value is called that was not looked up by name and is not
+    /// registered as global, `name` is empty.
+    fn is_safe_function(&self, name: &str, func: &Value) -> bool {
+        let _ = (name, func);
+        true
+    }
+
+    /// Checks if the filter `name` can be applied.
+    ///
+    /// This is also invoked for filters applied by name from other filters
+    /// such as `map`.  Templates loaded while a policy is set do not evaluate
+    /// filters at compile time.
+    fn is_safe_filter(&self, name: &str) -> bool {
+        let _ = name;
+        true
+    }
+
+    /// Checks if the test `name` can be performed.
+    ///
+    /// This is also invoked for tests performed by name from filters such
+    /// as `select`.
+    fn is_safe_test(&self, name: &str) -> bool {
+        let _ = name;
+        true
+    }
+
+    /// The maximum number of items the `range()` function may produce.
+    ///
+    /// Independently of this setting ranges cannot exceed 100.000 items.
+    fn max_range(&self) -> Option<usize> {
+        None
+    }
+
+    /// The maximum length in bytes of strings created by repeating or
+    /// concatenating strings.
+    fn max_string_length(&self) -> Option<usize> {
+        None
+    }
+
+    /// The maximum number of items of sequences created by repeating or
+    /// concatenating sequences.
+    fn max_sequence_length(&self) -> Option<usize> {
+        None
+    }
+}
+
+fn security_error(msg: String) -> Error {
+    Error::new(ErrorKind::SecurityError, msg)
+}
+
+pub(crate) fn check_attribute(
+    policy: &dyn SandboxPolicy,
+    obj: &Value,
+    attr: &str,
+) -> Result<(), Error> {
+    if policy.is_safe_attribute(obj, attr) {
+        Ok(())
+    } else {
+        Err(security_error(format!(
+            "access to attribute `{attr}` of {} is not allowed",
+            obj.kind()
+        )))
+    }
+}
+
+pub(crate) fn check_item(
+    policy: &dyn SandboxPolicy,
+    obj: &Value,
+    key: &Value,
+) -> Result<(), Error> {
+    if policy.is_safe_item(obj, key) {
+        Ok(())
+    } else {
+        Err(security_error(format!(
+            "access to item {key:?} of {} is not allowed",
+            obj.kind()
+        )))
+    }
+}
+
+pub(crate) fn check_method(
+    policy: &dyn SandboxPolicy,
+    obj: &Value,
+    method: &str,
+) -> Result<(), Error> {
+    if policy.is_safe_method(obj, method) {
+        Ok(())
+    } else {
+        Err(security_error(format!(
+            "calling method `{method}` of {} is not allowed",
+            obj.kind()
+        )))
+    }
+}
+
+pub(crate) fn check_function(
+    policy: &dyn SandboxPolicy,
+    name: &str,
+    func: &Value,
+) -> Result<(), Error> {
+    if policy.is_safe_function(name, func) {
+        Ok(())
+    } else {
+        Err(security_error(format!(
+            "calling function `{name}` is not allowed"
+        )))
+    }
+}
+
+/// Checks if a value can be called.
+///
+/// `name` is the name the value was looked up under, if any.  The value is
+/// also checked under all names it's registered with as global function.
+pub(crate) fn check_call(
+    policy: &dyn SandboxPolicy,
+    env: &Environment<'_>,
+    name: Option<&str>,
+    func: &Value,
+) -> Result<(), Error> {
+    let mut checked = false;
+    if let Some(name) = name {
+        ok!(check_function(policy, name, func));
+        checked = true;
+    }
+    if let Some(obj) = func.as_object() {
+        for global_name in env.sandbox_global_names(obj) {
+            if Some(global_name as &str) != name {
+                ok!(check_function(policy, global_name, func));
+                checked = true;
+            }
+        }
+    }
+    if !checked && !policy.is_safe_function("", func) {
+        return Err(security_error(format!(
+            "calling {} is not allowed",
+            func.kind()
+        )));
+    }
+    Ok(())
+}
+
+pub(crate) fn check_filter(policy: &dyn SandboxPolicy, name: &str) -> Result<(), Error> {
+    if policy.is_safe_filter(name) {
+        Ok(())
+    } else {
+        Err(security_error(format!("filter `{name}` is not allowed")))
+    }
+}
+
+pub(crate) fn check_test(policy: &dyn SandboxPolicy, name: &str) -> Result<(), Error> {
+    if policy.is_safe_test(name) {
+        Ok(())
+    } else {
+        Err(security_error(format!("test `{name}` is not allowed")))
+    }
+}
+
+#[cfg(feature = "builtins")]
+pub(crate) fn check_range(policy: &dyn SandboxPolicy, len: usize) -> Result<(), Error> {
+    match policy.max_range() {
+        Some(max) if len > max => Err(security_error(format!(
+            "range of {len} items exceeds the limit of {max}"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Checks the size of the result of a binary operation before it's performed.
+pub(crate) fn check_binop(
+    policy: &dyn SandboxPolicy,
+    instr: &Instruction,
+    a: &Value,
+    b: &Value,
+) -> Result<(), Error> {
+    let new_len = match instr {
+        Instruction::Mul => {
+            let (value, n) = if is_repeatable(a) { (a, b) } else { (b, a) };
+            match (value.as_str(), value.len(), n.as_usize()) {
+                (Some(s), _, Some(n)) => Some((true, s.len().saturating_mul(n))),
+                (None, Some(len), Some(n)) if is_repeatable(value) => {
+                    Some((false, len.saturating_mul(n)))
+                }
+                _ => None,
+            }
+        }
+        Instruction::Add => match (a.as_str(), b.as_str()) {
+            (Some(a), Some(b)) => Some((true, a.len().saturating_add(b.len()))),
+            _ => match (a.len(), b.len()) {
+                (Some(x), Some(y)) if is_sequence(a) && is_sequence(b) => {
+                    Some((false, x.saturating_add(y)))
+                }
+                _ => None,
+            },
+        },
+        _ => None,
+    };
+    match new_len {
+        Some((true, len)) => check_string_length(policy, len),
+        Some((false, len)) => match policy.max_sequence_length() {
+            Some(max) if len > max => Err(security_error(format!(
+                "sequence of {len} items exceeds the limit of {max}"
+            ))),
+            _ => Ok(()),
+        },
+        None => Ok(()),
+    }
+}
+
+/// Checks the length of a string that was created by the engine.
+pub(crate) fn check_string_length(policy: &dyn SandboxPolicy, len: usize) -> Result<(), Error> {
+    match policy.max_string_length() {
+        Some(max) if len > max => Err(security_error(format!(
+            "string of {len} bytes exceeds the limit of {max}"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+fn is_sequence(value: &Value) -> bool {
+    matches!(
+        value.kind(),
+        ValueKind::Seq | ValueKind::Tuple | ValueKind::Iterable
+    )
+}
+
+fn is_repeatable(value: &Value) -> bool {
+    value.kind() == ValueKind::String || is_sequence(value)
+}
@@ -34,6 +34,7 @@
 //!   - [`{% autoescape %}`](#-autoescape-)
 //!   - [`{% raw %}`](#-raw-)
 //!   - [`{% break %} / {% continue %}`](#-break----continue-)
+//!   - [`{% trans %}`](#-trans-)
 #![cfg_attr(
     feature = "custom_syntax",
     doc = "- [Custom Delimiters](#custom-delimiters)"
@@ -115,6 +116,10 @@
 //! - ``/``: Divide two numbers. ``{{ 1 / 2 }}`` is ``0.5``.  See note on divisions below.
 //! - ``//``: Integer divide two numbers. ``{{ 5 // 3 }}`` is ``1``.  See note on divisions below.
 //! - ``%``: Calculate the remainder of an integer division.  ``{{ 11 % 7 }}`` is ``4``.
+//!   If the left operand is a string, this instead performs printf-style formatting
+//!   like the [`format`](crate::filters::format) filter.  A tuple provides multiple
+//!   arguments, a map can be used for named fields.  ``{{ "%s, %s!" % ("Hello", name) }}``
+//!   and ``{{ "%(x)d" % {"x": 42} }}``.
 //! - ``*``: Multiply the left operand with the right one.  ``{{ 2 * 2 }}`` would return ``4``.
 //! - ``**``: Raise the left operand to the power of the right operand.  ``{{ 2**3 }}``
 //!   would return ``8``.
@@ -593,6 +598,19 @@
 //! Macros also accept a hidden `caller` keyword argument for the use with
 //! `{% call %}`.
 //!
+//! Extra arguments are rejected unless the macro collects them.  Like in Jinja2
+//! a macro that refers to the special `varargs` and `kwargs` variables
+//! receives extra positional arguments as list in `varargs` and extra keyword
+//! arguments as map in `kwargs`.  Alternatively they can be declared explicitly
+//! under a custom name:
+//!
+//! ```jinja
+//! {% macro tag(name, *children, **attrs) -%}
+//!   <{{ name }}{% for key, value in attrs|items %} {{ key }}="{{ value }}"{% endfor
+//!   %}>{{ children|join }}</{{ name }}>
+//! {%- endmacro %}
+//! ```
+//!
 //! ## `{% call %}`
 //!
 //! **Feature:** `macros` (included by default)
@@ -713,6 +731,15 @@
 //! The exact auto escaping behavior is determined by the value of
 //! [`AutoEscape`](crate::AutoEscape) set to the template.
 //!
+//! Instead of a boolean the name of an escaping format can be given to switch
+//! to it: `"html"`, `"json"`, `"shell"`, `"css"`, `"url"`, `"latex"` or `"none"`.
+//!
+//! ```jinja
+//! {% autoescape "shell" %}
+//!   cp {{ source }} {{ target }}
+//! {% endautoescape %}
+//! ```
+//!
 //! ## `{% raw %}`
 //!
 //! A raw block is a special construct that lets you ignore the embedded template
@@ -760,6 +787,27 @@
 //! This is because accessing that attribute will peak into the iterator and
 //! there is no support for "putting values back".
 //!
+//! ## `{% trans %}`
+//!
+//! If MiniJinja was compiled with the `i18n` feature, the `trans` tag marks a
+//! section as translatable.  Within the section only plain variables can be
+//! used, other expressions have to be bound in the tag.  An optional
+//! `pluralize` section provides the plural form which is picked based on the
+//! first variable bound in the tag (or the variable given to `pluralize`):
+//!
+//! ```jinja
+//! {% trans name=user.name %}Hello {{ name }}!{% endtrans %}
+//! {% trans count=items|length %}
+//!   One item.
+//! {% pluralize %}
+//!   {{ count }} items.
+//! {% endtrans %}
+//! ```
+//!
+//! With `{% trans trimmed %}` the whitespace around line breaks is collapsed.
+//! The translations themselves are provided by a translator registered on the
+//! environment.  For more information see the `i18n` module.
+//!
 #![cfg_attr(
     feature = "custom_syntax",
     doc = r###"
@@ -1,7 +1,9 @@
 use std::cell::RefCell;
 use std::collections::{BTreeMap, HashSet};
 use std::ops::Deref;
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std::{fmt, io};
 
 use crate::vendor::self_cell::self_cell;
@@ -10,14 +12,22 @@ use serde::Serialize;
 use crate::compiler::codegen::CodeGenerator;
 use crate::compiler::instructions::Instructions;
 use crate::compiler::lexer::WhitespaceConfig;
+#[cfg(feature = "multi_template")]
+use crate::compiler::meta::find_template_references;
 use crate::compiler::meta::find_undeclared;
+use crate::compiler::optimizer::optimize;
 use crate::compiler::parser::parse;
 use crate::environment::Environment;
 use crate::error::{attach_basic_debug_info, Error};
-use crate::output::{Output, WriteWrapper};
+#[cfg(feature = "async")]
+use crate::output::SuspendedOutput;
+use crate::output::{ChunkWriter, Flush, Output, WriteWrapper};
+use crate::profile::Profile;
 use crate::syntax::SyntaxConfig;
-use crate::utils::AutoEscape;
+use crate::utils::{AutoEscape, UndefinedBehavior};
 use crate::value::Value;
+#[cfg(feature = "async")]
+use crate::vm::AsyncCalls;
 use crate::vm::{prepare_blocks, Context, State, Vm};
 
 /// Callback for auto escape determination
@@ -32,6 +42,10 @@ pub struct TemplateConfig {
     pub ws_config: WhitespaceConfig,
     /// The callback that determines the initial auto escaping for templates.
     pub default_auto_escape: Arc<AutoEscapeFunc>,
+    /// Enables contextual escaping in HTML templates.
+    pub contextual_html_escaping: bool,
+    /// Makes the tuple syntax create tuples instead of lists.
+    pub python_tuples: bool,
 }
 
 impl TemplateConfig {
@@ -40,10 +54,179 @@ impl TemplateConfig {
             syntax_config: SyntaxConfig::default(),
             ws_config: WhitespaceConfig::default(),
             default_auto_escape,
+            contextual_html_escaping: false,
+            python_tuples: false,
         }
     }
 }
 
+/// Options for a single render.
+///
+/// These are passed to [`Template::render_with`] or
+/// [`Template::eval_to_state_with`] and only apply to that render.  They
+/// override some of the settings of the [`Environment`] without having to
+/// clone and reconfigure it:
+///
+/// ```
+/// # use minijinja::{Environment, ErrorKind, RenderOptions, UndefinedBehavior};
+/// let env = Environment::new();
+/// let tmpl = env.template_from_str("Hello {{ name }}{{ suffix }}").unwrap();
+/// let options = RenderOptions::new()
+///     .with_undefined_behavior(UndefinedBehavior::Strict)
+///     .with_global("name", "World");
+/// let err = tmpl.render_with((), &options).unwrap_err();
+/// assert_eq!(err.kind(), ErrorKind::UndefinedError);
+/// ```
+///
+/// They can also be used to stop renders that take too long or that are
+/// no longer needed, for instance because the client of an HTTP request
+/// disconnected.  The engine checks the options periodically while
+/// evaluating the template and fails with
+/// [`ErrorKind::Cancelled`](crate::ErrorKind::Cancelled) once the deadline
+/// passed or the render was cancelled.
+///
+/// ```
+/// # use minijinja::{Environment, ErrorKind, RenderOptions};
+/// use std::sync::atomic::{AtomicBool, Ordering};
+/// use std::sync::Arc;
+///
+/// let env = Environment::new();
+/// let tmpl = env.template_from_str("{% for x in range(10) %}{{ x }}{% endfor %}").unwrap();
+/// let cancelled = Arc::new(AtomicBool::new(false));
+/// let options = RenderOptions::new().with_cancel_flag(cancelled.clone());
+/// assert_eq!(tmpl.render_with((), &options).unwrap(), "0123456789");
+/// cancelled.store(true, Ordering::Relaxed);
+/// assert_eq!(tmpl.render_with((), &options).unwrap_err().kind(), ErrorKind::Cancelled);
+/// ```
+///
+/// Cancellation is cooperative: the engine does not interrupt filters,
+/// functions or methods that are currently being executed.
+#[derive(Debug, Clone, Default)]
+pub struct RenderOptions {
+    pub(crate) deadline: Option<Instant>,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) cancel_flag: Option<Arc<AtomicBool>>,
+    #[cfg(feature = "fuel")]
+    pub(crate) fuel: Option<Option<u64>>,
+    pub(crate) undefined_behavior: Option<UndefinedBehavior>,
+    pub(crate) auto_escape: Option<AutoEscape>,
+    pub(crate) recursion_limit: Option<usize>,
+    pub(crate) globals: Option<Arc<BTreeMap<String, Value>>>,
+    pub(crate) profiling: bool,
+}
+
+impl RenderOptions {
+    /// Creates render options that do not change the render.
+    pub fn new() -> RenderOptions {
+        RenderOptions::default()
+    }
+
+    /// Overrides the fuel of the environment.
+    ///
+    /// `None` disables fuel tracking for this render.  For more information
+    /// see [`Environment::set_fuel`].
+    #[cfg(feature = "fuel")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "fuel")))]
+    pub fn with_fuel(mut self, fuel: Option<u64>) -> RenderOptions {
+        self.fuel = Some(fuel);
+        self
+    }
+
+    /// Overrides the undefined behavior of the environment.
+    ///
+    /// For more information see [`Environment::set_undefined_behavior`].
+    pub fn with_undefined_behavior(mut self, behavior: UndefinedBehavior) -> RenderOptions {
+        self.undefined_behavior = Some(behavior);
+        self
+    }
+
+    /// Overrides the initial auto escaping of the rendered template.
+    ///
+    /// Normally the auto escaping is determined by the auto escape callback
+    /// of the environment (see [`Environment::set_auto_escape_callback`]).
+    /// This only changes the auto escaping of the template that is rendered,
+    /// templates it includes or extends still use the callback.
+    pub fn with_auto_escape(mut self, auto_escape: AutoEscape) -> RenderOptions {
+        self.auto_escape = Some(auto_escape);
+        self
+    }
+
+    /// Overrides the recursion limit of the environment.
+    ///
+    /// For more information see [`Environment::set_recursion_limit`].
+    pub fn with_recursion_limit(mut self, level: usize) -> RenderOptions {
+        #[cfg(not(feature = "stacker"))]
+        {
+            self.recursion_limit = Some(level.min(crate::environment::MAX_RECURSION));
+        }
+        #[cfg(feature = "stacker")]
+        {
+            self.recursion_limit = Some(level);
+        }
+        self
+    }
+
+    /// Enables profiling of the render.
+    ///
+    /// The resulting [`Profile`] is returned by [`Captured::profile`] when
+    /// rendering with [`Template::render_captured_with`].  For more
+    /// information see the [`profile`](crate::profile) module.
+    pub fn with_profiling(mut self, yes: bool) -> RenderOptions {
+        self.profiling = yes;
+        self
+    }
+
+    /// Adds a global variable for this render.
+    ///
+    /// Like the globals of the environment (see [`Environment::add_global`])
+    /// these are visible in all templates of the render but the context
+    /// takes precedence.  They also take precedence over the globals of the
+    /// environment.
+    pub fn with_global<N, V>(mut self, name: N, value: V) -> RenderOptions
+    where
+        N: Into<String>,
+        V: Into<Value>,
+    {
+        Arc::make_mut(self.globals.get_or_insert_with(Default::default))
+            .insert(name.into(), value.into());
+        self
+    }
+
+    /// Aborts the render if it takes longer than `timeout`.
+    ///
+    /// The timeout is measured from the moment the render starts, so the
+    /// same options can be reused for many renders.
+    pub fn with_timeout(mut self, timeout: Duration) -> RenderOptions {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Returns the deadline of a render that starts now.
+    pub(crate) fn effective_deadline(&self) -> Option<Instant> {
+        let from_timeout = self
+            .timeout
+            .and_then(|timeout| Instant::now().checked_add(timeout));
+        match (self.deadline, from_timeout) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Aborts the render once `deadline` passed.
+    pub fn with_deadline(mut self, deadline: Instant) -> RenderOptions {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Aborts the render once `flag` is set to `true`.
+    ///
+    /// The flag can be set from another thread.
+    pub fn with_cancel_flag(mut self, flag: Arc<AtomicBool>) -> RenderOptions {
+        self.cancel_flag = Some(flag);
+        self
+    }
+}
+
 /// Represents a handle to a template.
 ///
 /// Templates are stored in the [`Environment`] as bytecode instructions.  With the
@@ -60,6 +243,7 @@ pub struct Template<'env: 'source, 'source> {
 struct CapturedData<'state> {
     output: String,
     state: State<'state, 'state>,
+    profile: Option<Profile>,
 }
 
 self_cell! {
@@ -91,6 +275,7 @@ impl fmt::Debug for Captured<'_> {
         f.debug_struct("Captured")
             .field("output", &self.output())
             .field("state", &self.state())
+            .field("profile", &self.profile())
             .finish()
     }
 }
@@ -104,6 +289,14 @@ impl<'source> Captured<'source> {
         self.cell.borrow_dependent().output.as_str()
     }
 
+    /// Returns the profile of the render.
+    ///
+    /// This is only available if profiling was enabled with
+    /// [`RenderOptions::with_profiling`].
+    pub fn profile(&self) -> Option<&Profile> {
+        self.cell.borrow_dependent().profile.as_ref()
+    }
+
     /// Returns a reference to the captured state.
     pub fn state(&self) -> &State<'_, '_> {
         &self.cell.borrow_dependent().state
@@ -128,6 +321,26 @@ impl<'source> Captured<'source> {
     }
 }
 
+/// An iterator over the chunks of a render.
+///
+/// Returned by [`Template::render_iter`].
+#[derive(Debug)]
+pub struct RenderIter {
+    chunks: std::vec::IntoIter<String>,
+    err: Option<Error>,
+}
+
+impl Iterator for RenderIter {
+    type Item = Result<String, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.chunks.next() {
+            Some(chunk) => Some(Ok(chunk)),
+            None => self.err.take().map(Err),
+        }
+    }
+}
+
 impl fmt::Debug for Template<'_, '_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut ds = f.debug_struct("Template");
@@ -191,6 +404,29 @@ impl<'env, 'source> Template<'env, 'source> {
         self._render(Value::from_serialize(&ctx)).map(|x| x.0)
     }
 
+    /// Like [`render`](Self::render) but with [`RenderOptions`].
+    ///
+    /// ```
+    /// # use minijinja::{Environment, context, RenderOptions};
+    /// use std::time::Duration;
+    ///
+    /// # let mut env = Environment::new();
+    /// # env.add_template("hello", "Hello {{ name }}!").unwrap();
+    /// let tmpl = env.get_template("hello").unwrap();
+    /// let options = RenderOptions::new().with_timeout(Duration::from_secs(1));
+    /// println!("{}", tmpl.render_with(context!(name => "John"), &options).unwrap());
+    /// ```
+    pub fn render_with<S: Serialize>(
+        &self,
+        ctx: S,
+        options: &RenderOptions,
+    ) -> Result<String, Error> {
+        let root = Value::from_serialize(&ctx);
+        let mut rv = String::with_capacity(self.compiled.buffer_size_hint);
+        ok!(self._eval_with(root, &mut Output::new(&mut rv), options));
+        Ok(rv)
+    }
+
     /// Like [`render`](Self::render) but also return the evaluated [`State`].
     ///
     /// This can be used to inspect the [`State`] of the template post evaluation
@@ -238,7 +474,30 @@ impl<'env, 'source> Template<'env, 'source> {
     /// ```
     pub fn render_captured<S: Serialize>(&self, ctx: S) -> Result<Captured<'source>, Error> {
         self.clone()
-            ._capture_state(Value::from_serialize(&ctx), true)
+            ._capture_state(Value::from_serialize(&ctx), true, &RenderOptions::default())
+    }
+
+    /// Like [`render_captured`](Self::render_captured) but with [`RenderOptions`].
+    ///
+    /// ```
+    /// # use minijinja::{Environment, RenderOptions};
+    /// let env = Environment::new();
+    /// let options = RenderOptions::new().with_profiling(true);
+    /// let captured = env
+    ///     .template_from_str("Hello {{ name }}!")
+    ///     .unwrap()
+    ///     .render_captured_with((), &options)
+    ///     .unwrap();
+    /// assert_eq!(captured.output(), "Hello !");
+    /// assert!(captured.profile().unwrap().total_instructions() > 0);
+    /// ```
+    pub fn render_captured_with<S: Serialize>(
+        &self,
+        ctx: S,
+        options: &RenderOptions,
+    ) -> Result<Captured<'source>, Error> {
+        self.clone()
+            ._capture_state(Value::from_serialize(&ctx), true, options)
     }
 
     /// Like [`render`](Self::render) but writes to an [`io::Write`] and keeps
@@ -265,21 +524,178 @@ impl<'env, 'source> Template<'env, 'source> {
         &self,
         ctx: S,
         w: W,
+    ) -> Result<Captured<'source>, Error> {
+        self.render_captured_to_with(ctx, w, &RenderOptions::default())
+    }
+
+    /// Like [`render_captured_to`](Self::render_captured_to) but with
+    /// [`RenderOptions`].
+    ///
+    /// This is also the replacement for
+    /// [`render_to_write`](Self::render_to_write) with options.
+    ///
+    /// ```
+    /// # use minijinja::{Environment, RenderOptions};
+    /// use std::time::Duration;
+    ///
+    /// let env = Environment::new();
+    /// let options = RenderOptions::new().with_timeout(Duration::from_secs(1));
+    /// let mut buf = Vec::new();
+    /// env.template_from_str("Hello {{ name }}!")
+    ///     .unwrap()
+    ///     .render_captured_to_with((), &mut buf, &options)
+    ///     .unwrap();
+    /// assert_eq!(std::str::from_utf8(&buf).unwrap(), "Hello !");
+    /// ```
+    pub fn render_captured_to_with<S: Serialize, W: io::Write>(
+        &self,
+        ctx: S,
+        w: W,
+        options: &RenderOptions,
     ) -> Result<Captured<'source>, Error> {
         let root = Value::from_serialize(&ctx);
         let w = std::cell::RefCell::new(WriteWrapper { w, err: None });
         self.clone()
-            ._capture_state_with_output(root, &w)
+            ._capture_state_with_output(root, &w, options)
             .map_err(|err| w.into_inner().take_err(err))
     }
 
+    /// Renders the template in chunks.
+    ///
+    /// The returned iterator yields the output whenever the template calls
+    /// the [`flush`](crate::functions::flush) function and once more at the
+    /// end of the render.  Empty chunks are never produced.  This is useful
+    /// for streaming responses where every chunk should go out as one piece,
+    /// for instance by wrapping the iterator into a `Stream<Item = String>`.
+    /// If the render fails, the error is yielded after the chunks flushed
+    /// before the failure.
+    ///
+    /// ```
+    /// # use minijinja::{Environment, context};
+    /// let env = Environment::new();
+    /// let tmpl = env
+    ///     .template_from_str("<head>{{ flush() }}<body>{{ body }}</body>")
+    ///     .unwrap();
+    /// let chunks = tmpl
+    ///     .render_iter(context!(body => "Hello!"))
+    ///     .collect::<Result<Vec<_>, _>>()
+    ///     .unwrap();
+    /// assert_eq!(chunks, ["<head>", "<body>Hello!</body>"]);
+    /// ```
+    ///
+    /// The template is rendered right away, the iterator only hands out the
+    /// chunks collected during the render.  To process the chunks while the
+    /// template is still being rendered use
+    /// [`render_captured_to`](Self::render_captured_to) with a writer that
+    /// handles the flushes.
+    pub fn render_iter<S: Serialize>(&self, ctx: S) -> RenderIter {
+        let mut chunks = Vec::new();
+        let mut w = ChunkWriter {
+            buf: String::new(),
+            f: |chunk| {
+                chunks.push(chunk);
+                Ok(())
+            },
+        };
+        let rv = self
+            ._eval(
+                Value::from_serialize(&ctx),
+                &mut Output::new_flushable(&mut w),
+            )
+            .and_then(|_| w.flush().map_err(Error::from));
+        RenderIter {
+            chunks: chunks.into_iter(),
+            err: rv.err(),
+        }
+    }
+
+    /// Renders the template into a string asynchronously.
+    ///
+    /// This works like [`render`](Self::render) but permits the template to
+    /// call async functions and filters (see
+    /// [`Environment::add_async_function`] and
+    /// [`Value::from_async_function`](crate::value::Value::from_async_function)).
+    /// The returned future does not depend on a specific runtime and can be
+    /// awaited from any executor.
+    ///
+    /// ```
+    /// # use minijinja::{Environment, context, value::{from_args, Value}};
+    /// # async fn test() -> Result<(), minijinja::Error> {
+    /// let mut env = Environment::new();
+    /// env.add_async_function("get_config", |args: Vec<Value>| async move {
+    ///     let (key,): (String,) = from_args(&args)?;
+    ///     Ok(Value::from(format!("config value for {key}")))
+    /// });
+    /// env.add_template("hello", "{{ get_config(key) }}")?;
+    /// let tmpl = env.get_template("hello")?;
+    /// let rv = tmpl.render_async(context!(key => "title")).await?;
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// **How it works:** the engine itself is not async.  When the template
+    /// invokes an async function the evaluation is suspended at the
+    /// instruction that made the call and the future is awaited by the
+    /// returned future.  The evaluation then resumes at that instruction,
+    /// which this time receives the result of the future.  No threads are
+    /// involved, the template is evaluated while the returned future is
+    /// polled.  Async functions invoked by other functions or filters (for
+    /// instance by `map`) suspend the instruction that invoked that function
+    /// or filter.  Such an instruction is evaluated again once the future
+    /// resolved, with the async calls it already made answered from the
+    /// results of the earlier attempts.  Dropping the future cancels the
+    /// render.
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn render_async<S: Serialize>(
+        &self,
+        ctx: S,
+    ) -> impl std::future::Future<Output = Result<String, Error>> + '_ {
+        // serialize eagerly so the future does not have to hold on to `ctx`.
+        self._render_async(Value::from_serialize(&ctx))
+    }
+
+    #[cfg(feature = "async")]
+    async fn _render_async(&self, root: Value) -> Result<String, Error> {
+        let vm = Vm::new(self.env);
+        let calls = Arc::new(AsyncCalls::default());
+        let mut state = ok!(vm.new_async_state(
+            &self.compiled.instructions,
+            root,
+            &self.compiled.blocks,
+            self.compiled.initial_auto_escape,
+            calls.clone(),
+        ));
+        let mut rv = String::with_capacity(self.compiled.buffer_size_hint);
+        let mut captures = SuspendedOutput::default();
+        loop {
+            // the output is not `Send` so it cannot be held across the await.
+            // Instead the captures are carried over to the next step.
+            let pending = {
+                let mut out = Output::new(&mut rv);
+                out.resume(captures);
+                let pending = ok!(vm.eval_async(&mut state, &mut out));
+                captures = out.suspend();
+                pending
+            };
+            match pending {
+                Some(future) => calls.resolve(future.await),
+                None => return Ok(rv),
+            }
+        }
+    }
+
     fn _render(&self, root: Value) -> Result<(String, State<'_, 'env>), Error> {
         let mut rv = String::with_capacity(self.compiled.buffer_size_hint);
         self._eval(root, &mut Output::new(&mut rv))
             .map(|(_, state)| (rv, state))
     }
 
-    fn _capture_state(self, root: Value, capture_output: bool) -> Result<Captured<'source>, Error> {
+    fn _capture_state(
+        self,
+        root: Value,
+        capture_output: bool,
+        options: &RenderOptions,
+    ) -> Result<Captured<'source>, Error> {
         let this: Template<'source, 'source> = self;
         let cell = ok!(CapturedCell::try_new(
             this,
@@ -290,29 +706,41 @@ impl<'env, 'source> Template<'env, 'source> {
                     String::new()
                 };
                 let (_, state) = if capture_output {
-                    ok!(template._eval(root, &mut Output::new(&mut output)))
+                    ok!(template._eval_with(root, &mut Output::new(&mut output), options))
                 } else {
-                    ok!(template._eval(root, &mut Output::null()))
+                    ok!(template._eval_with(root, &mut Output::null(), options))
                 };
-                Ok(CapturedData { output, state })
+                let profile = state.profiler.as_ref().map(|x| x.finish());
+                Ok(CapturedData {
+                    output,
+                    state,
+                    profile,
+                })
             }
         ));
         Ok(Captured { cell })
     }
 
-    fn _capture_state_with_output<W: io::Write>(
+    fn _capture_state_with_output<W: Flush>(
         self,
         root: Value,
-        w: &RefCell<WriteWrapper<W>>,
+        w: &RefCell<W>,
+        options: &RenderOptions,
     ) -> Result<Captured<'source>, Error> {
         let this: Template<'source, 'source> = self;
         let cell = ok!(CapturedCell::try_new(
             this,
             move |template| -> Result<CapturedData<'_>, Error> {
-                let (_, state) = ok!(template._eval(root, &mut Output::new(&mut *w.borrow_mut())));
+                let (_, state) = ok!(template._eval_with(
+                    root,
+                    &mut Output::new_flushable(&mut *w.borrow_mut()),
+                    options
+                ));
+                let profile = state.profiler.as_ref().map(|x| x.finish());
                 Ok(CapturedData {
                     output: String::new(),
                     state,
+                    profile,
                 })
             }
         ));
@@ -343,9 +771,12 @@ impl<'env, 'source> Template<'env, 'source> {
         w: W,
     ) -> Result<State<'_, 'env>, Error> {
         let mut wrapper = WriteWrapper { w, err: None };
-        self._eval(Value::from_serialize(&ctx), &mut Output::new(&mut wrapper))
-            .map(|(_, state)| state)
-            .map_err(|err| wrapper.take_err(err))
+        self._eval(
+            Value::from_serialize(&ctx),
+            &mut Output::new_flushable(&mut wrapper),
+        )
+        .map(|(_, state)| state)
+        .map_err(|err| wrapper.take_err(err))
     }
 
     /// Evaluates the template into a [`State`].
@@ -385,6 +816,44 @@ impl<'env, 'source> Template<'env, 'source> {
         Ok(state)
     }
 
+    /// Like [`eval_to_state`](Self::eval_to_state) but with [`RenderOptions`].
+    ///
+    /// ```
+    /// # use minijinja::{Environment, RenderOptions, value::Value};
+    /// let env = Environment::new();
+    /// let tmpl = env.template_from_str("{% set x = greeting ~ '!' %}").unwrap();
+    /// let options = RenderOptions::new().with_global("greeting", "Hello");
+    /// let state = tmpl.eval_to_state_with((), &options).unwrap();
+    /// assert_eq!(state.lookup("x"), Some(Value::from("Hello!")));
+    /// ```
+    pub fn eval_to_state_with<S: Serialize>(
+        &self,
+        ctx: S,
+        options: &RenderOptions,
+    ) -> Result<State<'_, 'env>, Error> {
+        let root = Value::from_serialize(&ctx);
+        self._eval_with(root, &mut Output::null(), options)
+            .map(|(_, state)| state)
+    }
+
+    fn _eval_with(
+        &self,
+        root: Value,
+        out: &mut Output,
+        options: &RenderOptions,
+    ) -> Result<(Option<Value>, State<'_, 'env>), Error> {
+        Vm::new(self.env).eval_with(
+            &self.compiled.instructions,
+            root,
+            &self.compiled.blocks,
+            out,
+            options
+                .auto_escape
+                .unwrap_or(self.compiled.initial_auto_escape),
+            options,
+        )
+    }
+
     fn _eval(
         &self,
         root: Value,
@@ -435,6 +904,66 @@ impl<'env, 'source> Template<'env, 'source> {
         }
     }
 
+    /// Returns all templates referenced by this template.
+    ///
+    /// This statically finds all `extends`, `include`, `import` and
+    /// `from ... import` statements in the template, including the ones
+    /// in blocks and macros.  Template names given as constants are resolved
+    /// with the [path join callback](crate::Environment::set_path_join_callback)
+    /// just like they would be at runtime.  If a name is computed at runtime
+    /// the reference is still returned but marked as dynamic.  If the
+    /// template cannot be parsed, the syntax error is returned.
+    ///
+    /// ```rust
+    /// # use minijinja::Environment;
+    /// let mut env = Environment::new();
+    /// env.add_template("x", r#"{% extends "base.html" %}{% include partial %}"#).unwrap();
+    /// let tmpl = env.get_template("x").unwrap();
+    /// let refs = tmpl.referenced_templates().unwrap();
+    /// assert_eq!(refs[0].name(), Some("base.html"));
+    /// assert!(refs[1].is_dynamic());
+    /// ```
+    ///
+    /// To get the references of all templates in an environment use
+    /// [`Environment::template_graph`](crate::Environment::template_graph).
+    #[cfg(feature = "multi_template")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "multi_template")))]
+    pub fn referenced_templates(&self) -> Result<Vec<crate::TemplateReference>, Error> {
+        let ast = ok!(self.parse_ast());
+        let mut rv = find_template_references(&ast);
+        for reference in rv.iter_mut() {
+            if let Some(ref mut name) = reference.name {
+                let joined = self.env.join_template_path(name, self.name()).into_owned();
+                *name = joined;
+            }
+        }
+        Ok(rv)
+    }
+
+    /// Serializes the compiled template into bytecode.
+    ///
+    /// The bytecode contains the compiled instructions, the block tables and
+    /// span information together with the source of the template.  It can be
+    /// loaded into an environment with
+    /// [`Environment::add_template_bytecode`](crate::Environment::add_template_bytecode)
+    /// which skips parsing and compiling the template again.  The format is
+    /// specific to the version of MiniJinja that created it.
+    #[cfg(feature = "bytecode")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "bytecode")))]
+    pub fn to_bytecode(&self) -> Result<Vec<u8>, Error> {
+        crate::compiler::bytecode::encode(&self.compiled)
+    }
+
+    /// Parses the source of the template again for static analysis.
+    pub(crate) fn parse_ast(&self) -> Result<crate::compiler::ast::Stmt<'_>, Error> {
+        parse(
+            self.compiled.instructions.source(),
+            self.name(),
+            self.compiled.syntax_config.clone(),
+            Default::default(),
+        )
+    }
+
     /// Creates an empty [`State`] for this template.
     ///
     /// It's very rare that you need to actually do this but it can be useful when
@@ -529,18 +1058,46 @@ impl fmt::Debug for CompiledTemplate<'_> {
 
 impl<'source> CompiledTemplate<'source> {
     /// Creates a compiled template from name and source using the given settings.
+    #[cfg(feature = "unstable_machinery")]
     pub fn new(
         name: &'source str,
         source: &'source str,
         config: &TemplateConfig,
     ) -> Result<CompiledTemplate<'source>, Error> {
-        attach_basic_debug_info(Self::_new_impl(name, source, config), source)
+        Self::compile(name, source, config, None)
+    }
+
+    /// Compiles a template for an environment.
+    ///
+    /// Unlike [`new`](Self::new) this evaluates filters applied to constants
+    /// if filter folding is enabled on the environment.
+    pub(crate) fn new_for_env(
+        name: &'source str,
+        source: &'source str,
+        env: &Environment<'_>,
+    ) -> Result<CompiledTemplate<'source>, Error> {
+        Self::compile(name, source, env.template_config(), Some(env))
+    }
+
+    fn compile(
+        name: &'source str,
+        source: &'source str,
+        config: &TemplateConfig,
+        env: Option<&Environment<'_>>,
+    ) -> Result<CompiledTemplate<'source>, Error> {
+        #[cfg(feature = "tracing")]
+        let span = crate::trace::compile(name);
+        let rv = attach_basic_debug_info(Self::_new_impl(name, source, config, env), source);
+        #[cfg(feature = "tracing")]
+        crate::trace::record_result(&span, &rv);
+        rv
     }
 
     fn _new_impl(
         name: &'source str,
         source: &'source str,
         config: &TemplateConfig,
+        env: Option<&Environment<'_>>,
     ) -> Result<CompiledTemplate<'source>, Error> {
         let ast = ok!(parse(
             source,
@@ -548,16 +1105,30 @@ impl<'source> CompiledTemplate<'source> {
             config.syntax_config.clone(),
             config.ws_config
         ));
+        let initial_auto_escape = (config.default_auto_escape)(name);
         let mut g = CodeGenerator::new(name, source);
+        if config.contextual_html_escaping && initial_auto_escape == AutoEscape::Html {
+            g.enable_html_context_tracking();
+        }
+        if config.python_tuples {
+            g.enable_tuples();
+        }
         g.compile_stmt(&ast);
+        if let Some(err) = g.take_html_context_error() {
+            return Err(err);
+        }
         let buffer_size_hint = g.buffer_size_hint();
-        let (instructions, blocks) = g.finish();
+        let (mut instructions, mut blocks) = g.finish();
+        optimize(&mut instructions, env);
+        for block in blocks.values_mut() {
+            optimize(block, env);
+        }
         Ok(CompiledTemplate {
             instructions,
             blocks,
             buffer_size_hint,
             syntax_config: config.syntax_config.clone(),
-            initial_auto_escape: (config.default_auto_escape)(name),
+            initial_auto_escape,
         })
     }
 }
@@ -0,0 +1,151 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The statement that created a [`TemplateReference`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum TemplateReferenceKind {
+    /// `{% extends ... %}`
+    Extends,
+    /// `{% include ... %}`
+    Include,
+    /// `{% import ... as ... %}`
+    Import,
+    /// `{% from ... import ... %}`
+    FromImport,
+}
+
+/// A reference from one template to another.
+///
+/// References are discovered statically which means that only template names
+/// given as constants can be reported.  If the name is computed at runtime
+/// (for instance `{% extends layout %}`) the reference is reported as dynamic
+/// and [`name`](Self::name) returns `None`.
+///
+/// This is returned by [`Template::referenced_templates`](crate::Template::referenced_templates).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateReference {
+    pub(crate) kind: TemplateReferenceKind,
+    pub(crate) name: Option<String>,
+    pub(crate) ignore_missing: bool,
+    pub(crate) line: usize,
+}
+
+impl TemplateReference {
+    /// Returns the kind of statement that created the reference.
+    pub fn kind(&self) -> TemplateReferenceKind {
+        self.kind
+    }
+
+    /// Returns the name of the referenced template.
+    ///
+    /// If the name can only be determined at runtime, `None` is returned.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Returns `true` if the name of the template is only known at runtime.
+    pub fn is_dynamic(&self) -> bool {
+        self.name.is_none()
+    }
+
+    /// Returns `true` if this is an include with `ignore missing`.
+    pub fn ignore_missing(&self) -> bool {
+        self.ignore_missing
+    }
+
+    /// Returns the line of the reference in the referencing template.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+/// A dependency graph over templates.
+///
+/// The graph maps every template to the templates it references.  It is
+/// returned by [`Environment::template_graph`](crate::Environment::template_graph)
+/// and can be used to figure out which templates need to be invalidated when
+/// a template changes or to produce build manifests.
+///
+/// ```
+/// # use minijinja::Environment;
+/// let mut env = Environment::new();
+/// env.add_template("layout.html", "{% block body %}{% endblock %}").unwrap();
+/// env.add_template("macros.html", "{% macro x() %}{% endmacro %}").unwrap();
+/// env.add_template("index.html", r#"{% extends "layout.html" %}
+///   {% from "macros.html" import x %}"#).unwrap();
+///
+/// let graph = env.template_graph().unwrap();
+/// assert_eq!(
+///     graph.dependencies("index.html").into_iter().collect::<Vec<_>>(),
+///     ["layout.html", "macros.html"]
+/// );
+/// assert_eq!(
+///     graph.dependents("layout.html").into_iter().collect::<Vec<_>>(),
+///     ["index.html"]
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TemplateGraph {
+    pub(crate) references: BTreeMap<String, Vec<TemplateReference>>,
+}
+
+impl TemplateGraph {
+    /// Iterates over the names of all templates in the graph.
+    pub fn templates(&self) -> impl Iterator<Item = &str> {
+        self.references.keys().map(|x| x.as_str())
+    }
+
+    /// Returns the references of a single template.
+    ///
+    /// If the template is not part of the graph, an empty slice is returned.
+    pub fn references(&self, name: &str) -> &[TemplateReference] {
+        self.references.get(name).map_or(&[], |x| &x[..])
+    }
+
+    /// Returns `true` if the template has references that cannot be resolved
+    /// statically.
+    ///
+    /// Such templates might depend on any other template and should be
+    /// treated conservatively when invalidating caches.
+    pub fn has_dynamic_references(&self, name: &str) -> bool {
+        self.references(name).iter().any(|x| x.is_dynamic())
+    }
+
+    /// Returns all templates the given template depends on.
+    ///
+    /// This follows references transitively.  Templates that are referenced
+    /// but not loaded into the environment are included as well.  Dynamic
+    /// references are not considered.
+    pub fn dependencies(&self, name: &str) -> BTreeSet<&str> {
+        let mut rv = BTreeSet::new();
+        let mut todo = vec![name];
+        while let Some(current) = todo.pop() {
+            for dep in self.references(current).iter().filter_map(|x| x.name()) {
+                if rv.insert(dep) {
+                    todo.push(dep);
+                }
+            }
+        }
+        rv.remove(name);
+        rv
+    }
+
+    /// Returns all templates that depend on the given template.
+    ///
+    /// This is the inverse of [`dependencies`](Self::dependencies) and
+    /// returns the templates that need to be invalidated if the given
+    /// template changes.  Dynamic references are not considered.
+    pub fn dependents(&self, name: &str) -> BTreeSet<&str> {
+        let mut rv = BTreeSet::new();
+        let mut todo = vec![name];
+        while let Some(current) = todo.pop() {
+            for (parent, refs) in &self.references {
+                if refs.iter().any(|x| x.name() == Some(current)) && rv.insert(parent.as_str()) {
+                    todo.push(parent);
+                }
+            }
+        }
+        rv.remove(name);
+        rv
+    }
+}
@@ -206,11 +206,12 @@ mod builtins {
     ///
     /// ```jinja
     /// {{ [1, 2, 3] is sequence }} -> true
+    /// {{ (1, 2, 3) is sequence }} -> true
     /// {{ 42 is sequence }} -> false
     /// ```
     #[cfg_attr(docsrs, doc(cfg(feature = "builtins")))]
     pub fn is_sequence(v: &Value) -> bool {
-        matches!(v.kind(), ValueKind::Seq)
+        matches!(v.kind(), ValueKind::Seq | ValueKind::Tuple)
     }
 
     /// Checks if this value can be iterated over.
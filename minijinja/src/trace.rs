@@ -0,0 +1,101 @@
+//! Helpers for the `tracing` integration.
+//!
+//! All spans carry the name of the template they operate on.  Spans that
+//! evaluate templates also record the consumed fuel if fuel tracking is
+//! enabled.  Failures are recorded on the span as `error.kind` and
+//! `error.message`.
+use tracing::field::{debug, display, Empty};
+use tracing::span::EnteredSpan;
+use tracing::Span;
+
+use crate::error::Error;
+use crate::vm::State;
+
+/// Enters the span for compiling a template.
+pub fn compile(name: &str) -> EnteredSpan {
+    tracing::debug_span!(
+        "compile",
+        template = name,
+        error.kind = Empty,
+        error.message = Empty
+    )
+    .entered()
+}
+
+/// Enters the span for invoking the loader.
+pub fn load(name: &str) -> EnteredSpan {
+    tracing::debug_span!(
+        "load",
+        template = name,
+        error.kind = Empty,
+        error.message = Empty
+    )
+    .entered()
+}
+
+/// Enters the span for a render.
+pub fn render(name: &str) -> EnteredSpan {
+    tracing::info_span!(
+        "render",
+        template = name,
+        fuel_consumed = Empty,
+        error.kind = Empty,
+        error.message = Empty
+    )
+    .entered()
+}
+
+/// Enters the span for an include.
+#[cfg(feature = "multi_template")]
+pub fn include(name: &str) -> EnteredSpan {
+    tracing::debug_span!(
+        "include",
+        template = name,
+        fuel_consumed = Empty,
+        error.kind = Empty,
+        error.message = Empty
+    )
+    .entered()
+}
+
+/// Enters the span for a macro call.
+#[cfg(feature = "macros")]
+pub fn call_macro(name: &str, template: &str) -> EnteredSpan {
+    tracing::debug_span!(
+        "macro",
+        name = name,
+        template = template,
+        fuel_consumed = Empty,
+        error.kind = Empty,
+        error.message = Empty
+    )
+    .entered()
+}
+
+/// Returns the fuel consumed so far, used as baseline for [`record_fuel`].
+pub fn fuel_consumed(state: &State) -> Option<u64> {
+    #[cfg(feature = "fuel")]
+    {
+        state.fuel_levels().map(|(consumed, _)| consumed)
+    }
+    #[cfg(not(feature = "fuel"))]
+    {
+        let _ = state;
+        None
+    }
+}
+
+/// Records the fuel consumed since `baseline` was taken.
+pub fn record_fuel(span: &Span, state: &State, baseline: Option<u64>) {
+    if let Some(consumed) = fuel_consumed(state) {
+        span.record("fuel_consumed", consumed - baseline.unwrap_or(0));
+    }
+}
+
+/// Records the error of a result on the span.
+pub fn record_result<T>(span: &Span, rv: &Result<T, Error>) {
+    if let Err(err) = rv {
+        span.record("error.kind", debug(err.kind()));
+        span.record("error.message", display(err));
+    }
+}
@@ -0,0 +1,272 @@
+//! Implementation of the `urlize` filter.
+//!
+//! This follows the behavior of Jinja2's `urlize` but implements the regular
+//! expressions used there by hand.
+use std::fmt::Write;
+
+/// Options for [`urlize`].
+pub struct UrlizeOptions<'a> {
+    pub trim_url_limit: Option<usize>,
+    pub rel: Option<&'a str>,
+    pub target: Option<&'a str>,
+    pub extra_schemes: &'a [String],
+}
+
+/// Escapes text for [`urlize`].
+///
+/// Unlike the regular HTML escaping this leaves slashes alone as the URL
+/// detection runs on the escaped text.
+pub fn escape(text: &str) -> String {
+    let mut rv = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => rv.push_str("&amp;"),
+            '<' => rv.push_str("&lt;"),
+            '>' => rv.push_str("&gt;"),
+            '"' => rv.push_str("&quot;"),
+            '\'' => rv.push_str("&#39;"),
+            c => rv.push(c),
+        }
+    }
+    rv
+}
+
+/// Converts URLs and email addresses in already escaped text into links.
+pub fn urlize(text: &str, options: &UrlizeOptions) -> String {
+    let mut attrs = String::new();
+    if let Some(rel) = options.rel {
+        write!(attrs, " rel=\"{}\"", escape(rel)).ok();
+    }
+    if let Some(target) = options.target {
+        write!(attrs, " target=\"{}\"", escape(target)).ok();
+    }
+
+    let mut rv = String::with_capacity(text.len());
+    let mut rest = text;
+    while !rest.is_empty() {
+        let is_space = rest.starts_with(char::is_whitespace);
+        let end = rest
+            .find(|c: char| c.is_whitespace() != is_space)
+            .unwrap_or(rest.len());
+        let (word, next) = rest.split_at(end);
+        if is_space {
+            rv.push_str(word);
+        } else {
+            urlize_word(&mut rv, word, &attrs, options);
+        }
+        rest = next;
+    }
+    rv
+}
+
+fn urlize_word(rv: &mut String, word: &str, attrs: &str, options: &UrlizeOptions) {
+    let mut middle = word;
+    loop {
+        if middle.starts_with(['(', '<']) {
+            middle = &middle[1..];
+        } else if let Some(rest) = middle.strip_prefix("&lt;") {
+            middle = rest;
+        } else {
+            break;
+        }
+    }
+    let head = &word[..word.len() - middle.len()];
+
+    let mut tail_start = middle.len();
+    loop {
+        let current = &middle[..tail_start];
+        if current.ends_with("&gt;") {
+            tail_start -= 4;
+        } else if current.ends_with([')', '>', '.', ',', '\n']) {
+            tail_start -= 1;
+        } else {
+            break;
+        }
+    }
+    let mut middle = middle[..tail_start].to_string();
+    let mut tail = &word[head.len() + tail_start..];
+
+    // prefer balancing parentheses in urls over ignoring trailing characters
+    for (start, end) in [("(", ")"), ("<", ">"), ("&lt;", "&gt;")] {
+        let start_count = middle.matches(start).count();
+        if start_count <= middle.matches(end).count() {
+            continue;
+        }
+        for _ in 0..start_count.min(tail.matches(end).count()) {
+            let end_index = tail.find(end).unwrap() + end.len();
+            middle.push_str(&tail[..end_index]);
+            tail = &tail[end_index..];
+        }
+    }
+
+    rv.push_str(head);
+    if is_http_url(&middle) {
+        let scheme = if middle.starts_with("https://") || middle.starts_with("http://") {
+            ""
+        } else {
+            "https://"
+        };
+        write!(
+            rv,
+            "<a href=\"{scheme}{middle}\"{attrs}>{}</a>",
+            trim_url(&middle, options.trim_url_limit)
+        )
+        .ok();
+    } else if middle.strip_prefix("mailto:").is_some_and(is_email) {
+        write!(rv, "<a href=\"{middle}\">{}</a>", &middle[7..]).ok();
+    } else if middle.contains('@')
+        && !middle.starts_with("www.")
+        && !middle.contains(':')
+        && is_email(&middle)
+    {
+        write!(rv, "<a href=\"mailto:{middle}\">{middle}</a>").ok();
+    } else if options
+        .extra_schemes
+        .iter()
+        .any(|scheme| middle != *scheme && middle.starts_with(scheme.as_str()))
+    {
+        write!(rv, "<a href=\"{middle}\"{attrs}>{middle}</a>").ok();
+    } else {
+        rv.push_str(&middle);
+    }
+    rv.push_str(tail);
+}
+
+fn trim_url(url: &str, limit: Option<usize>) -> String {
+    match limit {
+        Some(limit) if url.chars().count() > limit => {
+            format!("{}...", url.chars().take(limit).collect::<String>())
+        }
+        _ => url.to_string(),
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn is_label_char(c: char) -> bool {
+    is_word_char(c) || c == '%' || c == '-'
+}
+
+/// Checks if a string is a URL or looks like a domain.
+fn is_http_url(s: &str) -> bool {
+    let lower = s.to_ascii_lowercase();
+    let (has_scheme, rest) = if let Some(rest) = lower.strip_prefix("https://") {
+        (true, rest)
+    } else if let Some(rest) = lower.strip_prefix("http://") {
+        (true, rest)
+    } else {
+        (false, lower.as_str())
+    };
+
+    let host_end = if has_scheme && rest.starts_with('[') {
+        rest.find(']').map_or(rest.len(), |x| x + 1)
+    } else {
+        rest.find([':', '/', '?', '#']).unwrap_or(rest.len())
+    };
+    let (host, mut rest) = rest.split_at(host_end);
+    if let Some(port) = rest.strip_prefix(':') {
+        let digits = port.bytes().take_while(|x| x.is_ascii_digit()).count();
+        if !(1..=5).contains(&digits) {
+            return false;
+        }
+        rest = &port[digits..];
+    }
+    if !(rest.is_empty() || rest.starts_with(['/', '?', '#'])) {
+        return false;
+    }
+
+    if has_scheme {
+        is_ipv4(host) || is_ipv6(host) || is_host_with_tld(host)
+    } else if let Some(host) = host.strip_prefix("www.") {
+        is_host_with_tld(host)
+    } else {
+        is_basic_domain(host)
+    }
+}
+
+/// Matches `([\w%-]+\.)*([a-z]{2,63}|xn--[\w%]{2,59})`.
+fn is_host_with_tld(host: &str) -> bool {
+    let (labels, tld) = match host.rsplit_once('.') {
+        Some((labels, tld)) => (Some(labels), tld),
+        None => (None, host),
+    };
+    let tld_ok = if let Some(idna) = tld.strip_prefix("xn--") {
+        (2..=59).contains(&idna.chars().count())
+            && idna.chars().all(|c| is_word_char(c) || c == '%')
+    } else {
+        (2..=63).contains(&tld.len()) && tld.bytes().all(|x| x.is_ascii_lowercase())
+    };
+    tld_ok
+        && labels.map_or(true, |labels| {
+            labels
+                .split('.')
+                .all(|label| !label.is_empty() && label.chars().all(is_label_char))
+        })
+}
+
+/// Matches `([\w%-]{2,63}\.)+(com|net|int|edu|gov|org|info|mil)`.
+fn is_basic_domain(host: &str) -> bool {
+    match host.rsplit_once('.') {
+        Some((labels, tld)) => {
+            matches!(
+                tld,
+                "com" | "net" | "int" | "edu" | "gov" | "org" | "info" | "mil"
+            ) && labels.split('.').all(|label| {
+                (2..=63).contains(&label.chars().count()) && label.chars().all(is_label_char)
+            })
+        }
+        None => false,
+    }
+}
+
+fn is_ipv4(host: &str) -> bool {
+    let parts = host.split('.').collect::<Vec<_>>();
+    parts.len() == 4
+        && parts
+            .iter()
+            .all(|x| (1..=3).contains(&x.len()) && x.bytes().all(|x| x.is_ascii_digit()))
+}
+
+fn is_ipv6(host: &str) -> bool {
+    host.strip_prefix('[')
+        .and_then(|x| x.strip_suffix(']'))
+        .is_some_and(|addr| {
+            addr.matches(':').count() >= 2
+                && addr.bytes().all(|x| x == b':' || x.is_ascii_hexdigit())
+                && addr.split(':').all(|x| x.len() <= 4)
+        })
+}
+
+/// Matches `\S+@\w[\w.-]*\.\w+`.
+fn is_email(s: &str) -> bool {
+    let Some((local, domain)) = s.rsplit_once('@') else {
+        return false;
+    };
+    let Some((_, tld)) = domain.rsplit_once('.') else {
+        return false;
+    };
+    !local.is_empty()
+        && !local.contains(char::is_whitespace)
+        && domain.starts_with(is_word_char)
+        && domain
+            .chars()
+            .all(|c| is_word_char(c) || c == '.' || c == '-')
+        && !tld.is_empty()
+        && tld.chars().all(is_word_char)
+}
+
+/// Checks if a string is a valid URI scheme prefix such as `ftp://`.
+pub fn is_valid_scheme(s: &str) -> bool {
+    match s.split_once(':') {
+        Some((scheme, slashes)) => {
+            scheme.chars().count() >= 2
+                && scheme
+                    .chars()
+                    .all(|c| is_word_char(c) || matches!(c, '.' | '+' | '-'))
+                && matches!(slashes, "" | "/" | "//")
+        }
+        None => false,
+    }
+}
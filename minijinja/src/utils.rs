@@ -1,7 +1,7 @@
 use std::char::decode_utf16;
 use std::cmp::Ordering;
 use std::collections::BTreeMap;
-use std::fmt;
+use std::fmt::{self, Write};
 use std::iter::{once, repeat};
 use std::str::Chars;
 use std::sync::OnceLock;
@@ -118,6 +118,24 @@ fn write_with_html_escaping(out: &mut Output, value: &Value) -> fmt::Result {
     }
 }
 
+fn write_with_escaping(
+    out: &mut Output,
+    value: &Value,
+    write_escaped: fn(&mut Output, &str) -> fmt::Result,
+) -> Result<(), Error> {
+    if let Some(s) = value.as_str() {
+        write_escaped(out, s)
+    } else if matches!(
+        value.kind(),
+        ValueKind::Undefined | ValueKind::None | ValueKind::Bool | ValueKind::Number
+    ) {
+        write!(out, "{value}")
+    } else {
+        write_escaped(out, &value.to_string())
+    }
+    .map_err(Error::from)
+}
+
 #[cold]
 fn invalid_autoescape(name: &str) -> Result<(), Error> {
     Err(Error::new(
@@ -150,6 +168,18 @@ pub fn write_escaped(
         AutoEscape::Html => write_with_html_escaping(out, value).map_err(Error::from),
         #[cfg(feature = "json")]
         AutoEscape::Json => json_escape_write(out, value),
+        AutoEscape::Shell => {
+            write_with_escaping(out, value, |out, s| write!(out, "{}", ShellEscape(s)))
+        }
+        AutoEscape::Css => {
+            write_with_escaping(out, value, |out, s| write!(out, "{}", CssEscape(s)))
+        }
+        AutoEscape::Url => {
+            write_with_escaping(out, value, |out, s| write!(out, "{}", UrlEscape(s)))
+        }
+        AutoEscape::Latex => {
+            write_with_escaping(out, value, |out, s| write!(out, "{}", LatexEscape(s)))
+        }
         AutoEscape::Custom(name) => invalid_autoescape(name),
     }
 }
@@ -175,10 +205,32 @@ pub enum AutoEscape {
     #[cfg(feature = "json")]
     #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
     Json,
+    /// Use POSIX shell quoting rules.
+    ///
+    /// Strings that contain characters other than ASCII letters, digits and
+    /// `_@%+=:,./-` are wrapped in single quotes so that they end up as a
+    /// single shell word.  Empty strings are printed as `''`.
+    Shell,
+    /// Use escaping rules suitable for CSS strings and identifiers.
+    ///
+    /// All ASCII characters other than letters and digits are replaced by
+    /// six digit hexadecimal escapes (eg: `\00003c`).
+    Css,
+    /// Use percent encoding suitable for URL components.
+    ///
+    /// All characters except ASCII letters, digits and `-._~` are percent
+    /// encoded as UTF-8.
+    Url,
+    /// Use escaping rules suitable for LaTeX documents.
+    ///
+    /// The special characters `\`, `{`, `}`, `$`, `&`, `#`, `%`, `_`, `^`, `~`,
+    /// `<` and `>` are replaced by their escaped equivalents.
+    Latex,
     /// A custom auto escape format.
     ///
-    /// The default formatter does not know how to deal with a custom escaping
-    /// format and would error.  The use of these requires a custom formatter.
+    /// The default formatter supports custom formats registered with
+    /// [`add_auto_escape`](crate::Environment::add_auto_escape) and errors for
+    /// all others.  Alternatively they can be handled by a custom formatter.
     /// See [`set_formatter`](crate::Environment::set_formatter).
     Custom(&'static str),
 }
@@ -228,17 +280,17 @@ impl UndefinedBehavior {
     /// Utility method used in the engine to determine what to do when an undefined is
     /// encountered.
     ///
-    /// The flag indicates if this is the first or second level of undefined value.  The
-    /// parent value is passed too.
-    pub(crate) fn handle_undefined(self, parent_was_undefined: bool) -> Result<Value, Error> {
-        match (self, parent_was_undefined) {
+    /// The parent value is the value the lookup was performed on.  Looking up
+    /// something on an undefined value fails unless the behavior is chainable.
+    pub(crate) fn handle_undefined(self, parent: &Value) -> Result<(), Error> {
+        match (self, parent.is_undefined()) {
             (UndefinedBehavior::Lenient, false)
             | (UndefinedBehavior::Strict, false)
             | (UndefinedBehavior::SemiStrict, false)
-            | (UndefinedBehavior::Chainable, _) => Ok(Value::UNDEFINED),
+            | (UndefinedBehavior::Chainable, _) => Ok(()),
             (UndefinedBehavior::Lenient, true)
             | (UndefinedBehavior::Strict, true)
-            | (UndefinedBehavior::SemiStrict, true) => Err(Error::from(ErrorKind::UndefinedError)),
+            | (UndefinedBehavior::SemiStrict, true) => Err(parent.undefined_error()),
         }
     }
 
@@ -249,8 +301,8 @@ impl UndefinedBehavior {
     pub(crate) fn is_true(self, value: &Value) -> Result<bool, Error> {
         match (self, &value.0) {
             // silent undefined doesn't error, even in strict mode
-            (UndefinedBehavior::Strict, &ValueRepr::Undefined(UndefinedType::Default)) => {
-                Err(Error::from(ErrorKind::UndefinedError))
+            (UndefinedBehavior::Strict, &ValueRepr::Undefined(UndefinedType::Default, _)) => {
+                Err(value.undefined_error())
             }
             _ => Ok(value.is_true()),
         }
@@ -273,8 +325,8 @@ impl UndefinedBehavior {
             // silent undefined doesn't error, even in strict mode
             (
                 UndefinedBehavior::Strict | UndefinedBehavior::SemiStrict,
-                &ValueRepr::Undefined(UndefinedType::Default),
-            ) => Err(Error::from(ErrorKind::UndefinedError)),
+                &ValueRepr::Undefined(UndefinedType::Default, _),
+            ) => Err(value.undefined_error()),
             _ => Ok(()),
         }
     }
@@ -291,8 +343,8 @@ impl UndefinedBehavior {
             // silent undefined never errors
             (
                 UndefinedBehavior::Strict | UndefinedBehavior::SemiStrict,
-                &ValueRepr::Undefined(UndefinedType::Default),
-            ) => Err(Error::from(ErrorKind::UndefinedError)),
+                &ValueRepr::Undefined(UndefinedType::Default, _),
+            ) => Err(value.undefined_error()),
             _ => Ok(()),
         }
     }
@@ -349,6 +401,85 @@ impl fmt::Display for HtmlEscape<'_> {
     }
 }
 
+/// Helper to quote a string for POSIX shells.
+pub struct ShellEscape<'a>(pub &'a str);
+
+impl fmt::Display for ShellEscape<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.0.is_empty()
+            && self.0.bytes().all(|b| {
+                b.is_ascii_alphanumeric()
+                    || matches!(
+                        b,
+                        b'_' | b'@' | b'%' | b'+' | b'=' | b':' | b',' | b'.' | b'/' | b'-'
+                    )
+            })
+        {
+            return f.write_str(self.0);
+        }
+        ok!(f.write_char('\''));
+        for (idx, part) in self.0.split('\'').enumerate() {
+            if idx > 0 {
+                ok!(f.write_str("'\"'\"'"));
+            }
+            ok!(f.write_str(part));
+        }
+        f.write_char('\'')
+    }
+}
+
+/// Helper to escape a string for CSS.
+pub struct CssEscape<'a>(pub &'a str);
+
+impl fmt::Display for CssEscape<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            if c.is_ascii_alphanumeric() || !c.is_ascii() {
+                ok!(f.write_char(c));
+            } else {
+                ok!(write!(f, "\\{:06x}", c as u32));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Helper to percent encode a URL component.
+pub struct UrlEscape<'a>(pub &'a str);
+
+impl fmt::Display for UrlEscape<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for b in self.0.bytes() {
+            if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~') {
+                ok!(f.write_char(b as char));
+            } else {
+                ok!(write!(f, "%{b:02X}"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Helper to escape a string for LaTeX.
+pub struct LatexEscape<'a>(pub &'a str);
+
+impl fmt::Display for LatexEscape<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            ok!(match c {
+                '\\' => f.write_str("\\textbackslash{}"),
+                '{' | '}' | '$' | '&' | '#' | '%' | '_' => write!(f, "\\{c}"),
+                '^' => f.write_str("\\textasciicircum{}"),
+                '~' => f.write_str("\\textasciitilde{}"),
+                '<' => f.write_str("\\textless{}"),
+                '>' => f.write_str("\\textgreater{}"),
+                c => f.write_char(c),
+            });
+        }
+        Ok(())
+    }
+}
+
 struct Unescaper {
     out: String,
     pending_surrogate: u16,
@@ -41,7 +41,7 @@ impl<I: Into<Value>> FunctionResult for I {
 /// Since it's more convenient to write filters and tests with concrete
 /// types instead of values, this helper trait exists to automatically
 /// perform this conversion.  It is implemented for functions up to an
-/// arity of 5 parameters.
+/// arity of 7 parameters.
 ///
 /// For each argument the conversion is performed via the [`ArgType`]
 /// trait which is implemented for many common types.  For manual
@@ -251,6 +251,8 @@ tuple_impls! { A *B }
 tuple_impls! { A B *C }
 tuple_impls! { A B C *D }
 tuple_impls! { A B C D *E }
+tuple_impls! { A B C D E *F }
+tuple_impls! { A B C D E F *G }
 
 impl From<ValueRepr> for Value {
     #[inline(always)]
@@ -958,7 +960,7 @@ impl TryFrom<Value> for Kwargs {
 
     fn try_from(value: Value) -> Result<Self, Self::Error> {
         match value.0 {
-            ValueRepr::Undefined(_) => Ok(Kwargs::new(Default::default())),
+            ValueRepr::Undefined(..) => Ok(Kwargs::new(Default::default())),
             ValueRepr::Object(_) => {
                 Kwargs::extract(&value).ok_or_else(|| Error::from(ErrorKind::InvalidOperation))
             }
@@ -1036,7 +1038,10 @@ impl<'a, T: ArgType<'a, Output = T>> ArgType<'a> for Vec<T> {
             Some(value) => {
                 let iter = ok!(value
                     .as_object()
-                    .filter(|x| matches!(x.repr(), ObjectRepr::Seq | ObjectRepr::Iterable))
+                    .filter(|x| matches!(
+                        x.repr(),
+                        ObjectRepr::Seq | ObjectRepr::Tuple | ObjectRepr::Iterable
+                    ))
                     .and_then(|x| x.try_iter())
                     .ok_or_else(|| { Error::new(ErrorKind::InvalidOperation, "not iterable") }));
                 let mut rv = Vec::new();
@@ -1057,7 +1062,10 @@ impl<'a, T: ArgType<'a, Output = T>> ArgType<'a> for Vec<T> {
             Some(value) => {
                 let iter = ok!(value
                     .as_object()
-                    .filter(|x| matches!(x.repr(), ObjectRepr::Seq | ObjectRepr::Iterable))
+                    .filter(|x| matches!(
+                        x.repr(),
+                        ObjectRepr::Seq | ObjectRepr::Tuple | ObjectRepr::Iterable
+                    ))
                     .and_then(|x| x.try_iter())
                     .ok_or_else(|| { Error::new(ErrorKind::InvalidOperation, "not iterable") }));
                 let mut rv = Vec::new();
@@ -1072,7 +1080,10 @@ impl<'a, T: ArgType<'a, Output = T>> ArgType<'a> for Vec<T> {
     fn from_value_owned(value: Value) -> Result<Self, Error> {
         let iter = ok!(value
             .as_object()
-            .filter(|x| matches!(x.repr(), ObjectRepr::Seq | ObjectRepr::Iterable))
+            .filter(|x| matches!(
+                x.repr(),
+                ObjectRepr::Seq | ObjectRepr::Tuple | ObjectRepr::Iterable
+            ))
             .and_then(|x| x.try_iter())
             .ok_or_else(|| { Error::new(ErrorKind::InvalidOperation, "not iterable") }));
         let mut rv = Vec::new();
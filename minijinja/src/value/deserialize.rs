@@ -181,11 +181,11 @@ impl<'de> Deserializer<'de> for Value {
             ValueRepr::F64(v) => visitor.visit_f64(v),
             ValueRepr::String(ref v, _) => visitor.visit_str(v),
             ValueRepr::SmallStr(v) => visitor.visit_str(v.as_str()),
-            ValueRepr::Undefined(_) | ValueRepr::None => visitor.visit_unit(),
+            ValueRepr::Undefined(..) | ValueRepr::None => visitor.visit_unit(),
             ValueRepr::Bytes(ref v) => visitor.visit_bytes(v),
             ValueRepr::Object(o) => match o.repr() {
                 ObjectRepr::Plain => Err(de::Error::custom("cannot deserialize plain objects")),
-                ObjectRepr::Seq | ObjectRepr::Iterable => {
+                ObjectRepr::Seq | ObjectRepr::Tuple | ObjectRepr::Iterable => {
                     visitor.visit_seq(SeqDeserializer::new(o.try_iter().into_iter().flatten()))
                 }
                 ObjectRepr::Map => visitor.visit_map(MapDeserializer::new(
@@ -197,7 +197,7 @@ impl<'de> Deserializer<'de> for Value {
 
     fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
         match self.0 {
-            ValueRepr::None | ValueRepr::Undefined(_) => visitor.visit_unit(),
+            ValueRepr::None | ValueRepr::Undefined(..) => visitor.visit_unit(),
             _ => visitor.visit_some(self),
         }
     }
@@ -306,10 +306,12 @@ impl<'de> VariantAccess<'de> for VariantDeserializer {
 
     fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
         match self.value.as_ref().and_then(|x| x.as_object()) {
-            Some(obj) if matches!(obj.repr(), ObjectRepr::Seq) => Deserializer::deserialize_any(
-                SeqDeserializer::new(obj.try_iter().into_iter().flatten()),
-                visitor,
-            ),
+            Some(obj) if matches!(obj.repr(), ObjectRepr::Seq | ObjectRepr::Tuple) => {
+                Deserializer::deserialize_any(
+                    SeqDeserializer::new(obj.try_iter().into_iter().flatten()),
+                    visitor,
+                )
+            }
             _ => Err(de::Error::invalid_type(
                 self.value
                     .as_ref()
@@ -405,7 +407,7 @@ impl de::Error for Error {
 
 fn value_to_unexpected(value: &Value) -> Unexpected<'_> {
     match value.0 {
-        ValueRepr::Undefined(_) | ValueRepr::None => Unexpected::Unit,
+        ValueRepr::Undefined(..) | ValueRepr::None => Unexpected::Unit,
         ValueRepr::Bool(val) => Unexpected::Bool(val),
         ValueRepr::U64(val) => Unexpected::Unsigned(val),
         ValueRepr::I64(val) => Unexpected::Signed(val),
@@ -217,6 +217,7 @@ use crate::vm::State;
 
 pub use crate::value::argtypes::{from_args, ArgType, FunctionArgs, FunctionResult, Kwargs, Rest};
 pub use crate::value::merge_object::merge_maps;
+use crate::value::object::Tuple;
 pub use crate::value::object::{DynObject, Enumerator, Object, ObjectExt, ObjectRepr};
 
 #[macro_use]
@@ -353,6 +354,8 @@ pub enum ValueKind {
     Bytes,
     /// The value is an array of other values.
     Seq,
+    /// The value is an immutable array of other values (a tuple).
+    Tuple,
     /// The value is a key/value mapping.
     Map,
     /// An iterable
@@ -378,6 +381,7 @@ impl fmt::Display for ValueKind {
             ValueKind::String => "string",
             ValueKind::Bytes => "bytes",
             ValueKind::Seq => "sequence",
+            ValueKind::Tuple => "tuple",
             ValueKind::Map => "map",
             ValueKind::Iterable => "iterator",
             ValueKind::Plain => "plain object",
@@ -400,6 +404,24 @@ pub(crate) enum UndefinedType {
     Silent,
 }
 
+/// Records where an undefined value was created.
+///
+/// This is attached to undefined values produced by failed lookups in the
+/// engine so that undefined errors can point to the lookup that failed.  As
+/// failed lookups are common (eg: `x is defined`), this only records the
+/// instruction of the lookup.  It's turned into a message by the engine once
+/// an error is actually raised in the same instructions.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct UndefinedOrigin {
+    /// The [id](crate::compiler::instructions::Instructions::id) of the
+    /// instructions the lookup happened in.
+    pub instructions_id: usize,
+    /// The index of the lookup instruction.
+    pub pc: u32,
+    /// The kind of the value an attribute or item was missing on.
+    pub parent: Option<ValueKind>,
+}
+
 /// Wraps an internal copyable value but marks it as packed.
 ///
 /// This is used for `i128`/`u128` in the value repr to avoid
@@ -456,7 +478,7 @@ impl SmallStr {
 #[derive(Clone)]
 pub(crate) enum ValueRepr {
     None,
-    Undefined(UndefinedType),
+    Undefined(UndefinedType, Option<UndefinedOrigin>),
     Bool(bool),
     U64(u64),
     I64(i64),
@@ -473,7 +495,7 @@ pub(crate) enum ValueRepr {
 impl fmt::Debug for ValueRepr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
-            ValueRepr::Undefined(_) => f.write_str("undefined"),
+            ValueRepr::Undefined(..) => f.write_str("undefined"),
             ValueRepr::Bool(ref val) => fmt::Debug::fmt(val, f),
             ValueRepr::U64(ref val) => fmt::Debug::fmt(val, f),
             ValueRepr::I64(ref val) => fmt::Debug::fmt(val, f),
@@ -503,7 +525,7 @@ impl fmt::Debug for ValueRepr {
 impl Hash for Value {
     fn hash<H: Hasher>(&self, state: &mut H) {
         match self.0 {
-            ValueRepr::None | ValueRepr::Undefined(_) => 0u8.hash(state),
+            ValueRepr::None | ValueRepr::Undefined(..) => 0u8.hash(state),
             ValueRepr::String(ref s, _) => s.hash(state),
             ValueRepr::SmallStr(ref s) => s.as_str().hash(state),
             ValueRepr::Bool(b) => b.hash(state),
@@ -533,7 +555,7 @@ impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
         match (&self.0, &other.0) {
             (&ValueRepr::None, &ValueRepr::None) => true,
-            (&ValueRepr::Undefined(_), &ValueRepr::Undefined(_)) => true,
+            (&ValueRepr::Undefined(..), &ValueRepr::Undefined(..)) => true,
             (&ValueRepr::String(ref a, _), &ValueRepr::String(ref b, _)) => a == b,
             (&ValueRepr::SmallStr(ref a), &ValueRepr::SmallStr(ref b)) => a.as_str() == b.as_str(),
             (&ValueRepr::Bytes(ref a), &ValueRepr::Bytes(ref b)) => a == b,
@@ -582,8 +604,8 @@ impl PartialEq for Value {
                                 }
                             }
                             (
-                                ObjectRepr::Seq | ObjectRepr::Iterable,
-                                ObjectRepr::Seq | ObjectRepr::Iterable,
+                                ObjectRepr::Seq | ObjectRepr::Tuple | ObjectRepr::Iterable,
+                                ObjectRepr::Seq | ObjectRepr::Tuple | ObjectRepr::Iterable,
                             ) => {
                                 if let (Some(ak), Some(bk)) = (a.try_iter(), b.try_iter()) {
                                     ak.eq(bk)
@@ -632,7 +654,7 @@ impl Ord for Value {
         }
         match (&self.0, &other.0) {
             (&ValueRepr::None, &ValueRepr::None) => Ordering::Equal,
-            (&ValueRepr::Undefined(_), &ValueRepr::Undefined(_)) => Ordering::Equal,
+            (&ValueRepr::Undefined(..), &ValueRepr::Undefined(..)) => Ordering::Equal,
             (&ValueRepr::String(ref a, _), &ValueRepr::String(ref b, _)) => a.cmp(b),
             (&ValueRepr::SmallStr(ref a), &ValueRepr::SmallStr(ref b)) => {
                 a.as_str().cmp(b.as_str())
@@ -667,8 +689,8 @@ impl Ord for Value {
                                 }
                             }
                             (
-                                ObjectRepr::Seq | ObjectRepr::Iterable,
-                                ObjectRepr::Seq | ObjectRepr::Iterable,
+                                ObjectRepr::Seq | ObjectRepr::Tuple | ObjectRepr::Iterable,
+                                ObjectRepr::Seq | ObjectRepr::Tuple | ObjectRepr::Iterable,
                             ) => match (a.try_iter(), b.try_iter()) {
                                 (Some(a), Some(b)) => a.cmp(b),
                                 _ => unreachable!(),
@@ -696,7 +718,7 @@ impl fmt::Debug for Value {
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self.0 {
-            ValueRepr::Undefined(_) => Ok(()),
+            ValueRepr::Undefined(..) => Ok(()),
             ValueRepr::Bool(val) => val.fmt(f),
             ValueRepr::U64(val) => val.fmt(f),
             ValueRepr::I64(val) => val.fmt(f),
@@ -727,7 +749,7 @@ impl fmt::Display for Value {
 
 impl Default for Value {
     fn default() -> Value {
-        ValueRepr::Undefined(UndefinedType::Default).into()
+        ValueRepr::Undefined(UndefinedType::Default, None).into()
     }
 }
 
@@ -743,7 +765,7 @@ impl Value {
     ///
     /// This constant exists because the undefined type does not exist in Rust
     /// and this is the only way to construct it.
-    pub const UNDEFINED: Value = Value(ValueRepr::Undefined(UndefinedType::Default));
+    pub const UNDEFINED: Value = Value(ValueRepr::Undefined(UndefinedType::Default, None));
 
     /// Creates a value from something that can be serialized.
     ///
@@ -898,6 +920,27 @@ impl Value {
         Value::from(ValueRepr::Object(value.into()))
     }
 
+    /// Creates a tuple from an iterator of values.
+    ///
+    /// Tuples behave like sequences but report [`ValueKind::Tuple`] as kind and
+    /// render with parentheses.  This is also what the tuple syntax in templates
+    /// creates if [`Environment::set_python_tuples`](crate::Environment::set_python_tuples)
+    /// is enabled.
+    ///
+    /// ```
+    /// # use minijinja::value::{Value, ValueKind};
+    /// let val = Value::from_tuple([1, 2]);
+    /// assert_eq!(val.kind(), ValueKind::Tuple);
+    /// assert_eq!(val.to_string(), "(1, 2)");
+    /// ```
+    pub fn from_tuple<I, T>(items: I) -> Value
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<Value>,
+    {
+        Value::from_object(Tuple(items.into_iter().map(Into::into).collect()))
+    }
+
     /// Creates a value that is an iterable.
     ///
     /// The function is invoked to create a new iterator every time the value is
@@ -1105,13 +1148,43 @@ impl Value {
         functions::BoxedFunction::new(f).to_value()
     }
 
+    /// Creates a callable value from a function returning a future.
+    ///
+    /// The function is invoked with all arguments (including keyword arguments
+    /// as last argument) and must return a future that resolves to the return
+    /// value.  Such functions can only be called from templates that are
+    /// rendered with [`Template::render_async`](crate::Template::render_async).
+    /// Calling them in a synchronous render fails with an error.  When called
+    /// from Rust code (for instance a filter like `map`) the error the call
+    /// returns while the render is suspended must be passed on.
+    ///
+    /// ```
+    /// # use minijinja::value::{from_args, Value};
+    /// let fetch = Value::from_async_function(|args: Vec<Value>| async move {
+    ///     let (key,): (String,) = from_args(&args)?;
+    ///     Ok(Value::from(format!("value of {key}")))
+    /// });
+    /// ```
+    ///
+    /// As objects can return callables from [`Object::get_value`], this can
+    /// also be used to expose async methods on objects.
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn from_async_function<F, Fut>(f: F) -> Value
+    where
+        F: Fn(Vec<Value>) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = Result<Value, Error>> + Send + 'static,
+    {
+        Value::from_object(crate::vm::AsyncFunction::new(f))
+    }
+
     /// Returns the kind of the value.
     ///
     /// This can be used to determine what's in the value before trying to
     /// perform operations on it.
     pub fn kind(&self) -> ValueKind {
         match self.0 {
-            ValueRepr::Undefined(_) => ValueKind::Undefined,
+            ValueRepr::Undefined(..) => ValueKind::Undefined,
             ValueRepr::Bool(_) => ValueKind::Bool,
             ValueRepr::U64(_) | ValueRepr::I64(_) | ValueRepr::F64(_) => ValueKind::Number,
             ValueRepr::None => ValueKind::None,
@@ -1123,6 +1196,7 @@ impl Value {
             ValueRepr::Object(ref obj) => match obj.repr() {
                 ObjectRepr::Map => ValueKind::Map,
                 ObjectRepr::Seq => ValueKind::Seq,
+                ObjectRepr::Tuple => ValueKind::Tuple,
                 ObjectRepr::Iterable => ValueKind::Iterable,
                 ObjectRepr::Plain => ValueKind::Plain,
             },
@@ -1175,7 +1249,7 @@ impl Value {
             ValueRepr::String(ref x, _) => !x.is_empty(),
             ValueRepr::SmallStr(ref x) => !x.is_empty(),
             ValueRepr::Bytes(ref x) => !x.is_empty(),
-            ValueRepr::None | ValueRepr::Undefined(_) | ValueRepr::Invalid(_) => false,
+            ValueRepr::None | ValueRepr::Undefined(..) | ValueRepr::Invalid(_) => false,
             ValueRepr::Object(ref x) => x.is_true(),
         }
     }
@@ -1187,7 +1261,24 @@ impl Value {
 
     /// Returns `true` if this value is undefined.
     pub fn is_undefined(&self) -> bool {
-        matches!(&self.0, ValueRepr::Undefined(_))
+        matches!(&self.0, ValueRepr::Undefined(..))
+    }
+
+    /// Creates an undefined value that remembers where it was created.
+    pub(crate) fn undefined_with_origin(origin: UndefinedOrigin) -> Value {
+        ValueRepr::Undefined(UndefinedType::Default, Some(origin)).into()
+    }
+
+    /// Creates the error for an invalid use of this undefined value.
+    ///
+    /// If the origin of the undefined value is known, it's attached to the
+    /// error so that the engine can report the failed lookup.
+    pub(crate) fn undefined_error(&self) -> Error {
+        let mut err = Error::from(ErrorKind::UndefinedError);
+        if let ValueRepr::Undefined(_, Some(origin)) = self.0 {
+            err.set_undefined_origin(Some(origin));
+        }
+        err
     }
 
     /// Returns `true` if this value is none.
@@ -1295,7 +1386,7 @@ impl Value {
     /// ```
     pub fn get_attr(&self, key: &str) -> Result<Value, Error> {
         let value = match self.0 {
-            ValueRepr::Undefined(_) => return Err(Error::from(ErrorKind::UndefinedError)),
+            ValueRepr::Undefined(..) => return Err(self.undefined_error()),
             ValueRepr::Object(ref dy) => dy.get_value_by_str(key),
             _ => None,
         };
@@ -1346,8 +1437,8 @@ impl Value {
     /// assert_eq!(value.to_string(), "Foo");
     /// ```
     pub fn get_item(&self, key: &Value) -> Result<Value, Error> {
-        if let ValueRepr::Undefined(_) = self.0 {
-            Err(Error::from(ErrorKind::UndefinedError))
+        if let ValueRepr::Undefined(..) = self.0 {
+            Err(self.undefined_error())
         } else {
             Ok(self.get_item_opt(key).unwrap_or(Value::UNDEFINED))
         }
@@ -1359,7 +1450,8 @@ impl Value {
     /// has a different behavior.
     ///
     /// * [`ValueKind::Map`]: the iterator yields the keys of the map.
-    /// * [`ValueKind::Seq`] / [`ValueKind::Iterable`]: the iterator yields the items in the sequence.
+    /// * [`ValueKind::Seq`] / [`ValueKind::Tuple`] / [`ValueKind::Iterable`]: the iterator
+    ///   yields the items in the sequence.
     /// * [`ValueKind::String`]: the iterator yields characters in a string.
     /// * [`ValueKind::None`] / [`ValueKind::Undefined`]: the iterator is empty.
     ///
@@ -1380,7 +1472,7 @@ impl Value {
     /// ```
     pub fn try_iter(&self) -> Result<ValueIter, Error> {
         match self.0 {
-            ValueRepr::None | ValueRepr::Undefined(_) => Some(ValueIterImpl::Empty),
+            ValueRepr::None | ValueRepr::Undefined(..) => Some(ValueIterImpl::Empty),
             ValueRepr::String(ref s, _) => {
                 Some(ValueIterImpl::Chars(0, s.chars().count(), Arc::clone(s)))
             }
@@ -1411,7 +1503,7 @@ impl Value {
     ///   reversible itself, it consumes it and then reverses it.
     pub fn reverse(&self) -> Result<Value, Error> {
         match self.0 {
-            ValueRepr::Undefined(_) | ValueRepr::None => Some(self.clone()),
+            ValueRepr::Undefined(..) | ValueRepr::None => Some(self.clone()),
             ValueRepr::String(ref s, _) => Some(Value::from(s.chars().rev().collect::<String>())),
             ValueRepr::SmallStr(ref s) => {
                 // TODO: add small str optimization here
@@ -1541,7 +1633,7 @@ impl Value {
                     }
                     None
                 }
-                ObjectRepr::Seq => {
+                ObjectRepr::Seq | ObjectRepr::Tuple => {
                     let idx = index(key, || dy.enumerator_len()).map(Value::from);
                     dy.get_value(idx.as_ref().unwrap_or(key))
                 }
@@ -1715,7 +1807,7 @@ impl Serialize for Value {
             ValueRepr::U64(u) => serializer.serialize_u64(u),
             ValueRepr::I64(i) => serializer.serialize_i64(i),
             ValueRepr::F64(f) => serializer.serialize_f64(f),
-            ValueRepr::None | ValueRepr::Undefined(_) | ValueRepr::Invalid(_) => {
+            ValueRepr::None | ValueRepr::Undefined(..) | ValueRepr::Invalid(_) => {
                 serializer.serialize_unit()
             }
             ValueRepr::U128(u) => serializer.serialize_u128(u.0),
@@ -1736,6 +1828,17 @@ impl Serialize for Value {
 
                     seq.end()
                 }
+                ObjectRepr::Tuple => {
+                    use serde::ser::SerializeTuple;
+                    let mut tup = ok!(serializer.serialize_tuple(o.enumerator_len().unwrap_or(0)));
+                    if let Some(iter) = o.try_iter() {
+                        for item in iter {
+                            ok!(tup.serialize_element(&item));
+                        }
+                    }
+
+                    tup.end()
+                }
                 ObjectRepr::Map => {
                     use serde::ser::SerializeMap;
                     let mut map = ok!(serializer.serialize_map(None));
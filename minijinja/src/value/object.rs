@@ -205,7 +205,9 @@ pub trait Object: fmt::Debug + Send + Sync {
     fn enumerate(self: &Arc<Self>) -> Enumerator {
         match self.repr() {
             ObjectRepr::Plain => Enumerator::NonEnumerable,
-            ObjectRepr::Iterable | ObjectRepr::Map | ObjectRepr::Seq => Enumerator::Empty,
+            ObjectRepr::Iterable | ObjectRepr::Map | ObjectRepr::Seq | ObjectRepr::Tuple => {
+                Enumerator::Empty
+            }
         }
     }
 
@@ -315,6 +317,16 @@ pub trait Object: fmt::Debug + Send + Sync {
                 }
                 dbg.finish()
             }
+            // the empty tuple needs special casing as the tuple debug
+            // helper does not emit parentheses without fields.
+            ObjectRepr::Tuple if self.enumerator_len() == Some(0) => f.write_str("()"),
+            ObjectRepr::Tuple if self.enumerator_len().is_some() => {
+                let mut dbg = f.debug_tuple("");
+                for value in self.try_iter().into_iter().flatten() {
+                    dbg.field(&value);
+                }
+                dbg.finish()
+            }
             _ => {
                 write!(f, "{self:?}")
             }
@@ -622,6 +634,14 @@ pub enum ObjectRepr {
     /// - **Serialize:** Serializes as list
     Seq,
 
+    /// Represents an immutable sequence (eg: a tuple).
+    ///
+    /// - **Default Render:** `(value,...)`
+    /// - **Collection Behavior:** looks like a list, can be indexed by index, has a length
+    /// - **Iteration Behavior:** iterates over values
+    /// - **Serialize:** Serializes as tuple
+    Tuple,
+
     /// Represents a non indexable, iterable object.
     ///
     /// - **Default Render:** `[value,...]` (if length is known), `"<iterator>"` otherwise.
@@ -679,6 +699,14 @@ impl DynObject {
         self.ptr == other.ptr && self.vtable == other.vtable
     }
 
+    /// Returns the address of the object.
+    ///
+    /// Two dyn objects that are alive at the same time have the same address
+    /// only if they are the same object.
+    pub(crate) fn addr(&self) -> usize {
+        self.ptr as usize
+    }
+
     /// Checks if the two dyn objects are of the same type.
     pub(crate) fn is_same_object_type(&self, other: &DynObject) -> bool {
         self.type_id() == other.type_id()
@@ -938,6 +966,37 @@ macro_rules! impl_value_map {
 }
 
 impl_value_vec!(Vec);
+
+/// The object backing tuples created from templates or [`Value::from_tuple`].
+pub(crate) struct Tuple(pub Vec<Value>);
+
+impl fmt::Debug for Tuple {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return f.write_str("()");
+        }
+        let mut dbg = f.debug_tuple("");
+        for value in &self.0 {
+            dbg.field(value);
+        }
+        dbg.finish()
+    }
+}
+
+impl Object for Tuple {
+    fn repr(self: &Arc<Self>) -> ObjectRepr {
+        ObjectRepr::Tuple
+    }
+
+    #[inline(always)]
+    fn get_value(self: &Arc<Self>, key: &Value) -> Option<Value> {
+        self.0.get(some!(key.as_usize())).cloned()
+    }
+
+    fn enumerate(self: &Arc<Self>) -> Enumerator {
+        Enumerator::Seq(self.0.len())
+    }
+}
 impl_value_map!(BTreeMap, mapped_rev_enumerator);
 impl_str_map!(BTreeMap, mapped_rev_enumerator);
 
@@ -199,20 +199,26 @@ pub fn slice(value: Value, start: Value, stop: Value, step: Value) -> Result<Val
                 ))
             }
         }
-        ValueRepr::Undefined(_) | ValueRepr::None => Ok(Value::from(Vec::<Value>::new())),
-        ValueRepr::Object(obj) if matches!(obj.repr(), ObjectRepr::Seq | ObjectRepr::Iterable) => {
-            if step > 0 {
+        ValueRepr::Undefined(..) | ValueRepr::None => Ok(Value::from(Vec::<Value>::new())),
+        ValueRepr::Object(obj)
+            if matches!(
+                obj.repr(),
+                ObjectRepr::Seq | ObjectRepr::Tuple | ObjectRepr::Iterable
+            ) =>
+        {
+            let is_tuple = obj.repr() == ObjectRepr::Tuple;
+            let rv = if step > 0 {
                 let len = obj.enumerator_len().unwrap_or_default();
                 let (start, len) = get_offset_and_len(start, stop, || len);
-                Ok(Value::make_object_iterable(obj, move |obj| {
+                Value::make_object_iterable(obj, move |obj| {
                     if let Some(iter) = obj.try_iter() {
                         Box::new(iter.skip(start).take(len).step_by(step as usize))
                     } else {
                         Box::new(None.into_iter())
                     }
-                }))
+                })
             } else {
-                Ok(Value::make_object_iterable(obj.clone(), move |obj| {
+                Value::make_object_iterable(obj.clone(), move |obj| {
                     if let Some(iter) = obj.try_iter() {
                         let vec: Vec<Value> = iter.collect();
                         Box::new(
@@ -222,7 +228,13 @@ pub fn slice(value: Value, start: Value, stop: Value, step: Value) -> Result<Val
                     } else {
                         Box::new(None.into_iter())
                     }
-                }))
+                })
+            };
+            // slicing a tuple creates a new tuple
+            if is_tuple {
+                Ok(Value::from_tuple(rv.try_iter().into_iter().flatten()))
+            } else {
+                Ok(rv)
             }
         }
         _ => error,
@@ -271,10 +283,47 @@ macro_rules! math_binop {
     }
 }
 
-pub fn add(lhs: &Value, rhs: &Value) -> Result<Value, Error> {
-    if matches!(lhs.kind(), ValueKind::Seq | ValueKind::Iterable)
-        && matches!(rhs.kind(), ValueKind::Seq | ValueKind::Iterable)
-    {
+/// Fails if a string of `len` bytes exceeds the size limit.
+pub fn check_string_size(len: usize, size_limit: Option<usize>) -> Result<(), Error> {
+    match size_limit {
+        Some(limit) if len > limit => Err(Error::new(
+            ErrorKind::SizeLimitExceeded,
+            format!("string of {len} bytes exceeds the size limit of {limit}"),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Fails if a sequence of `len` items exceeds the size limit.
+pub fn check_seq_size(len: usize, size_limit: Option<usize>) -> Result<(), Error> {
+    match size_limit {
+        Some(limit) if len > limit => Err(Error::new(
+            ErrorKind::SizeLimitExceeded,
+            format!("sequence of {len} items exceeds the size limit of {limit}"),
+        )),
+        _ => Ok(()),
+    }
+}
+
+pub fn add(lhs: &Value, rhs: &Value, size_limit: Option<usize>) -> Result<Value, Error> {
+    if lhs.kind() == ValueKind::Tuple && rhs.kind() == ValueKind::Tuple {
+        if let (Some(a), Some(b)) = (lhs.len(), rhs.len()) {
+            ok!(check_seq_size(a.saturating_add(b), size_limit));
+        }
+        return Ok(Value::from_tuple(
+            ok!(lhs.try_iter()).chain(ok!(rhs.try_iter())),
+        ));
+    }
+    if matches!(
+        lhs.kind(),
+        ValueKind::Seq | ValueKind::Tuple | ValueKind::Iterable
+    ) && matches!(
+        rhs.kind(),
+        ValueKind::Seq | ValueKind::Tuple | ValueKind::Iterable
+    ) {
+        if let (Some(a), Some(b)) = (lhs.len(), rhs.len()) {
+            ok!(check_seq_size(a.saturating_add(b), size_limit));
+        }
         let lhs = lhs.clone();
         let rhs = rhs.clone();
         return Ok(Value::make_iterable(move || {
@@ -293,33 +342,80 @@ pub fn add(lhs: &Value, rhs: &Value) -> Result<Value, Error> {
             .ok_or_else(|| failed_op("+", lhs, rhs))
             .map(int_as_value),
         Some(CoerceResult::F64(a, b)) => Ok((a + b).into()),
-        Some(CoerceResult::Str(a, b)) => Ok(Value::from([a, b].concat())),
+        Some(CoerceResult::Str(a, b)) => {
+            ok!(check_string_size(
+                a.len().saturating_add(b.len()),
+                size_limit
+            ));
+            Ok(Value::from([a, b].concat()))
+        }
         _ => Err(impossible_op("+", lhs, rhs)),
     }
 }
 
 math_binop!(sub, checked_sub, -);
-math_binop!(rem, checked_rem_euclid, %);
+math_binop!(math_rem, checked_rem_euclid, %);
+
+#[cfg_attr(not(feature = "builtins"), allow(unused_variables))]
+pub fn rem(lhs: &Value, rhs: &Value, size_limit: Option<usize>) -> Result<Value, Error> {
+    #[cfg(feature = "builtins")]
+    {
+        if let Some(format_str) = lhs.as_str() {
+            return string_format(format_str, rhs, size_limit);
+        }
+    }
+    math_rem(lhs, rhs)
+}
 
-pub fn mul(lhs: &Value, rhs: &Value) -> Result<Value, Error> {
+/// Implements Python's `%` string formatting.
+///
+/// Like in Python a tuple on the right side provides the positional
+/// arguments, every other value is used as the only argument.  This also
+/// means that a map can be used for the `%(name)s` syntax.  The code
+/// generator compiles a tuple literal on the right side into a tuple even if
+/// python tuples are disabled.
+#[cfg(feature = "builtins")]
+fn string_format(
+    format_str: &str,
+    args: &Value,
+    size_limit: Option<usize>,
+) -> Result<Value, Error> {
+    use crate::format_utils::format_operator;
+
+    let args = if args.kind() == ValueKind::Tuple {
+        ok!(args.try_iter()).collect()
+    } else {
+        vec![args.clone()]
+    };
+    format_operator(format_str, &args, size_limit).map(Value::from)
+}
+
+pub fn mul(lhs: &Value, rhs: &Value, size_limit: Option<usize>) -> Result<Value, Error> {
     if let Some((s, n)) = lhs
         .as_str()
         .map(|s| (s, rhs))
         .or_else(|| rhs.as_str().map(|s| (s, lhs)))
     {
-        return Ok(Value::from(s.repeat(ok!(n.as_usize().ok_or_else(|| {
+        let n = ok!(n.as_usize().ok_or_else(|| {
             Error::new(
                 ErrorKind::InvalidOperation,
                 "strings can only be multiplied with integers",
             )
-        })))));
+        }));
+        ok!(check_string_size(s.len().saturating_mul(n), size_limit));
+        return Ok(Value::from(s.repeat(n)));
     } else if let Some((seq, n)) = lhs
         .as_object()
         .map(|s| (s, rhs))
         .or_else(|| rhs.as_object().map(|s| (s, lhs)))
-        .filter(|x| matches!(x.0.repr(), ObjectRepr::Iterable | ObjectRepr::Seq))
+        .filter(|x| {
+            matches!(
+                x.0.repr(),
+                ObjectRepr::Iterable | ObjectRepr::Seq | ObjectRepr::Tuple
+            )
+        })
     {
-        return repeat_iterable(n, seq);
+        return repeat_iterable(n, seq, size_limit);
     }
 
     match coerce(lhs, rhs, true) {
@@ -332,7 +428,7 @@ pub fn mul(lhs: &Value, rhs: &Value) -> Result<Value, Error> {
     }
 }
 
-fn repeat_iterable(n: &Value, seq: &DynObject) -> Result<Value, Error> {
+fn repeat_iterable(n: &Value, seq: &DynObject, size_limit: Option<usize>) -> Result<Value, Error> {
     let n = ok!(n.as_usize().ok_or_else(|| {
         Error::new(
             ErrorKind::InvalidOperation,
@@ -346,6 +442,7 @@ fn repeat_iterable(n: &Value, seq: &DynObject) -> Result<Value, Error> {
             "cannot repeat unsized iterables",
         )
     }));
+    ok!(check_seq_size(len.saturating_mul(n), size_limit));
 
     // This is not optimal.  We only query the enumerator for the length once
     // but we support repeated iteration.  We could both lie about our length
@@ -435,8 +532,14 @@ pub fn neg(val: &Value) -> Result<Value, Error> {
 }
 
 /// Attempts a string concatenation.
-pub fn string_concat(left: Value, right: &Value) -> Value {
-    Value::from(format!("{left}{right}"))
+pub fn string_concat(
+    left: Value,
+    right: &Value,
+    size_limit: Option<usize>,
+) -> Result<Value, Error> {
+    let rv = format!("{left}{right}");
+    ok!(check_string_size(rv.len(), size_limit));
+    Ok(Value::from(rv))
 }
 
 /// Implements a containment operation on values.
@@ -456,7 +559,7 @@ pub fn contains(container: &Value, value: &Value) -> Result<Value, Error> {
         match obj.repr() {
             ObjectRepr::Plain => false,
             ObjectRepr::Map => obj.get_value(value).is_some(),
-            ObjectRepr::Seq | ObjectRepr::Iterable => {
+            ObjectRepr::Seq | ObjectRepr::Tuple | ObjectRepr::Iterable => {
                 obj.try_iter().into_iter().flatten().any(|v| &v == value)
             }
         }
@@ -483,22 +586,22 @@ mod tests {
 
     #[test]
     fn test_adding() {
-        let err = add(&Value::from("a"), &Value::from(42)).unwrap_err();
+        let err = add(&Value::from("a"), &Value::from(42), None).unwrap_err();
         assert_eq!(
             err.to_string(),
             "invalid operation: tried to use + operator on unsupported types string and number"
         );
 
         assert_eq!(
-            add(&Value::from(1), &Value::from(2)).unwrap(),
+            add(&Value::from(1), &Value::from(2), None).unwrap(),
             Value::from(3)
         );
         assert_eq!(
-            add(&Value::from("foo"), &Value::from("bar")).unwrap(),
+            add(&Value::from("foo"), &Value::from("bar"), None).unwrap(),
             Value::from("foobar")
         );
 
-        let err = add(&Value::from(i128::MAX), &Value::from(1)).unwrap_err();
+        let err = add(&Value::from(i128::MAX), &Value::from(1), None).unwrap_err();
         assert_eq!(
             err.to_string(),
             "invalid operation: unable to calculate 170141183460469231731687303715884105727 + 1"
@@ -554,11 +657,11 @@ mod tests {
     #[test]
     fn test_concat() {
         assert_eq!(
-            string_concat(Value::from("foo"), &Value::from(42)),
+            string_concat(Value::from("foo"), &Value::from(42), None).unwrap(),
             Value::from("foo42")
         );
         assert_eq!(
-            string_concat(Value::from(23), &Value::from(42)),
+            string_concat(Value::from(23), &Value::from(42), None).unwrap(),
             Value::from("2342")
         );
     }
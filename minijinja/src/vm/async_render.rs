@@ -0,0 +1,223 @@
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use crate::compiler::instructions::Instructions;
+use crate::error::{Error, ErrorKind};
+use crate::utils::AutoEscape;
+use crate::value::{Object, ObjectRepr, Value};
+use crate::vm::context::Stack;
+use crate::vm::State;
+
+#[cfg(feature = "macros")]
+use crate::output::SuspendedOutput;
+#[cfg(feature = "multi_template")]
+use crate::template::Template;
+#[cfg(feature = "multi_template")]
+use crate::vm::IncludeFrame;
+
+pub(crate) type BoxedFuture = Pin<Box<dyn Future<Output = Result<Value, Error>> + Send + 'static>>;
+type AsyncFuncFunc = dyn Fn(Vec<Value>) -> BoxedFuture + Sync + Send + 'static;
+
+/// Keeps track of the futures of an async render.
+///
+/// The VM itself is synchronous.  When an async function is called, the
+/// future is stashed away here and the evaluation is suspended: every level
+/// of the evaluation saves what it needs to continue into a [`Suspended`]
+/// record and returns.  The render then awaits the future and resumes the
+/// evaluation, which re-runs the instruction that made the call.  This time
+/// the call is answered with the result of the future.
+///
+/// Async functions invoked indirectly (for instance by a filter such as
+/// `map`) suspend the instruction that invoked the filter.  On resume that
+/// instruction is re-run as a whole, and all async calls it makes are
+/// answered in order with the results collected so far.
+#[derive(Default)]
+pub(crate) struct AsyncCalls {
+    pending: Mutex<Option<BoxedFuture>>,
+    resolved: Mutex<Resolved>,
+}
+
+#[derive(Default)]
+struct Resolved {
+    results: Vec<Result<Value, Error>>,
+    next: usize,
+}
+
+impl AsyncCalls {
+    /// Performs an async call.
+    ///
+    /// If the result of this call is known, it's returned.  Otherwise the
+    /// future is stashed and the evaluation has to suspend.
+    fn call<F: FnOnce() -> BoxedFuture>(&self, f: F) -> Result<Value, Error> {
+        {
+            let mut resolved = self.resolved.lock().unwrap();
+            let next = resolved.next;
+            if let Some(rv) = resolved.results.get(next) {
+                let rv = match rv {
+                    Ok(value) => Ok(value.clone()),
+                    Err(err) => Err(err.internal_clone()),
+                };
+                resolved.next += 1;
+                return rv;
+            }
+        }
+        *self.pending.lock().unwrap() = Some(f());
+        Err(Error::new(
+            ErrorKind::InvalidOperation,
+            "async render was suspended",
+        ))
+    }
+
+    /// Returns `true` if the evaluation is being suspended.
+    pub(crate) fn is_pending(&self) -> bool {
+        self.pending.lock().unwrap().is_some()
+    }
+
+    /// Takes the future the evaluation was suspended for.
+    pub(crate) fn take_pending(&self) -> Option<BoxedFuture> {
+        self.pending.lock().unwrap().take()
+    }
+
+    /// Records the result of the future the evaluation was suspended for.
+    pub(crate) fn resolve(&self, rv: Result<Value, Error>) {
+        let mut resolved = self.resolved.lock().unwrap();
+        resolved.results.push(rv);
+        resolved.next = 0;
+    }
+
+    /// Forgets about the results once the suspended instruction finished.
+    pub(crate) fn finish(&self) {
+        let mut resolved = self.resolved.lock().unwrap();
+        resolved.results.clear();
+        resolved.next = 0;
+    }
+}
+
+/// The async render a [`State`] belongs to.
+pub(crate) struct Suspension<'template, 'env> {
+    pub(crate) calls: Arc<AsyncCalls>,
+    /// Can this state be suspended?  This is only the case for the states
+    /// that are driven by the VM directly and not by other code.
+    pub(crate) suspendable: bool,
+    /// The records of a suspended evaluation, the outermost last.
+    records: Vec<Suspended<'template, 'env>>,
+}
+
+impl<'template, 'env> Suspension<'template, 'env> {
+    pub(crate) fn new(calls: Arc<AsyncCalls>, suspendable: bool) -> Self {
+        Suspension {
+            calls,
+            suspendable,
+            records: Vec::new(),
+        }
+    }
+
+    /// Saves a record for resuming the evaluation.
+    pub(crate) fn push(&mut self, record: Suspended<'template, 'env>) {
+        self.records.push(record);
+    }
+
+    /// Takes the record for resuming the evaluation if `f` accepts it.
+    pub(crate) fn take<T, F>(&mut self, f: F) -> Option<T>
+    where
+        F: FnOnce(Suspended<'template, 'env>) -> Result<T, Suspended<'template, 'env>>,
+    {
+        let record = self.records.pop()?;
+        match f(record) {
+            Ok(rv) => Some(rv),
+            Err(record) => {
+                self.records.push(record);
+                None
+            }
+        }
+    }
+
+    /// Returns `true` if there is nothing left to resume.
+    pub(crate) fn is_resumed(&self) -> bool {
+        self.records.is_empty()
+    }
+}
+
+/// What a level of a suspended evaluation needs to continue.
+pub(crate) enum Suspended<'template, 'env> {
+    Eval(SuspendedEval<'env>),
+    #[cfg(feature = "multi_template")]
+    Include(Box<SuspendedInclude<'template, 'env>>),
+    #[cfg(feature = "multi_template")]
+    Block {
+        block: &'env str,
+        instructions: &'template Instructions<'env>,
+    },
+    Super {
+        instructions: &'template Instructions<'env>,
+    },
+    #[cfg(feature = "macros")]
+    Macro(Box<SuspendedMacro<'template, 'env>>),
+}
+
+/// The evaluation loop of a suspended evaluation.
+pub(crate) struct SuspendedEval<'env> {
+    pub(crate) pc: u32,
+    pub(crate) stack: Stack,
+    pub(crate) initial_auto_escape: AutoEscape,
+    pub(crate) auto_escape_stack: Vec<AutoEscape>,
+    pub(crate) next_loop_recursion_jump: Option<(u32, bool)>,
+    #[cfg(feature = "multi_template")]
+    pub(crate) parent_instructions: Option<&'env Instructions<'env>>,
+    #[cfg(not(feature = "multi_template"))]
+    pub(crate) _marker: std::marker::PhantomData<&'env ()>,
+}
+
+/// An include of a suspended evaluation.
+#[cfg(feature = "multi_template")]
+pub(crate) struct SuspendedInclude<'template, 'env> {
+    pub(crate) template: Template<'env, 'env>,
+    pub(crate) frame: IncludeFrame<'template, 'env>,
+}
+
+/// A macro call of a suspended evaluation.
+#[cfg(feature = "macros")]
+pub(crate) struct SuspendedMacro<'template, 'env> {
+    pub(crate) state: State<'template, 'env>,
+    pub(crate) output: String,
+    pub(crate) captures: SuspendedOutput,
+}
+
+/// A callable that produces a future.
+///
+/// See [`Value::from_async_function`] for more information.
+pub(crate) struct AsyncFunction(Arc<AsyncFuncFunc>);
+
+impl AsyncFunction {
+    pub(crate) fn new<F, Fut>(f: F) -> AsyncFunction
+    where
+        F: Fn(Vec<Value>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Value, Error>> + Send + 'static,
+    {
+        AsyncFunction(Arc::new(move |args| Box::pin(f(args)) as BoxedFuture))
+    }
+}
+
+impl fmt::Debug for AsyncFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("async function")
+    }
+}
+
+impl Object for AsyncFunction {
+    fn repr(self: &Arc<Self>) -> ObjectRepr {
+        ObjectRepr::Plain
+    }
+
+    fn call(self: &Arc<Self>, state: &State<'_, '_>, args: &[Value]) -> Result<Value, Error> {
+        match state.suspension {
+            Some(ref suspension) => suspension.calls.call(|| (self.0)(args.to_vec())),
+            None => Err(Error::new(
+                ErrorKind::InvalidOperation,
+                "async functions can only be called from an async render",
+            )),
+        }
+    }
+}
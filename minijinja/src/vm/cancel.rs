@@ -0,0 +1,58 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::error::{Error, ErrorKind};
+
+/// How many instructions are executed between two checks.
+const CHECK_INTERVAL: u32 = 256;
+
+/// Helper for cooperatively cancelling renders.
+pub struct CancelTracker {
+    deadline: Option<Instant>,
+    flag: Option<Arc<AtomicBool>>,
+    ticks: AtomicU32,
+}
+
+impl CancelTracker {
+    /// Creates a new cancel tracker.
+    ///
+    /// Like the fuel tracker this is shared across nested invocations of
+    /// the template evaluation.
+    pub fn new(deadline: Option<Instant>, flag: Option<Arc<AtomicBool>>) -> Arc<CancelTracker> {
+        Arc::new(CancelTracker {
+            deadline,
+            flag,
+            ticks: AtomicU32::new(0),
+        })
+    }
+
+    /// Tracks an instruction.
+    ///
+    /// Every few instructions this checks if the render was cancelled or
+    /// ran past its deadline in which case an error is returned.
+    #[inline]
+    pub fn track(&self) -> Result<(), Error> {
+        if self.ticks.fetch_add(1, Ordering::Relaxed) % CHECK_INTERVAL == 0 {
+            self.check()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Checks if the render was cancelled.
+    #[cold]
+    pub fn check(&self) -> Result<(), Error> {
+        if let Some(ref flag) = self.flag {
+            if flag.load(Ordering::Relaxed) {
+                return Err(Error::new(ErrorKind::Cancelled, "render was cancelled"));
+            }
+        }
+        if let Some(deadline) = self.deadline {
+            if Instant::now() >= deadline {
+                return Err(Error::new(ErrorKind::Cancelled, "deadline exceeded"));
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,8 +1,6 @@
 use std::borrow::Cow;
 use std::collections::{BTreeMap, HashSet};
 use std::fmt;
-
-#[cfg(feature = "macros")]
 use std::sync::Arc;
 
 use crate::environment::Environment;
@@ -100,12 +98,22 @@ impl Stack {
         self.values[start..].reverse();
     }
 
-    pub fn get_call_args(&mut self, n: Option<u16>) -> &[Value] {
+    pub fn get_call_args(&self, n: Option<u16>) -> &[Value] {
+        // a dynamic argument count stays on the stack until the call is
+        // done so that the call can be performed again.
+        let (n, end) = match n {
+            Some(n) => (n as usize, self.values.len()),
+            None => (self.peek().as_usize().unwrap(), self.values.len() - 1),
+        };
+        &self.values[end - n..end]
+    }
+
+    pub fn drop_call_args(&mut self, n: Option<u16>) {
         let n = match n {
             Some(n) => n as usize,
             None => self.pop().as_usize().unwrap(),
         };
-        &self.values[self.values.len() - n..]
+        self.drop_top(n);
     }
 
     pub fn drop_top(&mut self, n: usize) {
@@ -133,6 +141,7 @@ pub(crate) struct Context<'env> {
     stack: Vec<Frame<'env>>,
     outer_stack_depth: usize,
     recursion_limit: usize,
+    globals: Option<Arc<BTreeMap<String, Value>>>,
 }
 
 impl fmt::Debug for Context<'_> {
@@ -156,7 +165,29 @@ impl<'env> Context<'env> {
             stack: Vec::with_capacity(40),
             outer_stack_depth: 0,
             recursion_limit: env.recursion_limit(),
+            globals: None,
+        }
+    }
+
+    /// Overrides the recursion limit and adds extra globals.
+    pub fn apply_options(
+        &mut self,
+        recursion_limit: Option<usize>,
+        globals: Option<Arc<BTreeMap<String, Value>>>,
+    ) {
+        if let Some(recursion_limit) = recursion_limit {
+            self.recursion_limit = recursion_limit;
         }
+        if globals.is_some() {
+            self.globals = globals;
+        }
+    }
+
+    /// Copies the options applied to another context.
+    #[cfg(feature = "macros")]
+    pub fn inherit_options(&mut self, other: &Context<'env>) {
+        self.recursion_limit = other.recursion_limit;
+        self.globals = other.globals.clone();
     }
 
     /// Creates a context
@@ -258,6 +289,9 @@ impl<'env> Context<'env> {
             }
         }
 
+        if let Some(rv) = self.globals.as_ref().and_then(|x| x.get(key)) {
+            return Some(rv.clone());
+        }
         self.env.get_global(key)
     }
 
@@ -291,6 +325,9 @@ impl<'env> Context<'env> {
         }
         if with_globals {
             seen.extend(self.env.globals().map(|x| Cow::Borrowed(x.0)));
+            if let Some(ref globals) = self.globals {
+                seen.extend(globals.keys().map(|x| Cow::Borrowed(x.as_str())));
+            }
         }
         seen
     }
@@ -308,6 +345,11 @@ impl<'env> Context<'env> {
         self.stack.pop().unwrap()
     }
 
+    /// Returns the number of layers.
+    pub fn frame_count(&self) -> usize {
+        self.stack.len()
+    }
+
     /// Returns the root locals (exports)
     #[track_caller]
     pub fn exports(&self) -> &Locals<'env> {
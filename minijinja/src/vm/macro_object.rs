@@ -3,9 +3,10 @@ use std::fmt;
 use std::sync::Arc;
 
 use crate::error::{Error, ErrorKind};
-use crate::output::Output;
 use crate::utils::AutoEscape;
-use crate::value::{Enumerator, Kwargs, Object, Value};
+use crate::value::{Enumerator, Kwargs, Object, Value, ValueMap};
+#[cfg(feature = "async")]
+use crate::vm::async_render::SuspendedMacro;
 use crate::vm::state::State;
 use crate::vm::Vm;
 
@@ -20,6 +21,8 @@ pub(crate) struct Macro {
     pub state_id: isize,
     pub closure: Value,
     pub caller_reference: bool,
+    pub catch_varargs: bool,
+    pub catch_kwargs: bool,
 }
 
 impl fmt::Debug for Macro {
@@ -30,7 +33,13 @@ impl fmt::Debug for Macro {
 
 impl Object for Macro {
     fn enumerate(self: &Arc<Self>) -> Enumerator {
-        Enumerator::Str(&["name", "arguments", "caller"])
+        Enumerator::Str(&[
+            "name",
+            "arguments",
+            "caller",
+            "catch_varargs",
+            "catch_kwargs",
+        ])
     }
 
     fn get_value(self: &Arc<Self>, key: &Value) -> Option<Value> {
@@ -38,11 +47,38 @@ impl Object for Macro {
             "name" => self.name.clone(),
             "arguments" => Value::from_iter(self.arg_spec.iter().cloned()),
             "caller" => Value::from(self.caller_reference),
+            "catch_varargs" => Value::from(self.catch_varargs),
+            "catch_kwargs" => Value::from(self.catch_kwargs),
             _ => return None,
         })
     }
 
     fn call(self: &Arc<Self>, state: &State<'_, '_>, args: &[Value]) -> Result<Value, Error> {
+        self.invoke(
+            state,
+            args,
+            #[cfg(feature = "async")]
+            None,
+        )
+    }
+
+    fn render(self: &Arc<Self>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<macro {}>", self.name)
+    }
+}
+
+impl Macro {
+    /// Invokes the macro.
+    ///
+    /// `suspended` is passed on to [`Vm::eval_macro`].
+    pub(crate) fn invoke<'template, 'env>(
+        &self,
+        state: &State<'template, 'env>,
+        args: &[Value],
+        #[cfg(feature = "async")] suspended: Option<
+            &mut Option<Box<SuspendedMacro<'template, 'env>>>,
+        >,
+    ) -> Result<Value, Error> {
         // we can only call macros that point to loaded template state.
         if state.id != self.state_id {
             return Err(Error::new(
@@ -59,7 +95,7 @@ impl Object for Macro {
             _ => (args, None),
         };
 
-        if args.len() > self.arg_spec.len() {
+        if args.len() > self.arg_spec.len() && !self.catch_varargs {
             return Err(Error::from(ErrorKind::TooManyArguments));
         }
 
@@ -93,6 +129,12 @@ impl Object for Macro {
             });
         }
 
+        if self.catch_varargs {
+            arg_values.push(Value::from(
+                args.get(self.arg_spec.len()..).unwrap_or_default().to_vec(),
+            ));
+        }
+
         let caller = if self.caller_reference {
             kwargs_used.insert("caller");
             Some(
@@ -105,7 +147,17 @@ impl Object for Macro {
             None
         };
 
-        if let Some(kwargs) = kwargs {
+        if self.catch_kwargs {
+            let mut extra_kwargs = ValueMap::new();
+            if let Some(kwargs) = kwargs {
+                for (key, value) in kwargs.values.iter() {
+                    if !key.as_str().is_some_and(|x| kwargs_used.contains(x)) {
+                        extra_kwargs.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+            arg_values.push(Value::from_object(extra_kwargs));
+        } else if let Some(kwargs) = kwargs {
             for key in kwargs.values.keys().filter_map(|x| x.as_str()) {
                 if !kwargs_used.contains(key) {
                     return Err(Error::new(
@@ -117,7 +169,6 @@ impl Object for Macro {
         }
 
         let vm = Vm::new(state.env());
-        let mut rv = String::new();
 
         // This requires some explanation here.  Because we get the state as
         // &State and not &mut State we are required to create a new state in
@@ -126,13 +177,13 @@ impl Object for Macro {
         // anything other than strings (most importantly they) can't return
         // other macros this is however not an issue, as modifications in the
         // macro cannot leak out.
-        ok!(vm.eval_macro(
+        let rv = ok!(vm.eval_macro(
             state,
-            self.macro_ref_id,
-            &mut Output::new(&mut rv),
-            self.closure.clone(),
+            self,
             caller,
-            arg_values
+            arg_values,
+            #[cfg(feature = "async")]
+            suspended,
         ));
 
         Ok(if !matches!(state.auto_escape(), AutoEscape::None) {
@@ -141,8 +192,4 @@ impl Object for Macro {
             Value::from(rv)
         })
     }
-
-    fn render(self: &Arc<Self>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "<macro {}>", self.name)
-    }
 }
@@ -1,23 +1,34 @@
 use std::borrow::Cow;
 use std::collections::BTreeMap;
+#[cfg(feature = "multi_template")]
+use std::collections::BTreeSet;
 use std::mem;
 
-#[cfg(feature = "macros")]
+#[cfg(any(feature = "macros", feature = "async"))]
 use std::sync::Arc;
 
+use crate::compiler::html_context::write_contextual;
 use crate::compiler::instructions::{
     Instruction, Instructions, LOOP_FLAG_RECURSIVE, LOOP_FLAG_WITH_LOOP_VAR, MAX_LOCALS,
 };
 use crate::environment::Environment;
 use crate::error::{Error, ErrorKind};
+use crate::instrument::Location;
 use crate::output::{CaptureMode, Output};
-use crate::utils::{untrusted_size_hint, write_escaped, AutoEscape, UndefinedBehavior};
+use crate::sandbox;
+use crate::template::RenderOptions;
+#[cfg(feature = "multi_template")]
+use crate::template::Template;
+use crate::utils::{untrusted_size_hint, AutoEscape, UndefinedBehavior};
 use crate::value::namespace_object::Namespace;
 use crate::value::{
-    ops, value_map_with_capacity, Kwargs, ObjectRepr, UndefinedType, Value, ValueMap, ValueRepr,
+    ops, value_map_with_capacity, Kwargs, ObjectRepr, UndefinedOrigin, UndefinedType, Value,
+    ValueKind, ValueMap, ValueRepr,
 };
+use crate::vm::cancel::CancelTracker;
 use crate::vm::context::{Frame, Stack};
 use crate::vm::loop_object::{Loop, LoopState};
+use crate::vm::profiler::Profiler;
 use crate::vm::state::BlockStack;
 
 #[cfg(feature = "macros")]
@@ -26,6 +37,19 @@ use crate::vm::closure_object::Closure;
 pub(crate) use crate::vm::context::Context;
 pub use crate::vm::state::State;
 
+#[cfg(all(feature = "async", feature = "multi_template"))]
+use crate::vm::async_render::SuspendedInclude;
+#[cfg(all(feature = "async", feature = "macros"))]
+use crate::vm::async_render::SuspendedMacro;
+#[cfg(feature = "async")]
+use crate::vm::async_render::{BoxedFuture, Suspended, SuspendedEval, Suspension};
+
+#[cfg(feature = "async")]
+pub(crate) use crate::vm::async_render::{AsyncCalls, AsyncFunction};
+
+#[cfg(feature = "async")]
+mod async_render;
+mod cancel;
 #[cfg(feature = "macros")]
 mod closure_object;
 mod context;
@@ -36,6 +60,7 @@ mod loop_object;
 mod macro_object;
 #[cfg(feature = "multi_template")]
 mod module_object;
+mod profiler;
 mod state;
 
 // the cost of a single include against the stack limit.
@@ -61,6 +86,34 @@ pub(crate) fn prepare_blocks<'env, 'template>(
         .collect()
 }
 
+/// The parts of the state that are replaced while an include is evaluated.
+#[cfg(feature = "multi_template")]
+pub(crate) struct IncludeFrame<'template, 'env> {
+    auto_escape: AutoEscape,
+    instructions: &'template Instructions<'env>,
+    blocks: BTreeMap<&'env str, BlockStack<'template, 'env>>,
+    loaded_templates: BTreeSet<&'env str>,
+    /// The closure of the including template.  This is not swapped as it's
+    /// stored on the current frame.
+    #[cfg(feature = "macros")]
+    closure: Option<Arc<Closure>>,
+}
+
+#[cfg(feature = "multi_template")]
+impl<'template, 'env> IncludeFrame<'template, 'env> {
+    /// Puts this frame into the state and returns the one it replaced.
+    fn swap(self, state: &mut State<'template, 'env>) -> IncludeFrame<'template, 'env> {
+        IncludeFrame {
+            auto_escape: state.auto_escape.replace(self.auto_escape),
+            instructions: mem::replace(&mut state.instructions, self.instructions),
+            blocks: mem::replace(&mut state.blocks, self.blocks),
+            loaded_templates: mem::replace(&mut state.loaded_templates, self.loaded_templates),
+            #[cfg(feature = "macros")]
+            closure: self.closure,
+        }
+    }
+}
+
 fn get_or_lookup_local<'a, F>(vec: &mut [Option<&'a Value>], idx: u8, f: F) -> Option<&'a Value>
 where
     F: FnOnce() -> Option<&'a Value>,
@@ -76,7 +129,7 @@ where
     }
 }
 
-fn normalize_filter_test_name(name: &str) -> Cow<'_, str> {
+pub(crate) fn normalize_filter_test_name(name: &str) -> Cow<'_, str> {
     if name.as_bytes().iter().any(|b| b.is_ascii_whitespace()) {
         let mut normalized = String::with_capacity(name.len());
         normalized.extend(name.chars().filter(|c| !c.is_ascii_whitespace()));
@@ -104,56 +157,304 @@ impl<'env> Vm<'env> {
         out: &mut Output,
         auto_escape: AutoEscape,
     ) -> Result<(Option<Value>, State<'template, 'env>), Error> {
+        self.eval_with(
+            instructions,
+            root,
+            blocks,
+            out,
+            auto_escape,
+            &RenderOptions::default(),
+        )
+    }
+
+    /// Evaluates the given inputs with render options.
+    pub(crate) fn eval_with<'template>(
+        &self,
+        instructions: &'template Instructions<'env>,
+        root: Value,
+        blocks: &'template BTreeMap<&'env str, Instructions<'env>>,
+        out: &mut Output,
+        auto_escape: AutoEscape,
+        options: &RenderOptions,
+    ) -> Result<(Option<Value>, State<'template, 'env>), Error> {
+        let mut state = State::new(
+            Context::new_with_frame(self.env, ok!(Frame::new_checked(root))),
+            auto_escape,
+            instructions,
+            prepare_blocks(blocks),
+        );
+        let deadline = options.effective_deadline();
+        if deadline.is_some() || options.cancel_flag.is_some() {
+            state.cancel_tracker = Some(CancelTracker::new(deadline, options.cancel_flag.clone()));
+        }
+        #[cfg(feature = "fuel")]
+        if let Some(fuel) = options.fuel {
+            state.fuel_tracker = fuel.map(fuel::FuelTracker::new);
+        }
+        if options.profiling {
+            state.profiler = Some(Profiler::new(instructions.name()));
+        }
+        if let Some(undefined_behavior) = options.undefined_behavior {
+            state.undefined_behavior = undefined_behavior;
+        }
+        state
+            .ctx
+            .apply_options(options.recursion_limit, options.globals.clone());
+        #[cfg(feature = "tracing")]
+        let span = crate::trace::render(instructions.name());
+        let rv = self.eval_state(&mut state, out);
+        #[cfg(feature = "tracing")]
+        {
+            crate::trace::record_fuel(&span, &state, None);
+            crate::trace::record_result(&span, &rv);
+        }
+        rv.map(|x| (x, state))
+    }
+
+    /// Creates the state for an async render.
+    #[cfg(feature = "async")]
+    pub(crate) fn new_async_state<'template>(
+        &self,
+        instructions: &'template Instructions<'env>,
+        root: Value,
+        blocks: &'template BTreeMap<&'env str, Instructions<'env>>,
+        auto_escape: AutoEscape,
+        calls: Arc<AsyncCalls>,
+    ) -> Result<State<'template, 'env>, Error> {
         let mut state = State::new(
             Context::new_with_frame(self.env, ok!(Frame::new_checked(root))),
             auto_escape,
             instructions,
             prepare_blocks(blocks),
         );
-        self.eval_state(&mut state, out).map(|x| (x, state))
+        state.suspension = Some(Suspension::new(calls, true));
+        Ok(state)
+    }
+
+    /// Evaluates an async render until it finishes or suspends.
+    ///
+    /// If the evaluation was suspended, the future it waits for is returned.
+    /// Once the result of the future was handed to [`AsyncCalls::resolve`]
+    /// the evaluation continues with another call to this method.
+    #[cfg(feature = "async")]
+    pub(crate) fn eval_async(
+        &self,
+        state: &mut State<'_, 'env>,
+        out: &mut Output,
+    ) -> Result<Option<BoxedFuture>, Error> {
+        #[cfg(feature = "tracing")]
+        let span = crate::trace::render(state.instructions.name());
+        let rv = self.eval_state(state, out);
+        let suspension = state.suspension.as_ref().unwrap();
+        let rv = match (suspension.calls.take_pending(), rv) {
+            (Some(future), Err(_)) if !suspension.is_resumed() => Ok(Some(future)),
+            // the call was made by code that did not pass on the error it
+            // received, so there is no way to continue the evaluation.
+            (Some(_), _) => Err(Error::new(
+                ErrorKind::InvalidOperation,
+                "async function was called from a place that cannot be suspended",
+            )),
+            (None, rv) => rv.map(|_| None),
+        };
+        #[cfg(feature = "tracing")]
+        {
+            crate::trace::record_fuel(&span, state, None);
+            crate::trace::record_result(&span, &rv);
+        }
+        rv
     }
 
     /// Evaluate a macro in a state.
+    ///
+    /// If `suspended` is provided the macro call can suspend an async render.
+    /// The record to continue it is then stored in there.
     #[cfg(feature = "macros")]
-    pub fn eval_macro(
+    pub(crate) fn eval_macro<'template>(
         &self,
-        state: &State,
-        macro_id: usize,
-        out: &mut Output,
-        closure: Value,
+        state: &State<'template, 'env>,
+        mac: &macro_object::Macro,
         caller: Option<Value>,
         args: Vec<Value>,
-    ) -> Result<Option<Value>, Error> {
-        let (instructions, pc) = &state.macros[macro_id];
+        #[cfg(feature = "async")] mut suspended: Option<
+            &mut Option<Box<SuspendedMacro<'template, 'env>>>,
+        >,
+    ) -> Result<String, Error> {
+        let (instructions, pc) = state.macros[mac.macro_ref_id];
+        let name = mac.name.as_str().unwrap_or_default();
+        let mut rv = String::new();
+
+        // a suspended macro call continues with the state it left off with.
+        #[cfg(feature = "async")]
+        let (resumed, direct) = match suspended {
+            Some(ref mut slot) => (slot.take(), true),
+            None => (None, false),
+        };
+        #[cfg(not(feature = "async"))]
+        let direct = false;
+        #[cfg(feature = "async")]
+        let mut captures = None;
+        #[cfg(feature = "async")]
+        let mut macro_state = match resumed {
+            Some(saved) => {
+                rv = saved.output;
+                captures = Some(saved.captures);
+                saved.state
+            }
+            None => ok!(self.enter_macro(state, mac, instructions, caller, direct)),
+        };
+        #[cfg(not(feature = "async"))]
+        let mut macro_state = ok!(self.enter_macro(state, mac, instructions, caller, direct));
+
+        let frame = macro_state.profiler_frame(|| format!("macro {name}"));
+        #[cfg(feature = "tracing")]
+        let (span, fuel_baseline) = (
+            crate::trace::call_macro(name, instructions.name()),
+            crate::trace::fuel_consumed(&macro_state),
+        );
+        let mut out = Output::new(&mut rv);
+        #[cfg(feature = "async")]
+        if let Some(captures) = captures {
+            out.resume(captures);
+        }
+        let result = self.do_eval(&mut macro_state, &mut out, Stack::from(args), pc);
+        #[cfg(feature = "async")]
+        let result = match (result, suspended) {
+            (Err(err), Some(slot)) if macro_state.is_suspending() => {
+                let captures = out.suspend();
+                *slot = Some(Box::new(SuspendedMacro {
+                    state: macro_state,
+                    output: rv,
+                    captures,
+                }));
+                return Err(err);
+            }
+            (result, _) => result,
+        };
+        #[cfg(feature = "tracing")]
+        {
+            crate::trace::record_fuel(&span, &macro_state, fuel_baseline);
+            crate::trace::record_result(&span, &result);
+        }
+        if let Some(instrumentation) = self.env.instrumentation() {
+            instrumentation.on_frame_pop(&macro_state);
+            instrumentation.on_macro_exit(&macro_state, name);
+        }
+        drop(frame);
+        result.map(|_| rv)
+    }
+
+    /// Calls a value from a call instruction.
+    fn call_value(
+        &self,
+        state: &mut State<'_, 'env>,
+        func: &Value,
+        args: &[Value],
+    ) -> Result<Value, Error> {
+        #[cfg(all(feature = "async", feature = "macros"))]
+        if let Some(rv) = self.call_suspendable_macro(state, func, args) {
+            return rv;
+        }
+        func.call(state, args)
+    }
+
+    /// Calls a method from a call instruction.
+    fn call_method(
+        &self,
+        state: &mut State<'_, 'env>,
+        obj: &Value,
+        name: &str,
+        args: &[Value],
+    ) -> Result<Value, Error> {
+        #[cfg(all(feature = "async", feature = "macros"))]
+        if state.suspension.is_some() {
+            if let Some(rv) = obj
+                .get_attr_fast(name)
+                .and_then(|func| self.call_suspendable_macro(state, &func, args))
+            {
+                return rv;
+            }
+        }
+        obj.call_method(state, name, args)
+    }
+
+    /// Calls `func` if it's a macro so that it can suspend an async render.
+    ///
+    /// Macros called by other code (for instance by the `map` filter) are
+    /// evaluated again as a whole when resuming, this is only needed for
+    /// macros the VM calls itself.
+    #[cfg(all(feature = "async", feature = "macros"))]
+    fn call_suspendable_macro<'template>(
+        &self,
+        state: &mut State<'template, 'env>,
+        func: &Value,
+        args: &[Value],
+    ) -> Option<Result<Value, Error>> {
+        let mac = func.downcast_object_ref::<macro_object::Macro>()?;
+        if !state.suspension.as_ref().is_some_and(|x| x.suspendable) {
+            return None;
+        }
+        let mut suspended = state.take_suspended(|x| match x {
+            Suspended::Macro(saved) => Ok(saved),
+            x => Err(x),
+        });
+        let rv = mac.invoke(state, args, Some(&mut suspended));
+        if let Some(saved) = suspended {
+            state.push_suspended(Suspended::Macro(saved));
+        }
+        Some(rv)
+    }
+
+    /// Creates the state a macro is evaluated in.
+    #[cfg(feature = "macros")]
+    #[cfg_attr(not(feature = "async"), allow(unused_variables))]
+    fn enter_macro<'template>(
+        &self,
+        state: &State<'template, 'env>,
+        mac: &macro_object::Macro,
+        instructions: &'template Instructions<'env>,
+        caller: Option<Value>,
+        direct: bool,
+    ) -> Result<State<'template, 'env>, Error> {
         let context_base = state.ctx.clone_base();
         let mut ctx = Context::new_with_frame(self.env, Frame::new(context_base));
-        ok!(ctx.push_frame(Frame::new(closure)));
+        ctx.inherit_options(&state.ctx);
+        ok!(ctx.push_frame(Frame::new(mac.closure.clone())));
         if let Some(caller) = caller {
             ctx.store("caller", caller);
         }
         ok!(ctx.incr_depth(state.ctx.depth() + MACRO_RECURSION_COST));
-        self.do_eval(
-            &mut State {
-                ctx,
-                current_block: None,
-                auto_escape: std::cell::Cell::new(state.auto_escape()),
-                instructions,
-                blocks: BTreeMap::default(),
-                temps: state.temps.clone(),
-                loaded_templates: Default::default(),
-                #[cfg(feature = "macros")]
-                id: state.id,
-                #[cfg(feature = "macros")]
-                macros: state.macros.clone(),
-                #[cfg(feature = "macros")]
-                closure_tracker: state.closure_tracker.clone(),
-                #[cfg(feature = "fuel")]
-                fuel_tracker: state.fuel_tracker.clone(),
-            },
-            out,
-            Stack::from(args),
-            *pc,
-        )
+        let macro_state = State {
+            ctx,
+            current_block: None,
+            auto_escape: std::cell::Cell::new(state.auto_escape()),
+            flush_requested: std::cell::Cell::new(false),
+            undefined_behavior: state.undefined_behavior,
+            instructions,
+            blocks: BTreeMap::default(),
+            temps: state.temps.clone(),
+            loaded_templates: Default::default(),
+            #[cfg(feature = "macros")]
+            id: state.id,
+            #[cfg(feature = "macros")]
+            macros: state.macros.clone(),
+            #[cfg(feature = "macros")]
+            closure_tracker: state.closure_tracker.clone(),
+            #[cfg(feature = "fuel")]
+            fuel_tracker: state.fuel_tracker.clone(),
+            cancel_tracker: state.cancel_tracker.clone(),
+            profiler: state.profiler.clone(),
+            #[cfg(feature = "async")]
+            suspension: state
+                .suspension
+                .as_ref()
+                .map(|x| Suspension::new(x.calls.clone(), x.suspendable && direct)),
+        };
+        if let Some(instrumentation) = self.env.instrumentation() {
+            let name = mac.name.as_str().unwrap_or_default();
+            instrumentation.on_macro_enter(&macro_state, name);
+            instrumentation.on_frame_push(&macro_state);
+        }
+        Ok(macro_state)
     }
 
     /// This is the actual evaluation loop that works with a specific context.
@@ -174,16 +475,29 @@ impl<'env> Vm<'env> {
         stack: Stack,
         pc: u32,
     ) -> Result<Option<Value>, Error> {
+        out.init_limit(self.env.output_limit());
+        let frame_count = state.ctx.frame_count();
         #[cfg(feature = "stacker")]
-        {
-            stacker::maybe_grow(32 * 1024, 1024 * 1024, || {
-                self.eval_impl(state, out, stack, pc)
-            })
-        }
-        #[cfg(not(feature = "stacker"))]
-        {
+        let rv = stacker::maybe_grow(32 * 1024, 1024 * 1024, || {
             self.eval_impl(state, out, stack, pc)
+        });
+        #[cfg(not(feature = "stacker"))]
+        let rv = self.eval_impl(state, out, stack, pc);
+
+        // a failed evaluation leaves the scopes it pushed behind.  Pop them
+        // so that the caller finds the stack as it left it and the
+        // instrumentation sees a pop for every push.  A suspended evaluation
+        // needs them to continue later.
+        if rv.is_err() && !state.is_suspending() {
+            let instrumentation = self.env.instrumentation();
+            while state.ctx.frame_count() > frame_count {
+                if let Some(instrumentation) = instrumentation {
+                    instrumentation.on_frame_pop(state);
+                }
+                state.ctx.pop_frame();
+            }
         }
+        rv
     }
 
     #[inline]
@@ -194,8 +508,12 @@ impl<'env> Vm<'env> {
         mut stack: Stack,
         mut pc: u32,
     ) -> Result<Option<Value>, Error> {
-        let initial_auto_escape = state.auto_escape.get();
+        #[cfg_attr(not(feature = "async"), allow(unused_mut))]
+        let mut initial_auto_escape = state.auto_escape.get();
         let undefined_behavior = state.undefined_behavior();
+        let sandbox_policy = state.env().sandbox_policy();
+        let instrumentation = state.env().instrumentation();
+        let size_limit = state.env().size_limit();
         let strict_undefined = matches!(
             undefined_behavior,
             UndefinedBehavior::Strict | UndefinedBehavior::SemiStrict
@@ -211,6 +529,31 @@ impl<'env> Vm<'env> {
         #[cfg(feature = "multi_template")]
         let mut parent_instructions = None;
 
+        // A suspended evaluation continues where it left off by running the
+        // instruction that suspended it again.  `resumed` is set while that
+        // instruction runs and tells if it's the innermost level, which is
+        // the level the results of the async calls are meant for.
+        #[cfg(feature = "async")]
+        let mut resumed = None;
+        #[cfg(feature = "async")]
+        if let Some(saved) = state.take_suspended(|x| match x {
+            Suspended::Eval(saved) => Ok(saved),
+            x => Err(x),
+        }) {
+            pc = saved.pc;
+            stack = saved.stack;
+            initial_auto_escape = saved.initial_auto_escape;
+            auto_escape_stack = saved.auto_escape_stack;
+            next_loop_recursion_jump = saved.next_loop_recursion_jump;
+            #[cfg(feature = "multi_template")]
+            {
+                parent_instructions = saved.parent_instructions;
+            }
+            resumed = state.suspension.as_ref().map(|x| x.is_resumed());
+        }
+
+        let mut failed = None;
+
         macro_rules! recurse_loop {
             ($capture:expr, $loop_object:expr) => {{
                 let Some(jump_target) = $loop_object.recurse_jump_target else {
@@ -234,7 +577,7 @@ impl<'env> Vm<'env> {
 
         // looks nicer this way
         #[allow(clippy::while_let_loop)]
-        loop {
+        'eval: loop {
             let instr = match state.instructions.get(pc) {
                 Some(instr) => instr,
                 #[cfg(not(feature = "multi_template"))]
@@ -266,7 +609,6 @@ impl<'env> Vm<'env> {
             // code bloat generated here.  Same with the error.
             let a;
             let b;
-            let mut err;
 
             macro_rules! func_binop {
                 ($method:ident) => {{
@@ -276,6 +618,17 @@ impl<'env> Vm<'env> {
                 }};
             }
 
+            macro_rules! limited_binop {
+                ($method:ident) => {{
+                    b = stack.pop();
+                    a = stack.pop();
+                    if let Some(policy) = sandbox_policy {
+                        ctx_ok!(sandbox::check_binop(policy, instr, &a, &b));
+                    }
+                    stack.push(ctx_ok!(ops::$method(&a, &b, size_limit)));
+                }};
+            }
+
             macro_rules! op_binop {
                 ($op:tt) => {{
                     b = stack.pop();
@@ -288,9 +641,8 @@ impl<'env> Vm<'env> {
 
             macro_rules! bail {
                 ($err:expr) => {{
-                    err = $err;
-                    process_err(&mut err, pc, state);
-                    return Err(err);
+                    failed = Some($err);
+                    break 'eval;
                 }};
             }
 
@@ -313,11 +665,33 @@ impl<'env> Vm<'env> {
                 }};
             }
 
-            // if the fuel consumption feature is enabled, track the fuel
-            // consumption here.
-            #[cfg(feature = "fuel")]
-            if let Some(ref tracker) = state.fuel_tracker {
-                ctx_ok!(tracker.track(instr));
+            // the instruction that is run again after resuming was already
+            // accounted for.
+            #[cfg(feature = "async")]
+            let track = resumed.is_none();
+            #[cfg(not(feature = "async"))]
+            let track = true;
+            if track {
+                // if the fuel consumption feature is enabled, track the fuel
+                // consumption here.
+                #[cfg(feature = "fuel")]
+                if let Some(ref tracker) = state.fuel_tracker {
+                    ctx_ok!(tracker.track(instr));
+                }
+                if let Some(ref tracker) = state.cancel_tracker {
+                    ctx_ok!(tracker.track());
+                }
+                if let Some(ref profiler) = state.profiler {
+                    profiler.track(state.instructions.name(), state.instructions.get_line(pc));
+                }
+                if let Some(instrumentation) = instrumentation {
+                    let location = Location {
+                        pc,
+                        line: state.instructions.get_line(pc),
+                        span: state.instructions.get_span(pc),
+                    };
+                    instrumentation.on_instruction(state, &location);
+                }
             }
 
             match instr {
@@ -328,19 +702,29 @@ impl<'env> Vm<'env> {
                     stack.push(b);
                 }
                 Instruction::EmitRaw(val) => {
-                    // this only produces a format error, no need to attach
-                    // location information.
-                    ok!(out.write_str(val).map_err(Error::from));
+                    ctx_ok!(out.write_str(val).map_err(Error::from));
+                }
+                Instruction::EmitRawConst(val) => {
+                    ctx_ok!(out
+                        .write_str(val.as_str().unwrap_or_default())
+                        .map_err(Error::from));
                 }
-                Instruction::Emit => {
+                Instruction::Emit | Instruction::EmitContextual(_) => {
                     let value = stack.pop();
                     if self.env.is_default_formatter() {
                         if strict_undefined
-                            && matches!(value.0, ValueRepr::Undefined(UndefinedType::Default))
+                            && matches!(value.0, ValueRepr::Undefined(UndefinedType::Default, _))
                         {
-                            bail!(Error::from(ErrorKind::UndefinedError));
+                            bail!(value.undefined_error());
                         }
-                        ctx_ok!(write_escaped(out, state.auto_escape.get(), &value));
+                        ctx_ok!(match instr {
+                            Instruction::EmitContextual(html_ctx)
+                                if state.auto_escape.get() == AutoEscape::Html =>
+                            {
+                                write_contextual(out, *html_ctx, &value)
+                            }
+                            _ => self.env.write_escaped(out, state.auto_escape.get(), &value),
+                        });
                     } else {
                         ctx_ok!(self.env.format(&value, state, out));
                     }
@@ -349,12 +733,16 @@ impl<'env> Vm<'env> {
                     state.ctx.store(name, stack.pop());
                 }
                 Instruction::Lookup(name) => {
-                    stack.push(assert_valid!(state
-                        .lookup(name)
-                        .unwrap_or(Value::UNDEFINED)));
+                    stack.push(match state.lookup(name) {
+                        Some(value) => assert_valid!(value),
+                        None => undefined_from_lookup(state, pc, None),
+                    });
                 }
                 Instruction::GetAttr(name) => {
                     a = stack.pop();
+                    if let Some(policy) = sandbox_policy {
+                        ctx_ok!(sandbox::check_attribute(policy, &a, name));
+                    }
                     // This is a common enough operation that it's interesting to consider a fast
                     // path here.  This is slightly faster than the regular attr lookup because we
                     // do not need to pass down the error object for the more common success case.
@@ -362,7 +750,10 @@ impl<'env> Vm<'env> {
                     // special case.
                     stack.push(match a.get_attr_fast(name) {
                         Some(value) => assert_valid!(value),
-                        None => ctx_ok!(undefined_behavior.handle_undefined(a.is_undefined())),
+                        None => {
+                            ctx_ok!(undefined_behavior.handle_undefined(&a));
+                            undefined_from_lookup(state, pc, Some(a.kind()))
+                        }
                     });
                 }
                 Instruction::SetAttr(name) => {
@@ -380,9 +771,15 @@ impl<'env> Vm<'env> {
                 Instruction::GetItem => {
                     a = stack.pop();
                     b = stack.pop();
+                    if let Some(policy) = sandbox_policy {
+                        ctx_ok!(sandbox::check_item(policy, &b, &a));
+                    }
                     stack.push(match b.get_item_opt(&a) {
                         Some(value) => assert_valid!(value),
-                        None => ctx_ok!(undefined_behavior.handle_undefined(b.is_undefined())),
+                        None => {
+                            ctx_ok!(undefined_behavior.handle_undefined(&b));
+                            undefined_from_lookup(state, pc, Some(b.kind()))
+                        }
                     });
                 }
                 Instruction::Slice => {
@@ -391,7 +788,7 @@ impl<'env> Vm<'env> {
                     b = stack.pop();
                     a = stack.pop();
                     if a.is_undefined() && matches!(undefined_behavior, UndefinedBehavior::Strict) {
-                        bail!(Error::from(ErrorKind::UndefinedError));
+                        bail!(a.undefined_error());
                     }
                     stack.push(ctx_ok!(ops::slice(a, b, stop, step)));
                 }
@@ -421,7 +818,9 @@ impl<'env> Vm<'env> {
                 Instruction::MergeKwargs(count) => {
                     let mut kwargs_sources = Vec::from_iter((0..*count).map(|_| stack.pop()));
                     kwargs_sources.reverse();
-                    stack.push(ctx_ok!(self.merge_kwargs(kwargs_sources)));
+                    stack.push(ctx_ok!(
+                        self.merge_kwargs(kwargs_sources, undefined_behavior)
+                    ));
                 }
                 Instruction::BuildList(n) => {
                     let count = n.unwrap_or_else(|| stack.pop().try_into().unwrap());
@@ -432,6 +831,14 @@ impl<'env> Vm<'env> {
                     v.reverse();
                     stack.push(Value::from_object(v))
                 }
+                Instruction::BuildTuple(count) => {
+                    let mut v = Vec::with_capacity(untrusted_size_hint(*count));
+                    for _ in 0..*count {
+                        v.push(stack.pop());
+                    }
+                    v.reverse();
+                    stack.push(Value::from_tuple(v))
+                }
                 Instruction::UnpackList(count) => {
                     ctx_ok!(self.unpack_list(&mut stack, *count));
                 }
@@ -446,12 +853,12 @@ impl<'env> Vm<'env> {
                     }
                     stack.push(Value::from(len));
                 }
-                Instruction::Add => func_binop!(add),
+                Instruction::Add => limited_binop!(add),
                 Instruction::Sub => func_binop!(sub),
-                Instruction::Mul => func_binop!(mul),
+                Instruction::Mul => limited_binop!(mul),
                 Instruction::Div => func_binop!(div),
                 Instruction::IntDiv => func_binop!(int_div),
-                Instruction::Rem => func_binop!(rem),
+                Instruction::Rem => limited_binop!(rem),
                 Instruction::Pow => func_binop!(pow),
                 Instruction::Eq => op_binop!(==),
                 Instruction::Ne => op_binop!(!=),
@@ -468,7 +875,11 @@ impl<'env> Vm<'env> {
                     b = stack.pop();
                     ctx_ok!(undefined_behavior.assert_value_not_undefined(&b));
                     ctx_ok!(undefined_behavior.assert_value_not_undefined(&a));
-                    stack.push(ops::string_concat(b, &a));
+                    let rv = ctx_ok!(ops::string_concat(b, &a, size_limit));
+                    if let (Some(policy), Some(s)) = (sandbox_policy, rv.as_str()) {
+                        ctx_ok!(sandbox::check_string_length(policy, s.len()));
+                    }
+                    stack.push(rv);
                 }
                 Instruction::In => {
                     a = stack.pop();
@@ -485,11 +896,20 @@ impl<'env> Vm<'env> {
                 }
                 Instruction::PushWith => {
                     ctx_ok!(state.ctx.push_frame(Frame::default()));
+                    if let Some(instrumentation) = instrumentation {
+                        instrumentation.on_frame_push(state);
+                    }
                 }
                 Instruction::PopFrame => {
+                    if let Some(instrumentation) = instrumentation {
+                        instrumentation.on_frame_pop(state);
+                    }
                     state.ctx.pop_frame();
                 }
                 Instruction::PopLoopFrame => {
+                    if let Some(instrumentation) = instrumentation {
+                        instrumentation.on_frame_pop(state);
+                    }
                     let mut l = state.ctx.pop_frame().current_loop.unwrap();
                     if let Some((target, end_capture)) = l.current_recursion_jump.take() {
                         pc = target;
@@ -507,6 +927,9 @@ impl<'env> Vm<'env> {
                 Instruction::PushLoop(flags) => {
                     a = stack.pop();
                     ctx_ok!(self.push_loop(state, a, *flags, pc, next_loop_recursion_jump.take()));
+                    if let Some(instrumentation) = instrumentation {
+                        instrumentation.on_frame_push(state);
+                    }
                 }
                 Instruction::Iterate(jump_target) => {
                     match state.ctx.current_loop().unwrap().next() {
@@ -567,6 +990,9 @@ impl<'env> Vm<'env> {
                 }
                 Instruction::ApplyFilter(name, arg_count, local_id) => {
                     let normalized_name = normalize_filter_test_name(name);
+                    if let Some(policy) = sandbox_policy {
+                        ctx_ok!(sandbox::check_filter(policy, normalized_name.as_ref()));
+                    }
                     let filter =
                         ctx_ok!(get_or_lookup_local(&mut loaded_filters, *local_id, || {
                             state.env().get_filter(normalized_name.as_ref())
@@ -578,13 +1004,15 @@ impl<'env> Vm<'env> {
                             )
                         }));
                     let args = stack.get_call_args(*arg_count);
-                    let arg_count = args.len();
                     a = ctx_ok!(filter.call(state, args));
-                    stack.drop_top(arg_count);
+                    stack.drop_call_args(*arg_count);
                     stack.push(a);
                 }
                 Instruction::PerformTest(name, arg_count, local_id) => {
                     let normalized_name = normalize_filter_test_name(name);
+                    if let Some(policy) = sandbox_policy {
+                        ctx_ok!(sandbox::check_test(policy, normalized_name.as_ref()));
+                    }
                     let test = ctx_ok!(get_or_lookup_local(&mut loaded_tests, *local_id, || {
                         state.env().get_test(normalized_name.as_ref())
                     })
@@ -595,9 +1023,8 @@ impl<'env> Vm<'env> {
                         )
                     }));
                     let args = stack.get_call_args(*arg_count);
-                    let arg_count = args.len();
                     a = ctx_ok!(test.call(state, args));
-                    stack.drop_top(arg_count);
+                    stack.drop_call_args(*arg_count);
                     stack.push(Value::from(a.is_true()));
                 }
                 Instruction::CallFunction(name, arg_count) => {
@@ -612,6 +1039,9 @@ impl<'env> Vm<'env> {
                         }
                         ctx_ok!(self.perform_super(state, out, true))
                     } else if let Some(func) = state.lookup(name) {
+                        if let Some(policy) = sandbox_policy {
+                            ctx_ok!(sandbox::check_call(policy, self.env, Some(name), &func));
+                        }
                         // calling loops is a special operation that starts the recursion process.
                         // this bypasses the actual `call` implementation which would just fail
                         // with an error.
@@ -622,9 +1052,13 @@ impl<'env> Vm<'env> {
                                     "loop() takes one argument"
                                 ));
                             }
+                            // the loop keeps the argument but not its count.
+                            if arg_count.is_none() {
+                                stack.pop();
+                            }
                             recurse_loop!(true, loop_object);
                         } else {
-                            ctx_ok!(func.call(state, args))
+                            ctx_ok!(self.call_value(state, &func, args))
                         }
                     } else {
                         bail!(Error::new(
@@ -632,23 +1066,40 @@ impl<'env> Vm<'env> {
                             format!("{name} is unknown"),
                         ));
                     };
-                    let arg_count = args.len();
-                    stack.drop_top(arg_count);
+                    stack.drop_call_args(*arg_count);
                     stack.push(rv);
+                    if state.flush_requested.replace(false) {
+                        ctx_ok!(out.flush().map_err(Error::from));
+                    }
                 }
                 Instruction::CallMethod(name, arg_count) => {
                     let args = stack.get_call_args(*arg_count);
-                    let arg_count = args.len();
-                    a = ctx_ok!(args[0].call_method(state, name, &args[1..]));
-                    stack.drop_top(arg_count);
+                    if let Some(policy) = sandbox_policy {
+                        ctx_ok!(sandbox::check_method(policy, &args[0], name));
+                        // objects call their attributes by default (eg: a
+                        // function stored in a map), check those as well.
+                        if let Some(func) = args[0].get_attr_fast(name) {
+                            ctx_ok!(sandbox::check_call(policy, self.env, None, &func));
+                        }
+                    }
+                    a = ctx_ok!(self.call_method(state, &args[0], name, &args[1..]));
+                    stack.drop_call_args(*arg_count);
                     stack.push(a);
+                    if state.flush_requested.replace(false) {
+                        ctx_ok!(out.flush().map_err(Error::from));
+                    }
                 }
                 Instruction::CallObject(arg_count) => {
                     let args = stack.get_call_args(*arg_count);
-                    let arg_count = args.len();
-                    a = ctx_ok!(args[0].call(state, &args[1..]));
-                    stack.drop_top(arg_count);
+                    if let Some(policy) = sandbox_policy {
+                        ctx_ok!(sandbox::check_call(policy, self.env, None, &args[0]));
+                    }
+                    a = ctx_ok!(self.call_value(state, &args[0], &args[1..]));
+                    stack.drop_call_args(*arg_count);
                     stack.push(a);
+                    if state.flush_requested.replace(false) {
+                        ctx_ok!(out.flush().map_err(Error::from));
+                    }
                 }
                 Instruction::DupTop => {
                     stack.push(stack.peek().clone());
@@ -700,8 +1151,11 @@ impl<'env> Vm<'env> {
                 }
                 #[cfg(feature = "multi_template")]
                 Instruction::Include(ignore_missing) => {
-                    a = stack.pop();
+                    // the name stays on the stack until the include finished
+                    // so that a suspended include can be resumed.
+                    a = stack.peek().clone();
                     ctx_ok!(self.perform_include(a, state, out, *ignore_missing));
+                    stack.pop();
                 }
                 #[cfg(feature = "multi_template")]
                 Instruction::ExportLocals => {
@@ -748,17 +1202,112 @@ impl<'env> Vm<'env> {
                             .map_or(Value::UNDEFINED, |x| Value::from_dyn_object(x.clone())),
                     );
                 }
+                #[cfg(feature = "i18n")]
+                Instruction::Translate(has_plural) => {
+                    let variables = stack.pop();
+                    let count = if *has_plural { Some(stack.pop()) } else { None };
+                    let plural = if *has_plural { Some(stack.pop()) } else { None };
+                    let singular = stack.pop();
+                    stack.push(ctx_ok!(crate::i18n::translate_block(
+                        state,
+                        &singular,
+                        plural.as_ref().zip(count.as_ref()),
+                        &variables,
+                    )));
+                }
+            }
+            #[cfg(feature = "async")]
+            if let Some(ref suspension) = state.suspension {
+                if resumed.take() == Some(true) {
+                    suspension.calls.finish();
+                }
+                // the async call was made by code that did not pass on the
+                // error it received, so there is no way to continue.
+                if suspension.suspendable && suspension.calls.take_pending().is_some() {
+                    bail!(Error::new(
+                        ErrorKind::InvalidOperation,
+                        "async function was called from a place that cannot be suspended",
+                    ));
+                }
             }
             pc += 1;
         }
 
-        Ok(stack.try_pop())
+        // errors are handled here to keep the evaluation loop small.
+        let Some(mut err) = failed else {
+            return Ok(stack.try_pop());
+        };
+        #[cfg(feature = "async")]
+        if state.is_suspending() {
+            return Err(self.suspend_eval(
+                state,
+                err,
+                SuspendedEval {
+                    pc,
+                    stack,
+                    initial_auto_escape,
+                    auto_escape_stack,
+                    next_loop_recursion_jump,
+                    #[cfg(feature = "multi_template")]
+                    parent_instructions,
+                    #[cfg(not(feature = "multi_template"))]
+                    _marker: std::marker::PhantomData,
+                },
+            ));
+        }
+        if err.kind() == ErrorKind::WriteFailure {
+            if let Some(limit_err) = out.limit_error() {
+                err = limit_err;
+            }
+        }
+        process_err(&mut err, pc, state);
+        Err(err)
+    }
+
+    /// Saves the evaluation loop of a suspended evaluation.
+    #[cfg(feature = "async")]
+    fn suspend_eval(
+        &self,
+        state: &mut State<'_, 'env>,
+        mut err: Error,
+        saved: SuspendedEval<'env>,
+    ) -> Error {
+        match state.instructions.get(saved.pc) {
+            Some(
+                Instruction::CallFunction(..)
+                | Instruction::CallMethod(..)
+                | Instruction::CallObject(..)
+                | Instruction::ApplyFilter(..)
+                | Instruction::PerformTest(..)
+                | Instruction::FastSuper,
+            ) => {}
+            #[cfg(feature = "multi_template")]
+            Some(Instruction::Include(..) | Instruction::CallBlock(..)) => {}
+            _ => {
+                // other instructions cannot be run again, so fail instead.
+                if let Some(ref suspension) = state.suspension {
+                    suspension.calls.take_pending();
+                }
+                err = Error::new(
+                    ErrorKind::InvalidOperation,
+                    "async function was called from a place that cannot be suspended",
+                );
+                process_err(&mut err, saved.pc, state);
+                return err;
+            }
+        }
+        state.push_suspended(Suspended::Eval(saved));
+        err
     }
 
-    fn merge_kwargs(&self, values: Vec<Value>) -> Result<Value, Error> {
+    fn merge_kwargs(
+        &self,
+        values: Vec<Value>,
+        undefined_behavior: UndefinedBehavior,
+    ) -> Result<Value, Error> {
         let mut rv = ValueMap::new();
         for value in values {
-            ok!(self.env.undefined_behavior().assert_iterable(&value));
+            ok!(undefined_behavior.assert_iterable(&value));
             let iter = ok!(value
                 .as_object()
                 .filter(|x| x.repr() == ObjectRepr::Map)
@@ -787,6 +1336,17 @@ impl<'env> Vm<'env> {
         out: &mut Output,
         ignore_missing: bool,
     ) -> Result<(), Error> {
+        // a suspended include continues with the template it left off with.
+        #[cfg(feature = "async")]
+        if let Some(saved) = state.take_suspended(|x| match x {
+            Suspended::Include(saved) => Ok(saved),
+            x => Err(x),
+        }) {
+            let saved = *saved;
+            let old = saved.frame.swap(state);
+            return self.eval_include(saved.template, old, state, out);
+        }
+
         let obj = name.as_object();
         let choices = obj
             .as_ref()
@@ -816,38 +1376,28 @@ impl<'env> Vm<'env> {
             };
 
             let (new_instructions, new_blocks) = ok!(tmpl.instructions_and_blocks());
-            let old_escape = state.auto_escape.replace(tmpl.initial_auto_escape());
-            let old_instructions = mem::replace(&mut state.instructions, new_instructions);
-            let old_blocks = mem::replace(&mut state.blocks, prepare_blocks(new_blocks));
-            // we need to make a copy of the loaded templates here as we want
-            // to forget about the templates that an include triggered by the
-            // time the include finishes.
-            let old_loaded_templates = state.loaded_templates.clone();
             ok!(state.ctx.incr_depth(INCLUDE_RECURSION_COST));
-            let rv;
-            #[cfg(feature = "macros")]
-            {
-                let old_closure = state.ctx.take_closure();
-                rv = self.eval_state(state, out);
-                state.ctx.reset_closure(old_closure);
+            let old = IncludeFrame {
+                auto_escape: tmpl.initial_auto_escape(),
+                instructions: new_instructions,
+                blocks: prepare_blocks(new_blocks),
+                // we need to make a copy of the loaded templates here as we want
+                // to forget about the templates that an include triggered by the
+                // time the include finishes.
+                loaded_templates: state.loaded_templates.clone(),
+                #[cfg(feature = "macros")]
+                closure: None,
             }
-            #[cfg(not(feature = "macros"))]
-            {
-                rv = self.eval_state(state, out);
+            .swap(state);
+            #[cfg(feature = "macros")]
+            let old = IncludeFrame {
+                closure: state.ctx.take_closure(),
+                ..old
+            };
+            if let Some(instrumentation) = state.env().instrumentation() {
+                instrumentation.on_include_enter(state, tmpl.name());
             }
-            state.ctx.decr_depth(INCLUDE_RECURSION_COST);
-            state.loaded_templates = old_loaded_templates;
-            state.auto_escape.set(old_escape);
-            state.instructions = old_instructions;
-            state.blocks = old_blocks;
-            ok!(rv.map_err(|err| {
-                Error::new(
-                    ErrorKind::BadInclude,
-                    format!("error in \"{}\"", tmpl.name()),
-                )
-                .with_source(err)
-            }));
-            return Ok(());
+            return self.eval_include(tmpl, old, state, out);
         }
         if !templates_tried.is_empty() && !ignore_missing {
             Err(Error::new(
@@ -869,9 +1419,67 @@ impl<'env> Vm<'env> {
         }
     }
 
-    fn perform_super(
+    /// Evaluates an included template.
+    ///
+    /// `old` holds the parts of the state to restore once the include finished.
+    #[cfg(feature = "multi_template")]
+    fn eval_include<'template>(
         &self,
-        state: &mut State<'_, 'env>,
+        tmpl: Template<'env, 'env>,
+        old: IncludeFrame<'template, 'env>,
+        state: &mut State<'template, 'env>,
+        out: &mut Output,
+    ) -> Result<(), Error> {
+        let frame = state.profiler_frame(|| format!("include {}", tmpl.name()));
+        #[cfg(feature = "tracing")]
+        let (span, fuel_baseline) = (
+            crate::trace::include(tmpl.name()),
+            crate::trace::fuel_consumed(state),
+        );
+        let rv = self.eval_state(state, out);
+        // a suspended include hands the state back to the including
+        // template until it's resumed.
+        #[cfg(feature = "async")]
+        if rv.is_err() && state.is_suspending() {
+            let frame = old.swap(state);
+            state.push_suspended(Suspended::Include(Box::new(SuspendedInclude {
+                template: tmpl,
+                frame,
+            })));
+            return rv.map(|_| ());
+        }
+        #[cfg(feature = "tracing")]
+        {
+            crate::trace::record_fuel(&span, state, fuel_baseline);
+            crate::trace::record_result(&span, &rv);
+            drop(span);
+        }
+        if let Some(instrumentation) = state.env().instrumentation() {
+            instrumentation.on_include_exit(state, tmpl.name());
+        }
+        drop(frame);
+        state.ctx.decr_depth(INCLUDE_RECURSION_COST);
+        #[cfg(feature = "macros")]
+        state.ctx.reset_closure(old.closure.clone());
+        old.swap(state);
+        ok!(rv.map_err(|mut err| {
+            // cancellations are not wrapped so that they can be detected
+            if err.kind() == ErrorKind::Cancelled {
+                return err;
+            }
+            resolve_undefined_origin(&mut err, state);
+            Error::new(
+                ErrorKind::BadInclude,
+                format!("error in \"{}\"", tmpl.name()),
+            )
+            .with_source(err)
+        }));
+        Ok(())
+    }
+
+    fn perform_super<'template>(
+        &self,
+        state: &mut State<'template, 'env>,
         out: &mut Output,
         capture: bool,
     ) -> Result<Value, Error> {
@@ -879,26 +1487,62 @@ impl<'env> Vm<'env> {
             Error::new(ErrorKind::InvalidOperation, "cannot super outside of block")
         }));
 
-        let block_stack = state.blocks.get_mut(name).unwrap();
-        if !block_stack.push() {
-            return Err(Error::new(
-                ErrorKind::InvalidOperation,
-                "no parent block exists",
-            ));
-        }
+        // a suspended super block continues with the parent block it left
+        // off with.  The block stack and capture were already set up.
+        #[cfg(feature = "async")]
+        let resumed = state.take_suspended(|x| match x {
+            Suspended::Super { instructions } => Ok(instructions),
+            x => Err(x),
+        });
+        #[cfg(not(feature = "async"))]
+        let resumed = None;
 
-        if capture {
-            out.begin_capture(CaptureMode::Capture);
-        }
+        let new_instructions = match resumed {
+            Some(instructions) => instructions,
+            None => {
+                let block_stack = state.blocks.get_mut(name).unwrap();
+                if !block_stack.push() {
+                    return Err(Error::new(
+                        ErrorKind::InvalidOperation,
+                        "no parent block exists",
+                    ));
+                }
+                if capture {
+                    out.begin_capture(CaptureMode::Capture);
+                }
+                ok!(state.ctx.push_frame(Frame::default()));
+                block_stack.instructions()
+            }
+        };
 
-        let old_instructions = mem::replace(&mut state.instructions, block_stack.instructions());
-        ok!(state.ctx.push_frame(Frame::default()));
+        let old_instructions = mem::replace(&mut state.instructions, new_instructions);
+        let frame = state.profiler_frame(|| format!("super {name}"));
+        let instrumentation = state.env().instrumentation();
+        if let (Some(instrumentation), None) = (instrumentation, resumed) {
+            instrumentation.on_frame_push(state);
+            instrumentation.on_block_enter(state, name);
+        }
         let rv = self.eval_state(state, out);
+        #[cfg(feature = "async")]
+        if rv.is_err() && state.is_suspending() {
+            let instructions = mem::replace(&mut state.instructions, old_instructions);
+            state.push_suspended(Suspended::Super { instructions });
+            return rv.map(|_| Value::UNDEFINED);
+        }
+        if let Some(instrumentation) = instrumentation {
+            instrumentation.on_block_exit(state, name);
+            instrumentation.on_frame_pop(state);
+        }
+        drop(frame);
         state.ctx.pop_frame();
         state.instructions = old_instructions;
         state.blocks.get_mut(name).unwrap().pop();
 
-        ok!(rv.map_err(|err| {
+        ok!(rv.map_err(|mut err| {
+            if err.kind() == ErrorKind::Cancelled {
+                return err;
+            }
+            resolve_undefined_origin(&mut err, state);
             Error::new(ErrorKind::EvalBlock, "error in super block").with_source(err)
         }));
         if capture {
@@ -940,34 +1584,73 @@ impl<'env> Vm<'env> {
     }
 
     #[cfg(feature = "multi_template")]
-    pub(crate) fn call_block(
+    pub(crate) fn call_block<'template>(
         &self,
         name: &str,
-        state: &mut State<'_, 'env>,
+        state: &mut State<'template, 'env>,
         out: &mut Output,
     ) -> Result<Option<Value>, Error> {
-        if let Some((name, block_stack)) = state.blocks.get_key_value(name) {
-            if block_stack.len() == 1 && block_stack.instructions().is_required_block() {
-                return Err(Error::new(
-                    ErrorKind::InvalidOperation,
-                    format!("Required block '{name}' not found"),
-                ));
+        // a suspended block continues where it left off.
+        #[cfg(feature = "async")]
+        let resumed = state.take_suspended(|x| match x {
+            Suspended::Block {
+                block,
+                instructions,
+            } => Ok((block, instructions)),
+            x => Err(x),
+        });
+        #[cfg(not(feature = "async"))]
+        let resumed = None;
+
+        let (name, new_instructions) = match resumed {
+            Some((name, instructions)) => (name, instructions),
+            None => {
+                let Some((&name, block_stack)) = state.blocks.get_key_value(name) else {
+                    return Err(Error::new(
+                        ErrorKind::UnknownBlock,
+                        format!("block '{name}' not found"),
+                    ));
+                };
+                if block_stack.len() == 1 && block_stack.instructions().is_required_block() {
+                    return Err(Error::new(
+                        ErrorKind::InvalidOperation,
+                        format!("Required block '{name}' not found"),
+                    ));
+                }
+                let instructions = block_stack.instructions();
+                state.ctx.push_frame(Frame::default())?;
+                (name, instructions)
             }
-            let old_block = state.current_block.replace(name);
-            let old_instructions =
-                mem::replace(&mut state.instructions, block_stack.instructions());
-            state.ctx.push_frame(Frame::default())?;
-            let rv = self.eval_state(state, out);
-            state.ctx.pop_frame();
-            state.instructions = old_instructions;
+        };
+
+        let old_block = state.current_block.replace(name);
+        let old_instructions = mem::replace(&mut state.instructions, new_instructions);
+        let frame = state.profiler_frame(|| format!("block {name}"));
+        let instrumentation = state.env().instrumentation();
+        if let (Some(instrumentation), None) = (instrumentation, resumed) {
+            instrumentation.on_frame_push(state);
+            instrumentation.on_block_enter(state, name);
+        }
+        let rv = self.eval_state(state, out);
+        #[cfg(feature = "async")]
+        if rv.is_err() && state.is_suspending() {
+            let instructions = mem::replace(&mut state.instructions, old_instructions);
             state.current_block = old_block;
-            rv
-        } else {
-            Err(Error::new(
-                ErrorKind::UnknownBlock,
-                format!("block '{name}' not found"),
-            ))
+            state.push_suspended(Suspended::Block {
+                block: name,
+                instructions,
+            });
+            return rv;
         }
+        if let Some(instrumentation) = instrumentation {
+            instrumentation.on_block_exit(state, name);
+            instrumentation.on_frame_pop(state);
+        }
+        drop(frame);
+        state.ctx.pop_frame();
+        state.instructions = old_instructions;
+        state.current_block = old_block;
+        rv
     }
 
     fn derive_auto_escape(
@@ -979,16 +1662,23 @@ impl<'env> Vm<'env> {
             (Some("html"), _) => Ok(AutoEscape::Html),
             #[cfg(feature = "json")]
             (Some("json"), _) => Ok(AutoEscape::Json),
+            (Some("shell"), _) => Ok(AutoEscape::Shell),
+            (Some("css"), _) => Ok(AutoEscape::Css),
+            (Some("url"), _) => Ok(AutoEscape::Url),
+            (Some("latex"), _) => Ok(AutoEscape::Latex),
             (Some("none"), _) | (None, false) => Ok(AutoEscape::None),
             (None, true) => Ok(if matches!(initial_auto_escape, AutoEscape::None) {
                 AutoEscape::Html
             } else {
                 initial_auto_escape
             }),
-            _ => Err(Error::new(
-                ErrorKind::InvalidOperation,
-                "invalid value to autoescape tag",
-            )),
+            (Some(name), _) => match self.env.custom_auto_escape_name(name) {
+                Some(name) => Ok(AutoEscape::Custom(name)),
+                None => Err(Error::new(
+                    ErrorKind::InvalidOperation,
+                    "invalid value to autoescape tag",
+                )),
+            },
         }
     }
 
@@ -1072,7 +1762,8 @@ impl<'env> Vm<'env> {
         name: &str,
         flags: u8,
     ) {
-        use crate::{compiler::instructions::MACRO_CALLER, vm::macro_object::Macro};
+        use crate::compiler::instructions::{MACRO_CALLER, MACRO_KWARGS, MACRO_VARARGS};
+        use crate::vm::macro_object::Macro;
 
         let arg_spec = stack.pop().try_iter().unwrap().collect();
         let closure = stack.pop();
@@ -1085,13 +1776,90 @@ impl<'env> Vm<'env> {
             state_id: state.id,
             closure,
             caller_reference: (flags & MACRO_CALLER) != 0,
+            catch_varargs: (flags & MACRO_VARARGS) != 0,
+            catch_kwargs: (flags & MACRO_KWARGS) != 0,
         }));
     }
 }
 
+/// Creates an undefined value that remembers the failed lookup at `pc`.
+#[inline(always)]
+fn undefined_from_lookup(state: &State, pc: u32, parent: Option<ValueKind>) -> Value {
+    Value::undefined_with_origin(UndefinedOrigin {
+        instructions_id: state.instructions.id(),
+        pc,
+        parent,
+    })
+}
+
+/// Makes an undefined error point to the lookup that created the undefined
+/// value if the lookup happened in the current instructions.
+fn resolve_undefined_origin(err: &mut Error, state: &State) {
+    let Some(origin) = err.undefined_origin() else {
+        return;
+    };
+    if let Some(detail) = describe_undefined_origin(state, origin) {
+        err.set_undefined_origin(None);
+        err.set_detail(detail);
+        if let Some(span) = state.instructions.get_span(origin.pc) {
+            err.set_filename_and_span(state.instructions.name(), span);
+        } else if let Some(lineno) = state.instructions.get_line(origin.pc) {
+            err.set_filename_and_line(state.instructions.name(), lineno);
+        }
+    }
+}
+
+/// Describes the failed lookup an undefined value originated from.
+///
+/// Returns `None` if the lookup did not happen in the current instructions.
+#[inline(never)]
+#[cold]
+fn describe_undefined_origin(state: &State, origin: UndefinedOrigin) -> Option<String> {
+    let instructions = state.instructions;
+    if origin.instructions_id != instructions.id() {
+        return None;
+    }
+    let source = instructions.get_span(origin.pc).and_then(|span| {
+        instructions
+            .source()
+            .get(span.start_offset as usize..span.end_offset as usize)
+    });
+    let (expr, reason) = match (instructions.get(origin.pc), origin.parent) {
+        (Some(Instruction::Lookup(name)), _) => (name.to_string(), None),
+        (Some(Instruction::GetAttr(name)), Some(parent)) => (
+            source.map_or_else(|| name.to_string(), |x| x.to_string()),
+            Some(format!("`{name}` missing on {parent}")),
+        ),
+        (Some(Instruction::GetItem), Some(parent)) => {
+            // the key is only known if it's a constant
+            let key = match origin.pc.checked_sub(1).and_then(|pc| instructions.get(pc)) {
+                Some(Instruction::LoadConst(key)) => Some(key),
+                _ => None,
+            };
+            (
+                match (source, key) {
+                    (Some(source), _) => source.to_string(),
+                    (None, Some(key)) => format!("[{key:?}]"),
+                    (None, None) => "[...]".to_string(),
+                },
+                Some(match key {
+                    Some(key) => format!("`{key}` missing on {parent}"),
+                    None => format!("item missing on {parent}"),
+                }),
+            )
+        }
+        _ => return None,
+    };
+    Some(match reason {
+        Some(reason) => format!("`{expr}` is undefined ({reason})"),
+        None => format!("`{expr}` is undefined"),
+    })
+}
+
 #[inline(never)]
 #[cold]
 fn process_err(err: &mut Error, pc: u32, state: &State) {
+    resolve_undefined_origin(err, state);
     // only attach line information if the error does not have line info yet.
     if err.line().is_none() {
         if let Some(span) = state.instructions.get_span(pc) {
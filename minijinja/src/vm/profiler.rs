@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::profile::{Profile, ProfileEntry};
+
+/// Helper for profiling renders.
+///
+/// Like the fuel tracker this is shared across nested invocations of the
+/// template evaluation.
+pub struct Profiler {
+    inner: Mutex<ProfilerInner>,
+}
+
+/// Keeps a frame pushed onto the profiler until dropped.
+pub struct ProfilerFrame {
+    profiler: Arc<Profiler>,
+}
+
+impl Drop for ProfilerFrame {
+    fn drop(&mut self) {
+        self.profiler.inner.lock().unwrap().current.pop();
+    }
+}
+
+struct Sample {
+    node: usize,
+    template: usize,
+    line: usize,
+    instructions: u64,
+    time: Duration,
+}
+
+#[derive(Default)]
+struct ProfilerInner {
+    // stacks are stored as a tree of frames, `current` is the path to the
+    // frame that is currently executing.
+    nodes: Vec<(Option<usize>, String)>,
+    node_ids: HashMap<(Option<usize>, String), usize>,
+    current: Vec<usize>,
+    templates: Vec<String>,
+    template_ids: HashMap<String, usize>,
+    samples: Vec<Sample>,
+    sample_ids: HashMap<(usize, usize, usize), usize>,
+    last: Option<(usize, Instant)>,
+}
+
+impl ProfilerInner {
+    fn push(&mut self, label: String) {
+        let parent = self.current.last().copied();
+        let node = match self.node_ids.get(&(parent, label.clone())) {
+            Some(&node) => node,
+            None => {
+                self.nodes.push((parent, label.clone()));
+                self.node_ids.insert((parent, label), self.nodes.len() - 1);
+                self.nodes.len() - 1
+            }
+        };
+        self.current.push(node);
+    }
+
+    fn template_id(&mut self, name: &str) -> usize {
+        if let Some(&id) = self.template_ids.get(name) {
+            return id;
+        }
+        self.templates.push(name.to_string());
+        self.template_ids
+            .insert(name.to_string(), self.templates.len() - 1);
+        self.templates.len() - 1
+    }
+
+    fn stack(&self, mut node: Option<usize>) -> Vec<String> {
+        let mut rv = Vec::new();
+        while let Some(idx) = node {
+            let (parent, ref label) = self.nodes[idx];
+            rv.push(label.clone());
+            node = parent;
+        }
+        rv.reverse();
+        rv
+    }
+}
+
+impl Profiler {
+    /// Creates a new profiler for a render of the given template.
+    pub fn new(name: &str) -> Arc<Profiler> {
+        let mut inner = ProfilerInner::default();
+        inner.push(name.to_string());
+        Arc::new(Profiler {
+            inner: Mutex::new(inner),
+        })
+    }
+
+    /// Records the execution of an instruction.
+    ///
+    /// The time since the last recorded instruction is attributed to that
+    /// instruction.
+    pub fn track(&self, template: &str, line: Option<usize>) {
+        let now = Instant::now();
+        let mut inner = self.inner.lock().unwrap();
+        if let Some((idx, last)) = inner.last {
+            inner.samples[idx].time += now - last;
+        }
+        let node = inner.current.last().copied().unwrap_or(0);
+        let template = inner.template_id(template);
+        let line = line.unwrap_or(0);
+        let idx = match inner.sample_ids.get(&(node, template, line)) {
+            Some(&idx) => idx,
+            None => {
+                inner.samples.push(Sample {
+                    node,
+                    template,
+                    line,
+                    instructions: 0,
+                    time: Duration::ZERO,
+                });
+                let idx = inner.samples.len() - 1;
+                inner.sample_ids.insert((node, template, line), idx);
+                idx
+            }
+        };
+        inner.samples[idx].instructions += 1;
+        inner.last = Some((idx, now));
+    }
+
+    /// Pushes a frame that is popped when the returned value is dropped.
+    pub fn enter(self: &Arc<Self>, label: String) -> ProfilerFrame {
+        self.inner.lock().unwrap().push(label);
+        ProfilerFrame {
+            profiler: self.clone(),
+        }
+    }
+
+    /// Finishes the measurements and returns the profile.
+    pub fn finish(&self) -> Profile {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some((idx, last)) = inner.last.take() {
+            inner.samples[idx].time += last.elapsed();
+        }
+        Profile {
+            entries: inner
+                .samples
+                .iter()
+                .map(|sample| ProfileEntry {
+                    stack: inner.stack(Some(sample.node)),
+                    template: inner.templates[sample.template].clone(),
+                    line: sample.line,
+                    instructions: sample.instructions,
+                    time: sample.time,
+                })
+                .collect(),
+        }
+    }
+}
@@ -13,8 +13,10 @@ use crate::utils::{AutoEscape, UndefinedBehavior};
 use crate::value::{ArgType, Object, Value};
 use crate::vm::context::Context;
 
+use crate::vm::cancel::CancelTracker;
 #[cfg(feature = "fuel")]
 use crate::vm::fuel::FuelTracker;
+use crate::vm::profiler::{Profiler, ProfilerFrame};
 
 /// When macros are used, the state carries an `id` counter.  Whenever a state is
 /// created, the counter is incremented.  This exists because macros can keep a reference
@@ -45,6 +47,8 @@ pub struct State<'template, 'env> {
     pub(crate) ctx: Context<'env>,
     pub(crate) current_block: Option<&'env str>,
     pub(crate) auto_escape: Cell<AutoEscape>,
+    pub(crate) flush_requested: Cell<bool>,
+    pub(crate) undefined_behavior: UndefinedBehavior,
     pub(crate) instructions: &'template Instructions<'env>,
     pub(crate) temps: Arc<Mutex<BTreeMap<Box<str>, Value>>>,
     pub(crate) blocks: BTreeMap<&'env str, BlockStack<'template, 'env>>,
@@ -58,6 +62,10 @@ pub struct State<'template, 'env> {
     pub(crate) closure_tracker: std::sync::Arc<crate::vm::closure_object::ClosureTracker>,
     #[cfg(feature = "fuel")]
     pub(crate) fuel_tracker: Option<std::sync::Arc<FuelTracker>>,
+    pub(crate) cancel_tracker: Option<std::sync::Arc<CancelTracker>>,
+    pub(crate) profiler: Option<std::sync::Arc<Profiler>>,
+    #[cfg(feature = "async")]
+    pub(crate) suspension: Option<crate::vm::async_render::Suspension<'template, 'env>>,
 }
 
 impl fmt::Debug for State<'_, '_> {
@@ -85,6 +93,8 @@ impl<'template, 'env> State<'template, 'env> {
             id: STATE_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed),
             current_block: None,
             auto_escape: Cell::new(auto_escape),
+            flush_requested: Cell::new(false),
+            undefined_behavior: ctx.env().undefined_behavior(),
             instructions,
             blocks,
             temps: Default::default(),
@@ -95,6 +105,10 @@ impl<'template, 'env> State<'template, 'env> {
             closure_tracker: Default::default(),
             #[cfg(feature = "fuel")]
             fuel_tracker: ctx.env().fuel().map(FuelTracker::new),
+            cancel_tracker: None,
+            profiler: None,
+            #[cfg(feature = "async")]
+            suspension: None,
             ctx,
         }
     }
@@ -109,6 +123,50 @@ impl<'template, 'env> State<'template, 'env> {
         )
     }
 
+    /// Returns `true` if the evaluation of this state is being suspended.
+    #[inline(always)]
+    pub(crate) fn is_suspending(&self) -> bool {
+        #[cfg(feature = "async")]
+        {
+            self.suspension
+                .as_ref()
+                .is_some_and(|x| x.suspendable && x.calls.is_pending())
+        }
+        #[cfg(not(feature = "async"))]
+        {
+            false
+        }
+    }
+
+    /// Takes the record of a suspended evaluation if `f` accepts it.
+    #[cfg(feature = "async")]
+    pub(crate) fn take_suspended<T, F>(&mut self, f: F) -> Option<T>
+    where
+        F: FnOnce(
+            crate::vm::async_render::Suspended<'template, 'env>,
+        ) -> Result<T, crate::vm::async_render::Suspended<'template, 'env>>,
+    {
+        self.suspension.as_mut().and_then(|x| x.take(f))
+    }
+
+    /// Saves the record of a suspended evaluation.
+    #[cfg(feature = "async")]
+    pub(crate) fn push_suspended(
+        &mut self,
+        record: crate::vm::async_render::Suspended<'template, 'env>,
+    ) {
+        if let Some(ref mut suspension) = self.suspension {
+            suspension.push(record);
+        }
+    }
+
+    /// Pushes a frame onto the profiler if the render is profiled.
+    pub(crate) fn profiler_frame<F: FnOnce() -> String>(&self, label: F) -> Option<ProfilerFrame> {
+        self.profiler
+            .as_ref()
+            .map(|profiler| profiler.enter(label()))
+    }
+
     /// Returns a reference to the current environment.
     #[inline(always)]
     pub fn env(&self) -> &'env Environment<'env> {
@@ -144,7 +202,7 @@ impl<'template, 'env> State<'template, 'env> {
     /// Returns the current undefined behavior.
     #[inline(always)]
     pub fn undefined_behavior(&self) -> UndefinedBehavior {
-        self.env().undefined_behavior()
+        self.undefined_behavior
     }
 
     /// Returns the name of the innermost block.
@@ -227,7 +285,7 @@ impl<'template, 'env> State<'template, 'env> {
     {
         let mut wrapper = crate::output::WriteWrapper { w, err: None };
         crate::vm::Vm::new(self.env())
-            .call_block(block, self, &mut Output::new(&mut wrapper))
+            .call_block(block, self, &mut Output::new_flushable(&mut wrapper))
             .map(|_| ())
             .map_err(|err| wrapper.take_err(err))
     }
@@ -273,6 +331,9 @@ impl<'template, 'env> State<'template, 'env> {
     /// assert_eq!(rv.as_str(), Some("HELLO WORLD"));
     /// ```
     pub fn apply_filter(&self, filter: &str, args: &[Value]) -> Result<Value, Error> {
+        if let Some(policy) = self.env().sandbox_policy() {
+            ok!(crate::sandbox::check_filter(policy, filter));
+        }
         match self.env().get_filter(filter) {
             Some(filter) => filter.call(self, args),
             None => Err(Error::from(ErrorKind::UnknownFilter)),
@@ -291,6 +352,9 @@ impl<'template, 'env> State<'template, 'env> {
     /// assert!(rv);
     /// ```
     pub fn perform_test(&self, test: &str, args: &[Value]) -> Result<bool, Error> {
+        if let Some(policy) = self.env().sandbox_policy() {
+            ok!(crate::sandbox::check_test(policy, test));
+        }
         match self.env().get_test(test) {
             Some(test) => test.call(self, args).map(|x| x.is_true()),
             None => Err(Error::from(ErrorKind::UnknownTest)),
@@ -0,0 +1,126 @@
+#![cfg(all(feature = "macros", feature = "multi_template"))]
+use minijinja::ast::{walk_expr, walk_stmt, CallArg, Expr, Stmt, Visitor};
+use minijinja::{Environment, ErrorKind};
+
+use similar_asserts::assert_eq;
+
+#[derive(Default)]
+struct Collector<'a> {
+    filters: Vec<(&'a str, usize)>,
+    vars: Vec<&'a str>,
+    kwargs: Vec<&'a str>,
+    macros: Vec<&'a str>,
+    stmts: usize,
+}
+
+impl<'a> Visitor<'a> for Collector<'a> {
+    fn visit_stmt(&mut self, stmt: &Stmt<'a>) {
+        self.stmts += 1;
+        if let Stmt::Macro(m) = stmt {
+            self.macros.push(m.name);
+        }
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr<'a>) {
+        match expr {
+            Expr::Var(var) => self.vars.push(var.id),
+            Expr::Filter(f) => self.filters.push((f.name, expr.span().start_line as usize)),
+            _ => {}
+        }
+        walk_expr(self, expr);
+    }
+
+    fn visit_call_arg(&mut self, arg: &CallArg<'a>) {
+        if let CallArg::Kwarg(name, _) = arg {
+            self.kwargs.push(name);
+        }
+        minijinja::ast::walk_call_arg(self, arg);
+    }
+}
+
+#[test]
+fn test_visitor() {
+    let env = Environment::new();
+    let source = r#"{% extends layout %}
+{% macro greet(name, greeting="Hello") %}{{ greeting }} {{ name|title }}{% endmacro %}
+{% block body %}
+  {% for user in users if user.active %}
+    {{ greet(user.name, greeting=salutation|default("Hi")) }}
+  {% endfor %}
+{% endblock %}"#;
+    let ast = env.parse("test.html", source).unwrap();
+
+    let mut collector = Collector::default();
+    collector.visit_stmt(&ast);
+    assert_eq!(collector.filters, vec![("title", 2), ("default", 5)]);
+    assert_eq!(
+        collector.vars,
+        vec![
+            "layout",
+            "name",
+            "greeting",
+            "greeting",
+            "name",
+            "user",
+            "users",
+            "user",
+            "greet",
+            "user",
+            "salutation"
+        ]
+    );
+    assert_eq!(collector.kwargs, vec!["greeting"]);
+    assert_eq!(collector.macros, vec!["greet"]);
+    assert_eq!(collector.stmts, 15);
+}
+
+#[test]
+fn test_spans() {
+    let env = Environment::new();
+    let source = "Hello {{ name }}!\n{% if x %}{{ y }}{% endif %}";
+    let ast = env.parse("test.txt", source).unwrap();
+    let Stmt::Template(t) = ast else {
+        panic!("expected template");
+    };
+    let spans = t
+        .children
+        .iter()
+        .map(|stmt| {
+            let span = stmt.span();
+            &source[span.start_offset as usize..span.end_offset as usize]
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        spans,
+        vec!["Hello ", "{{ name", "!\n", "if x %}{{ y }}{% endif"]
+    );
+}
+
+#[test]
+fn test_parse_uses_environment_config() {
+    let mut env = Environment::new();
+    env.set_trim_blocks(true);
+    let ast = env
+        .parse("test.txt", "{% if true %}\nyes{% endif %}")
+        .unwrap();
+    let Stmt::Template(t) = ast else {
+        panic!("expected template");
+    };
+    let Stmt::IfCond(cond) = &t.children[0] else {
+        panic!("expected if");
+    };
+    let Stmt::EmitRaw(raw) = &cond.true_body[0] else {
+        panic!("expected raw");
+    };
+    assert_eq!(raw.raw, "yes");
+}
+
+#[test]
+fn test_parse_error() {
+    let env = Environment::new();
+    let err = env.parse("test.txt", "{% if %}").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::SyntaxError);
+    assert_eq!(err.name(), Some("test.txt"));
+    assert_eq!(err.line(), Some(1));
+}
@@ -0,0 +1,331 @@
+#![cfg(feature = "async")]
+use std::future::Future;
+use std::pin::pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+use minijinja::value::{from_args, Object, Value};
+use minijinja::{context, Environment, Error, ErrorKind, State};
+
+fn noop_waker() -> Waker {
+    fn noop_raw_waker() -> RawWaker {
+        fn clone(_: *const ()) -> RawWaker {
+            noop_raw_waker()
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+    unsafe { Waker::from_raw(noop_raw_waker()) }
+}
+
+fn block_on<F: Future>(fut: F) -> F::Output {
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut fut = pin!(fut);
+    loop {
+        if let Poll::Ready(rv) = fut.as_mut().poll(&mut cx) {
+            return rv;
+        }
+    }
+}
+
+/// A future that is pending once before it resolves.
+async fn yield_now() {
+    let mut yielded = false;
+    std::future::poll_fn(move |_| {
+        if yielded {
+            Poll::Ready(())
+        } else {
+            yielded = true;
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+#[test]
+fn test_async_function() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mut env = Environment::new();
+    env.add_async_function("get_config", {
+        let calls = calls.clone();
+        move |args: Vec<Value>| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            async move {
+                let (key,): (String,) = from_args(&args)?;
+                yield_now().await;
+                Ok(Value::from(key.to_uppercase()))
+            }
+        }
+    });
+    env.add_template("hello", "{{ get_config(a) }}|{{ get_config(b) }}")
+        .unwrap();
+    let tmpl = env.get_template("hello").unwrap();
+    let fut = tmpl.render_async(context!(a => "title", b => "footer"));
+    fn assert_send<T: Send>(_: &T) {}
+    assert_send(&fut);
+    let rv = block_on(fut).unwrap();
+    assert_eq!(rv, "TITLE|FOOTER");
+    assert_eq!(calls.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn test_async_filter() {
+    let mut env = Environment::new();
+    env.add_async_filter("lookup", |args: Vec<Value>| async move {
+        let (id,): (i64,) = from_args(&args)?;
+        Ok(Value::from(format!("user-{id}")))
+    });
+    let tmpl = env
+        .template_from_str(
+            "{% for id in ids %}{{ id|lookup }}{% if not loop.last %}, {% endif %}{% endfor %}",
+        )
+        .unwrap();
+    let rv = block_on(tmpl.render_async(context!(ids => vec![1, 2, 3]))).unwrap();
+    assert_eq!(rv, "user-1, user-2, user-3");
+}
+
+#[test]
+fn test_async_object_method() {
+    #[derive(Debug)]
+    struct Site;
+
+    impl Object for Site {
+        fn get_value(self: &Arc<Self>, key: &Value) -> Option<Value> {
+            match key.as_str()? {
+                "get_title" => Some(Value::from_async_function(|_| async move {
+                    Ok(Value::from("My Title"))
+                })),
+                _ => None,
+            }
+        }
+    }
+
+    let env = Environment::new();
+    let tmpl = env
+        .template_from_str("title: {{ site.get_title() }}")
+        .unwrap();
+    let rv = block_on(tmpl.render_async(context!(site => Value::from_object(Site)))).unwrap();
+    assert_eq!(rv, "title: My Title");
+}
+
+#[test]
+fn test_async_error() {
+    let mut env = Environment::new();
+    env.add_async_function("fail", |_| async move {
+        Err::<Value, _>(Error::new(ErrorKind::InvalidOperation, "broken"))
+    });
+    let tmpl = env.template_from_str("{{ fail() }}").unwrap();
+    let err = block_on(tmpl.render_async(())).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidOperation);
+    assert_eq!(err.detail(), Some("broken"));
+}
+
+#[test]
+fn test_async_function_in_sync_render() {
+    let mut env = Environment::new();
+    env.add_async_function("fetch", |_| async move { Ok(Value::from(42)) });
+    let err = env.render_str("{{ fetch() }}", ()).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidOperation);
+    assert_eq!(
+        err.detail(),
+        Some("async functions can only be called from an async render")
+    );
+}
+
+#[test]
+#[cfg(feature = "macros")]
+fn test_async_in_macro() {
+    let mut env = Environment::new();
+    env.add_async_function("double", |args: Vec<Value>| async move {
+        let (x,): (i64,) = from_args(&args)?;
+        yield_now().await;
+        Ok(Value::from(x * 2))
+    });
+    let tmpl = env
+        .template_from_str("{% macro m(x) %}[{{ double(x) }}]{% endmacro %}{{ m(1) }}{{ m(2) }}")
+        .unwrap();
+    let rv = block_on(tmpl.render_async(())).unwrap();
+    assert_eq!(rv, "[2][4]");
+}
+
+#[test]
+fn test_async_evaluates_once() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mut env = Environment::new();
+    env.add_function("count", {
+        let calls = calls.clone();
+        move || calls.fetch_add(1, Ordering::Relaxed)
+    });
+    env.add_async_function("fetch", |args: Vec<Value>| async move {
+        yield_now().await;
+        Ok(args[0].clone())
+    });
+    let tmpl = env
+        .template_from_str("{% for x in items %}{{ count() }}:{{ fetch(x) }} {% endfor %}")
+        .unwrap();
+    let rv = block_on(tmpl.render_async(context! {
+        items => Value::make_one_shot_iterator(["a", "b", "c"].into_iter().map(Value::from)),
+    }))
+    .unwrap();
+    assert_eq!(rv, "0:a 1:b 2:c ");
+    assert_eq!(calls.load(Ordering::Relaxed), 3);
+}
+
+#[test]
+fn test_async_cancel() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mut env = Environment::new();
+    env.add_function("count", {
+        let calls = calls.clone();
+        move || calls.fetch_add(1, Ordering::Relaxed)
+    });
+    env.add_async_function("never", |_| std::future::pending());
+    let tmpl = env
+        .template_from_str("{{ count() }}{{ never() }}{{ count() }}")
+        .unwrap();
+
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut fut = Box::pin(tmpl.render_async(()));
+    assert!(fut.as_mut().poll(&mut cx).is_pending());
+    assert!(fut.as_mut().poll(&mut cx).is_pending());
+    drop(fut);
+    assert_eq!(calls.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+#[should_panic = "boom"]
+fn test_async_panic() {
+    let mut env = Environment::new();
+    env.add_function("boom", || -> Value { panic!("boom") });
+    let tmpl = env.template_from_str("{{ boom() }}").unwrap();
+    block_on(tmpl.render_async(())).ok();
+}
+
+fn env_with_fetch() -> Environment<'static> {
+    let mut env = Environment::new();
+    env.add_async_function("fetch", |args: Vec<Value>| async move {
+        yield_now().await;
+        Ok(args[0].clone())
+    });
+    env
+}
+
+#[test]
+#[cfg(feature = "multi_template")]
+fn test_async_in_include() {
+    let mut env = env_with_fetch();
+    env.add_template("item", "[{{ fetch(x) }}]").unwrap();
+    env.add_template(
+        "list",
+        "{% for x in items %}{% include 'item' %}{% endfor %}!",
+    )
+    .unwrap();
+    let tmpl = env.get_template("list").unwrap();
+    let rv = block_on(tmpl.render_async(context!(items => vec!["a", "b"]))).unwrap();
+    assert_eq!(rv, "[a][b]!");
+}
+
+#[test]
+#[cfg(feature = "multi_template")]
+fn test_async_in_blocks() {
+    let mut env = env_with_fetch();
+    env.add_template(
+        "base",
+        "<{% block body %}{{ fetch('base') }}{% endblock %}>{% block footer %}{% endblock %}",
+    )
+    .unwrap();
+    env.add_template(
+        "child",
+        "{% extends 'base' %}{% block body %}{{ fetch('child') }}|{{ super() }}{% endblock %}\
+         {% block footer %}({{ super() }}{{ fetch('footer') }}){% endblock %}",
+    )
+    .unwrap();
+    let tmpl = env.get_template("child").unwrap();
+    let rv = block_on(tmpl.render_async(())).unwrap();
+    assert_eq!(rv, "<child|base>(footer)");
+}
+
+#[test]
+#[cfg(feature = "macros")]
+fn test_async_in_call_block() {
+    let env = env_with_fetch();
+    let tmpl = env
+        .template_from_str(
+            "{% macro wrap(x) %}({{ fetch(x) }}:{{ caller() }}){% endmacro %}\
+             {% call wrap('a') %}{{ fetch('b') }}{% endcall %}",
+        )
+        .unwrap();
+    let rv = block_on(tmpl.render_async(())).unwrap();
+    assert_eq!(rv, "(a:b)");
+}
+
+#[test]
+#[cfg(all(feature = "macros", feature = "multi_template"))]
+fn test_async_in_imported_macro() {
+    let mut env = env_with_fetch();
+    env.add_template("helpers", "{% macro m(x) %}<{{ fetch(x) }}>{% endmacro %}")
+        .unwrap();
+    env.add_template(
+        "main",
+        "{% import 'helpers' as h %}{{ h.m('a') }}{% from 'helpers' import m %}{{ m('b') }}",
+    )
+    .unwrap();
+    let tmpl = env.get_template("main").unwrap();
+    let rv = block_on(tmpl.render_async(())).unwrap();
+    assert_eq!(rv, "<a><b>");
+}
+
+#[test]
+fn test_async_in_captures() {
+    let env = env_with_fetch();
+    let tmpl = env
+        .template_from_str(
+            "{% set x %}{{ fetch('a') }}!{% endset %}[{{ x }}]\
+             {% filter upper %}{{ fetch('b') }}{% endfilter %}",
+        )
+        .unwrap();
+    let rv = block_on(tmpl.render_async(())).unwrap();
+    assert_eq!(rv, "[a!]B");
+}
+
+#[test]
+fn test_async_called_by_filter() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mut env = Environment::new();
+    env.add_async_filter("lookup", {
+        let calls = calls.clone();
+        move |args: Vec<Value>| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            async move {
+                let (id,): (i64,) = from_args(&args)?;
+                yield_now().await;
+                Ok(Value::from(format!("user-{id}")))
+            }
+        }
+    });
+    let tmpl = env
+        .template_from_str("{{ ids|map('lookup')|join(', ') }}")
+        .unwrap();
+    let rv = block_on(tmpl.render_async(context!(ids => vec![1, 2, 3]))).unwrap();
+    assert_eq!(rv, "user-1, user-2, user-3");
+    assert_eq!(calls.load(Ordering::Relaxed), 3);
+}
+
+#[test]
+fn test_async_not_suspendable() {
+    let mut env = env_with_fetch();
+    env.add_function("swallow", |state: &State, f: Value| {
+        f.call(state, &[Value::from(1)]).unwrap_or_default()
+    });
+    let tmpl = env.template_from_str("{{ swallow(fetch) }}").unwrap();
+    let err = block_on(tmpl.render_async(())).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidOperation);
+    assert_eq!(
+        err.detail(),
+        Some("async function was called from a place that cannot be suspended")
+    );
+}
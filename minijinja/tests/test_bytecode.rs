@@ -0,0 +1,172 @@
+#![cfg(all(
+    feature = "bytecode",
+    feature = "multi_template",
+    feature = "macros",
+    feature = "builtins"
+))]
+use std::fs;
+use std::path::Path;
+
+use minijinja::value::{Rest, Value};
+use minijinja::{context, Environment, ErrorKind};
+
+use similar_asserts::assert_eq;
+
+fn make_env() -> Environment<'static> {
+    let mut env = Environment::new();
+    env.add_function("get_args", |args: Rest<Value>| -> Value {
+        Value::from(args.0)
+    });
+    env
+}
+
+fn render_result(env: &Environment<'_>, name: &str, ctx: &Value) -> String {
+    let tmpl = env.get_template(name).unwrap();
+    match tmpl.render(ctx) {
+        Ok(rv) => rv,
+        Err(err) => format!("{err:#?}"),
+    }
+}
+
+#[test]
+fn test_roundtrip_inputs() {
+    let inputs = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/inputs");
+    let mut refs = Vec::new();
+    for entry in fs::read_dir(inputs.join("refs")).unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_name().unwrap().to_str().unwrap().to_string();
+        refs.push((name, fs::read_to_string(&path).unwrap()));
+    }
+
+    let mut checked = 0;
+    for entry in fs::read_dir(&inputs).unwrap() {
+        let path = entry.unwrap().path();
+        if !path.is_file() {
+            continue;
+        }
+        let filename = path.file_name().unwrap().to_str().unwrap().to_string();
+        let contents = fs::read_to_string(&path).unwrap();
+        let (ctx, source) = contents.split_once("\n---\n").unwrap();
+        let ctx: Value = serde_json::from_str(ctx).unwrap();
+        // debug.txt dumps the environment which differs between the two
+        if filename == "debug.txt" || !ctx.get_attr("$settings").unwrap().is_undefined() {
+            continue;
+        }
+
+        let mut env = make_env();
+        for (name, source) in &refs {
+            env.add_template_owned(name.clone(), source.clone())
+                .unwrap();
+        }
+        if env.add_template_owned(filename.clone(), source).is_err() {
+            continue;
+        }
+
+        let mut loaded_env = make_env();
+        for (name, tmpl) in env.templates() {
+            let bytecode = tmpl.to_bytecode().expect(name);
+            loaded_env
+                .add_template_bytecode(name.to_string(), &bytecode)
+                .unwrap();
+        }
+
+        let ctx = context! {
+            one_shot_iterator => Value::make_one_shot_iterator(0..3),
+            ..ctx
+        };
+        let expected = render_result(&env, &filename, &ctx);
+        let ctx = context! {
+            one_shot_iterator => Value::make_one_shot_iterator(0..3),
+            ..ctx
+        };
+        let actual = render_result(&loaded_env, &filename, &ctx);
+        assert_eq!(expected, actual, "bytecode mismatch for {filename}");
+        checked += 1;
+    }
+    assert!(checked > 100);
+}
+
+#[test]
+fn test_source_and_errors_survive() {
+    let mut env = Environment::new();
+    env.add_template("hello.txt", "Hello {{ name }}!\n{{ name - 1 }}")
+        .unwrap();
+    let tmpl = env.get_template("hello.txt").unwrap();
+    let bytecode = tmpl.to_bytecode().unwrap();
+    let expected_err = tmpl.render(context! { name => "World" }).unwrap_err();
+
+    let mut env = Environment::new();
+    env.add_template_bytecode("hello.txt", &bytecode).unwrap();
+    let tmpl = env.get_template("hello.txt").unwrap();
+    assert_eq!(tmpl.name(), "hello.txt");
+    assert_eq!(tmpl.source(), "Hello {{ name }}!\n{{ name - 1 }}");
+    let err = tmpl.render(context! { name => "World" }).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidOperation);
+    assert_eq!(err.line(), Some(2));
+    assert_eq!(err.range(), expected_err.range());
+    assert!(err.range().is_some());
+}
+
+#[test]
+fn test_contextual_escaping_survives() {
+    let mut env = Environment::new();
+    env.set_contextual_html_escaping(true);
+    env.add_template("x.html", "<a href=\"{{ url }}\" title={{ url }}>")
+        .unwrap();
+    let bytecode = env.get_template("x.html").unwrap().to_bytecode().unwrap();
+
+    let mut env = Environment::new();
+    env.add_template_bytecode("x.html", &bytecode).unwrap();
+    let rv = env
+        .get_template("x.html")
+        .unwrap()
+        .render(context! { url => "javascript:x" })
+        .unwrap();
+    assert_eq!(rv, "<a href=\"#ZgotmplZ\" title=javascript:x>");
+}
+
+#[test]
+fn test_rejects_bad_bytecode() {
+    let env = Environment::new();
+    let bytecode = env
+        .template_from_str("{% for item in seq %}{{ item }}{% endfor %}")
+        .unwrap()
+        .to_bytecode()
+        .unwrap();
+
+    let mut env = Environment::new();
+    let err = env.add_template_bytecode("x", b"garbage").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidBytecode);
+
+    let err = env
+        .add_template_bytecode("x", &bytecode[..bytecode.len() - 1])
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidBytecode);
+
+    let mut old_format = bytecode.clone();
+    old_format[4] = 1;
+    let err = env.add_template_bytecode("x", &old_format).unwrap_err();
+    assert!(err.to_string().contains("incompatible version"));
+
+    // version string lives right after the magic and format version
+    let mut stale = bytecode.clone();
+    stale[7] = b'0';
+    let err = env.add_template_bytecode("x", &stale).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidBytecode);
+    assert!(err.to_string().contains("incompatible version"));
+
+    let mut corrupted = bytecode.clone();
+    let last = corrupted.len() - 1;
+    corrupted[last] ^= 0xff;
+    let err = env.add_template_bytecode("x", &corrupted).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidBytecode);
+    assert!(err.to_string().contains("checksum"));
+
+    env.add_template_bytecode("x", &bytecode).unwrap();
+    let rv = env
+        .get_template("x")
+        .unwrap()
+        .render(context! { seq => [1, 2, 3] })
+        .unwrap();
+    assert_eq!(rv, "123");
+}
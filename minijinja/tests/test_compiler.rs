@@ -2,8 +2,9 @@
 use std::collections::BTreeMap;
 
 use minijinja::machinery::ast::Var;
-use minijinja::machinery::{CodeGenerator, Instruction};
+use minijinja::machinery::{optimize, parse, CodeGenerator, ConstExpr, Instruction};
 use minijinja::value::Value;
+use minijinja::Environment;
 
 #[test]
 fn test_for_loop() {
@@ -204,3 +205,17 @@ fn test_const_folding() {
     ));
     assert_eq!(binop_expr.as_const(), None);
 }
+
+#[test]
+fn test_optimizer() {
+    let source = "{{ 'a'|upper }}{# x #}{{ x|upper }}{# y #}!\n{% if x %}a{% elif y %}b{% endif %}";
+    let ast = parse(source, "<unknown>", Default::default(), Default::default()).unwrap();
+    let mut c = CodeGenerator::new("<unknown>", source);
+    c.compile_stmt(&ast);
+    let mut instructions = c.finish().0;
+    let mut env = Environment::new();
+    env.set_filter_folding(true);
+    optimize(&mut instructions, Some(&env));
+
+    insta::assert_debug_snapshot!(&instructions);
+}
@@ -0,0 +1,379 @@
+use similar_asserts::assert_eq;
+
+use minijinja::{context, Environment, ErrorKind, Value};
+
+fn render(source: &str, ctx: Value) -> String {
+    let mut env = Environment::new();
+    env.set_contextual_html_escaping(true);
+    env.add_template("test.html", source).unwrap();
+    env.get_template("test.html").unwrap().render(ctx).unwrap()
+}
+
+#[test]
+fn test_text_and_attributes() {
+    let ctx = context! { x => "<b>\"x\" y</b>" };
+    assert_eq!(
+        render("<p title=\"{{ x }}\">{{ x }}</p>", ctx.clone()),
+        "<p title=\"&lt;b&gt;&quot;x&quot; y&lt;&#x2f;b&gt;\">&lt;b&gt;&quot;x&quot; y&lt;&#x2f;b&gt;</p>"
+    );
+    assert_eq!(
+        render("<p title={{ x }} class=a>", ctx),
+        "<p title=&lt;b&gt;&quot;x&quot;&#32;y&lt;&#x2f;b&gt; class=a>"
+    );
+    assert_eq!(
+        render("<p title={{ x }}>", context! { x => "" }),
+        "<p title=ZgotmplZ>"
+    );
+    assert_eq!(
+        render(
+            "<textarea><script>{{ x }}</textarea>",
+            context! { x => "'" }
+        ),
+        "<textarea><script>&#x27;</textarea>"
+    );
+}
+
+#[test]
+fn test_script() {
+    let ctx = context! {
+        s => "</script><b>\"'",
+        n => 42,
+        items => vec![Value::from(1), Value::from("a"), Value::from(())],
+    };
+    assert_eq!(
+        render("<script>var s = {{ s }}, n = {{ n }};</script>", ctx.clone()),
+        "<script>var s = \"\\u003c\\/script\\u003e\\u003cb\\u003e\\u0022\\u0027\", n =  42 ;</script>"
+    );
+    assert_eq!(
+        render("<script>var x = {{ items }};</script>", ctx.clone()),
+        "<script>var x = [1,\"a\",null];</script>"
+    );
+    assert_eq!(
+        render("<script>var s = 'a{{ s }}';</script>", ctx.clone()),
+        "<script>var s = 'a\\u003c\\/script\\u003e\\u003cb\\u003e\\u0022\\u0027';</script>"
+    );
+    assert_eq!(
+        render(
+            "<script>var r = /{{ r }}/; // {{ s }}\n</script>",
+            context! { r => "a.b" }
+        ),
+        "<script>var r = /a\\.b/; // \n</script>"
+    );
+    assert_eq!(
+        render("<script>x = a / {{ n }} / 2;</script>", ctx),
+        "<script>x = a /  42  / 2;</script>"
+    );
+}
+
+#[test]
+fn test_event_handlers() {
+    assert_eq!(
+        render(
+            "<a onclick=\"go({{ x }}, '{{ x }}')\">",
+            context! { x => "a\"b" }
+        ),
+        "<a onclick=\"go(&quot;a\\u0022b&quot;, 'a\\u0022b')\">"
+    );
+}
+
+#[test]
+fn test_urls() {
+    assert_eq!(
+        render(
+            "<a href=\"{{ url }}\">",
+            context! { url => "javascript:alert(1)" }
+        ),
+        "<a href=\"#ZgotmplZ\">"
+    );
+    assert_eq!(
+        render(
+            "<a href=\"{{ url }}\">",
+            context! { url => "https://example.com/a b?x=\"1\"&y=2" }
+        ),
+        "<a href=\"https:&#x2f;&#x2f;example.com&#x2f;a%20b?x=%221%22&amp;y=2\">"
+    );
+    assert_eq!(
+        render(
+            "<a href=\"/search?q={{ q }}\">",
+            context! { q => "a&b c/d" }
+        ),
+        "<a href=\"/search?q=a%26b%20c%2Fd\">"
+    );
+    assert_eq!(
+        render(
+            "<img src=\"/img/{{ name }}\">",
+            context! { name => "javascript:x" }
+        ),
+        "<img src=\"/img/javascript:x\">"
+    );
+}
+
+#[test]
+fn test_css() {
+    assert_eq!(
+        render(
+            "<style>p { color: {{ a }}; background: {{ b }}; }</style>",
+            context! { a => "red", b => "url(evil)" }
+        ),
+        "<style>p { color: red; background: ZgotmplZ; }</style>"
+    );
+    assert_eq!(
+        render(
+            "<p style=\"font-family: '{{ font }}'\">",
+            context! { font => "a'b" }
+        ),
+        "<p style=\"font-family: 'a\\000027b'\">"
+    );
+}
+
+#[test]
+fn test_attribute_names_and_comments() {
+    assert_eq!(
+        render(
+            "<input {{ a }} {{ b }}><!-- {{ a }} -->",
+            context! { a => "checked", b => "onclick" }
+        ),
+        "<input checked ZgotmplZ><!--  -->"
+    );
+    let mut env = Environment::new();
+    env.set_contextual_html_escaping(true);
+    let err = env
+        .add_template("test.html", "<input data-{{ a }}=1>")
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::SyntaxError);
+    assert_eq!(
+        err.detail(),
+        Some("cannot emit a value inside an HTML attribute name")
+    );
+}
+
+fn render_err(env: &Environment, source: &str) -> String {
+    let err = env
+        .render_named_str("test.html", source, context! { x => "a" })
+        .unwrap_err();
+    err.detail().unwrap().to_string()
+}
+
+#[test]
+fn test_safe_values() {
+    assert_eq!(
+        render(
+            "<p title=\"{{ x|safe }}\">{{ x|safe }}</p>",
+            context! { x => "<b>" }
+        ),
+        "<p title=\"<b>\"><b></p>"
+    );
+    assert_eq!(
+        render(
+            "<script>var x = {% autoescape false %}{{ x }}{% endautoescape %};</script>",
+            context! { x => "{\"a\": 1}" }
+        ),
+        "<script>var x = {\"a\": 1};</script>"
+    );
+
+    let mut env = Environment::new();
+    env.set_contextual_html_escaping(true);
+    assert_eq!(
+        render_err(&env, "<script>var x = {{ x|safe }};</script>"),
+        "cannot emit a safe string in JavaScript, only plain values are escaped for it"
+    );
+    assert_eq!(
+        render_err(&env, "<a href=\"{{ x|safe }}\">"),
+        "cannot emit a safe string in a URL, only plain values are escaped for it"
+    );
+    assert_eq!(
+        render_err(
+            &env,
+            "{% set y %}<b>{{ x }}</b>{% endset %}<script>var y = '{{ y }}';</script>"
+        ),
+        "cannot emit a safe string in a JavaScript string, only plain values are escaped for it"
+    );
+}
+
+#[test]
+fn test_template_literals() {
+    let ctx = context! { x => "${alert(1)}`", n => 1 };
+    assert_eq!(
+        render("<script>var s = `a{{ x }}`;</script>", ctx.clone()),
+        "<script>var s = `a\\u0024\\u007balert(1)\\u007d\\u0060`;</script>"
+    );
+    assert_eq!(
+        render(
+            "<script>var s = `${ {a: 1}.a + {{ n }} }{{ x }}`;</script>",
+            ctx.clone()
+        ),
+        "<script>var s = `${ {a: 1}.a +  1  }\\u0024\\u007balert(1)\\u007d\\u0060`;</script>"
+    );
+    assert_eq!(
+        render(
+            "<script>var s = `${ `${ {{ n }} }` }`, t = {{ n }};</script>",
+            ctx
+        ),
+        "<script>var s = `${ `${  1  }` }`, t =  1 ;</script>"
+    );
+}
+
+#[test]
+fn test_control_flow() {
+    assert_eq!(
+        render(
+            "<input {% if c %}checked{% endif %} value=\"{{ v }}\">\
+             <script>{% for x in xs %}f({{ x }});{% endfor %}</script>",
+            context! { c => true, v => "a", xs => vec!["a", "b"] }
+        ),
+        "<input checked value=\"a\"><script>f(\"a\");f(\"b\");</script>"
+    );
+
+    let mut env = Environment::new();
+    env.set_contextual_html_escaping(true);
+    let err = env
+        .add_template("test.html", "{% if c %}<script>{% endif %}{{ x }}")
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::SyntaxError);
+    assert_eq!(
+        err.detail(),
+        Some("if branches end in different HTML contexts (JavaScript and HTML text)")
+    );
+    let err = env
+        .add_template("test.html", "<a title=\"{% for x in y %}\"{% endfor %}\">")
+        .unwrap_err();
+    assert_eq!(
+        err.detail(),
+        Some("loop iterations end in different HTML contexts (HTML tag and attribute value)")
+    );
+    let err = env
+        .add_template(
+            "test.html",
+            "<script>{% if c %}x{% else %}({% endif %} / 2</script>",
+        )
+        .unwrap_err();
+    assert_eq!(
+        err.detail(),
+        Some("'/' could start a division or regular expression")
+    );
+}
+
+#[cfg(feature = "macros")]
+#[test]
+fn test_macros() {
+    assert_eq!(
+        render(
+            "{% macro link(u) %}<a href=\"{{ u }}\">{% endmacro %}\
+             <script>var x = {{ x }};</script>{{ link(u) }}",
+            context! { x => "a", u => "javascript:x" }
+        ),
+        "<script>var x = \"a\";</script><a href=\"#ZgotmplZ\">"
+    );
+}
+
+#[cfg(feature = "macros")]
+#[test]
+fn test_macro_output_in_script() {
+    let mut env = Environment::new();
+    env.set_contextual_html_escaping(true);
+    assert_eq!(
+        render_err(
+            &env,
+            "{% macro m() %}{{ x }}{% endmacro %}<script>var x = {{ m() }};</script>"
+        ),
+        "cannot emit a safe string in JavaScript, only plain values are escaped for it"
+    );
+    assert_eq!(
+        render_err(
+            &env,
+            "{% macro m() %}<script>{{ caller() }}</script>{% endmacro %}\
+             {% call m() %}{{ x }}{% endcall %}"
+        ),
+        "cannot emit a safe string in JavaScript, only plain values are escaped for it"
+    );
+}
+
+#[cfg(feature = "multi_template")]
+#[test]
+fn test_blocks_and_includes() {
+    let mut env = Environment::new();
+    env.set_contextual_html_escaping(true);
+    env.add_template(
+        "layout.html",
+        "<title>{% block title %}{% endblock %}</title>{% block body %}{% endblock %}",
+    )
+    .unwrap();
+    env.add_template(
+        "index.html",
+        "{% extends 'layout.html' %}{% block title %}{{ x }}{% endblock %}\
+         {% block body %}<script>var x = {{ x }};</script>{% endblock %}",
+    )
+    .unwrap();
+    assert_eq!(
+        env.get_template("index.html")
+            .unwrap()
+            .render(context! { x => "<a>" })
+            .unwrap(),
+        "<title>&lt;a&gt;</title><script>var x = \"\\u003ca\\u003e\";</script>"
+    );
+
+    let err = env
+        .add_template(
+            "layout.html",
+            "<script>{% block script %}{% endblock %}</script>",
+        )
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::SyntaxError);
+    assert_eq!(err.detail(), Some("cannot use a block in JavaScript"));
+    let err = env
+        .add_template("layout.html", "<a href=\"{% block url %}{% endblock %}\">")
+        .unwrap_err();
+    assert_eq!(err.detail(), Some("cannot use a block in URL"));
+    let err = env
+        .add_template("index.html", "{% block body %}<script>{% endblock %}")
+        .unwrap_err();
+    assert_eq!(
+        err.detail(),
+        Some("blocks start and end in different HTML contexts (JavaScript and HTML text)")
+    );
+    let err = env
+        .add_template(
+            "index.html",
+            "{% block a %}{% endblock %}<script>{{ self.a() }}</script>",
+        )
+        .unwrap_err();
+    assert_eq!(err.detail(), Some("cannot render a block in JavaScript"));
+    let err = env
+        .add_template("index.html", "<script>{% include 'inc.html' %}</script>")
+        .unwrap_err();
+    assert_eq!(
+        err.detail(),
+        Some("cannot include a template in JavaScript")
+    );
+    let err = env
+        .add_template("inc.html", "<script>var x = 1;")
+        .unwrap_err();
+    assert_eq!(err.detail(), Some("template ends in JavaScript"));
+}
+
+#[test]
+fn test_disabled() {
+    let mut env = Environment::new();
+    assert!(!env.contextual_html_escaping());
+    let source = "<script>var x = {{ x }};</script>";
+    assert_eq!(
+        env.render_named_str("test.html", source, context! { x => "a" })
+            .unwrap(),
+        "<script>var x = a;</script>"
+    );
+    env.set_contextual_html_escaping(true);
+    assert_eq!(
+        env.render_named_str("test.txt", source, context! { x => "a" })
+            .unwrap(),
+        "<script>var x = a;</script>"
+    );
+    assert_eq!(
+        env.render_named_str(
+            "test.html",
+            "<script>{% autoescape false %}{{ x }}{% endautoescape %}</script>",
+            context! { x => "a" }
+        )
+        .unwrap(),
+        "<script>a</script>"
+    );
+}
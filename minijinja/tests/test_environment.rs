@@ -3,8 +3,8 @@ use std::collections::BTreeMap;
 use insta::assert_snapshot;
 use similar_asserts::assert_eq;
 
-use minijinja::Environment;
 use minijinja::Value;
+use minijinja::{context, default_auto_escape_callback, AutoEscape, Environment, Error, ErrorKind};
 
 #[test]
 fn test_basic() {
@@ -98,7 +98,7 @@ fn test_globals() {
     assert_eq!(tmpl.render(()).unwrap(), "42");
     assert_eq!(
         env.globals().map(|x| x.0).collect::<Vec<_>>(),
-        vec!["a", "debug", "dict", "namespace", "range"]
+        vec!["a", "debug", "dict", "flush", "namespace", "range"]
     );
 }
 
@@ -159,7 +159,6 @@ fn test_keep_trailing_newlines() {
 #[cfg(feature = "builtins")]
 fn test_unknown_method_callback() {
     use minijinja::value::{from_args, ValueKind};
-    use minijinja::{Error, ErrorKind};
 
     let mut env = Environment::new();
     env.set_unknown_method_callback(|_state, value, method, args| {
@@ -199,3 +198,51 @@ fn test_iter() {
     assert!(renders.contains(&("hello", "Hello World!".into())));
     assert!(renders.contains(&("goodbye", "Goodbye World!".into())));
 }
+
+#[test]
+fn test_custom_auto_escape() {
+    let mut env = Environment::new();
+    env.add_auto_escape("md", |out, value| {
+        write!(out, "{}", value.to_string().replace('*', "\\*")).map_err(Error::from)
+    });
+    env.set_auto_escape_callback(|name| {
+        if name.ends_with(".md") {
+            AutoEscape::Custom("md")
+        } else {
+            default_auto_escape_callback(name)
+        }
+    });
+    let ctx = context! { value => "*<b>*" };
+
+    assert_eq!(
+        env.render_named_str("x.md", "{{ value }}|{{ value|safe }}", ctx.clone())
+            .unwrap(),
+        "\\*<b>\\*|*<b>*"
+    );
+    assert_eq!(
+        env.render_named_str(
+            "x.md",
+            "{% autoescape false %}{{ value }}|{{ value|e }}{% endautoescape %}",
+            ctx.clone()
+        )
+        .unwrap(),
+        "*<b>*|\\*<b>\\*"
+    );
+    assert_eq!(
+        env.render_named_str(
+            "x.html",
+            "{{ value }}|{% autoescape 'md' %}{{ value }}{% endautoescape %}",
+            ctx.clone()
+        )
+        .unwrap(),
+        "*&lt;b&gt;*|\\*<b>\\*"
+    );
+
+    let err = env
+        .render_str(
+            "{% autoescape 'unknown' %}{{ value }}{% endautoescape %}",
+            ctx,
+        )
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidOperation);
+}
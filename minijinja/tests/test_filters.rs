@@ -3,7 +3,7 @@ use minijinja::value::{Kwargs, Value, ValueKind};
 use minijinja::{args, context, Environment};
 use similar_asserts::assert_eq;
 
-use minijinja::filters::{abs, indent};
+use minijinja::filters::{abs, indent, join, round};
 use minijinja::{escape_formatter, AutoEscape};
 
 #[test]
@@ -156,6 +156,14 @@ fn test_abs_overflow() {
     assert_eq!(err.to_string(), "invalid operation: overflow on abs");
 }
 
+#[test]
+fn test_direct_calls() {
+    let items = Value::from(vec![1, 2, 3]);
+    assert_eq!(join(&items, Some(", ".into())).unwrap(), "1, 2, 3");
+    assert_eq!(round(Value::from(2.5), None).unwrap(), Value::from(3.0));
+    assert_eq!(round(Value::from(2.55), Some(1)).unwrap(), Value::from(2.6));
+}
+
 #[test]
 fn test_chain_lists() {
     let env = Environment::new();
@@ -450,3 +458,362 @@ fn test_escape_filter_custom_formatter() {
     let result = tmpl.render(context! { value => "*" }).unwrap();
     assert_eq!(result, "\\*");
 }
+
+#[test]
+fn test_center() {
+    let env = Environment::new();
+    assert_eq!(
+        env.render_str("[{{ 'foo'|center(9) }}]", ()).unwrap(),
+        "[   foo   ]"
+    );
+    assert_eq!(
+        env.render_str("[{{ 'ab'|center(5) }}]", ()).unwrap(),
+        "[  ab ]"
+    );
+    assert_eq!(
+        env.render_str("[{{ 'foobar'|center(3) }}]", ()).unwrap(),
+        "[foobar]"
+    );
+    assert_eq!(env.render_str("{{ 'x'|center|length }}", ()).unwrap(), "80");
+}
+
+#[test]
+fn test_forceescape() {
+    let env = Environment::new();
+    assert_eq!(
+        env.render_named_str("a.html", "{{ '<b>'|safe|forceescape }}", ())
+            .unwrap(),
+        "&lt;b&gt;"
+    );
+    assert_eq!(
+        env.render_named_str("a.html", "{{ '<b>'|forceescape|forceescape }}", ())
+            .unwrap(),
+        "&amp;lt;b&amp;gt;"
+    );
+}
+
+#[test]
+fn test_xmlattr() {
+    let env = Environment::new();
+    let ctx = context! {
+        attrs => context! {
+            class => "a \"b\"",
+            id => 42,
+            missing => (),
+            raw => Value::from_safe_string("&amp;".into()),
+        },
+    };
+    assert_eq!(
+        env.render_named_str("a.html", "<ul{{ attrs|xmlattr }}>", ctx.clone())
+            .unwrap(),
+        r#"<ul class="a &quot;b&quot;" id="42" raw="&amp;">"#
+    );
+    assert_eq!(
+        env.render_named_str("a.html", "<ul {{ attrs|xmlattr(false) }}>", ctx)
+            .unwrap(),
+        r#"<ul class="a &quot;b&quot;" id="42" raw="&amp;">"#
+    );
+    assert_eq!(env.render_str("[{{ {}|xmlattr }}]", ()).unwrap(), "[]");
+
+    let err = env.render_str("{{ {'a b': 1}|xmlattr }}", ()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid operation: invalid character in attribute name: \"a b\" (in <string>:1)"
+    );
+    let err = env.render_str("{{ [1]|xmlattr }}", ()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid operation: xmlattr filter expects a map (in <string>:1)"
+    );
+}
+
+#[test]
+fn test_urlize() {
+    let env = Environment::new();
+    let urlize = |text: &str, args: &str| {
+        env.render_named_str(
+            "a.html",
+            &format!("{{{{ text|urlize({args}) }}}}"),
+            context! { text },
+        )
+        .unwrap()
+    };
+
+    assert_eq!(
+        urlize("see https://example.com/x?a=1&b=2.", ""),
+        "see <a href=\"https://example.com/x?a=1&amp;b=2\" rel=\"noopener\">\
+         https://example.com/x?a=1&amp;b=2</a>."
+    );
+    assert_eq!(
+        urlize("(www.example.org/wiki/Foo_(bar))", ""),
+        "(<a href=\"https://www.example.org/wiki/Foo_(bar)\" rel=\"noopener\">\
+         www.example.org/wiki/Foo_(bar)</a>)"
+    );
+    assert_eq!(
+        urlize("example.com <script>", ""),
+        "<a href=\"https://example.com\" rel=\"noopener\">example.com</a> &lt;script&gt;"
+    );
+    assert_eq!(
+        urlize("http://127.0.0.1:8080/", ""),
+        "<a href=\"http://127.0.0.1:8080/\" rel=\"noopener\">http://127.0.0.1:8080/</a>"
+    );
+    assert_eq!(
+        urlize("mail foo@example.com or mailto:bar@example.com", ""),
+        "mail <a href=\"mailto:foo@example.com\">foo@example.com</a> \
+         or <a href=\"mailto:bar@example.com\">bar@example.com</a>"
+    );
+    assert_eq!(
+        urlize("not.a.link and foo@bar", ""),
+        "not.a.link and foo@bar"
+    );
+    assert_eq!(
+        urlize(
+            "https://example.com/very/long",
+            "10, nofollow=true, target='_blank'"
+        ),
+        "<a href=\"https://example.com/very/long\" rel=\"nofollow noopener\" \
+         target=\"_blank\">https://ex...</a>"
+    );
+    assert_eq!(
+        urlize("https://example.com", "rel='external noopener'"),
+        "<a href=\"https://example.com\" rel=\"external noopener\">https://example.com</a>"
+    );
+    assert_eq!(
+        urlize("ftp://example.com/file", "extra_schemes=['ftp://']"),
+        "<a href=\"ftp://example.com/file\" rel=\"noopener\">ftp://example.com/file</a>"
+    );
+
+    let err = env
+        .render_str("{{ 'x'|urlize(extra_schemes=['f']) }}", ())
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid operation: \"f\" is not a valid URI scheme prefix (in <string>:1)"
+    );
+
+    // the output is only marked safe if html escaping is enabled
+    assert_eq!(
+        env.render_str("{{ 'www.example.com'|urlize|e }}", ())
+            .unwrap(),
+        "&lt;a href=&quot;https:&#x2f;&#x2f;www.example.com&quot; rel=&quot;noopener&quot;&gt;\
+         www.example.com&lt;&#x2f;a&gt;"
+    );
+}
+
+#[test]
+fn test_aggregate_attribute_args() {
+    let env = Environment::new();
+    let ctx = context! {
+        items => vec![
+            context! { price => 10, user => context! { name => "b" } },
+            context! { price => 5, user => context! { name => "A" } },
+            context! { price => 7, user => context! { name => "c" } },
+        ],
+    };
+    let render = |tmpl: &str| env.render_str(tmpl, ctx.clone()).unwrap();
+
+    assert_eq!(render("{{ items|sum(attribute='price') }}"), "22");
+    assert_eq!(render("{{ items|sum('price', 100) }}"), "122");
+    assert_eq!(render("{{ [1, 2]|sum(start=10) }}"), "13");
+    assert_eq!(
+        render("{{ items|min(attribute='price') }}"),
+        render("{{ items[1] }}")
+    );
+    assert_eq!(
+        render("{{ items|max(attribute='user.name') }}"),
+        render("{{ items[2] }}")
+    );
+    assert_eq!(
+        render("{{ items|max(true, 'user.name') }}"),
+        render("{{ items[2] }}")
+    );
+    assert_eq!(
+        render("{{ items|join(', ', attribute='user.name') }}"),
+        "b, A, c"
+    );
+    assert_eq!(
+        render("{{ items|join(d='|', attribute='price') }}"),
+        "10|5|7"
+    );
+    assert_eq!(render("{{ [[1, 2], [3, 4]]|join(',', 1) }}"), "2,4");
+    assert_eq!(
+        render("{{ items|unique(attribute='user.name')|map(attribute='price')|list }}"),
+        "[10, 5, 7]"
+    );
+}
+
+#[test]
+fn test_min_max_case_sensitive() {
+    let env = Environment::new();
+    let render = |tmpl: &str| env.render_str(tmpl, ()).unwrap();
+
+    assert_eq!(render("{{ ['b', 'a', 'C']|min }}"), "C");
+    assert_eq!(
+        render("{{ ['b', 'a', 'C']|min(case_sensitive=false) }}"),
+        "a"
+    );
+    assert_eq!(render("{{ ['B', 'a', 'C']|max }}"), "a");
+    assert_eq!(
+        render("{{ ['B', 'a', 'C']|max(case_sensitive=false) }}"),
+        "C"
+    );
+    assert_eq!(render("{{ ['B', 'a', 'C']|max(false) }}"), "C");
+    assert_eq!(render("{{ ['a', 'A']|max(case_sensitive=false) }}"), "a");
+    assert_eq!(render("{{ ['A', 'a']|min(case_sensitive=false) }}"), "A");
+    assert_eq!(render("{{ []|min is undefined }}"), "true");
+}
+
+#[test]
+fn test_unique_positional_args() {
+    let env = Environment::new();
+    let render = |tmpl: &str| env.render_str(tmpl, ()).unwrap();
+
+    assert_eq!(
+        render("{{ ['foo', 'bar', 'foobar', 'FooBar']|unique|list }}"),
+        r#"["foo", "bar", "foobar"]"#
+    );
+    assert_eq!(
+        render("{{ ['foo', 'bar', 'foobar', 'FooBar']|unique(true)|list }}"),
+        r#"["foo", "bar", "foobar", "FooBar"]"#
+    );
+    assert_eq!(
+        render("{{ [[1, 'a'], [2, 'A'], [3, 'b']]|unique(false, 1)|list }}"),
+        "[[1, \"a\"], [3, \"b\"]]"
+    );
+}
+
+#[test]
+fn test_map_passes_kwargs() {
+    let env = Environment::new();
+    let render = |tmpl: &str| env.render_str(tmpl, ()).unwrap();
+
+    assert_eq!(
+        render("{{ [1.21, 2.55]|map('round', method='ceil')|list }}"),
+        "[2.0, 3.0]"
+    );
+    assert_eq!(
+        render("{{ [1.21, 2.55]|map('round', 1, method='floor')|list }}"),
+        "[1.2, 2.5]"
+    );
+    assert_eq!(
+        render("{{ [{'a': {'b': 1}}, {}]|map(attribute='a.b', default=0)|list }}"),
+        "[1, 0]"
+    );
+}
+
+#[test]
+fn test_batch_slice_kwargs() {
+    let env = Environment::new();
+    let render = |tmpl: &str| env.render_str(tmpl, ()).unwrap();
+
+    assert_eq!(
+        render("{{ range(5)|batch(linecount=2, fill_with='-') }}"),
+        r#"[[0, 1], [2, 3], [4, "-"]]"#
+    );
+    assert_eq!(
+        render("{{ range(5)|batch(2, fill_with=0) }}"),
+        "[[0, 1], [2, 3], [4, 0]]"
+    );
+    assert_eq!(
+        render("{{ range(5)|slice(slices=2, fill_with=9) }}"),
+        "[[0, 1, 2], [3, 4, 9]]"
+    );
+    let err = env.render_str("{{ [1]|batch }}", ()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "missing argument: missing keyword argument 'linecount' (in <string>:1)"
+    );
+}
+
+#[test]
+fn test_indent_kwargs() {
+    let env = Environment::new();
+    assert_eq!(
+        env.render_str(
+            "{{ 'a\\n\\nb'|indent(width=2, first=true, blank=true) }}",
+            ()
+        )
+        .unwrap(),
+        "  a\n  \n  b"
+    );
+}
+
+#[test]
+fn test_round_method() {
+    let env = Environment::new();
+    let render = |tmpl: &str| env.render_str(tmpl, ()).unwrap();
+
+    assert_eq!(render("{{ 42.55|round }}"), "43.0");
+    assert_eq!(render("{{ 42.55|round(1, 'floor') }}"), "42.5");
+    assert_eq!(render("{{ 42.51|round(method='floor') }}"), "42.0");
+    assert_eq!(
+        render("{{ 42.21|round(precision=1, method='ceil') }}"),
+        "42.3"
+    );
+    assert_eq!(render("{{ 42|round(method='ceil') }}"), "42");
+
+    let err = env
+        .render_str("{{ 42.5|round(method='up') }}", ())
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid operation: method must be 'common', 'ceil' or 'floor' (in <string>:1)"
+    );
+}
+
+#[test]
+fn test_dictsort_positional_args() {
+    let env = Environment::new();
+    let ctx = context! { d => context! { b => 1, A => 3, c => 2 } };
+    let render = |tmpl: &str| env.render_str(tmpl, ctx.clone()).unwrap();
+
+    assert_eq!(
+        render("{{ d|dictsort }}"),
+        r#"[["A", 3], ["b", 1], ["c", 2]]"#
+    );
+    assert_eq!(
+        render("{{ d|dictsort(false, 'value') }}"),
+        r#"[["b", 1], ["c", 2], ["A", 3]]"#
+    );
+    assert_eq!(
+        render("{{ d|dictsort(true, 'key', true) }}"),
+        r#"[["c", 2], ["b", 1], ["A", 3]]"#
+    );
+    assert_eq!(
+        render("{{ d|dictsort(by='value', reverse=true) }}"),
+        r#"[["A", 3], ["c", 2], ["b", 1]]"#
+    );
+
+    let err = env
+        .render_str("{{ d|dictsort(by='other') }}", ctx.clone())
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid operation: can only sort by either 'key' or 'value' (in <string>:1)"
+    );
+}
+
+#[test]
+fn test_escape_filter_escape_modes() {
+    let env = Environment::new();
+    let ctx = context! { value => "a 'b'" };
+    assert_eq!(
+        env.render_named_str(
+            "run.sh",
+            "{% autoescape false %}{{ value|e }}{% endautoescape %}",
+            ctx.clone()
+        )
+        .unwrap(),
+        r#"'a '"'"'b'"'"''"#
+    );
+    assert_eq!(
+        env.render_named_str("style.css", "{{ value|e|e }}", ctx.clone())
+            .unwrap(),
+        r"a\000020\000027b\000027"
+    );
+    assert_eq!(
+        env.render_named_str("doc.tex", "{{ 'a_b'|forceescape }}", ctx)
+            .unwrap(),
+        r"a\_b"
+    );
+}
@@ -400,3 +400,57 @@ fn test_format_error() {
     assert!(eval_err_expr("'% %s' | format('arg')")
         .contains("invalid conversion type '%' in format spec"));
 }
+
+#[test]
+fn test_format_operator() {
+    let env = Environment::new();
+
+    assert_eq!(eval_expr(&env, "'%s!' % 'Hello'"), "Hello!");
+    assert_eq!(
+        eval_expr(&env, "'%s, %s!' % ('Hello', 'World')"),
+        "Hello, World!"
+    );
+    assert_eq!(eval_expr(&env, "'%05.1f|%x' % (3.14159, 255)"), "003.1|ff");
+    assert_eq!(eval_expr(&env, "'%s' % ([1, 2],)"), "[1, 2]");
+    assert_eq!(eval_expr(&env, "'%s' % [1, 2]"), "[1, 2]");
+    assert_eq!(
+        eval_expr(&env, "'%(name)s is %(age)d' % {'name': 'Peter', 'age': 42}"),
+        "Peter is 42"
+    );
+    assert_eq!(eval_expr(&env, "11 % 7"), "4");
+
+    let rv = env
+        .render_str("{{ 'Hello %s!' % name }}", context!(name => "World"))
+        .unwrap();
+    assert_eq!(rv, "Hello World!");
+    let rv = env
+        .render_str("{{ '%s' % x }}", context!(x => vec![1, 2, 3]))
+        .unwrap();
+    assert_eq!(rv, "[1, 2, 3]");
+    let rv = env
+        .render_str("{{ '%s/%s' % (x, y) }}", context!(x => 1, y => 2))
+        .unwrap();
+    assert_eq!(rv, "1/2");
+
+    assert!(eval_err_expr("'%s and %s' % ('one',)").contains("missing an argument"));
+    assert!(eval_err_expr("'%s-%s' % [1, 2]").contains("missing an argument"));
+}
+
+#[test]
+fn test_format_operator_surplus_args() {
+    let mut env = Environment::new();
+    for python_tuples in [false, true] {
+        env.set_python_tuples(python_tuples);
+        for expr in ["'%s %s' % (1, 2, 3)", "'%s' % (1, 2)", "'x' % 42"] {
+            let err = env.compile_expression(expr).unwrap().eval(()).unwrap_err();
+            assert!(err
+                .to_string()
+                .contains("not all arguments converted during string formatting"));
+        }
+    }
+
+    // a mapping does not have to be used
+    assert_eq!(eval_expr(&env, "'x' % {'a': 1}"), "x");
+    // the format filter ignores surplus arguments
+    assert_eq!(eval_expr(&env, "'%s'|format(1, 2)"), "1");
+}
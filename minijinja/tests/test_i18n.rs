@@ -0,0 +1,427 @@
+#![cfg(all(feature = "i18n", feature = "builtins"))]
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+use minijinja::ast::Stmt;
+use minijinja::i18n::{Catalog, NullTranslator, Translator};
+use minijinja::{context, Environment, ErrorKind, State};
+
+use similar_asserts::assert_eq;
+
+struct Uppercase;
+
+impl Translator for Uppercase {
+    fn gettext<'a>(&'a self, _state: &State, message: &'a str) -> Cow<'a, str> {
+        // keep the placeholders intact
+        let mut rv = String::new();
+        for (idx, piece) in message.split("%(").enumerate() {
+            if idx == 0 {
+                rv.push_str(&piece.to_uppercase());
+            } else {
+                let (name, rest) = piece.split_once(")s").unwrap();
+                rv.push_str(&format!("%({name})s{}", rest.to_uppercase()));
+            }
+        }
+        Cow::Owned(rv)
+    }
+}
+
+struct Polish;
+
+impl Translator for Polish {
+    fn gettext<'a>(&'a self, _state: &State, message: &'a str) -> Cow<'a, str> {
+        Cow::Borrowed(message)
+    }
+
+    fn ngettext<'a>(
+        &'a self,
+        _state: &State,
+        _singular: &'a str,
+        _plural: &'a str,
+        n: i64,
+    ) -> Cow<'a, str> {
+        Cow::Owned(match n {
+            1 => "%(count)s plik".into(),
+            n if (2..=4).contains(&(n % 10)) && !(12..=14).contains(&(n % 100)) => {
+                "%(count)s pliki".into()
+            }
+            _ => "%(count)s plików".into(),
+        })
+    }
+}
+
+fn render(env: &Environment, source: &str, ctx: minijinja::Value) -> String {
+    env.render_str(source, ctx).unwrap()
+}
+
+#[test]
+fn test_trans_without_translator() {
+    let env = Environment::new();
+    assert_eq!(
+        render(
+            &env,
+            "{% trans %}Hello {{ user }}!{% endtrans %}",
+            context! { user => "Peter" }
+        ),
+        "Hello Peter!"
+    );
+    assert_eq!(
+        render(
+            &env,
+            "{% trans user=user.name|title %}Hello {{ user }}!{% endtrans %}",
+            context! { user => context! { name => "peter" } }
+        ),
+        "Hello Peter!"
+    );
+    assert_eq!(
+        render(&env, "{% trans %}100% sure{% endtrans %}", context! {}),
+        "100% sure"
+    );
+    assert_eq!(
+        render(
+            &env,
+            "{% trans %}{{ n }}% sure{% endtrans %}",
+            context! { n => 50 }
+        ),
+        "50% sure"
+    );
+
+    // the functions are only installed together with a translator
+    assert!(!env
+        .globals()
+        .any(|(name, _)| name == "_" || name == "gettext"));
+}
+
+#[test]
+fn test_trans_with_translator() {
+    let mut env = Environment::new();
+    env.set_translator(Uppercase);
+    assert_eq!(
+        render(
+            &env,
+            "{% trans %}Hello {{ user }}!{% endtrans %}",
+            context! { user => "Peter" }
+        ),
+        "HELLO Peter!"
+    );
+    assert_eq!(
+        render(
+            &env,
+            "{{ _('Hello %(user)s!', user=user) }}|{{ gettext('Bye') }}",
+            context! { user => "Peter" }
+        ),
+        "HELLO Peter!|BYE"
+    );
+}
+
+#[test]
+fn test_trans_auto_escape() {
+    let mut env = Environment::new();
+    env.set_translator(NullTranslator);
+    env.add_template(
+        "test.html",
+        "{% trans %}<b>Hello</b> {{ user }}!{% endtrans %}|{{ _('<i>%(user)s</i>', user=user) }}",
+    )
+    .unwrap();
+    let rv = env
+        .get_template("test.html")
+        .unwrap()
+        .render(context! { user => "<Peter>" })
+        .unwrap();
+    assert_eq!(rv, "<b>Hello</b> &lt;Peter&gt;!|<i>&lt;Peter&gt;</i>");
+}
+
+#[test]
+fn test_trans_plural() {
+    let env = Environment::new();
+    let source =
+        "{% trans count=items|length %}One item{% pluralize %}{{ count }} items{% endtrans %}";
+    assert_eq!(render(&env, source, context! { items => [1] }), "One item");
+    assert_eq!(
+        render(&env, source, context! { items => [1, 2, 3] }),
+        "3 items"
+    );
+
+    let source = "{% trans user, n=num %}{{ user }} has one file{% pluralize n %}{{ user }} has {{ n }} files{% endtrans %}";
+    assert_eq!(
+        render(&env, source, context! { user => "Peter", num => 1 }),
+        "Peter has one file"
+    );
+    assert_eq!(
+        render(&env, source, context! { user => "Peter", num => 7 }),
+        "Peter has 7 files"
+    );
+
+    // without tag variables the first referenced variable is the count
+    let source = "{% trans %}{{ n }} file{% pluralize %}{{ n }} files{% endtrans %}";
+    assert_eq!(render(&env, source, context! { n => 2 }), "2 files");
+
+    let mut env = Environment::new();
+    env.set_translator(Polish);
+    let source =
+        "{% trans count=n %}{{ count }} file{% pluralize %}{{ count }} files{% endtrans %}";
+    let rendered = [1, 2, 5, 22, 112]
+        .into_iter()
+        .map(|n| render(&env, source, context! { n }))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        rendered,
+        ["1 plik", "2 pliki", "5 plików", "22 pliki", "112 plików"]
+    );
+    assert_eq!(
+        render(
+            &env,
+            "{{ ngettext('%(num)s file', '%(num)s files', 3, count=3) }}",
+            context! {}
+        ),
+        "3 pliki"
+    );
+}
+
+#[test]
+fn test_ngettext_num() {
+    let mut env = Environment::new();
+    env.set_translator(NullTranslator);
+    assert_eq!(
+        render(
+            &env,
+            "{{ ngettext('%(num)s apple', '%(num)s apples', 1) }}, {{ ngettext('%(num)s apple', '%(num)s apples', 4) }}",
+            context! {}
+        ),
+        "1 apple, 4 apples"
+    );
+    let err = env
+        .render_str("{{ ngettext('a', 'b', 'x') }}", ())
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidOperation);
+}
+
+#[test]
+fn test_trans_trimmed() {
+    let env = Environment::new();
+    let ast = env
+        .parse(
+            "test.txt",
+            "{% trans trimmed user %}\n  Hello\n    {{ user }}!\n{% endtrans %}",
+        )
+        .unwrap();
+    let Stmt::Template(t) = ast else {
+        panic!("expected template");
+    };
+    let Stmt::Trans(trans) = &t.children[0] else {
+        panic!("expected trans");
+    };
+    assert_eq!(trans.singular, "Hello %(user)s!");
+    assert_eq!(trans.plural, None);
+    assert_eq!(
+        trans.variables.iter().map(|x| x.0).collect::<Vec<_>>(),
+        ["user"]
+    );
+
+    assert_eq!(
+        render(
+            &env,
+            "{% trans notrimmed %}\n  Hello\n{% endtrans %}",
+            context! {}
+        ),
+        "\n  Hello\n"
+    );
+}
+
+#[test]
+fn test_trans_syntax_errors() {
+    let env = Environment::new();
+    let cases = [
+        (
+            "{% trans %}{% if x %}{% endif %}{% endtrans %}",
+            "control structures in translatable sections are not allowed; saw `if`",
+        ),
+        (
+            "{% trans %}{% trans %}{% endtrans %}",
+            "trans blocks can't be nested; did you mean `endtrans`?",
+        ),
+        (
+            "{% trans %}{{ user.name }}{% endtrans %}",
+            "unexpected `.`, expected end of variable block",
+        ),
+        (
+            "{% trans %}a{% pluralize x %}b{% endtrans %}",
+            "unknown variable 'x' for pluralization",
+        ),
+        (
+            "{% trans %}a{% pluralize %}b{% endtrans %}",
+            "pluralize without variables",
+        ),
+        (
+            "{% trans x %}a{% pluralize %}b{% pluralize %}c{% endtrans %}",
+            "a translatable section can have only one pluralize section",
+        ),
+        (
+            "{% trans x, x %}{% endtrans %}",
+            "translatable variable 'x' defined twice",
+        ),
+        ("{% trans %}a", "unclosed translation block"),
+    ];
+    for (source, message) in cases {
+        let err = env.template_from_str(source).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::SyntaxError, "{source}");
+        assert_eq!(err.detail(), Some(message), "{source}");
+    }
+}
+
+#[test]
+fn test_undeclared_variables() {
+    let mut env = Environment::new();
+    env.add_template(
+        "test.txt",
+        "{% trans name=user.name %}{{ name }} has {{ count }}{% endtrans %}",
+    )
+    .unwrap();
+    let mut undeclared = env
+        .get_template("test.txt")
+        .unwrap()
+        .undeclared_variables(false)
+        .into_iter()
+        .collect::<Vec<_>>();
+    undeclared.sort();
+    assert_eq!(undeclared, ["count", "user"]);
+}
+
+#[test]
+fn test_extract_messages() {
+    let mut env = Environment::new();
+    env.add_template(
+        "b.html",
+        r#"{% trans %}Hello {{ user }}!{% endtrans %}
+{{ _("Tab\tand \"quotes\"") }}"#,
+    )
+    .unwrap();
+    env.add_template(
+        "a.html",
+        r#"{% macro x() %}{% trans %}Hello {{ user }}!{% endtrans %}{% endmacro %}
+{% trans count=n %}
+One file{% pluralize %}
+{{ count }} files{% endtrans %}
+{{ gettext(dynamic) }}{{ ngettext("apple", "apples", n) }}"#,
+    )
+    .unwrap();
+
+    let catalog = Catalog::from_environment(&env).unwrap();
+    let messages = catalog
+        .messages()
+        .iter()
+        .map(|msg| {
+            (
+                msg.id(),
+                msg.plural(),
+                msg.locations()
+                    .iter()
+                    .map(|(name, line)| format!("{name}:{line}"))
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        vec![
+            (
+                "Hello %(user)s!",
+                None,
+                vec!["a.html:1".to_string(), "b.html:1".to_string()]
+            ),
+            (
+                "\nOne file",
+                Some("\n%(count)s files"),
+                vec!["a.html:2".to_string()]
+            ),
+            ("apple", Some("apples"), vec!["a.html:5".to_string()]),
+            ("Tab\tand \"quotes\"", None, vec!["b.html:2".to_string()]),
+        ]
+    );
+
+    insta::assert_snapshot!(catalog.to_pot(), @r###"
+    #: a.html:1 b.html:1
+    #, python-format
+    msgid "Hello %(user)s!"
+    msgstr ""
+
+    #: a.html:2
+    #, python-format
+    msgid ""
+    "\n"
+    "One file"
+    msgid_plural ""
+    "\n"
+    "%(count)s files"
+    msgstr[0] ""
+    msgstr[1] ""
+
+    #: a.html:5
+    msgid "apple"
+    msgid_plural "apples"
+    msgstr[0] ""
+    msgstr[1] ""
+
+    #: b.html:2
+    msgid "Tab\tand \"quotes\""
+    msgstr ""
+    "###);
+}
+
+#[test]
+fn test_extract_from_ast() {
+    let env = Environment::new();
+    let ast = env
+        .parse("x.txt", "{% trans %}Line one\nline two\n{% endtrans %}")
+        .unwrap();
+    let mut catalog = Catalog::new();
+    catalog.add_ast("x.txt", &ast);
+    insta::assert_snapshot!(catalog.to_pot(), @r###"
+    #: x.txt:1
+    msgid ""
+    "Line one\n"
+    "line two\n"
+    msgstr ""
+    "###);
+}
+
+#[test]
+fn test_translator_gets_state() {
+    struct ByLang;
+
+    impl Translator for ByLang {
+        fn gettext<'a>(&'a self, state: &State, message: &'a str) -> Cow<'a, str> {
+            let lang = state.lookup("lang").unwrap_or_default();
+            let table = BTreeMap::from([("fr", "Bonjour"), ("de", "Hallo")]);
+            match (message, lang.as_str().and_then(|x| table.get(x))) {
+                ("Hello", Some(translated)) => Cow::Borrowed(translated),
+                _ => Cow::Borrowed(message),
+            }
+        }
+    }
+
+    let mut env = Environment::new();
+    env.set_translator(ByLang);
+    let source = "{% trans %}Hello{% endtrans %}";
+    assert_eq!(render(&env, source, context! { lang => "fr" }), "Bonjour");
+    assert_eq!(render(&env, source, context! { lang => "de" }), "Hallo");
+    assert_eq!(render(&env, source, context! {}), "Hello");
+}
+
+#[test]
+#[cfg(feature = "bytecode")]
+fn test_trans_bytecode() {
+    let mut env = Environment::new();
+    env.add_template(
+        "test.txt",
+        "{% trans count=n %}One{% pluralize %}{{ count }} many{% endtrans %}",
+    )
+    .unwrap();
+    let bytecode = env.get_template("test.txt").unwrap().to_bytecode().unwrap();
+
+    let mut env = Environment::new();
+    env.add_template_bytecode("test.txt", &bytecode).unwrap();
+    let tmpl = env.get_template("test.txt").unwrap();
+    assert_eq!(tmpl.render(context! { n => 1 }).unwrap(), "One");
+    assert_eq!(tmpl.render(context! { n => 3 }).unwrap(), "3 many");
+}
@@ -0,0 +1,172 @@
+#![cfg(all(feature = "multi_template", feature = "macros"))]
+use std::sync::{Arc, Mutex};
+
+use minijinja::instrument::{Instrumentation, Location};
+use minijinja::{Environment, State};
+
+use similar_asserts::assert_eq;
+
+#[derive(Default, Clone)]
+struct Recorder {
+    events: Arc<Mutex<Vec<String>>>,
+    lines: Arc<Mutex<Vec<(String, usize)>>>,
+}
+
+impl Recorder {
+    fn record(&self, event: String) {
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+impl Instrumentation for Recorder {
+    fn on_instruction(&self, state: &State, location: &Location) {
+        if let Some(line) = location.line() {
+            let mut lines = self.lines.lock().unwrap();
+            let entry = (state.name().to_string(), line);
+            if lines.last() != Some(&entry) {
+                lines.push(entry);
+            }
+        }
+    }
+
+    fn on_frame_push(&self, _state: &State) {
+        self.record("push".into());
+    }
+
+    fn on_frame_pop(&self, _state: &State) {
+        self.record("pop".into());
+    }
+
+    fn on_macro_enter(&self, state: &State, name: &str) {
+        self.record(format!("enter macro {name} ({})", state.name()));
+    }
+
+    fn on_macro_exit(&self, _state: &State, name: &str) {
+        self.record(format!("exit macro {name}"));
+    }
+
+    fn on_include_enter(&self, state: &State, name: &str) {
+        self.record(format!("enter include {name} ({})", state.name()));
+    }
+
+    fn on_include_exit(&self, _state: &State, name: &str) {
+        self.record(format!("exit include {name}"));
+    }
+
+    fn on_block_enter(&self, state: &State, name: &str) {
+        assert_eq!(state.current_block(), Some(name));
+        self.record(format!("enter block {name}"));
+    }
+
+    fn on_block_exit(&self, _state: &State, name: &str) {
+        self.record(format!("exit block {name}"));
+    }
+}
+
+#[test]
+fn test_events() {
+    let recorder = Recorder::default();
+    let mut env = Environment::new();
+    env.set_instrumentation(recorder.clone());
+    env.add_template("layout.html", "{% block body %}{% endblock %}")
+        .unwrap();
+    env.add_template("inc.html", "inc").unwrap();
+    env.add_template(
+        "index.html",
+        "{% extends 'layout.html' %}\
+         {% macro m(x) %}{{ x }}{% endmacro %}\
+         {% block body %}{% for x in [1] %}{{ m(x) }}{% endfor %}\
+         {% include 'inc.html' %}{% endblock %}",
+    )
+    .unwrap();
+    let rv = env.get_template("index.html").unwrap().render(()).unwrap();
+    assert_eq!(rv, "1inc");
+    assert_eq!(
+        *recorder.events.lock().unwrap(),
+        vec![
+            "push",
+            "enter block body",
+            "push",
+            "enter macro m (index.html)",
+            "push",
+            "pop",
+            "exit macro m",
+            "pop",
+            "enter include inc.html (inc.html)",
+            "exit include inc.html",
+            "exit block body",
+            "pop",
+        ]
+    );
+}
+
+#[test]
+fn test_instruction_lines() {
+    let recorder = Recorder::default();
+    let mut env = Environment::new();
+    env.set_instrumentation(recorder.clone());
+    env.add_template("inc.html", "a\n{{ 1 }}").unwrap();
+    env.add_template(
+        "index.html",
+        "{% set x = 1 %}\n{% include 'inc.html' %}\n{{ x }}",
+    )
+    .unwrap();
+    env.get_template("index.html").unwrap().render(()).unwrap();
+    assert_eq!(
+        *recorder.lines.lock().unwrap(),
+        vec![
+            ("index.html".to_string(), 1),
+            ("index.html".to_string(), 2),
+            ("inc.html".to_string(), 1),
+            ("inc.html".to_string(), 2),
+            ("index.html".to_string(), 2),
+            ("index.html".to_string(), 3),
+        ]
+    );
+}
+
+#[test]
+fn test_exit_after_error() {
+    let recorder = Recorder::default();
+    let mut env = Environment::new();
+    env.set_instrumentation(recorder.clone());
+    env.add_template("inc.html", "{{ missing() }}").unwrap();
+    env.add_template("index.html", "{% include 'inc.html' %}")
+        .unwrap();
+    let err = env.get_template("index.html").unwrap().render(());
+    assert!(err.is_err());
+    assert_eq!(
+        *recorder.events.lock().unwrap(),
+        vec!["enter include inc.html (inc.html)", "exit include inc.html",]
+    );
+}
+
+#[test]
+fn test_frames_after_error() {
+    let recorder = Recorder::default();
+    let mut env = Environment::new();
+    env.set_instrumentation(recorder.clone());
+    env.add_template(
+        "index.html",
+        "{% macro m() %}{% with y = 1 %}{{ missing() }}{% endwith %}{% endmacro %}\
+         {% for x in [1] %}{% with z = 2 %}{{ m() }}{% endwith %}{% endfor %}",
+    )
+    .unwrap();
+    let err = env.get_template("index.html").unwrap().render(());
+    assert!(err.is_err());
+    assert_eq!(
+        *recorder.events.lock().unwrap(),
+        vec![
+            "push",
+            "push",
+            "enter macro m (index.html)",
+            "push",
+            "push",
+            "pop",
+            "pop",
+            "exit macro m",
+            "pop",
+            "pop",
+        ]
+    );
+}
@@ -0,0 +1,135 @@
+#![cfg(feature = "builtins")]
+use minijinja::{context, Environment, ErrorKind};
+
+use similar_asserts::assert_eq;
+
+fn render_err(env: &Environment, source: &str) -> String {
+    let ctx = context! {
+        items => vec![1, 2, 3],
+        a => "abcdef",
+        b => "ghijkl",
+    };
+    let err = env.render_str(source, ctx).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::SizeLimitExceeded);
+    err.detail().unwrap().to_string()
+}
+
+#[test]
+fn test_output_limit() {
+    let mut env = Environment::new();
+    env.set_output_limit(Some(10));
+    assert_eq!(env.output_limit(), Some(10));
+    assert_eq!(env.render_str("0123456789", ()).unwrap(), "0123456789");
+    assert_eq!(
+        env.render_str("{% for x in range(10) %}{{ x }}{% endfor %}", ())
+            .unwrap(),
+        "0123456789"
+    );
+    assert_eq!(
+        render_err(&env, "0123456789!"),
+        "output exceeds the limit of 10 bytes"
+    );
+    let err = env
+        .render_str("{% for x in range(11) %}{{ x }}{% endfor %}", ())
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::SizeLimitExceeded);
+    assert_eq!(err.line(), Some(1));
+
+    // captured output is limited too
+    assert_eq!(
+        render_err(
+            &env,
+            "{% set x %}{% for x in range(20) %}{{ x }}{% endfor %}{% endset %}"
+        ),
+        "output exceeds the limit of 10 bytes"
+    );
+    assert_eq!(
+        render_err(
+            &env,
+            "{% macro m() %}{% for x in range(20) %}{{ x }}{% endfor %}{% endmacro %}{% set x = m() %}"
+        ),
+        "output exceeds the limit of 10 bytes"
+    );
+    // but discarded output is not
+    env.add_template("layout.html", "{% block body %}{% endblock %}")
+        .unwrap();
+    assert_eq!(
+        env.render_str(
+            "{% extends 'layout.html' %}{% for x in range(20) %}{{ x }}{% endfor %}\
+             {% block body %}ok{% endblock %}",
+            ()
+        )
+        .unwrap(),
+        "ok"
+    );
+}
+
+#[test]
+fn test_size_limit_operators() {
+    let mut env = Environment::new();
+    env.set_size_limit(Some(10));
+    assert_eq!(env.size_limit(), Some(10));
+    assert_eq!(env.render_str("{{ 'ab' * 5 }}", ()).unwrap(), "ababababab");
+    assert_eq!(
+        render_err(&env, "{{ 'x' * 100000000000 }}"),
+        "string of 100000000000 bytes exceeds the size limit of 10"
+    );
+    assert_eq!(
+        render_err(&env, "{{ (items * 4)|length }}"),
+        "sequence of 12 items exceeds the size limit of 10"
+    );
+    assert_eq!(
+        render_err(&env, "{{ (items + items + items + items)|length }}"),
+        "sequence of 12 items exceeds the size limit of 10"
+    );
+    assert_eq!(
+        render_err(&env, "{{ a + b }}"),
+        "string of 12 bytes exceeds the size limit of 10"
+    );
+    assert_eq!(
+        render_err(&env, "{{ a ~ b }}"),
+        "string of 12 bytes exceeds the size limit of 10"
+    );
+    assert_eq!(
+        render_err(&env, "{{ '%20s' % 'x' }}"),
+        "string of 20 bytes exceeds the size limit of 10"
+    );
+    assert_eq!(
+        render_err(&env, "{{ '%200000000s' % 'x' }}"),
+        "string of 200000000 bytes exceeds the size limit of 10"
+    );
+    assert_eq!(
+        render_err(&env, "{{ '%.200000000f' % 1.0 }}"),
+        "string of 200000000 bytes exceeds the size limit of 10"
+    );
+    assert_eq!(
+        render_err(&env, "{{ '%8s%8s' % ('x', 'y') }}"),
+        "string of 16 bytes exceeds the size limit of 10"
+    );
+}
+
+#[test]
+fn test_size_limit_filters() {
+    let mut env = Environment::new();
+    env.set_size_limit(Some(10));
+    assert_eq!(
+        render_err(&env, "{{ range(100)|join(',') }}"),
+        "string of 11 bytes exceeds the size limit of 10"
+    );
+    assert_eq!(
+        render_err(&env, "{{ a|replace('a', 'bbbbbbb') }}"),
+        "string of 12 bytes exceeds the size limit of 10"
+    );
+    assert_eq!(
+        render_err(&env, "{{ 'a\nb'|indent(100000000000) }}"),
+        "string of 200000000003 bytes exceeds the size limit of 10"
+    );
+    assert_eq!(
+        render_err(&env, "{{ '%20s'|format('x') }}"),
+        "string of 20 bytes exceeds the size limit of 10"
+    );
+    assert_eq!(
+        render_err(&env, "{{ '%200000000s'|format('x') }}"),
+        "string of 200000000 bytes exceeds the size limit of 10"
+    );
+}
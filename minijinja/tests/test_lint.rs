@@ -0,0 +1,201 @@
+#![cfg(all(feature = "macros", feature = "multi_template"))]
+use minijinja::lint::{LintRule, Linter};
+use minijinja::Environment;
+
+use similar_asserts::assert_eq;
+
+fn lint(env: &Environment, name: &str) -> Vec<(LintRule, usize, String)> {
+    let linter = Linter::new(env);
+    linter
+        .lint_template(&env.get_template(name).unwrap())
+        .unwrap()
+        .into_iter()
+        .map(|x| (x.rule(), x.line(), x.message().to_string()))
+        .collect()
+}
+
+#[test]
+fn test_unknown_filters_and_tests() {
+    let mut env = Environment::new();
+    env.add_filter("shout", |x: String| x.to_uppercase());
+    env.add_template(
+        "test.txt",
+        "{{ x|shout|upper|shuot }}\n{% if x is odd or x is oddd %}{% endif %}\n{% filter nope %}{% endfilter %}",
+    )
+    .unwrap();
+    assert_eq!(
+        lint(&env, "test.txt"),
+        vec![
+            (LintRule::UnknownFilter, 1, "unknown filter `shuot`".into()),
+            (LintRule::UnknownTest, 2, "unknown test `oddd`".into()),
+            (LintRule::UnknownFilter, 3, "unknown filter `nope`".into()),
+        ]
+    );
+}
+
+#[test]
+fn test_unused_variables_and_macros() {
+    let mut env = Environment::new();
+    env.add_template(
+        "test.txt",
+        r#"{% set a = 1 %}{% set b, c = 2, 3 %}{% set _d = 4 %}
+{% macro used() %}{{ a }}{% endmacro %}
+{% macro unused(x) %}{{ x }}{% endmacro %}
+{% for item in seq %}{% set e = item %}{% endfor %}
+{{ used() }}{{ b }}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        lint(&env, "test.txt"),
+        vec![
+            (
+                LintRule::UnusedVariable,
+                1,
+                "variable `c` is assigned but never used".into()
+            ),
+            (
+                LintRule::UnusedMacro,
+                3,
+                "macro `unused` is defined but never used".into()
+            ),
+            (
+                LintRule::UnusedVariable,
+                4,
+                "variable `e` is assigned but never used".into()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_unused_exported() {
+    let mut env = Environment::new();
+    env.add_template(
+        "macros.txt",
+        "{% set title = 'x' %}{% macro m() %}{% endmacro %}",
+    )
+    .unwrap();
+    env.add_template(
+        "child.txt",
+        "{% extends 'layout.txt' %}{% set title = 'x' %}",
+    )
+    .unwrap();
+    env.add_template("layout.txt", "{{ title }}").unwrap();
+    env.add_template("index.txt", "{% import 'macros.txt' as macros %}")
+        .unwrap();
+    assert_eq!(lint(&env, "macros.txt"), vec![]);
+    assert_eq!(lint(&env, "child.txt"), vec![]);
+}
+
+#[test]
+fn test_shadowed_loop_variables() {
+    let mut env = Environment::new();
+    env.add_template(
+        "test.txt",
+        r#"{% for item in seq %}
+  {% for item in item %}{% endfor %}
+  {% for key, item in item|items %}{% endfor %}
+  {% macro m(x) %}{% for item in x %}{% endfor %}{% endmacro %}{{ m(item) }}
+{% endfor %}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        lint(&env, "test.txt"),
+        vec![
+            (
+                LintRule::ShadowedLoopVariable,
+                2,
+                "loop variable `item` shadows the variable of an outer loop".into()
+            ),
+            (
+                LintRule::ShadowedLoopVariable,
+                3,
+                "loop variable `item` shadows the variable of an outer loop".into()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_blocks_and_unreachable_code() {
+    let mut env = Environment::new();
+    env.add_template(
+        "base.txt",
+        "{% block body %}{% block title %}{% endblock %}{% endblock %}",
+    )
+    .unwrap();
+    env.add_template(
+        "layout.txt",
+        "{% extends 'base.txt' %}{% block footer %}{% endblock %}",
+    )
+    .unwrap();
+    env.add_template(
+        "index.txt",
+        r#"{% extends "layout.txt" %}
+{% block title %}{% block subtitle %}{% endblock %}{% endblock %}
+{% block sidebar %}{% endblock %}
+Hello {{ name }}!
+{% block footer %}{% endblock %}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        lint(&env, "index.txt"),
+        vec![
+            (
+                LintRule::UnknownBlock,
+                3,
+                "block `sidebar` is not defined in parent template `layout.txt`".into()
+            ),
+            (
+                LintRule::UnreachableCode,
+                3,
+                "output after `extends` is never rendered".into()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_disabled_rules() {
+    let mut env = Environment::new();
+    env.add_template("test.txt", "{% set x = 1 %}{{ y|nope }}")
+        .unwrap();
+    let mut linter = Linter::new(&env);
+    linter.set_rule_enabled(LintRule::UnusedVariable, false);
+    assert!(!linter.is_rule_enabled(LintRule::UnusedVariable));
+    let diagnostics = linter.lint_templates().unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    let diag = &diagnostics[0];
+    assert_eq!(diag.rule(), LintRule::UnknownFilter);
+    assert_eq!(diag.name(), "test.txt");
+    assert_eq!(
+        diag.to_string(),
+        "unknown filter `nope` (in test.txt:1) [unknown-filter]"
+    );
+    assert_eq!(
+        LintRule::from_name("unused-macro"),
+        Some(LintRule::UnusedMacro)
+    );
+    assert_eq!(LintRule::from_name("whatever"), None);
+}
+
+#[test]
+fn test_diagnostic_range() {
+    let mut env = Environment::new();
+    let source = "{{ foo }}\n{% set unused = 1 %}";
+    env.add_template("test.txt", source).unwrap();
+    let linter = Linter::new(&env);
+    let diagnostics = linter
+        .lint_template(&env.get_template("test.txt").unwrap())
+        .unwrap();
+    assert_eq!(&source[diagnostics[0].range()], "unused");
+}
+
+#[test]
+fn test_include_disables_unused() {
+    let mut env = Environment::new();
+    env.add_template("test.txt", "{% set x = 1 %}{% include 'other.txt' %}")
+        .unwrap();
+    env.add_template("other.txt", "{{ x }}").unwrap();
+    assert_eq!(lint(&env, "test.txt"), vec![]);
+}
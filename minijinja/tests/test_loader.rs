@@ -1,6 +1,9 @@
 #![cfg(feature = "loader")]
 
-use minijinja::Environment;
+use std::fs;
+use std::path::PathBuf;
+
+use minijinja::{Environment, FileSystemLoader};
 
 use similar_asserts::assert_eq;
 
@@ -56,3 +59,98 @@ fn test_source_replace_dynamic() {
     let rv = env.get_template("a").unwrap().render(()).unwrap();
     assert_eq!(rv, "2");
 }
+
+fn make_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir()
+        .join(format!("minijinja-test-{}", std::process::id()))
+        .join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for (name, contents) in files {
+        let path = dir.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+    dir
+}
+
+fn render(loader: &FileSystemLoader, name: &str) -> String {
+    let mut env = Environment::new();
+    let loader = loader.clone();
+    env.set_loader(move |name| loader.load(name));
+    env.get_template(name).unwrap().render(()).unwrap()
+}
+
+#[test]
+fn test_file_system_loader_roots() {
+    let theme = make_dir(
+        "roots-theme",
+        &[("index.html", "themed {% include 'a.html' %}")],
+    );
+    let base = make_dir(
+        "roots-base",
+        &[
+            ("index.html", "base"),
+            ("a.html", "a"),
+            (".hidden.html", "hidden"),
+            ("dir/index.html", "admin fallback"),
+        ],
+    );
+    let admin = make_dir("roots-admin", &[("index.html", "admin")]);
+    let loader = FileSystemLoader::new([&theme, &base]).with_namespace("admin", &admin);
+
+    assert_eq!(render(&loader, "index.html"), "themed a");
+    assert_eq!(loader.source_root("index.html").as_deref(), Some(&*theme));
+    assert_eq!(loader.source_root("a.html").as_deref(), Some(&*base));
+    assert_eq!(
+        loader.source_path("a.html").as_deref(),
+        Some(&*base.join("a.html"))
+    );
+
+    assert_eq!(render(&loader, "admin/index.html"), "admin");
+    assert_eq!(
+        loader.source_root("admin/index.html").as_deref(),
+        Some(&*admin)
+    );
+    assert_eq!(loader.load("admin/a.html").unwrap(), None);
+    assert_eq!(
+        loader.load("dir/index.html").unwrap().as_deref(),
+        Some("admin fallback")
+    );
+
+    assert_eq!(loader.load(".hidden.html").unwrap(), None);
+    assert_eq!(loader.load("dir/../a.html").unwrap(), None);
+    assert_eq!(loader.load("missing.html").unwrap(), None);
+    assert_eq!(loader.load("dir").unwrap(), None);
+    assert_eq!(loader.source_root("missing.html"), None);
+}
+
+#[test]
+fn test_file_system_loader_changes() {
+    let theme = make_dir("changes-theme", &[]);
+    let base = make_dir("changes-base", &[("a.html", "a"), ("b.html", "b")]);
+    let loader = FileSystemLoader::new([&theme, &base]);
+    assert_eq!(loader.load("a.html").unwrap().as_deref(), Some("a"));
+    assert_eq!(loader.load("b.html").unwrap().as_deref(), Some("b"));
+    assert!(!loader.has_changes());
+    assert!(loader.changed_templates().is_empty());
+
+    // modified files are picked up
+    fs::write(base.join("a.html"), "changed").unwrap();
+    assert_eq!(loader.changed_templates(), vec!["a.html".to_string()]);
+    assert_eq!(loader.load("a.html").unwrap().as_deref(), Some("changed"));
+    assert!(!loader.has_changes());
+
+    // so are files that are overridden by a root with higher priority
+    fs::write(theme.join("b.html"), "themed b").unwrap();
+    assert_eq!(loader.changed_templates(), vec!["b.html".to_string()]);
+    assert_eq!(loader.load("b.html").unwrap().as_deref(), Some("themed b"));
+    assert_eq!(loader.source_root("b.html").as_deref(), Some(&*theme));
+
+    // and removed files
+    fs::remove_file(theme.join("b.html")).unwrap();
+    fs::remove_file(base.join("b.html")).unwrap();
+    assert_eq!(loader.changed_templates(), vec!["b.html".to_string()]);
+    assert_eq!(loader.load("b.html").unwrap(), None);
+    assert!(!loader.has_changes());
+}
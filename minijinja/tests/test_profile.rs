@@ -0,0 +1,122 @@
+#![cfg(all(feature = "multi_template", feature = "macros"))]
+use minijinja::profile::ProfileMetric;
+use minijinja::{Environment, RenderOptions};
+
+use similar_asserts::assert_eq;
+
+fn profiled_env() -> Environment<'static> {
+    let mut env = Environment::new();
+    env.add_template(
+        "layout.html",
+        "<title>{% block title %}{% endblock %}</title>\n{% block body %}{% endblock %}",
+    )
+    .unwrap();
+    env.add_template(
+        "macros.html",
+        "{% macro item(x) %}\n[{{ x }}]\n{% endmacro %}",
+    )
+    .unwrap();
+    env.add_template("footer.html", "footer").unwrap();
+    env.add_template(
+        "index.html",
+        "{% extends 'layout.html' %}\n\
+         {% from 'macros.html' import item %}\n\
+         {% block title %}Index{% endblock %}\n\
+         {% block body %}\n\
+         {% for x in [1, 2, 3] %}{{ item(x) }}{% endfor %}\n\
+         {% include 'footer.html' %}\n\
+         {% endblock %}",
+    )
+    .unwrap();
+    env
+}
+
+#[test]
+fn test_profile_entries() {
+    let env = profiled_env();
+    let tmpl = env.get_template("index.html").unwrap();
+    let options = RenderOptions::new().with_profiling(true);
+    let captured = tmpl.render_captured_with((), &options).unwrap();
+    assert_eq!(captured.output(), tmpl.render(()).unwrap());
+    let profile = captured.profile().unwrap();
+
+    let macro_entries = profile
+        .entries()
+        .iter()
+        .filter(|x| x.scope() == "macro item")
+        .collect::<Vec<_>>();
+    assert!(!macro_entries.is_empty());
+    for entry in &macro_entries {
+        assert_eq!(entry.template(), "macros.html");
+        assert_eq!(
+            entry.stack(),
+            &["index.html", "block body", "macro item"][..]
+        );
+    }
+    let emit_line = macro_entries.iter().find(|x| x.line() == 2).unwrap();
+    assert_eq!(emit_line.instructions() % 3, 0);
+
+    let loop_entry = profile
+        .entries()
+        .iter()
+        .find(|x| x.scope() == "block body" && x.line() == 5)
+        .unwrap();
+    assert_eq!(loop_entry.template(), "index.html");
+    assert!(loop_entry.instructions() > 3);
+
+    assert!(profile
+        .entries()
+        .iter()
+        .any(|x| x.scope() == "include footer.html" && x.template() == "footer.html"));
+    assert!(profile
+        .entries()
+        .iter()
+        .any(|x| x.scope() == "index.html" && x.template() == "layout.html"));
+    assert_eq!(
+        profile.total_instructions(),
+        profile
+            .entries()
+            .iter()
+            .map(|x| x.instructions())
+            .sum::<u64>()
+    );
+}
+
+#[test]
+fn test_folded_stacks() {
+    let env = profiled_env();
+    let tmpl = env.get_template("index.html").unwrap();
+    let options = RenderOptions::new().with_profiling(true);
+    let captured = tmpl.render_captured_with((), &options).unwrap();
+    let profile = captured.profile().unwrap();
+
+    let folded = profile.to_folded(ProfileMetric::Instructions);
+    let mut total = 0;
+    for line in folded.lines() {
+        let (stack, count) = line.rsplit_once(' ').unwrap();
+        assert!(stack.starts_with("index.html;") || stack.starts_with("index.html:"));
+        total += count.parse::<u64>().unwrap();
+    }
+    assert_eq!(total, profile.total_instructions());
+    assert!(folded
+        .lines()
+        .any(|x| x.starts_with("index.html;block body;macro item;macros.html:2 ")));
+
+    let folded = profile.to_folded(ProfileMetric::Time);
+    assert!(folded.lines().count() > 0);
+    assert!(folded
+        .lines()
+        .all(|x| x.rsplit_once(' ').unwrap().1.parse::<u128>().is_ok()));
+}
+
+#[test]
+fn test_no_profile() {
+    let env = profiled_env();
+    let tmpl = env.get_template("index.html").unwrap();
+    let captured = tmpl.render_captured(()).unwrap();
+    assert!(captured.profile().is_none());
+    let captured = tmpl
+        .render_captured_with((), &RenderOptions::new().with_profiling(false))
+        .unwrap();
+    assert!(captured.profile().is_none());
+}
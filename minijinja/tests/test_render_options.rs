@@ -0,0 +1,183 @@
+#![cfg(feature = "builtins")]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use minijinja::value::Value;
+use minijinja::{context, AutoEscape, Environment, ErrorKind, RenderOptions, UndefinedBehavior};
+
+use similar_asserts::assert_eq;
+
+const SLOW_TEMPLATE: &str = "\
+{% for x in range(100000) %}{% for y in range(100000) %}{% endfor %}{% endfor %}";
+
+#[test]
+fn test_timeout() {
+    let env = Environment::new();
+    let tmpl = env.template_from_str(SLOW_TEMPLATE).unwrap();
+    let started = Instant::now();
+    let options = RenderOptions::new().with_timeout(Duration::from_millis(50));
+    let err = tmpl.render_with((), &options).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Cancelled);
+    assert_eq!(err.detail(), Some("deadline exceeded"));
+    assert_eq!(err.line(), Some(1));
+    assert!(started.elapsed() < Duration::from_secs(10));
+}
+
+#[test]
+fn test_timeout_starts_with_render() {
+    let env = Environment::new();
+    let tmpl = env.template_from_str("Hello {{ name }}!").unwrap();
+    let options = RenderOptions::new().with_timeout(Duration::from_millis(50));
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(
+        tmpl.render_with(context!(name => "World"), &options)
+            .unwrap(),
+        "Hello World!"
+    );
+}
+
+#[test]
+fn test_timeout_to_write() {
+    let env = Environment::new();
+    let tmpl = env.template_from_str(SLOW_TEMPLATE).unwrap();
+    let options = RenderOptions::new().with_timeout(Duration::from_millis(50));
+    let mut buf = Vec::new();
+    let err = tmpl
+        .render_captured_to_with((), &mut buf, &options)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Cancelled);
+}
+
+#[test]
+fn test_cancel_flag() {
+    let mut env = Environment::new();
+    let flag = Arc::new(AtomicBool::new(false));
+    let flag_for_cancel = flag.clone();
+    env.add_function("cancel", move || {
+        flag_for_cancel.store(true, Ordering::Relaxed);
+    });
+    env.add_template("inc.html", "{% for x in range(1000) %}{{ x }}{% endfor %}")
+        .unwrap();
+    let tmpl = env
+        .template_from_str("{{ cancel() }}{% include 'inc.html' %}")
+        .unwrap();
+    let options = RenderOptions::new().with_cancel_flag(flag.clone());
+    let err = tmpl.render_with((), &options).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Cancelled);
+    assert_eq!(err.detail(), Some("render was cancelled"));
+
+    // the flag is only consulted for renders it was passed to
+    assert!(tmpl.render(()).unwrap().ends_with("998999"));
+}
+
+#[test]
+fn test_cancel_from_other_thread() {
+    let env = Environment::new();
+    let tmpl = env.template_from_str(SLOW_TEMPLATE).unwrap();
+    let flag = Arc::new(AtomicBool::new(false));
+    let options = RenderOptions::new().with_cancel_flag(flag.clone());
+    let handle = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(20));
+        flag.store(true, Ordering::Relaxed);
+    });
+    let err = tmpl.render_with((), &options).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Cancelled);
+    handle.join().unwrap();
+}
+
+#[test]
+fn test_no_options() {
+    let env = Environment::new();
+    let tmpl = env.template_from_str("Hello {{ 42 }}!").unwrap();
+    assert_eq!(
+        tmpl.render_with((), &RenderOptions::new()).unwrap(),
+        "Hello 42!"
+    );
+}
+
+#[test]
+fn test_undefined_behavior_override() {
+    let env = Environment::new();
+    let tmpl = env
+        .template_from_str("{% macro m() %}{{ missing }}{% endmacro %}[{{ m() }}]")
+        .unwrap();
+    assert_eq!(tmpl.render(()).unwrap(), "[]");
+    let options = RenderOptions::new().with_undefined_behavior(UndefinedBehavior::Strict);
+    let err = tmpl.render_with((), &options).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UndefinedError);
+    assert_eq!(env.undefined_behavior(), UndefinedBehavior::Lenient);
+}
+
+#[cfg(feature = "fuel")]
+#[test]
+fn test_fuel_override() {
+    let mut env = Environment::new();
+    env.set_fuel(Some(10));
+    let tmpl = env
+        .template_from_str("{% for x in range(100) %}{{ x }}{% endfor %}")
+        .unwrap();
+    assert_eq!(tmpl.render(()).unwrap_err().kind(), ErrorKind::OutOfFuel);
+    let options = RenderOptions::new().with_fuel(None);
+    assert!(tmpl.render_with((), &options).unwrap().ends_with("9899"));
+    let options = RenderOptions::new().with_fuel(Some(5));
+    let err = tmpl.render_with((), &options).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::OutOfFuel);
+}
+
+#[test]
+fn test_auto_escape_override() {
+    let env = Environment::new();
+    let tmpl = env.template_from_str("{{ value }}").unwrap();
+    let ctx = context! { value => "<a>" };
+    assert_eq!(tmpl.render(&ctx).unwrap(), "<a>");
+    let options = RenderOptions::new().with_auto_escape(AutoEscape::Html);
+    assert_eq!(tmpl.render_with(&ctx, &options).unwrap(), "&lt;a&gt;");
+}
+
+#[test]
+fn test_recursion_limit_override() {
+    let mut env = Environment::new();
+    env.add_template("rec.html", "{% include 'rec.html' %}")
+        .unwrap();
+    let tmpl = env.get_template("rec.html").unwrap();
+    let options = RenderOptions::new().with_recursion_limit(5);
+    let err = tmpl.render_with((), &options).unwrap_err();
+    let mut err: &dyn std::error::Error = &err;
+    while let Some(source) = err.source() {
+        err = source;
+    }
+    let err = err.downcast_ref::<minijinja::Error>().unwrap();
+    assert_eq!(err.kind(), ErrorKind::InvalidOperation);
+    assert!(err.detail().unwrap().contains("recursion limit exceeded"));
+}
+
+#[test]
+fn test_globals() {
+    let mut env = Environment::new();
+    env.add_global("site", "env");
+    env.add_template("inc.html", "{{ site }}/{{ user }}")
+        .unwrap();
+    let tmpl = env
+        .template_from_str(
+            "{{ site }}/{{ user }}|{% include 'inc.html' %}|\
+             {% macro m() %}{{ user }}{% endmacro %}{{ m() }}",
+        )
+        .unwrap();
+    assert_eq!(tmpl.render(()).unwrap(), "env/|env/|");
+    let options = RenderOptions::new()
+        .with_global("site", "render")
+        .with_global("user", "peter");
+    assert_eq!(
+        tmpl.render_with((), &options).unwrap(),
+        "render/peter|render/peter|peter"
+    );
+    assert_eq!(
+        tmpl.render_with(context! { user => "john" }, &options)
+            .unwrap(),
+        "render/john|render/john|john"
+    );
+
+    let state = tmpl.eval_to_state_with((), &options).unwrap();
+    assert_eq!(state.lookup("user"), Some(Value::from("peter")));
+}
@@ -0,0 +1,217 @@
+#![cfg(feature = "builtins")]
+use minijinja::sandbox::SandboxPolicy;
+use minijinja::value::Value;
+use minijinja::{context, Environment, ErrorKind};
+
+use similar_asserts::assert_eq;
+
+struct Policy;
+
+impl SandboxPolicy for Policy {
+    fn is_safe_attribute(&self, _obj: &Value, attr: &str) -> bool {
+        !attr.starts_with('_')
+    }
+
+    fn is_safe_item(&self, _obj: &Value, key: &Value) -> bool {
+        key.as_str().map_or(true, |key| !key.starts_with('_'))
+    }
+
+    fn is_safe_method(&self, _obj: &Value, method: &str) -> bool {
+        method != "secret"
+    }
+
+    fn is_safe_function(&self, name: &str, _func: &Value) -> bool {
+        name != "dangerous"
+    }
+
+    fn is_safe_filter(&self, name: &str) -> bool {
+        name != "tojson" && name != "upper"
+    }
+
+    fn is_safe_test(&self, name: &str) -> bool {
+        name != "odd"
+    }
+
+    fn max_range(&self) -> Option<usize> {
+        Some(10)
+    }
+
+    fn max_string_length(&self) -> Option<usize> {
+        Some(20)
+    }
+
+    fn max_sequence_length(&self) -> Option<usize> {
+        Some(5)
+    }
+}
+
+fn sandboxed_env() -> Environment<'static> {
+    let mut env = Environment::new();
+    env.set_sandbox_policy(Policy);
+    env.add_function("dangerous", || "boom");
+    env
+}
+
+fn render_err(env: &Environment, source: &str) -> String {
+    let ctx = context! {
+        user => context! { name => "Peter", _password => "secret" },
+        items => vec![1, 2, 3],
+    };
+    let err = env.render_str(source, ctx).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::SecurityError);
+    err.detail().unwrap().to_string()
+}
+
+#[test]
+fn test_allowed_operations() {
+    let env = sandboxed_env();
+    let rv = env
+        .render_str(
+            "{{ user.name }}|{{ user['name'] }}|{{ range(10)|length }}|{{ 'ab' * 10 }}|\
+             {{ items + [4, 5] }}|{{ 'x' ~ 'y' }}",
+            context! {
+                user => context! { name => "Peter" },
+                items => vec![1, 2, 3],
+            },
+        )
+        .unwrap();
+    assert_eq!(rv, "Peter|Peter|10|abababababababababab|[1, 2, 3, 4, 5]|xy");
+}
+
+#[test]
+fn test_denied_lookups() {
+    let env = sandboxed_env();
+    assert_eq!(
+        render_err(&env, "{{ user._password }}"),
+        "access to attribute `_password` of map is not allowed"
+    );
+    assert_eq!(
+        render_err(&env, "{{ user['_password'] }}"),
+        "access to item \"_password\" of map is not allowed"
+    );
+    assert_eq!(
+        render_err(&env, "{{ user.secret() }}"),
+        "calling method `secret` of map is not allowed"
+    );
+}
+
+#[test]
+fn test_denied_calls() {
+    let env = sandboxed_env();
+    assert_eq!(
+        render_err(&env, "{{ dangerous() }}"),
+        "calling function `dangerous` is not allowed"
+    );
+    assert_eq!(
+        render_err(&env, "{{ items|tojson }}"),
+        "filter `tojson` is not allowed"
+    );
+    assert_eq!(
+        render_err(&env, "{{ items is odd }}"),
+        "test `odd` is not allowed"
+    );
+}
+
+#[test]
+fn test_denied_indirect_calls() {
+    let env = sandboxed_env();
+    assert_eq!(
+        render_err(&env, "{{ [dangerous][0]() }}"),
+        "calling function `dangerous` is not allowed"
+    );
+    assert_eq!(
+        render_err(&env, "{% set f = dangerous %}{{ f() }}"),
+        "calling function `dangerous` is not allowed"
+    );
+    assert_eq!(
+        render_err(&env, "{{ {'f': dangerous}.f() }}"),
+        "calling function `dangerous` is not allowed"
+    );
+    assert_eq!(
+        render_err(&env, "{{ ['a']|map('upper')|join }}"),
+        "filter `upper` is not allowed"
+    );
+    assert_eq!(
+        render_err(&env, "{{ items|select('odd')|list }}"),
+        "test `odd` is not allowed"
+    );
+    assert_eq!(
+        render_err(&env, "{{ [user]|rejectattr('name', 'odd')|list }}"),
+        "test `odd` is not allowed"
+    );
+}
+
+#[test]
+fn test_indirect_calls_of_replaced_globals() {
+    let mut env = Environment::new();
+    env.add_function("dangerous", || "boom");
+    env.add_function("safe", || "ok");
+    env.set_sandbox_policy(Policy);
+    assert_eq!(
+        render_err(&env, "{% set f = dangerous %}{{ f() }}"),
+        "calling function `dangerous` is not allowed"
+    );
+
+    let safe = env.globals().find(|(name, _)| *name == "safe").unwrap().1;
+    env.add_global("dangerous", safe);
+    assert_eq!(
+        render_err(&env, "{% set f = safe %}{{ f() }}"),
+        "calling function `dangerous` is not allowed"
+    );
+    env.remove_global("dangerous");
+    assert_eq!(
+        env.render_str("{% set f = safe %}{{ f() }}", ()).unwrap(),
+        "ok"
+    );
+}
+
+#[test]
+fn test_no_constant_folding() {
+    let mut env = sandboxed_env();
+    env.set_filter_folding(true);
+    assert_eq!(
+        render_err(&env, "{{ 'a'|upper }}"),
+        "filter `upper` is not allowed"
+    );
+}
+
+#[test]
+fn test_size_limits() {
+    let env = sandboxed_env();
+    assert_eq!(
+        render_err(&env, "{{ range(11)|length }}"),
+        "range of 11 items exceeds the limit of 10"
+    );
+    assert_eq!(
+        render_err(&env, "{{ 'abc' * 10 }}"),
+        "string of 30 bytes exceeds the limit of 20"
+    );
+    assert_eq!(
+        render_err(&env, "{{ 10 * 'abc' }}"),
+        "string of 30 bytes exceeds the limit of 20"
+    );
+    assert_eq!(
+        render_err(&env, "{{ items * 2 }}"),
+        "sequence of 6 items exceeds the limit of 5"
+    );
+    assert_eq!(
+        render_err(&env, "{{ items + items }}"),
+        "sequence of 6 items exceeds the limit of 5"
+    );
+    assert_eq!(
+        render_err(
+            &env,
+            "{% set s = 'abcdefgh' %}{% set s = s ~ s %}{% set s = s ~ s %}{{ s }}"
+        ),
+        "string of 32 bytes exceeds the limit of 20"
+    );
+}
+
+#[test]
+fn test_no_policy() {
+    let env = Environment::new();
+    assert_eq!(
+        env.render_str("{{ 'ab' * 20 }}", ()).unwrap(),
+        "ab".repeat(20)
+    );
+}
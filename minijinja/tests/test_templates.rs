@@ -9,12 +9,15 @@
 use std::collections::BTreeMap;
 use std::fmt::Write;
 use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use insta::assert_snapshot;
 use minijinja::syntax::SyntaxConfig;
 use minijinja::value::{Enumerator, Object, ObjectRepr, Rest, Value};
-use minijinja::{context, render, Environment, Error, ErrorKind, State, UndefinedBehavior};
+use minijinja::{
+    context, render, Environment, Error, ErrorKind, State, TemplateReferenceKind, UndefinedBehavior,
+};
 
 use serde::Deserialize;
 use similar_asserts::assert_eq;
@@ -520,6 +523,106 @@ fn test_undeclared_variables() {
     );
 }
 
+#[test]
+fn test_referenced_templates() {
+    let mut env = Environment::new();
+    env.add_template(
+        "demo",
+        r#"{% extends "layout.html" %}
+{% import "macros.html" as m %}
+{% block body %}
+  {% from helpers import x %}
+  {% include ["a.html", "b" ~ ".html", name] ignore missing %}
+  {% macro foo() %}{% include "in_macro.html" %}{% endmacro %}
+{% endblock %}"#,
+    )
+    .unwrap();
+    let refs = env
+        .get_template("demo")
+        .unwrap()
+        .referenced_templates()
+        .unwrap();
+    let summary = refs
+        .iter()
+        .map(|x| (x.kind(), x.name(), x.ignore_missing(), x.line()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        vec![
+            (
+                TemplateReferenceKind::Extends,
+                Some("layout.html"),
+                false,
+                1
+            ),
+            (TemplateReferenceKind::Import, Some("macros.html"), false, 2),
+            (TemplateReferenceKind::FromImport, None, false, 4),
+            (TemplateReferenceKind::Include, Some("a.html"), true, 5),
+            (TemplateReferenceKind::Include, Some("b.html"), true, 5),
+            (TemplateReferenceKind::Include, None, true, 5),
+            (
+                TemplateReferenceKind::Include,
+                Some("in_macro.html"),
+                false,
+                6
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_template_graph() {
+    let mut env = Environment::new();
+    env.set_path_join_callback(|name, parent| {
+        let mut rv = parent.split('/').collect::<Vec<_>>();
+        rv.pop();
+        rv.push(name);
+        rv.join("/").into()
+    });
+    env.add_template("layout.html", "{% include 'parts/header.html' %}")
+        .unwrap();
+    env.add_template("parts/header.html", "{% include 'nav.html' %}")
+        .unwrap();
+    env.add_template("parts/nav.html", "nav").unwrap();
+    env.add_template("index.html", "{% extends 'layout.html' %}")
+        .unwrap();
+    env.add_template("dynamic.html", "{% extends layout %}")
+        .unwrap();
+
+    let graph = env.template_graph().unwrap();
+    assert_eq!(
+        graph.templates().collect::<Vec<_>>(),
+        [
+            "dynamic.html",
+            "index.html",
+            "layout.html",
+            "parts/header.html",
+            "parts/nav.html"
+        ]
+    );
+    assert_eq!(
+        graph.references("parts/header.html")[0].name(),
+        Some("parts/nav.html")
+    );
+    assert_eq!(
+        graph
+            .dependencies("index.html")
+            .into_iter()
+            .collect::<Vec<_>>(),
+        ["layout.html", "parts/header.html", "parts/nav.html"]
+    );
+    assert_eq!(
+        graph
+            .dependents("parts/nav.html")
+            .into_iter()
+            .collect::<Vec<_>>(),
+        ["index.html", "layout.html", "parts/header.html"]
+    );
+    assert!(graph.has_dynamic_references("dynamic.html"));
+    assert!(!graph.has_dynamic_references("index.html"));
+    assert!(graph.references("missing.html").is_empty());
+}
+
 #[test]
 fn test_undeclared_variables_bug() {
     // see https://github.com/mitsuhiko/minijinja/issues/484
@@ -674,6 +777,111 @@ fn test_render_captured_to() {
     );
 }
 
+#[test]
+fn test_render_captured_to_flushes() {
+    struct Recorder {
+        buf: Vec<u8>,
+        flushed_at: Vec<usize>,
+    }
+
+    impl std::io::Write for Recorder {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.buf.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.flushed_at.push(self.buf.len());
+            Ok(())
+        }
+    }
+
+    let env = Environment::new();
+    let tmpl = env
+        .template_from_str(
+            "a{{ flush() }}{% for x in [1, 2] %}{{ x }}{{ flush() }}{% endfor %}\
+             {% set y %}ignored{{ flush() }}{% endset %}{{ y }}",
+        )
+        .unwrap();
+    let mut w = Recorder {
+        buf: Vec::new(),
+        flushed_at: Vec::new(),
+    };
+    tmpl.render_captured_to((), &mut w).unwrap();
+    assert_eq!(String::from_utf8_lossy(&w.buf), "a12ignored");
+    assert_eq!(w.flushed_at, [1, 2, 3]);
+
+    // rendering to a string ignores flush points
+    assert_eq!(tmpl.render(()).unwrap(), "a12ignored");
+}
+
+#[test]
+fn test_render_iter() {
+    let mut env = Environment::new();
+    env.add_template("layout.html", "<h1>{% block title %}{% endblock %}</h1>{{ flush() }}<p>{% block body %}{% endblock %}</p>")
+        .unwrap();
+    env.add_template(
+        "index.html",
+        "{% extends 'layout.html' %}{% macro m() %}{{ flush() }}!{% endmacro %}\
+         {% block title %}{{ title }}{{ flush() }}{% endblock %}\
+         {% block body %}{{ m() }}{{ flush() }}{% endblock %}",
+    )
+    .unwrap();
+    let tmpl = env.get_template("index.html").unwrap();
+    let chunks = tmpl
+        .render_iter(context! { title => "<x>" })
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(chunks, ["<h1>&lt;x&gt;", "</h1>", "<p>!", "</p>"]);
+
+    let tmpl = env
+        .template_from_str("a{{ flush() }}b{{ fail() }}")
+        .unwrap();
+    let mut iter = tmpl.render_iter(());
+    assert_eq!(iter.next().unwrap().unwrap(), "a");
+    assert_eq!(
+        iter.next().unwrap().unwrap_err().kind(),
+        ErrorKind::UnknownFunction
+    );
+    assert!(iter.next().is_none());
+}
+
+#[test]
+fn test_render_iter_in_order() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mut env = Environment::new();
+    env.add_function("count", {
+        let calls = calls.clone();
+        move || calls.fetch_add(1, Ordering::Relaxed)
+    });
+    let tmpl = env
+        .template_from_str("{% for _ in range(3) %}{{ count() }}{{ flush() }}{% endfor %}")
+        .unwrap();
+
+    fn assert_send<T: Send>(_: &T) {}
+
+    let mut iter = tmpl.render_iter(());
+    assert_send(&iter);
+    assert_eq!(calls.load(Ordering::Relaxed), 3);
+    assert_eq!(iter.next().unwrap().unwrap(), "0");
+    assert_eq!(iter.next().unwrap().unwrap(), "1");
+    assert_eq!(iter.next().unwrap().unwrap(), "2");
+    assert!(iter.next().is_none());
+}
+
+#[test]
+fn test_flush_through_methods_and_objects() {
+    let env = Environment::new();
+    let tmpl = env
+        .template_from_str(
+            "{% set helpers = {'flush': flush} %}\
+             a{{ helpers.flush() }}b{{ [flush][0]() }}c",
+        )
+        .unwrap();
+    let chunks = tmpl.render_iter(()).collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(chunks, ["a", "b", "c"]);
+}
+
 #[test]
 fn test_functions() {
     assert_snapshot!(
@@ -772,3 +980,102 @@ fn test_test_caching() {
     let rv = env.get_template("child.txt").unwrap().render(()).unwrap();
     assert_eq!(rv, "false");
 }
+
+#[test]
+fn test_constant_folding() {
+    let mut env = Environment::new();
+    env.set_filter_folding(true);
+    env.add_template(
+        "folded.txt",
+        "{{ 'hello world'|title }}{# comment #}|{{ 3.14159|round(2) }}{% raw %}{{ x }}{% endraw %}",
+    )
+    .unwrap();
+    let rv = env.get_template("folded.txt").unwrap().render(()).unwrap();
+    assert_eq!(rv, "Hello World|3.14{{ x }}");
+
+    // replaced filters are not evaluated at compile time
+    env.add_filter("title", |value: String| format!("<{value}>"));
+    env.add_template("replaced.txt", "{{ 'hello'|title }}")
+        .unwrap();
+    let rv = env
+        .get_template("replaced.txt")
+        .unwrap()
+        .render(())
+        .unwrap();
+    assert_eq!(rv, "<hello>");
+
+    // folded filters see the settings of the environment
+    env.set_size_limit(Some(10));
+    let err = env
+        .render_str("{{ 'abc'|replace('b', 'bbbbbbbbbb') }}", ())
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::SizeLimitExceeded);
+    assert_eq!(err.line(), Some(1));
+}
+
+#[test]
+fn test_no_constant_folding_by_default() {
+    let mut env = Environment::new();
+    assert!(!env.filter_folding());
+    env.add_template("upper.txt", "{{ 'a'|upper }} {{ x|upper }}")
+        .unwrap();
+    env.add_filter("upper", |value: String| format!("<{value}>"));
+    let rv = env
+        .get_template("upper.txt")
+        .unwrap()
+        .render(context!(x => "b"))
+        .unwrap();
+    assert_eq!(rv, "<a> <b>");
+}
+
+#[test]
+fn test_constant_folding_keeps_errors() {
+    let mut env = Environment::new();
+    env.set_filter_folding(true);
+    env.add_template("error.txt", "Hello\n{{ 'x'|int }}")
+        .unwrap();
+    let err = env
+        .get_template("error.txt")
+        .unwrap()
+        .render(())
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidOperation);
+    assert_eq!(err.line(), Some(2));
+}
+
+#[test]
+fn test_tuples() {
+    let mut env = Environment::new();
+    let rv = env
+        .render_str(
+            "{{ (1, 2) }}|{{ (1, 2) is sequence }}|{% set t = 1, 2 %}{{ t }}",
+            (),
+        )
+        .unwrap();
+    assert_eq!(rv, "[1, 2]|true|[1, 2]");
+
+    env.set_python_tuples(true);
+    let rv = env
+        .render_str(
+            "{{ (1, 2) }}|{{ (x,) }}|{{ () }}|{{ (1, 2) is sequence }}|{{ (1, 2, 3)[1:] }}|{{ (1,) + (2,) }}",
+            context!(x => 42),
+        )
+        .unwrap();
+    assert_eq!(rv, "(1, 2)|(42,)|()|true|(2, 3)|(1, 2)");
+
+    let rv = env
+        .render_str(
+            "{% set pairs = {(0, 0): 'origin', (0, 1): 'right'} %}{{ pairs[(0, y)] }}|{{ pairs[point] }}",
+            context!(y => 1, point => Value::from_tuple([0, 0])),
+        )
+        .unwrap();
+    assert_eq!(rv, "right|origin");
+
+    let rv = env
+        .render_str(
+            "{% set t = 1, 2 %}{{ t }}|{% for a, b in [(1, 2), (3, 4)] %}{{ a + b }}{% endfor %}",
+            (),
+        )
+        .unwrap();
+    assert_eq!(rv, "(1, 2)|37");
+}
@@ -0,0 +1,151 @@
+#![cfg(all(feature = "tracing", feature = "multi_template", feature = "macros"))]
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use minijinja::Environment;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+use similar_asserts::assert_eq;
+
+#[derive(Debug, Clone)]
+struct RecordedSpan {
+    name: &'static str,
+    parent: Option<usize>,
+    fields: BTreeMap<&'static str, String>,
+}
+
+impl Visit for RecordedSpan {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.fields.insert(field.name(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.fields.insert(field.name(), format!("{value:?}"));
+    }
+}
+
+#[derive(Default, Clone)]
+struct Recorder {
+    spans: Arc<Mutex<Vec<RecordedSpan>>>,
+    stack: Arc<Mutex<Vec<usize>>>,
+}
+
+impl Recorder {
+    fn spans(&self) -> Vec<RecordedSpan> {
+        self.spans.lock().unwrap().clone()
+    }
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        let mut span = RecordedSpan {
+            name: attrs.metadata().name(),
+            parent: self.stack.lock().unwrap().last().copied(),
+            fields: BTreeMap::new(),
+        };
+        attrs.record(&mut span);
+        let mut spans = self.spans.lock().unwrap();
+        spans.push(span);
+        Id::from_u64(spans.len() as u64)
+    }
+
+    fn record(&self, id: &Id, values: &Record<'_>) {
+        let mut spans = self.spans.lock().unwrap();
+        values.record(&mut spans[id.into_u64() as usize - 1]);
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, _event: &Event<'_>) {}
+
+    fn enter(&self, span: &Id) {
+        self.stack
+            .lock()
+            .unwrap()
+            .push(span.into_u64() as usize - 1);
+    }
+
+    fn exit(&self, _span: &Id) {
+        self.stack.lock().unwrap().pop();
+    }
+}
+
+#[test]
+fn test_render_spans() {
+    let recorder = Recorder::default();
+    tracing::subscriber::with_default(recorder.clone(), || {
+        let mut env = Environment::new();
+        env.set_loader(|name| match name {
+            "index.html" => Ok(Some(
+                "{% macro m() %}{% include 'inc.html' %}{% endmacro %}{{ m() }}".into(),
+            )),
+            "inc.html" => Ok(Some("inc".into())),
+            _ => Ok(None),
+        });
+        let tmpl = env.get_template("index.html").unwrap();
+        assert_eq!(tmpl.render(()).unwrap(), "inc");
+    });
+
+    let spans = recorder.spans();
+    let summary = spans
+        .iter()
+        .map(|span| {
+            let parent = span.parent.map_or("-", |idx| spans[idx].name);
+            format!("{} {} < {}", span.name, span.fields["template"], parent)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        vec![
+            "load index.html < -",
+            "compile index.html < load",
+            "render index.html < -",
+            "macro index.html < render",
+            "load inc.html < macro",
+            "compile inc.html < load",
+            "include inc.html < macro",
+        ]
+    );
+    let macro_span = spans.iter().find(|x| x.name == "macro").unwrap();
+    assert_eq!(macro_span.fields["name"], "m");
+}
+
+#[test]
+fn test_error_spans() {
+    let recorder = Recorder::default();
+    tracing::subscriber::with_default(recorder.clone(), || {
+        let env = Environment::new();
+        assert!(env.render_str("{{ missing() }}", ()).is_err());
+        assert!(env.template_from_str("{% if %}").is_err());
+    });
+
+    let spans = recorder.spans();
+    let render = spans.iter().find(|x| x.name == "render").unwrap();
+    assert_eq!(render.fields["error.kind"], "UnknownFunction");
+    assert!(render.fields["error.message"].starts_with("unknown function"));
+    let failed_compile = spans.iter().rfind(|x| x.name == "compile").unwrap();
+    assert_eq!(failed_compile.fields["error.kind"], "SyntaxError");
+}
+
+#[cfg(feature = "fuel")]
+#[test]
+fn test_fuel_recorded() {
+    let recorder = Recorder::default();
+    tracing::subscriber::with_default(recorder.clone(), || {
+        let mut env = Environment::new();
+        env.set_fuel(Some(1000));
+        env.render_str("{% for x in range(10) %}{{ x }}{% endfor %}", ())
+            .unwrap();
+    });
+
+    let spans = recorder.spans();
+    let render = spans.iter().find(|x| x.name == "render").unwrap();
+    assert!(render.fields["fuel_consumed"].parse::<u64>().unwrap() > 10);
+}
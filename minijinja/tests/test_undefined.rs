@@ -340,3 +340,68 @@ fn test_chainable_undefined() {
     assert_eq!(render!(in env, "<{{ undefined|test }}>"), "<>");
     assert_eq!(render!(in env, "{{ 42 in undefined }}"), "false");
 }
+
+#[test]
+fn test_undefined_origin() {
+    let mut env = Environment::new();
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+    let ctx = context! { user => context! { name => "Peter" } };
+
+    let err = env
+        .render_str("{% set x = user.profile %}\n{{ x|upper }}", ctx.clone())
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UndefinedError);
+    assert_eq!(
+        err.detail(),
+        Some("`user.profile` is undefined (`profile` missing on map)")
+    );
+    assert_eq!(err.line(), Some(1));
+
+    let err = env
+        .render_str("{{ items[3] }}", context! { items => vec![1, 2] })
+        .unwrap_err();
+    assert_eq!(
+        err.detail(),
+        Some("`items[3]` is undefined (`3` missing on sequence)")
+    );
+
+    let err = env
+        .render_str(
+            "{{ items[idx] }}",
+            context! { items => vec![1, 2], idx => 3 },
+        )
+        .unwrap_err();
+    assert_eq!(
+        err.detail(),
+        Some("`items[idx]` is undefined (item missing on sequence)")
+    );
+
+    let err = env.render_str("{{ missing }}", ()).unwrap_err();
+    assert_eq!(err.detail(), Some("`missing` is undefined"));
+
+    // undefined values used in other templates point back to their origin
+    env.add_template("inc", "{{ x|upper }}").unwrap();
+    let err = env
+        .render_str(
+            "\n{% set x = user.profile %}{% include 'inc' %}",
+            ctx.clone(),
+        )
+        .unwrap_err();
+    let err = std::error::Error::source(&err)
+        .and_then(|x| x.downcast_ref::<minijinja::Error>())
+        .unwrap();
+    assert_eq!(
+        err.detail(),
+        Some("`user.profile` is undefined (`profile` missing on map)")
+    );
+    assert_eq!(err.name(), Some("<string>"));
+    assert_eq!(err.line(), Some(2));
+
+    // lenient undefined errors when looking up attributes of undefined values
+    env.set_undefined_behavior(UndefinedBehavior::Lenient);
+    let err = env.render_str("{{ user.profile.name }}", ctx).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "undefined value: `user.profile` is undefined (`profile` missing on map) (in <string>:1)"
+    );
+}
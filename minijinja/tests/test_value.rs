@@ -559,6 +559,46 @@ fn test_complex_key() {
     );
 }
 
+#[test]
+fn test_tuple() {
+    let value = Value::from_tuple([1, 2]);
+    assert_eq!(value.kind(), ValueKind::Tuple);
+    assert_eq!(value.to_string(), "(1, 2)");
+    assert_eq!(Value::from_tuple([1]).to_string(), "(1,)");
+    assert_eq!(Value::from_tuple(Vec::<Value>::new()).to_string(), "()");
+    assert_eq!(value.len(), Some(2));
+    assert_eq!(value.get_item_by_index(1).unwrap(), Value::from(2));
+
+    let map = Value::from_iter([
+        (Value::from_tuple([0u32, 0u32]), "origin"),
+        (Value::from_tuple([0u32, 1u32]), "right"),
+    ]);
+    assert_eq!(
+        map.get_item(&Value::from_tuple([0, 1])).ok(),
+        Some(Value::from("right"))
+    );
+}
+
+#[test]
+fn test_tuple_serialization() {
+    let value = Value::from_tuple([Value::from(1), Value::from("a")]);
+    assert_eq!(serde_json::to_string(&value).unwrap(), r#"[1,"a"]"#);
+
+    let round_tripped = Value::from_serialize(&value);
+    assert_eq!(round_tripped.kind(), ValueKind::Tuple);
+    assert_eq!(round_tripped, value);
+}
+
+#[test]
+#[cfg(feature = "deserialization")]
+fn test_tuple_deserialize() {
+    use serde::Deserialize;
+
+    let value = Value::from_tuple([Value::from(42), Value::from("x")]);
+    let rv = <(i32, String)>::deserialize(value).unwrap();
+    assert_eq!(rv, (42, "x".to_string()));
+}
+
 #[test]
 #[cfg(feature = "deserialization")]
 fn test_deserialize() {